@@ -28,7 +28,24 @@ export type LogLevel = "error" | "warn" | "info" | "debug" | "trace";
 /**
  * Types of supported packages by [`cargo-packager`](https://docs.rs/cargo-packager).
  */
-export type PackageFormat = "all" | "default" | "app" | "dmg" | "wix" | "nsis" | "deb" | "appimage" | "pacman";
+export type PackageFormat =
+  | "all"
+  | "default"
+  | "app"
+  | "dmg"
+  | "pkg"
+  | "wix"
+  | "nsis"
+  | "msix"
+  | "deb"
+  | "appimage"
+  | "pacman"
+  | "rpm"
+  | "flatpak"
+  | "snap"
+  | "apk"
+  | "freebsd"
+  | "archive";
 /**
  * The possible app categories. Corresponds to `LSApplicationCategoryType` on macOS and the GNOME desktop categories on Debian.
  */
@@ -99,6 +116,57 @@ export type Resource =
  * A list of dependencies specified as either a list of Strings or as a path to a file that lists the dependencies, one per line.
  */
 export type Dependencies = string[] | string;
+/**
+ * Compression algorithms used for the archives of the Debian package.
+ */
+export type DebianCompression = "gzip" | "xz" | "zstd" | "none";
+/**
+ * A Debian, Alpine or FreeBSD maintainer script, specified as either a path to a script file or as an object with a path or the inline script contents.
+ *
+ * When `template` is set to `true`, the script is rendered as a Handlebars template with the following variables available: `package_name`, `product_name`, `version`, `identifier`, `main_binary_name` and `publisher`.
+ */
+export type MaintainerScript =
+  | string
+  | {
+      /**
+       * The path to the script file.
+       */
+      path: string;
+      /**
+       * Whether to render the script as a Handlebars template.
+       */
+      template?: boolean;
+      [k: string]: unknown;
+    }
+  | {
+      /**
+       * The script contents.
+       */
+      script: string;
+      /**
+       * Whether to render the script as a Handlebars template.
+       */
+      template?: boolean;
+      [k: string]: unknown;
+    };
+/**
+ * The confinement level of a snap.
+ *
+ * See <https://snapcraft.io/docs/snap-confinement>
+ */
+export type SnapConfinement = "strict" | "classic" | "devmode";
+/**
+ * The quality grade of a snap.
+ */
+export type SnapGrade = "stable" | "devel";
+/**
+ * Compression algorithms used for the FreeBSD package archive.
+ */
+export type FreeBsdCompression = "zstd" | "xz";
+/**
+ * The format of the [portable archive](PackageFormat::Archive).
+ */
+export type ArchiveFormat = "tar.gz" | "tar.xz" | "tar.zst" | "zip";
 /**
  * A wix language.
  */
@@ -199,7 +267,7 @@ export interface Config {
   /**
    * The target triple we are packaging for.
    *
-   * Defaults to the current OS target triple.
+   * Defaults to the current OS target triple, or `universal-apple-darwin` if [`MacOsConfig::target_triples`] is set and either the host is macOS or only the macOS formats are packaged.
    */
   targetTriple?: string | null;
   /**
@@ -261,7 +329,7 @@ export interface Config {
    *
    * For example, if you're packaging an external binary called `sqlite3`, the packager expects a binary named `sqlite3-x86_64-unknown-linux-gnu` on linux, and `sqlite3-x86_64-pc-windows-gnu.exe` on windows.
    *
-   * If you are building a universal binary for MacOS, the packager expects your external binary to also be universal, and named after the target triple, e.g. `sqlite3-universal-apple-darwin`. See <https://developer.apple.com/documentation/apple-silicon/building-a-universal-macos-binary>
+   * If you are building a universal binary for MacOS, the packager expects your external binary to also be universal, and named after the target triple, e.g. `sqlite3-universal-apple-darwin`, unless [`MacOsConfig::target_triples`] is set, in which case the external binaries of each target, e.g. `sqlite3-aarch64-apple-darwin` and `sqlite3-x86_64-apple-darwin`, are merged. See <https://developer.apple.com/documentation/apple-silicon/building-a-universal-macos-binary>
    */
   externalBinaries?: string[] | null;
   /**
@@ -288,6 +356,30 @@ export interface Config {
    * Pacman configuration.
    */
   pacman?: PacmanConfig | null;
+  /**
+   * RPM configuration.
+   */
+  rpm?: RpmConfig | null;
+  /**
+   * Flatpak configuration.
+   */
+  flatpak?: FlatpakConfig | null;
+  /**
+   * Snap configuration.
+   */
+  snap?: SnapConfig | null;
+  /**
+   * Alpine apk configuration.
+   */
+  apk?: ApkConfig | null;
+  /**
+   * FreeBSD pkg configuration.
+   */
+  freebsd?: FreeBsdConfig | null;
+  /**
+   * Portable archive configuration.
+   */
+  archive?: ArchiveConfig | null;
   /**
    * WiX configuration.
    */
@@ -296,10 +388,18 @@ export interface Config {
    * Nsis configuration.
    */
   nsis?: NsisConfig | null;
+  /**
+   * MSIX configuration.
+   */
+  msix?: MsixConfig | null;
   /**
    * Dmg configuration.
    */
   dmg?: DmgConfig | null;
+  /**
+   * macOS installer package configuration.
+   */
+  pkg?: PkgConfig | null;
 }
 /**
  * A binary to package within the final package.
@@ -323,7 +423,7 @@ export interface FileAssociation {
    */
   extensions: string[];
   /**
-   * The mime-type e.g. 'image/png' or 'text/plain'. **Linux-only**.
+   * The mime-type e.g. 'image/png' or 'text/plain'. **Linux and MSIX only**.
    */
   mimeType?: string | null;
   /**
@@ -387,9 +487,19 @@ export interface WindowsConfig {
   /**
    * Specify a custom command to sign the binaries. This command needs to have a `%1` in it which is just a placeholder for the binary path, which we will detect and replace before calling the command.
    *
-   * By Default we use `signtool.exe` which can be found only on Windows so if you are on another platform and want to cross-compile and sign you will need to use another tool like `osslsigncode`.
+   * By Default we use `signtool.exe` which can be found only on Windows, or the built-in signer when [`WindowsConfig::signing_certificate`] is set. This command takes precedence over both, e.g. to sign with a cloud HSM.
    */
   signCommand?: string | null;
+  /**
+   * Path to the code signing certificate, used to sign the binaries and installers on any platform without `signtool.exe`.
+   *
+   * Either a PKCS#12 (`.pfx`) file, or a PEM file containing the certificate followed by the intermediate certificates of its chain. Only RSA keys and the SHA-256 [`WindowsConfig::digest_algorithm`] are supported, and the [`WindowsConfig::timestamp_url`] is always used as an RFC 3161 server.
+   */
+  signingCertificate?: string | null;
+  /**
+   * Path to a PEM file containing the RSA private key of the [`WindowsConfig::signing_certificate`], if it is a PEM file which does not contain the key.
+   */
+  signingKey?: string | null;
 }
 /**
  * The macOS configuration.
@@ -421,6 +531,12 @@ export interface MacOsConfig {
    * This is typically of the form: `"Developer ID Application: TEAM_NAME (TEAM_ID)"`.
    */
   signingIdentity?: string | null;
+  /**
+   * Whether to download the missing issuer certificates of the [`MacOsConfig::signing_certificate`] chain from the location in their Authority Information Access extension.
+   *
+   * Signatures created without the Xcode command line tools embed the whole chain up to the root certificate, so when this is disabled the p12 file must contain all of it.
+   */
+  downloadIssuerCertificates?: boolean;
   /**
    * Provider short name for notarization.
    */
@@ -447,6 +563,14 @@ export interface MacOsConfig {
    * Sets the `LSUIElement` flag in the macOS plist file.
    */
   backgroundApp?: boolean;
+  /**
+   * The target triples whose binaries are merged into universal binaries, e.g. `["aarch64-apple-darwin", "x86_64-apple-darwin"]`.
+   *
+   * When set, [`Config::target_triple`] defaults to `universal-apple-darwin` on macOS hosts or when only the `app`, `dmg` and `pkg` formats are packaged, and the [`Config::binaries`] and [`Config::external_binaries`] of each target are merged into fat Mach-O files, producing a single `universal` .app and DMG. The binaries of each target are looked up in [`Config::target_binaries_dir`], while binaries with an absolute path are copied as is.
+   *
+   * When not set, packaging for the `universal-apple-darwin` target copies the binaries and external binaries built for it, which must already be universal.
+   */
+  targetTriples?: string[] | null;
 }
 /**
  * Linux configuration
@@ -465,6 +589,54 @@ export interface DebianConfig {
    * The list of Debian dependencies.
    */
   depends?: Dependencies | null;
+  /**
+   * List of Debian packages that must be installed and configured before this package is even unpacked.
+   *
+   * See : <https://www.debian.org/doc/debian-policy/ch-relationships.html#binary-dependencies-depends-recommends-suggests-enhances-pre-depends>
+   */
+  preDepends?: Dependencies | null;
+  /**
+   * List of Debian packages that would be found together with this package in all but unusual installations.
+   *
+   * See : <https://www.debian.org/doc/debian-policy/ch-relationships.html#binary-dependencies-depends-recommends-suggests-enhances-pre-depends>
+   */
+  recommends?: Dependencies | null;
+  /**
+   * List of Debian packages that are related to this package and may make it more useful.
+   *
+   * See : <https://www.debian.org/doc/debian-policy/ch-relationships.html#binary-dependencies-depends-recommends-suggests-enhances-pre-depends>
+   */
+  suggests?: Dependencies | null;
+  /**
+   * List of Debian packages that this package can enhance the functionality of.
+   *
+   * See : <https://www.debian.org/doc/debian-policy/ch-relationships.html#binary-dependencies-depends-recommends-suggests-enhances-pre-depends>
+   */
+  enhances?: Dependencies | null;
+  /**
+   * List of Debian packages that are broken by this package.
+   *
+   * See : <https://www.debian.org/doc/debian-policy/ch-relationships.html#packages-which-break-other-packages-breaks>
+   */
+  breaks?: Dependencies | null;
+  /**
+   * List of Debian packages that conflict with this package and must be removed before it can be installed.
+   *
+   * See : <https://www.debian.org/doc/debian-policy/ch-relationships.html#conflicting-binary-packages-conflicts>
+   */
+  conflicts?: Dependencies | null;
+  /**
+   * List of virtual packages that are provided by this package.
+   *
+   * See : <https://www.debian.org/doc/debian-policy/ch-relationships.html#virtual-packages-provides>
+   */
+  provides?: Dependencies | null;
+  /**
+   * List of Debian packages whose files are overwritten or which are completely replaced by this package.
+   *
+   * See : <https://www.debian.org/doc/debian-policy/ch-relationships.html#overwriting-files-and-replacing-packages-replaces>
+   */
+  replaces?: Dependencies | null;
   /**
    * Path to a custom desktop file Handlebars template.
    *
@@ -497,6 +669,60 @@ export interface DebianConfig {
    * Name to use for the `Package` field in the Debian Control file. Defaults to [`Config::product_name`] converted to kebab-case.
    */
   packageName?: string | null;
+  /**
+   * Whether to detect the shared libraries needed by the packaged binaries and add the Debian packages providing them to the `Depends` field, similar to `dpkg-shlibdeps`. By default, it is set to `false`.
+   *
+   * The `DT_NEEDED` entries of every ELF file in the package are resolved using [`DebianConfig::shlibs_file`], then the `shlibs` files and the file lists of the dpkg database.
+   */
+  shlibDepends?: boolean;
+  /**
+   * Path to a file mapping shared libraries to Debian dependencies, used by [`DebianConfig::shlib_depends`] and taking precedence over the dpkg database. The file uses the `shlibs` format: ```text libfoo 1 libfoo1 (>= 1.2) ```
+   *
+   * See : <https://www.debian.org/doc/debian-policy/ch-sharedlibs.html#the-shlibs-file-format>
+   */
+  shlibsFile?: string | null;
+  /**
+   * The compression to use for the `control` and `data` archives of the package. By default, it is set to `gzip`.
+   */
+  compression?: DebianCompression | null;
+  /**
+   * The compression level to use, its range depends on the [`DebianConfig::compression`]: `0-9` for `gzip` and `xz`, `1-22` for `zstd`. By default, each compression uses its default level.
+   */
+  compressionLevel?: number | null;
+  /**
+   * The license of the package as a short name used in the generated `copyright` file, ideally an SPDX identifier such as `MIT` or `Apache-2.0`. The license text is read from [`Config::license_file`]. By default, it is set to `custom`.
+   *
+   * See : <https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/#license-specification>
+   */
+  license?: string | null;
+  /**
+   * Path to the changelog of the app, which is compressed and installed as `/usr/share/doc/<package>/changelog.gz`.
+   */
+  changelog?: string | null;
+  /**
+   * The `preinst` maintainer script, run before the package is unpacked.
+   *
+   * See : <https://www.debian.org/doc/debian-policy/ch-maintainerscripts.html>
+   */
+  preinst?: MaintainerScript | null;
+  /**
+   * The `postinst` maintainer script, run after the package is unpacked.
+   *
+   * See : <https://www.debian.org/doc/debian-policy/ch-maintainerscripts.html>
+   */
+  postinst?: MaintainerScript | null;
+  /**
+   * The `prerm` maintainer script, run before the package is removed.
+   *
+   * See : <https://www.debian.org/doc/debian-policy/ch-maintainerscripts.html>
+   */
+  prerm?: MaintainerScript | null;
+  /**
+   * The `postrm` maintainer script, run after the package is removed.
+   *
+   * See : <https://www.debian.org/doc/debian-policy/ch-maintainerscripts.html>
+   */
+  postrm?: MaintainerScript | null;
 }
 /**
  * The Linux AppImage configuration.
@@ -526,6 +752,34 @@ export interface AppImageConfig {
    * List of globs of libraries to exclude from the final AppImage. For example, to exclude libnss3.so, you'd specify `libnss3*`
    */
   excludedLibs?: string[] | null;
+  /**
+   * Whether to build the AppImage using [`linuxdeploy`](https://github.com/linuxdeploy/linuxdeploy) instead of the built-in builder. Defaults to `false`.
+   *
+   * The built-in builder assembles the AppDir and creates the AppImage without running external tools. Like `linuxdeploy`, it bundles the libraries the app depends on, except for the ones expected on every system and the [`AppImageConfig::excluded_libs`]. `linuxdeploy` is always used if [`AppImageConfig::linuxdeploy_plugins`] is set, or if no runtime release is pinned for the target architecture and [`AppImageConfig::runtime`] is not set.
+   */
+  linuxdeploy?: boolean;
+  /**
+   * Path to the [AppImage type 2 runtime](https://github.com/AppImage/type2-runtime) used by the built-in builder. By default, the release pinned for the target architecture is downloaded, verified against its SHA-256 and cached.
+   */
+  runtime?: string | null;
+  /**
+   * The [update information](https://github.com/AppImage/AppImageSpec/blob/master/draft.md#update-information) embedded in the AppImage, used by tools like `AppImageUpdate` to find and apply delta updates. For example `zsync|https://example.com/app-latest-x86_64.AppImage.zsync` or `gh-releases-zsync|owner|repo|latest|app-*x86_64.AppImage.zsync`.
+   *
+   * When set, a `.zsync` file is also generated next to the AppImage, which must be uploaded alongside it. AppImages built with `linuxdeploy` get the update information from its appimage plugin, through the `UPDATE_INFORMATION` environment variable.
+   */
+  updateInformation?: string | null;
+  /**
+   * Whether to embed an OpenPGP signature of the AppImage, signed with the [`AppImageConfig::signing_key`], which can be checked by tools like `validate` and `AppImageUpdate`. Defaults to `false`.
+   *
+   * Only AppImages created by the built-in builder can be signed.
+   */
+  sign?: boolean;
+  /**
+   * Path to, or contents of, the ASCII-armored OpenPGP secret key used to sign the AppImage when [`AppImageConfig::sign`] is enabled.
+   *
+   * Defaults to the `CARGO_PACKAGER_GPG_PRIVATE_KEY` environment variable.
+   */
+  signingKey?: string | null;
 }
 /**
  * The Linux pacman configuration.
@@ -565,9 +819,369 @@ export interface PacmanConfig {
    * Source of the package to be stored at PKGBUILD. PKGBUILD is a bash script, so version can be referred as ${pkgver}
    */
   source?: string[] | null;
+  /**
+   * The licenses of the package, ideally as SPDX identifiers. By default, it is set to `custom`.
+   *
+   * See : <https://wiki.archlinux.org/title/PKGBUILD#license>
+   */
+  license?: string[] | null;
+  /**
+   * Optional dependencies of the app, each one as a package name optionally followed by a short description, for example `libnotify: desktop notifications`.
+   *
+   * See : <https://wiki.archlinux.org/title/PKGBUILD#optdepends>
+   */
+  optdepends?: string[] | null;
+  /**
+   * Files that contain user made changes and should be preserved during upgrades and removal of the package, as relative paths without a leading slash, for example `etc/app.conf`.
+   *
+   * See : <https://wiki.archlinux.org/title/PKGBUILD#backup>
+   */
+  backup?: string[] | null;
+  /**
+   * Path to the install script, defining functions like `post_install` and `pre_remove` that are run by pacman on package operations.
+   *
+   * See : <https://wiki.archlinux.org/title/PKGBUILD#install>
+   */
+  install?: string | null;
+  /**
+   * Whether to also generate a `.tar.gz` of the package contents and a `PKGBUILD` that can be used with `makepkg`, next to the `.pkg.tar.zst` package. Defaults to `false`.
+   */
+  generatePkgbuild?: boolean;
+  /**
+   * Configuration to generate a `PKGBUILD` and `.SRCINFO` ready to be published to the [AUR](https://aur.archlinux.org), in the `aur` directory next to the `.pkg.tar.zst` package.
+   */
+  aur?: AurConfig | null;
+}
+/**
+ * The [AUR](https://aur.archlinux.org) configuration.
+ *
+ * The generated `PKGBUILD` downloads the `.tar.gz` of the package contents, generated next to the `.pkg.tar.zst` package, from [`AurConfig::source_url`], so it needs to be uploaded there, for example as a GitHub release asset.
+ */
+export interface AurConfig {
+  /**
+   * The URL template of the `.tar.gz` of the package contents, for example `https://github.com/user/app/releases/download/v{{version}}/{{filename}}`.
+   *
+   * The following variables are available: - `version`: the app version. - `pkgver`: the pacman package version. - `arch`: the pacman package architecture. - `filename`: the file name of the `.tar.gz` of the package contents.
+   */
+  sourceUrl: string;
+  /**
+   * The name of the AUR package. By default, it is set to the pacman package name with a `-bin` suffix.
+   */
+  packageName?: string | null;
+}
+/**
+ * The Linux RPM configuration.
+ */
+export interface RpmConfig {
+  /**
+   * List of custom files to add to the RPM package. Maps a dir/file to a dir/file inside the RPM package.
+   */
+  files?: {
+    [k: string]: string;
+  } | null;
+  /**
+   * List of RPM dependencies, each entry is either a capability name or a capability name followed by a version constraint, e.g. `glibc >= 2.28`.
+   *
+   * See : <https://rpm-software-management.github.io/rpm/manual/dependencies.html>
+   */
+  depends?: Dependencies | null;
+  /**
+   * Additional capabilities that are provided by this app.
+   */
+  provides?: string[] | null;
+  /**
+   * Packages that conflict with the app.
+   */
+  conflicts?: string[] | null;
+  /**
+   * Obsolete packages that are replaced by this app. For example, if you rename any package.
+   */
+  obsoletes?: string[] | null;
+  /**
+   * The package release number, by default set to `1`.
+   */
+  release?: string | null;
+  /**
+   * The package epoch, by default no epoch is set.
+   */
+  epoch?: number | null;
+  /**
+   * The license of the package, ideally as an SPDX expression. By default, it is set to `Unknown`.
+   */
+  license?: string | null;
+  /**
+   * Name to use for the `Name` tag of the RPM package. Defaults to [`Config::product_name`] converted to kebab-case.
+   */
+  packageName?: string | null;
+  /**
+   * Path to a shell script that will be run before the package is installed (`%pre`).
+   */
+  preInstallScript?: string | null;
+  /**
+   * Path to a shell script that will be run after the package is installed (`%post`).
+   */
+  postInstallScript?: string | null;
+  /**
+   * Path to a shell script that will be run before the package is removed (`%preun`).
+   */
+  preRemoveScript?: string | null;
+  /**
+   * Path to a shell script that will be run after the package is removed (`%postun`).
+   */
+  postRemoveScript?: string | null;
+}
+/**
+ * The Linux Flatpak configuration.
+ *
+ * Building the Flatpak bundle requires `flatpak` and `flatpak-builder` to be installed, as well as the configured runtime and SDK.
+ */
+export interface FlatpakConfig {
+  /**
+   * The Flatpak application ID. Defaults to [`Config::identifier`].
+   */
+  appId?: string | null;
+  /**
+   * The runtime used by the app. Defaults to `org.freedesktop.Platform`.
+   */
+  runtime?: string | null;
+  /**
+   * The version of the runtime. Defaults to `24.08`.
+   */
+  runtimeVersion?: string | null;
+  /**
+   * The SDK used to build the app. Defaults to `org.freedesktop.Sdk`.
+   */
+  sdk?: string | null;
+  /**
+   * The arguments passed to `flatpak build-finish`, which set the app permissions. Defaults to `["--share=ipc", "--socket=fallback-x11", "--socket=wayland", "--device=dri"]`.
+   *
+   * See: <https://docs.flatpak.org/en/latest/sandbox-permissions.html>
+   */
+  finishArgs?: string[] | null;
+  /**
+   * The branch of the app in the bundle. Defaults to `master`.
+   */
+  branch?: string | null;
+  /**
+   * List of custom files to add to the Flatpak. Maps a dir/file to a dir/file inside the `/app` directory of the Flatpak.
+   */
+  files?: {
+    [k: string]: string;
+  } | null;
+}
+/**
+ * The Linux snap configuration.
+ *
+ * The snap is built without `snapcraft`, so the libraries the app needs must be provided by the [base](SnapConfig::base) snap, or added with [`SnapConfig::files`].
+ */
+export interface SnapConfig {
+  /**
+   * The snap name. Defaults to the main binary name converted to kebab-case.
+   */
+  name?: string | null;
+  /**
+   * A short summary of the snap, up to 78 characters. Defaults to [`Config::description`] or [`Config::product_name`].
+   */
+  summary?: string | null;
+  /**
+   * The base snap providing the run-time environment. Defaults to `core24`.
+   */
+  base?: string | null;
+  /**
+   * The confinement level of the snap. Defaults to [`SnapConfinement::Strict`].
+   */
+  confinement?: SnapConfinement | null;
+  /**
+   * The quality grade of the snap. Defaults to [`SnapGrade::Stable`].
+   */
+  grade?: SnapGrade | null;
+  /**
+   * The interfaces the app connects to. Defaults to `["desktop", "desktop-legacy", "wayland", "x11", "opengl", "home", "network"]`.
+   *
+   * See <https://snapcraft.io/docs/supported-interfaces>
+   */
+  plugs?: string[] | null;
+  /**
+   * The interfaces the app provides to other snaps.
+   */
+  slots?: string[] | null;
+  /**
+   * List of custom files to add to the snap. Maps a dir/file to a dir/file inside the snap.
+   */
+  files?: {
+    [k: string]: string;
+  } | null;
+}
+/**
+ * The Alpine Linux apk configuration.
+ */
+export interface ApkConfig {
+  /**
+   * The name of the package, defaults to the kebab-case [`Config::product_name`].
+   */
+  packageName?: string | null;
+  /**
+   * The package release number (`pkgrel`), appended to the version as `-r<release>`. Defaults to `0`.
+   */
+  release?: number | null;
+  /**
+   * The license of the package, as an SPDX license expression. Defaults to `custom`.
+   */
+  license?: string | null;
+  /**
+   * List of packages, shared objects (`so:libz.so.1`) or commands (`cmd:sh`) that must be installed for the app to run.
+   *
+   * See : <https://wiki.alpinelinux.org/wiki/APKBUILD_Reference#depends>
+   */
+  depends?: Dependencies | null;
+  /**
+   * Additional packages that are provided by this app.
+   *
+   * See : <https://wiki.alpinelinux.org/wiki/APKBUILD_Reference#provides>
+   */
+  provides?: string[] | null;
+  /**
+   * List of custom files to add to the apk package. Maps a dir/file to a dir/file inside the apk package.
+   */
+  files?: {
+    [k: string]: string;
+  } | null;
+  /**
+   * The `.pre-install` script, run before the package is installed.
+   *
+   * See : <https://wiki.alpinelinux.org/wiki/APKBUILD_Reference#install>
+   */
+  preInstall?: MaintainerScript | null;
+  /**
+   * The `.post-install` script, run after the package is installed.
+   */
+  postInstall?: MaintainerScript | null;
+  /**
+   * The `.pre-upgrade` script, run before the package is upgraded.
+   */
+  preUpgrade?: MaintainerScript | null;
+  /**
+   * The `.post-upgrade` script, run after the package is upgraded.
+   */
+  postUpgrade?: MaintainerScript | null;
+  /**
+   * The `.pre-deinstall` script, run before the package is removed.
+   */
+  preDeinstall?: MaintainerScript | null;
+  /**
+   * The `.post-deinstall` script, run after the package is removed.
+   */
+  postDeinstall?: MaintainerScript | null;
+  /**
+   * Path to, or contents of, the PEM encoded RSA private key used to sign the package, as generated by `abuild-keygen`.
+   *
+   * Defaults to the `CARGO_PACKAGER_APK_PRIVATE_KEY` environment variable, the package is not signed if neither is set.
+   */
+  signingKey?: string | null;
+  /**
+   * The file name of the public key that apk uses to verify the signature, which must be installed in `/etc/apk/keys`, for example `alice@example.com-5f4e3d2c.rsa.pub`.
+   *
+   * Defaults to the file name of [`ApkConfig::signing_key`] followed by `.pub` or `<package name>.rsa.pub` if the key is not a path. The public key is written next to the package using this name.
+   */
+  signingKeyName?: string | null;
+}
+/**
+ * The FreeBSD pkg configuration.
+ */
+export interface FreeBsdConfig {
+  /**
+   * The name of the package, defaults to the kebab-case [`Config::product_name`].
+   */
+  packageName?: string | null;
+  /**
+   * The categories of the package in the ports tree. Defaults to `misc`.
+   */
+  categories?: string[] | null;
+  /**
+   * The origin of the package, defaults to `<first category>/<package name>`.
+   */
+  origin?: string | null;
+  /**
+   * The ABI the package is built for, for example `FreeBSD:14:amd64`.
+   *
+   * Defaults to the FreeBSD version of the host when packaging on FreeBSD or FreeBSD 14 otherwise, with the architecture of [`Config::target_triple`].
+   */
+  abi?: string | null;
+  /**
+   * The licenses of the package, for example `MIT`. All of them apply if multiple are specified.
+   */
+  licenses?: string[] | null;
+  /**
+   * The packages that must be installed for the app to run.
+   */
+  depends?: FreeBsdDependency[] | null;
+  /**
+   * The compression of the package archive. Defaults to `zstd`.
+   */
+  compression?: FreeBsdCompression | null;
+  /**
+   * List of custom files to add to the FreeBSD package. Maps a dir/file to a dir/file inside the package, relative to the filesystem root.
+   */
+  files?: {
+    [k: string]: string;
+  } | null;
+  /**
+   * The `pre-install` script, run before the package is installed.
+   *
+   * See : <https://man.freebsd.org/cgi/man.cgi?query=pkg-create#MANIFEST_FILE_DETAILS>
+   */
+  preInstall?: MaintainerScript | null;
+  /**
+   * The `post-install` script, run after the package is installed.
+   */
+  postInstall?: MaintainerScript | null;
+  /**
+   * The `pre-deinstall` script, run before the package is removed.
+   */
+  preDeinstall?: MaintainerScript | null;
+  /**
+   * The `post-deinstall` script, run after the package is removed.
+   */
+  postDeinstall?: MaintainerScript | null;
+}
+/**
+ * A dependency of the FreeBSD package.
+ */
+export interface FreeBsdDependency {
+  /**
+   * The name of the package, for example `gtk3`.
+   */
+  name: string;
+  /**
+   * The origin of the package in the ports tree, for example `x11-toolkits/gtk30`.
+   */
+  origin: string;
+  /**
+   * The version of the package the app was built against, for example `3.24.43`.
+   */
+  version: string;
+}
+/**
+ * The portable archive configuration.
+ *
+ * The archive contains the app binaries, external binaries and resources next to each other, so the app can be run from wherever the archive is extracted.
+ */
+export interface ArchiveConfig {
+  /**
+   * The archive format. Defaults to `zip` when targeting Windows and `tar.gz` otherwise.
+   */
+  format?: ArchiveFormat | null;
+  /**
+   * Name of the directory containing the app files inside the archive. Set it to an empty string to put the files at the root of the archive.
+   *
+   * Defaults to the archive file name without its extension, `<main-binary-name>_<version>_<arch>`.
+   */
+  topLevelDir?: string | null;
 }
 /**
  * The wix format configuration
+ *
+ * On non-Windows hosts the MSI is generated without the WiX toolset, in which case [`Self::template`], the fragments, merge modules, element references and dialog images are not supported.
  */
 export interface WixConfig {
   /**
@@ -708,8 +1322,53 @@ export interface NsisConfig {
    */
   appdataPaths?: string[] | null;
 }
+/**
+ * The MSIX configuration.
+ */
+export interface MsixConfig {
+  /**
+   * The unique name of the package, defaults to [`Config::identifier`] with the characters other than ASCII letters, digits, `.` and `-` replaced with `-`.
+   *
+   * See : <https://learn.microsoft.com/en-us/uwp/schemas/appxpackage/uapmanifestschema/element-identity>
+   */
+  identityName?: string | null;
+  /**
+   * The publisher of the package, which must match the subject of the signing certificate, for example `CN=Contoso Software, O=Contoso Corporation, C=US`.
+   *
+   * Defaults to `CN=<publisher>` using [`Config::publisher`].
+   */
+  publisher?: string | null;
+  /**
+   * The publisher name displayed to users, defaults to [`Config::publisher`].
+   */
+  publisherDisplayName?: string | null;
+  /**
+   * The minimum Windows version the package runs on. Defaults to `10.0.17763.0`.
+   */
+  minVersion?: string | null;
+  /**
+   * The maximum Windows version the package was tested on. Defaults to `10.0.22621.0`.
+   */
+  maxVersionTested?: string | null;
+  /**
+   * The languages supported by the app, as BCP-47 language tags. Defaults to `en-us`.
+   */
+  languages?: string[] | null;
+  /**
+   * The background color of the app tiles, as a color name or `#RRGGBB`. Defaults to `transparent`.
+   */
+  backgroundColor?: string | null;
+  /**
+   * Additional capabilities requested by the app, for example `internetClient`. The `runFullTrust` capability required by desktop apps is always requested.
+   *
+   * See : <https://learn.microsoft.com/en-us/windows/uwp/packaging/app-capability-declarations>
+   */
+  capabilities?: string[] | null;
+}
 /**
  * The Apple Disk Image (.dmg) configuration.
+ *
+ * On non-macOS hosts the image is generated without `hdiutil`, as an HFS+ volume compressed into a UDIF image, and it is not codesigned.
  */
 export interface DmgConfig {
   /**
@@ -759,3 +1418,30 @@ export interface Size {
    */
   height: number;
 }
+/**
+ * The macOS installer package (.pkg) configuration.
+ *
+ * The package is a product archive installing the `.app` bundle, so it can be deployed through MDM solutions.
+ */
+export interface PkgConfig {
+  /**
+   * The folder the `.app` bundle is installed to. Defaults to `/Applications`.
+   */
+  installLocation?: string | null;
+  /**
+   * Path to a script executed before the app is installed.
+   *
+   * The script receives the package path, the install location and the target volume as arguments and a non-zero exit code aborts the installation.
+   */
+  preinstallScript?: string | null;
+  /**
+   * Path to a script executed after the app is installed.
+   */
+  postinstallScript?: string | null;
+  /**
+   * The `Developer ID Installer` identity signing the package, e.g. `"Developer ID Installer: TEAM_NAME (TEAM_ID)"`.
+   *
+   * It is loaded from the PKCS#12 certificate of [`MacOsConfig::signing_certificate`] (or the `APPLE_CERTIFICATE` and `APPLE_CERTIFICATE_PASSWORD` environment variables), which may also hold the identity signing the app.
+   */
+  signingIdentity?: string | null;
+}
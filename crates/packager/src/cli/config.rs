@@ -176,7 +176,18 @@ fn load_configs_from_cargo_workspace(cli: &super::Cli) -> Result<Vec<(Option<Pat
             }
 
             let mut cargo_out_dir = metadata.target_directory.as_std_path().to_path_buf();
-            if let Some(target_triple) = cli.target.as_ref().or(config.target_triple.as_ref()) {
+            let target_triple = cli
+                .target
+                .clone()
+                .or_else(|| config.target_triple.clone())
+                .or_else(|| {
+                    // universal binaries are packaged in their own target directory
+                    let formats = cli.formats.as_deref().or(config.formats.as_deref());
+                    config
+                        .uses_universal_target(formats)
+                        .then(|| crate::config::UNIVERSAL_TARGET_TRIPLE.to_string())
+                });
+            if let Some(target_triple) = target_triple {
                 cargo_out_dir.push(target_triple);
             }
             cargo_out_dir.push(profile);
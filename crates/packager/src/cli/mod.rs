@@ -15,6 +15,14 @@ use crate::{
 
 mod config;
 mod error;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod repo;
 mod signer;
 
 use self::error::{Error, Result};
@@ -22,6 +30,14 @@ use self::error::{Error, Result};
 #[derive(Debug, Clone, Subcommand)]
 enum Commands {
     Signer(signer::Options),
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    Repo(repo::Options),
 }
 
 #[derive(Parser, Debug)]
@@ -99,6 +115,14 @@ fn run_cli(cli: Cli) -> Result<()> {
     if let Some(command) = cli.command {
         match command {
             Commands::Signer(opts) => signer::command(opts)?,
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            Commands::Repo(opts) => repo::command(opts)?,
         }
         return Ok(());
     }
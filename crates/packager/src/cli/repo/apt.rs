@@ -0,0 +1,67 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::{fs, path::PathBuf};
+
+use clap::Parser;
+
+use crate::{
+    cli::{Error, Result},
+    repo::apt::AptRepositoryConfig,
+};
+
+#[derive(Debug, Clone, Parser)]
+#[clap(about = "Add Debian packages to an APT repository")]
+pub struct Options {
+    /// The repository directory, created if it doesn't exist.
+    dir: PathBuf,
+    /// The Debian packages to add.
+    #[clap(required = true)]
+    packages: Vec<PathBuf>,
+    /// The distribution suite. Defaults to `stable`.
+    #[clap(long)]
+    suite: Option<String>,
+    /// The component the packages are added to. Defaults to `main`.
+    #[clap(long)]
+    component: Option<String>,
+    /// The `Origin` field of the `Release` file.
+    #[clap(long)]
+    origin: Option<String>,
+    /// The `Label` field of the `Release` file.
+    #[clap(long)]
+    label: Option<String>,
+    /// The `Description` field of the `Release` file.
+    #[clap(long)]
+    description: Option<String>,
+    /// Load the ASCII-armored OpenPGP secret key used to sign the repository from a file or a string.
+    #[clap(long, env = "CARGO_PACKAGER_GPG_PRIVATE_KEY")]
+    gpg_key: Option<String>,
+    /// The password for the OpenPGP secret key.
+    #[clap(long, env = "CARGO_PACKAGER_GPG_PRIVATE_KEY_PASSWORD")]
+    gpg_key_password: Option<String>,
+}
+
+pub fn command(options: Options) -> Result<()> {
+    let signing_key = match options.gpg_key {
+        Some(path) if PathBuf::from(&path).exists() => Some(
+            fs::read_to_string(&path).map_err(|e| Error::IoWithPath(PathBuf::from(&path), e))?,
+        ),
+        key => key,
+    };
+
+    let config = AptRepositoryConfig {
+        suite: options.suite,
+        component: options.component,
+        origin: options.origin,
+        label: options.label,
+        description: options.description,
+        signing_key,
+        signing_key_password: options.gpg_key_password,
+    };
+    crate::repo::apt::add_packages(&config, &options.dir, &options.packages)?;
+
+    tracing::info!("Updated the APT repository at {}", options.dir.display());
+
+    Ok(())
+}
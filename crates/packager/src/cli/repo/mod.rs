@@ -0,0 +1,30 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use clap::{Parser, Subcommand};
+
+use super::Result;
+
+mod apt;
+mod pacman;
+
+#[derive(Debug, Clone, Subcommand)]
+enum Commands {
+    Apt(apt::Options),
+    Pacman(pacman::Options),
+}
+
+#[derive(Debug, Clone, Parser)]
+#[clap(about = "Create or update a package repository")]
+pub struct Options {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+pub fn command(options: Options) -> Result<()> {
+    match options.command {
+        Commands::Apt(opts) => apt::command(opts),
+        Commands::Pacman(opts) => pacman::command(opts),
+    }
+}
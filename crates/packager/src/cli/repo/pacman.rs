@@ -0,0 +1,36 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::{cli::Result, repo::pacman::PacmanRepositoryConfig};
+
+#[derive(Debug, Clone, Parser)]
+#[clap(about = "Add pacman packages to a pacman repository")]
+pub struct Options {
+    /// The repository directory, created if it doesn't exist.
+    dir: PathBuf,
+    /// The pacman packages to add.
+    #[clap(required = true)]
+    packages: Vec<PathBuf>,
+    /// The repository name, as used in `pacman.conf`. Defaults to the repository directory name.
+    #[clap(short, long)]
+    name: Option<String>,
+}
+
+pub fn command(options: Options) -> Result<()> {
+    let config = PacmanRepositoryConfig { name: options.name };
+    let databases = crate::repo::pacman::add_packages(&config, &options.dir, &options.packages)?;
+
+    for database in databases {
+        tracing::info!(
+            "Updated the pacman repository database {}",
+            database.display()
+        );
+    }
+
+    Ok(())
+}
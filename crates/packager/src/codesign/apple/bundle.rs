@@ -0,0 +1,378 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Signing of bundles: the `_CodeSignature/CodeResources` seal listing the hashes of the
+//! resources and nested code, covered by the signature of the main executable.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use regex::Regex;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use super::{
+    macho::{self, Entitlements, SignOptions},
+    Signer,
+};
+use crate::Error;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Include,
+    Optional,
+    Omit,
+    Nested,
+}
+
+/// A resource rule: the files whose path relative to the bundle contents matches the pattern
+/// get the action of the matching rule with the highest weight.
+struct Rule {
+    pattern: &'static str,
+    weight: u32,
+    action: Action,
+}
+
+const fn rule(pattern: &'static str, weight: u32, action: Action) -> Rule {
+    Rule {
+        pattern,
+        weight,
+        action,
+    }
+}
+
+/// The rules of the legacy `files` seal, as written by `codesign`.
+const RULES: &[Rule] = &[
+    rule("^Resources/", 1, Action::Include),
+    rule("^Resources/.*\\.lproj/", 1000, Action::Optional),
+    rule(
+        "^Resources/.*\\.lproj/locversion.plist$",
+        1100,
+        Action::Omit,
+    ),
+    rule("^Resources/Base\\.lproj/", 1010, Action::Include),
+    rule("^version.plist$", 1, Action::Include),
+];
+
+/// The rules of the `files2` seal, as written by `codesign`.
+const RULES2: &[Rule] = &[
+    rule(".*\\.dSYM($|/)", 11, Action::Include),
+    rule("^(.*/)?\\.DS_Store$", 2000, Action::Omit),
+    rule(
+        "^(Frameworks|SharedFrameworks|PlugIns|Plug-ins|XPCServices|Helpers|MacOS|Library/(Automator|Spotlight|LoginItems))/",
+        10,
+        Action::Nested,
+    ),
+    rule("^.*", 1, Action::Include),
+    rule("^Info\\.plist$", 20, Action::Omit),
+    rule("^PkgInfo$", 20, Action::Omit),
+    rule("^Resources/", 20, Action::Include),
+    rule("^Resources/.*\\.lproj/", 1000, Action::Optional),
+    rule("^Resources/.*\\.lproj/locversion.plist$", 1100, Action::Omit),
+    rule("^Resources/Base\\.lproj/", 1010, Action::Include),
+    rule("^[^/]+$", 10, Action::Nested),
+    rule("^embedded\\.provisionprofile$", 20, Action::Include),
+    rule("^version\\.plist$", 20, Action::Include),
+];
+
+struct Rules {
+    rules: &'static [Rule],
+    patterns: Vec<Regex>,
+}
+
+impl Rules {
+    fn new(rules: &'static [Rule]) -> Self {
+        Self {
+            rules,
+            patterns: rules
+                .iter()
+                .map(|r| Regex::new(r.pattern).expect("invalid resource rule"))
+                .collect(),
+        }
+    }
+
+    fn action(&self, path: &str) -> Option<Action> {
+        let mut matched = None::<&Rule>;
+        for (rule, pattern) in self.rules.iter().zip(&self.patterns) {
+            if pattern.is_match(path) && matched.is_none_or(|m| rule.weight > m.weight) {
+                matched = Some(rule);
+            }
+        }
+        matched.map(|r| r.action)
+    }
+
+    fn to_plist(&self) -> plist::Value {
+        let mut dict = plist::Dictionary::new();
+        let mut rules = self.rules.iter().collect::<Vec<_>>();
+        rules.sort_by_key(|r| r.pattern);
+        for rule in rules {
+            let value = if rule.action == Action::Include && rule.weight == 1 {
+                true.into()
+            } else {
+                let mut value = plist::Dictionary::new();
+                match rule.action {
+                    Action::Include => {}
+                    Action::Optional => {
+                        value.insert("optional".into(), true.into());
+                    }
+                    Action::Omit => {
+                        value.insert("omit".into(), true.into());
+                    }
+                    Action::Nested => {
+                        value.insert("nested".into(), true.into());
+                    }
+                }
+                value.insert("weight".into(), plist::Value::Real(rule.weight as f64));
+                value.into()
+            };
+            dict.insert(rule.pattern.into(), value);
+        }
+        dict.into()
+    }
+}
+
+/// The locations of the parts of a bundle.
+struct Bundle {
+    /// The directory holding the bundle contents, sealed by the signature.
+    contents: PathBuf,
+    info_plist: PathBuf,
+    executable: PathBuf,
+    identifier: String,
+}
+
+impl Bundle {
+    fn new(path: &Path) -> crate::Result<Self> {
+        let (contents, info_plist) = if path.join("Contents/Info.plist").exists() {
+            (path.join("Contents"), PathBuf::from("Info.plist"))
+        } else if path.join("Versions/Current").exists() {
+            // versioned frameworks are signed through their current version
+            let contents = dunce::canonicalize(path.join("Versions/Current"))?;
+            (contents, PathBuf::from("Resources/Info.plist"))
+        } else if path.join("Resources/Info.plist").exists() {
+            (path.to_path_buf(), PathBuf::from("Resources/Info.plist"))
+        } else {
+            (path.to_path_buf(), PathBuf::from("Info.plist"))
+        };
+        let info_plist = contents.join(info_plist);
+        let info = plist::Value::from_file(&info_plist)?;
+        let info = info.as_dictionary();
+        let value = |key: &str| {
+            info.and_then(|i| i.get(key))
+                .and_then(|v| v.as_string())
+                .map(ToString::to_string)
+                .ok_or_else(|| {
+                    Error::UnsupportedMachO(
+                        path.to_path_buf(),
+                        format!("missing {key} in {}", info_plist.display()),
+                    )
+                })
+        };
+        let executable = value("CFBundleExecutable")?;
+        let identifier = value("CFBundleIdentifier")?;
+        let executable = if contents.ends_with("Contents") {
+            contents.join("MacOS").join(executable)
+        } else {
+            contents.join(executable)
+        };
+        Ok(Self {
+            contents,
+            info_plist,
+            executable,
+            identifier,
+        })
+    }
+}
+
+/// Returns the main executable of a bundle.
+pub(super) fn executable(path: &Path) -> crate::Result<PathBuf> {
+    Bundle::new(path).map(|b| b.executable)
+}
+
+/// Seals the resources of the bundle at `path` and signs its main executable.
+pub(super) fn sign(
+    path: &Path,
+    signer: &Signer,
+    runtime: bool,
+    entitlements: Option<&Entitlements>,
+) -> crate::Result<()> {
+    let bundle = Bundle::new(path)?;
+    let code_resources = code_resources(&bundle, signer)?;
+
+    let signature_dir = bundle.contents.join("_CodeSignature");
+    fs::create_dir_all(&signature_dir).map_err(|e| Error::IoWithPath(signature_dir.clone(), e))?;
+    let code_resources_path = signature_dir.join("CodeResources");
+    fs::write(&code_resources_path, &code_resources)
+        .map_err(|e| Error::IoWithPath(code_resources_path, e))?;
+
+    let info_plist = fs::read(&bundle.info_plist)
+        .map_err(|e| Error::IoWithPath(bundle.info_plist.clone(), e))?;
+    super::sign_file(
+        &bundle.executable,
+        &SignOptions {
+            identifier: &bundle.identifier,
+            runtime,
+            entitlements,
+            info_plist: Some(&info_plist),
+            code_resources: Some(&code_resources),
+        },
+        signer,
+    )
+}
+
+/// Lists the hashes of the bundle resources and the signatures of its nested code.
+fn code_resources(bundle: &Bundle, signer: &Signer) -> crate::Result<Vec<u8>> {
+    let rules = Rules::new(RULES);
+    let rules2 = Rules::new(RULES2);
+    let mut files = BTreeMap::new();
+    let mut files2 = BTreeMap::new();
+
+    let mut entries = walkdir::WalkDir::new(&bundle.contents)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter();
+    while let Some(entry) = entries.next() {
+        let entry = entry?;
+        let path = entry.path();
+        let relative = path
+            .strip_prefix(&bundle.contents)?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if relative == "_CodeSignature" || path == bundle.executable {
+            entries.skip_current_dir();
+            continue;
+        }
+        let action = rules2.action(&relative);
+
+        if entry.file_type().is_dir() {
+            // nested bundles are sealed by the signature of their main executable
+            if action == Some(Action::Nested) && path.extension().is_some() {
+                if let Ok(executable) = executable(path) {
+                    entries.skip_current_dir();
+                    if let Some(nested) = nested_code(&executable, signer)? {
+                        files2.insert(relative, nested);
+                    }
+                }
+            }
+            continue;
+        }
+
+        if entry.file_type().is_symlink() {
+            if matches!(
+                action,
+                Some(Action::Include | Action::Optional | Action::Nested)
+            ) {
+                let target = fs::read_link(path).map_err(|e| Error::IoWithPath(path.into(), e))?;
+                let mut value = plist::Dictionary::new();
+                value.insert(
+                    "symlink".into(),
+                    target.to_string_lossy().into_owned().into(),
+                );
+                files2.insert(relative, value.into());
+            }
+            continue;
+        }
+
+        let action = match action {
+            Some(Action::Nested) => match nested_code(path, signer)? {
+                Some(nested) => {
+                    files2.insert(relative, nested);
+                    continue;
+                }
+                // not code, sealed as a regular resource
+                None => Action::Include,
+            },
+            Some(Action::Omit) | None => continue,
+            Some(action) => action,
+        };
+
+        let data = fs::read(path).map_err(|e| Error::IoWithPath(path.into(), e))?;
+        let sha1 = Sha1::digest(&data).to_vec();
+        let optional = action == Action::Optional;
+
+        let mut value = plist::Dictionary::new();
+        value.insert("hash".into(), plist::Value::Data(sha1.clone()));
+        value.insert(
+            "hash2".into(),
+            plist::Value::Data(Sha256::digest(&data).to_vec()),
+        );
+        if optional {
+            value.insert("optional".into(), true.into());
+        }
+        files2.insert(relative.clone(), value.into());
+
+        match rules.action(&relative) {
+            Some(Action::Include) => {
+                files.insert(relative, plist::Value::Data(sha1));
+            }
+            Some(Action::Optional) => {
+                let mut value = plist::Dictionary::new();
+                value.insert("hash".into(), plist::Value::Data(sha1));
+                value.insert("optional".into(), true.into());
+                files.insert(relative, value.into());
+            }
+            _ => {}
+        }
+    }
+
+    let mut dict = plist::Dictionary::new();
+    dict.insert(
+        "files".into(),
+        plist::Value::Dictionary(files.into_iter().collect()),
+    );
+    dict.insert(
+        "files2".into(),
+        plist::Value::Dictionary(files2.into_iter().collect()),
+    );
+    dict.insert("rules".into(), rules.to_plist());
+    dict.insert("rules2".into(), rules2.to_plist());
+    let mut out = Vec::new();
+    plist::Value::Dictionary(dict).to_writer_xml(&mut out)?;
+    Ok(out)
+}
+
+/// Returns the seal of a nested binary, or nothing if the file is not a signed Mach-O binary.
+fn nested_code(path: &Path, signer: &Signer) -> crate::Result<Option<plist::Value>> {
+    let data = fs::read(path).map_err(|e| Error::IoWithPath(path.into(), e))?;
+    if !macho::is_macho(&data) {
+        return Ok(None);
+    }
+    let Some(signature) = macho::read_signature(path, &data)? else {
+        return Ok(None);
+    };
+    let mut value = plist::Dictionary::new();
+    value.insert(
+        "cdhash".into(),
+        plist::Value::Data(signature.cdhash.to_vec()),
+    );
+    value.insert(
+        "requirement".into(),
+        signer.designated_requirement(&signature.identifier)?.into(),
+    );
+    Ok(Some(value.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_heaviest_rule() {
+        let rules = Rules::new(RULES2);
+        assert!(rules.action("Info.plist") == Some(Action::Omit));
+        assert!(rules.action("MacOS/helper") == Some(Action::Nested));
+        assert!(rules.action("Resources/icon.icns") == Some(Action::Include));
+        assert!(rules.action("Resources/en.lproj/a.strings") == Some(Action::Optional));
+        assert!(rules.action("Resources/Base.lproj/a.strings") == Some(Action::Include));
+        assert!(rules.action("Resources/a/.DS_Store") == Some(Action::Omit));
+
+        let rules = Rules::new(RULES);
+        assert!(rules.action("MacOS/helper").is_none());
+        assert!(rules.action("version.plist") == Some(Action::Include));
+    }
+}
@@ -0,0 +1,634 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Embedded code signatures of Mach-O binaries, thin or universal.
+//!
+//! The signature is a SuperBlob appended to the `__LINKEDIT` segment and referenced by the
+//! `LC_CODE_SIGNATURE` load command. It holds the CodeDirectory with the hashes of every page
+//! of the binary and of the bundle resources, the requirements, the entitlements and the CMS
+//! signature of the CodeDirectory.
+
+use std::path::Path;
+
+use cms::signed_data::EncapsulatedContentInfo;
+use der::{
+    asn1::{ObjectIdentifier, OctetString},
+    Any, Encode, Sequence, Tag, TagNumber,
+};
+use sha2::{Digest, Sha256};
+
+use super::Signer;
+use crate::{codesign::identity, package::universal, Error};
+
+const MH_MAGIC_64: u32 = 0xfeedfacf;
+const MH_EXECUTE: u32 = 2;
+const LC_SEGMENT_64: u32 = 0x19;
+const LC_CODE_SIGNATURE: u32 = 0x1d;
+const CPU_TYPE_ARM64: u32 = 0x0100000c;
+
+const CSMAGIC_REQUIREMENTS: u32 = 0xfade0c01;
+const CSMAGIC_CODEDIRECTORY: u32 = 0xfade0c02;
+const CSMAGIC_EMBEDDED_SIGNATURE: u32 = 0xfade0cc0;
+const CSMAGIC_EMBEDDED_ENTITLEMENTS: u32 = 0xfade7171;
+const CSMAGIC_EMBEDDED_DER_ENTITLEMENTS: u32 = 0xfade7172;
+const CSMAGIC_BLOBWRAPPER: u32 = 0xfade0b01;
+
+const CSSLOT_CODEDIRECTORY: u32 = 0;
+const CSSLOT_INFOSLOT: u32 = 1;
+const CSSLOT_REQUIREMENTS: u32 = 2;
+const CSSLOT_RESOURCEDIR: u32 = 3;
+const CSSLOT_ENTITLEMENTS: u32 = 5;
+const CSSLOT_DER_ENTITLEMENTS: u32 = 7;
+const CSSLOT_SIGNATURESLOT: u32 = 0x10000;
+
+/// CodeDirectory version with the executable segment fields.
+const CODE_DIRECTORY_VERSION: u32 = 0x20400;
+const CODE_DIRECTORY_HEADER_SIZE: usize = 88;
+const CS_RUNTIME: u32 = 0x10000;
+const CS_EXECSEG_MAIN_BINARY: u64 = 1;
+const CS_HASHTYPE_SHA256: u8 = 2;
+const HASH_SIZE: usize = 32;
+const PAGE_SIZE_LOG2: u8 = 12;
+const PAGE_SIZE: usize = 1 << PAGE_SIZE_LOG2;
+
+/// The CDHashes attributes of the CMS signature, binding it to the CodeDirectory hashes.
+const APPLE_CDHASHES: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113635.100.9.1");
+const APPLE_CDHASHES2: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113635.100.9.2");
+
+/// An entry of a dictionary of the DER entitlements.
+#[derive(Sequence)]
+struct Entitlement {
+    key: String,
+    value: Any,
+}
+
+/// A CodeDirectory hash of the `APPLE_CDHASHES2` attribute.
+#[derive(Sequence)]
+struct CdHash {
+    algorithm: ObjectIdentifier,
+    hash: OctetString,
+}
+
+/// Returns whether `data` starts with the magic number of a Mach-O or universal binary.
+pub(super) fn is_macho(data: &[u8]) -> bool {
+    data.get(0..4).is_some_and(|magic| {
+        [0xfeedface, 0xfeedfacf, 0xcafebabe, 0xcefaedfe, 0xcffaedfe]
+            .contains(&u32::from_be_bytes(magic.try_into().unwrap()))
+    })
+}
+
+/// The entitlements embedded in the signature, as XML and DER encoded property lists.
+pub(super) struct Entitlements {
+    xml: Vec<u8>,
+    der: Vec<u8>,
+}
+
+impl Entitlements {
+    pub(super) fn from_file(path: &Path) -> crate::Result<Self> {
+        let plist = plist::Value::from_file(path)?;
+        let mut xml = Vec::new();
+        plist.to_writer_xml(&mut xml)?;
+        // [APPLICATION 16] { version, entitlements }
+        let der = Any::new(
+            Tag::Application {
+                constructed: true,
+                number: TagNumber::new(16),
+            },
+            [1u8.to_der()?, der_entitlement(&plist)?.to_der()?].concat(),
+        )?
+        .to_der()?;
+        Ok(Self { xml, der })
+    }
+}
+
+/// Encodes an entitlement value the way the code signing DER entitlements expect it.
+fn der_entitlement(value: &plist::Value) -> der::Result<Any> {
+    match value {
+        plist::Value::Boolean(value) => Any::encode_from(value),
+        plist::Value::Integer(value) => match value.as_unsigned() {
+            Some(value) => Any::encode_from(&value),
+            None => Any::encode_from(&value.as_signed().unwrap_or_default()),
+        },
+        plist::Value::String(value) => Any::encode_from(value),
+        plist::Value::Array(values) => Any::encode_from(
+            &values
+                .iter()
+                .map(der_entitlement)
+                .collect::<der::Result<Vec<_>>>()?,
+        ),
+        plist::Value::Dictionary(dict) => {
+            // a [CONTEXT 16] set of { key, value } sequences, sorted by key
+            let mut entries = dict.iter().collect::<Vec<_>>();
+            entries.sort_by_key(|(key, _)| *key);
+            let mut content = Vec::new();
+            for (key, value) in entries {
+                Entitlement {
+                    key: key.clone(),
+                    value: der_entitlement(value)?,
+                }
+                .encode_to_vec(&mut content)?;
+            }
+            Any::new(
+                Tag::ContextSpecific {
+                    constructed: true,
+                    number: TagNumber::new(16),
+                },
+                content,
+            )
+        }
+        _ => Ok(Any::null()),
+    }
+}
+
+/// What the signature of a binary covers, besides its own contents.
+pub(super) struct SignOptions<'a> {
+    pub identifier: &'a str,
+    /// Enables the hardened runtime.
+    pub runtime: bool,
+    pub entitlements: Option<&'a Entitlements>,
+    /// The Info.plist and `_CodeSignature/CodeResources` of the bundle,
+    /// when signing its main executable.
+    pub info_plist: Option<&'a [u8]>,
+    pub code_resources: Option<&'a [u8]>,
+}
+
+/// Signs a thin or universal binary, returning the signed binary.
+pub(super) fn sign(
+    path: &Path,
+    data: &[u8],
+    options: &SignOptions,
+    signer: &Signer,
+) -> crate::Result<Vec<u8>> {
+    if !universal::is_fat(data) {
+        return sign_slice(path, data, options, signer);
+    }
+
+    let slices = universal::read_slices(data).map_err(|e| unsupported(path, e))?;
+    let signed = slices
+        .iter()
+        .map(|slice| sign_slice(path, slice.data, options, signer))
+        .collect::<crate::Result<Vec<_>>>()?;
+    universal::write_fat(
+        slices
+            .iter()
+            .zip(&signed)
+            .map(|(slice, signed)| universal::Slice {
+                data: signed,
+                ..*slice
+            })
+            .collect(),
+    )
+    .map_err(|e| unsupported(path, e))
+}
+
+fn unsupported(path: &Path, message: String) -> Error {
+    Error::UnsupportedMachO(path.to_path_buf(), message)
+}
+
+fn truncated(path: &Path) -> Error {
+    Error::UnsupportedMachO(path.to_path_buf(), "truncated file".into())
+}
+
+fn align(value: usize, alignment: usize) -> usize {
+    value.div_ceil(alignment) * alignment
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn write_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn write_u64(data: &mut [u8], offset: usize, value: u64) {
+    data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+/// The load commands of a 64-bit binary relevant to its signature.
+struct Layout {
+    cputype: u32,
+    filetype: u32,
+    ncmds: u32,
+    sizeofcmds: u32,
+    /// Offset of the `__LINKEDIT` segment command.
+    linkedit: usize,
+    /// File offset and size of the `__TEXT` segment.
+    text: (u64, u64),
+    /// Offset of the `LC_CODE_SIGNATURE` command.
+    code_signature: Option<usize>,
+    /// The lowest file offset of a section, limiting the growth of the load commands.
+    first_section: usize,
+}
+
+fn layout(path: &Path, data: &[u8]) -> crate::Result<Layout> {
+    let unsupported = |message: &str| Error::UnsupportedMachO(path.to_path_buf(), message.into());
+    if data.len() < 32 || read_u32(data, 0) != MH_MAGIC_64 {
+        return Err(unsupported(
+            "only 64-bit little endian binaries can be signed",
+        ));
+    }
+    let ncmds = read_u32(data, 16);
+    let sizeofcmds = read_u32(data, 20);
+    if 32 + sizeofcmds as usize > data.len() {
+        return Err(truncated(path));
+    }
+
+    let mut linkedit = None;
+    let mut text = None;
+    let mut code_signature = None;
+    let mut first_section = data.len();
+    let mut offset = 32;
+    for _ in 0..ncmds {
+        if offset + 8 > 32 + sizeofcmds as usize {
+            return Err(truncated(path));
+        }
+        let cmd = read_u32(data, offset);
+        let cmdsize = read_u32(data, offset + 4) as usize;
+        match cmd {
+            LC_SEGMENT_64 => {
+                let name = &data[offset + 8..offset + 24];
+                let name = &name[..name.iter().position(|b| *b == 0).unwrap_or(16)];
+                match name {
+                    b"__LINKEDIT" => linkedit = Some(offset),
+                    b"__TEXT" => {
+                        text = Some((read_u64(data, offset + 40), read_u64(data, offset + 48)))
+                    }
+                    _ => {}
+                }
+                let nsects = read_u32(data, offset + 64) as usize;
+                for section in 0..nsects {
+                    let section = offset + 72 + section * 80;
+                    let file_offset = read_u32(data, section + 48) as usize;
+                    // zero fill sections have no contents in the file
+                    let zero_fill =
+                        matches!(read_u32(data, section + 64) & 0xff, 0x01 | 0x0c | 0x12);
+                    if file_offset != 0 && !zero_fill {
+                        first_section = first_section.min(file_offset);
+                    }
+                }
+            }
+            LC_CODE_SIGNATURE => code_signature = Some(offset),
+            _ => {}
+        }
+        offset += cmdsize;
+    }
+
+    Ok(Layout {
+        cputype: read_u32(data, 4),
+        filetype: read_u32(data, 12),
+        ncmds,
+        sizeofcmds,
+        linkedit: linkedit.ok_or_else(|| unsupported("missing __LINKEDIT segment"))?,
+        text: text.ok_or_else(|| unsupported("missing __TEXT segment"))?,
+        code_signature,
+        first_section,
+    })
+}
+
+fn sign_slice(
+    path: &Path,
+    data: &[u8],
+    options: &SignOptions,
+    signer: &Signer,
+) -> crate::Result<Vec<u8>> {
+    let mut layout = layout(path, data)?;
+
+    // drop the previous signature, the code ends where it started
+    let code_end = match layout.code_signature {
+        Some(command) => read_u32(data, command + 8) as usize,
+        None => data.len(),
+    };
+    let mut out = data[..code_end.min(data.len())].to_vec();
+
+    let command = match layout.code_signature {
+        Some(command) => command,
+        None => {
+            let command = 32 + layout.sizeofcmds as usize;
+            if command + 16 > layout.first_section
+                || out[command..command + 16].iter().any(|b| *b != 0)
+            {
+                return Err(Error::UnsupportedMachO(
+                    path.to_path_buf(),
+                    "no room left for the code signature load command".into(),
+                ));
+            }
+            write_u32(&mut out, command, LC_CODE_SIGNATURE);
+            write_u32(&mut out, command + 4, 16);
+            layout.ncmds += 1;
+            layout.sizeofcmds += 16;
+            write_u32(&mut out, 16, layout.ncmds);
+            write_u32(&mut out, 20, layout.sizeofcmds);
+            command
+        }
+    };
+
+    let code_limit = align(out.len(), 16);
+    out.resize(code_limit, 0);
+
+    let requirements = requirements();
+    let entitlements = options.entitlements.map(|e| {
+        (
+            blob(CSMAGIC_EMBEDDED_ENTITLEMENTS, &e.xml),
+            blob(CSMAGIC_EMBEDDED_DER_ENTITLEMENTS, &e.der),
+        )
+    });
+
+    let mut special_slots = vec![None; CSSLOT_DER_ENTITLEMENTS as usize];
+    special_slots[CSSLOT_INFOSLOT as usize - 1] = options.info_plist.map(sha256);
+    special_slots[CSSLOT_REQUIREMENTS as usize - 1] = Some(sha256(&requirements));
+    special_slots[CSSLOT_RESOURCEDIR as usize - 1] = options.code_resources.map(sha256);
+    if let Some((xml, der)) = &entitlements {
+        special_slots[CSSLOT_ENTITLEMENTS as usize - 1] = Some(sha256(xml));
+        special_slots[CSSLOT_DER_ENTITLEMENTS as usize - 1] = Some(sha256(der));
+    }
+    let special_count = special_slots
+        .iter()
+        .rposition(Option::is_some)
+        .map(|i| i + 1)
+        .unwrap_or_default();
+    special_slots.truncate(special_count);
+
+    // the signature size is part of the hashed load commands, so it is reserved upfront
+    let page_count = code_limit.div_ceil(PAGE_SIZE);
+    let team_id = signer.team_id.as_deref();
+    let code_directory_size = CODE_DIRECTORY_HEADER_SIZE
+        + options.identifier.len()
+        + 1
+        + team_id.map(|t| t.len() + 1).unwrap_or_default()
+        + (special_count + page_count) * HASH_SIZE;
+    let mut certificates_size = 0;
+    for certificate in &signer.identity.certificates {
+        certificates_size += u32::from(certificate.encoded_len()?) as usize;
+    }
+    let cms_size = certificates_size
+        + 4096
+        + if signer.timestamp_url.is_some() {
+            16384
+        } else {
+            0
+        };
+    let signature_size = align(
+        12 + 5 * 8
+            + code_directory_size
+            + requirements.len()
+            + entitlements
+                .as_ref()
+                .map(|(xml, der)| xml.len() + der.len())
+                .unwrap_or_default()
+            + 8
+            + cms_size,
+        16,
+    );
+
+    write_u32(&mut out, command + 8, code_limit as u32);
+    write_u32(&mut out, command + 12, signature_size as u32);
+    let linkedit = layout.linkedit;
+    let linkedit_offset = read_u64(&out, linkedit + 40);
+    let linkedit_size = (code_limit + signature_size) as u64 - linkedit_offset;
+    let segment_alignment = if layout.cputype == CPU_TYPE_ARM64 {
+        0x4000
+    } else {
+        0x1000
+    };
+    write_u64(
+        &mut out,
+        linkedit + 32,
+        align(linkedit_size as usize, segment_alignment) as u64,
+    );
+    write_u64(&mut out, linkedit + 48, linkedit_size);
+
+    let code_directory = code_directory(
+        &out,
+        options,
+        team_id,
+        &special_slots,
+        (
+            layout.text.0,
+            layout.text.1,
+            if layout.filetype == MH_EXECUTE {
+                CS_EXECSEG_MAIN_BINARY
+            } else {
+                0
+            },
+        ),
+    );
+    let cms = cms_signature(&code_directory, signer)?;
+
+    let mut blobs = vec![
+        (CSSLOT_CODEDIRECTORY, code_directory),
+        (CSSLOT_REQUIREMENTS, requirements),
+    ];
+    if let Some((xml, der)) = entitlements {
+        blobs.push((CSSLOT_ENTITLEMENTS, xml));
+        blobs.push((CSSLOT_DER_ENTITLEMENTS, der));
+    }
+    blobs.push((CSSLOT_SIGNATURESLOT, blob(CSMAGIC_BLOBWRAPPER, &cms)));
+    let mut signature = super_blob(&blobs);
+    if signature.len() > signature_size {
+        return Err(Error::UnsupportedMachO(
+            path.to_path_buf(),
+            format!(
+                "the signature needs {} bytes but only {signature_size} were reserved",
+                signature.len()
+            ),
+        ));
+    }
+    signature.resize(signature_size, 0);
+    out.extend_from_slice(&signature);
+    Ok(out)
+}
+
+fn sha256(data: &[u8]) -> [u8; HASH_SIZE] {
+    Sha256::digest(data).into()
+}
+
+/// Wraps data in a code signing blob, made of its magic and length.
+fn blob(magic: u32, data: &[u8]) -> Vec<u8> {
+    let mut blob = Vec::with_capacity(data.len() + 8);
+    blob.extend_from_slice(&magic.to_be_bytes());
+    blob.extend_from_slice(&(data.len() as u32 + 8).to_be_bytes());
+    blob.extend_from_slice(data);
+    blob
+}
+
+fn super_blob(blobs: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let header_size = 12 + 8 * blobs.len();
+    let size = header_size + blobs.iter().map(|(_, b)| b.len()).sum::<usize>();
+    let mut out = Vec::with_capacity(size);
+    out.extend_from_slice(&CSMAGIC_EMBEDDED_SIGNATURE.to_be_bytes());
+    out.extend_from_slice(&(size as u32).to_be_bytes());
+    out.extend_from_slice(&(blobs.len() as u32).to_be_bytes());
+    let mut offset = header_size;
+    for (slot, blob) in blobs {
+        out.extend_from_slice(&slot.to_be_bytes());
+        out.extend_from_slice(&(offset as u32).to_be_bytes());
+        offset += blob.len();
+    }
+    for (_, blob) in blobs {
+        out.extend_from_slice(blob);
+    }
+    out
+}
+
+/// An empty requirement set, the designated requirement being derived from the certificate.
+fn requirements() -> Vec<u8> {
+    blob(CSMAGIC_REQUIREMENTS, &0u32.to_be_bytes())
+}
+
+fn code_directory(
+    code: &[u8],
+    options: &SignOptions,
+    team_id: Option<&str>,
+    special_slots: &[Option<[u8; HASH_SIZE]>],
+    (exec_seg_base, exec_seg_limit, exec_seg_flags): (u64, u64, u64),
+) -> Vec<u8> {
+    let page_count = code.len().div_ceil(PAGE_SIZE);
+    let identifier_offset = CODE_DIRECTORY_HEADER_SIZE;
+    let team_offset = identifier_offset + options.identifier.len() + 1;
+    let hash_offset = team_offset
+        + team_id.map(|t| t.len() + 1).unwrap_or_default()
+        + special_slots.len() * HASH_SIZE;
+    let size = hash_offset + page_count * HASH_SIZE;
+
+    let mut cd = Vec::with_capacity(size);
+    let flags = if options.runtime { CS_RUNTIME } else { 0 };
+    for value in [
+        CSMAGIC_CODEDIRECTORY,
+        size as u32,
+        CODE_DIRECTORY_VERSION,
+        flags,
+        hash_offset as u32,
+        identifier_offset as u32,
+        special_slots.len() as u32,
+        page_count as u32,
+        code.len() as u32,
+    ] {
+        cd.extend_from_slice(&value.to_be_bytes());
+    }
+    cd.extend_from_slice(&[HASH_SIZE as u8, CS_HASHTYPE_SHA256, 0, PAGE_SIZE_LOG2]);
+    // spare2 and scatter offset
+    cd.extend_from_slice(&[0; 8]);
+    cd.extend_from_slice(&(team_id.map(|_| team_offset as u32).unwrap_or_default()).to_be_bytes());
+    // spare3 and 64-bit code limit
+    cd.extend_from_slice(&[0; 12]);
+    cd.extend_from_slice(&exec_seg_base.to_be_bytes());
+    cd.extend_from_slice(&exec_seg_limit.to_be_bytes());
+    cd.extend_from_slice(&exec_seg_flags.to_be_bytes());
+    debug_assert_eq!(cd.len(), CODE_DIRECTORY_HEADER_SIZE);
+
+    cd.extend_from_slice(options.identifier.as_bytes());
+    cd.push(0);
+    if let Some(team_id) = team_id {
+        cd.extend_from_slice(team_id.as_bytes());
+        cd.push(0);
+    }
+    // the special slots are stored in reverse order before the code slots
+    for slot in special_slots.iter().rev() {
+        cd.extend_from_slice(&slot.unwrap_or_default());
+    }
+    for page in code.chunks(PAGE_SIZE) {
+        cd.extend_from_slice(&sha256(page));
+    }
+    cd
+}
+
+/// Signs the CodeDirectory, a detached CMS signature listing its hash in the signed attributes.
+fn cms_signature(code_directory: &[u8], signer: &Signer) -> crate::Result<Vec<u8>> {
+    let hash = sha256(code_directory);
+
+    let mut cdhashes = plist::Dictionary::new();
+    cdhashes.insert(
+        "cdhashes".into(),
+        plist::Value::Array(vec![plist::Value::Data(hash[..20].to_vec())]),
+    );
+    let mut cdhashes_plist = Vec::new();
+    plist::Value::Dictionary(cdhashes).to_writer_xml(&mut cdhashes_plist)?;
+
+    signer.identity.sign(
+        EncapsulatedContentInfo {
+            econtent_type: identity::DATA,
+            econtent: None,
+        },
+        Some(&hash),
+        vec![
+            identity::attribute(APPLE_CDHASHES, &OctetString::new(cdhashes_plist)?)?,
+            identity::attribute(
+                APPLE_CDHASHES2,
+                &CdHash {
+                    algorithm: identity::SHA256,
+                    hash: OctetString::new(hash)?,
+                },
+            )?,
+        ],
+        signer
+            .timestamp_url
+            .as_deref()
+            .map(|url| identity::Timestamp {
+                url,
+                attribute: identity::TIMESTAMP_TOKEN,
+            }),
+    )
+}
+
+/// Information about the signature of a binary, needed to reference it from its bundle.
+pub(super) struct CodeSignature {
+    /// The truncated hash of the CodeDirectory.
+    pub cdhash: [u8; 20],
+    pub identifier: String,
+}
+
+/// Reads the signature of a binary, of its first slice for a universal binary.
+pub(super) fn read_signature(path: &Path, data: &[u8]) -> crate::Result<Option<CodeSignature>> {
+    let data = if universal::is_fat(data) {
+        universal::read_slices(data)
+            .map_err(|e| unsupported(path, e))?
+            .first()
+            .map_or(data, |slice| slice.data)
+    } else {
+        data
+    };
+    let layout = layout(path, data)?;
+    let Some(command) = layout.code_signature else {
+        return Ok(None);
+    };
+    let offset = read_u32(data, command + 8) as usize;
+    let size = read_u32(data, command + 12) as usize;
+    let Some(signature) = data.get(offset..offset + size) else {
+        return Err(truncated(path));
+    };
+
+    let be = |data: &[u8], offset: usize| {
+        data.get(offset..offset + 4)
+            .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+            .ok_or_else(|| truncated(path))
+    };
+    if be(signature, 0)? != CSMAGIC_EMBEDDED_SIGNATURE {
+        return Ok(None);
+    }
+    for index in 0..be(signature, 8)? as usize {
+        if be(signature, 12 + index * 8)? != CSSLOT_CODEDIRECTORY {
+            continue;
+        }
+        let offset = be(signature, 16 + index * 8)? as usize;
+        let len = be(signature, offset + 4)? as usize;
+        let code_directory = signature
+            .get(offset..offset + len)
+            .ok_or_else(|| truncated(path))?;
+        let identifier_offset = be(code_directory, 20)? as usize;
+        let identifier = code_directory
+            .get(identifier_offset..)
+            .and_then(|i| i.split(|b| *b == 0).next())
+            .ok_or_else(|| truncated(path))?;
+        return Ok(Some(CodeSignature {
+            cdhash: sha256(code_directory)[..20].try_into().unwrap(),
+            identifier: String::from_utf8_lossy(identifier).into_owned(),
+        }));
+    }
+    Ok(None)
+}
@@ -0,0 +1,238 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Code signing and notarization of macOS apps without the Xcode command line tools.
+//!
+//! The signatures are embedded in the Mach-O binaries in-process, using the identity of the
+//! PKCS#12 certificate from [`crate::config::MacOsConfig::signing_certificate`], and the apps
+//! are notarized through the web API of the notary service.
+
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use der::{Decode, Encode};
+use sha1::{Digest, Sha1};
+use x509_cert::Certificate;
+
+use super::identity::{self, SigningIdentity};
+pub use super::{notarize_auth, signing_certificate, SignTarget};
+use crate::{config::MacOsNotarizationCredentials, util, Config, Error};
+
+mod bundle;
+mod macho;
+mod notary;
+
+const TIMESTAMP_URL: &str = "http://timestamp.apple.com/ts01";
+
+/// The identity signing the code, with the parameters of the signatures.
+struct Signer {
+    identity: SigningIdentity,
+    team_id: Option<String>,
+    timestamp_url: Option<String>,
+}
+
+impl Signer {
+    /// The designated requirement of code signed by this identity, as referenced by the
+    /// seal of the bundles it is nested in.
+    fn designated_requirement(&self, identifier: &str) -> crate::Result<String> {
+        Ok(match &self.team_id {
+            Some(team_id) => format!(
+                "identifier \"{identifier}\" and anchor apple generic and certificate 1[field.1.2.840.113635.100.6.2.6] /* exists */ and certificate leaf[field.1.2.840.113635.100.6.1.13] /* exists */ and certificate leaf[subject.OU] = {team_id}"
+            ),
+            None => format!(
+                "identifier \"{identifier}\" and certificate leaf = H\"{}\"",
+                hex::encode(Sha1::digest(self.identity.certificate().to_der()?))
+            ),
+        })
+    }
+}
+
+fn signer(identity: &str, config: &Config) -> crate::Result<Signer> {
+    let mut signing_identity = super::signing_identity(identity, config)?.ok_or_else(|| {
+        Error::InvalidSigningCertificate(
+            "APPLE_CERTIFICATE and APPLE_CERTIFICATE_PASSWORD must be set".into(),
+        )
+    })?;
+    complete_chain(
+        &mut signing_identity.certificates,
+        config
+            .macos()
+            .is_some_and(|macos| macos.download_issuer_certificates),
+    )?;
+
+    Ok(Signer {
+        team_id: identity::subject_attribute(
+            signing_identity.certificate(),
+            identity::ORGANIZATIONAL_UNIT,
+        ),
+        identity: signing_identity,
+        timestamp_url: Some(TIMESTAMP_URL.into()),
+    })
+}
+
+/// Makes sure the chain reaches a root certificate, as it must be embedded in the signature.
+///
+/// The issuer certificates are usually not exported with the signing certificate, so when
+/// `download` is set the missing ones are downloaded from their Authority Information Access
+/// location, otherwise an incomplete chain is an error.
+fn complete_chain(certificates: &mut Vec<Certificate>, download: bool) -> crate::Result<()> {
+    while let Some(last) = certificates.last() {
+        if identity::is_issued_by(last, last) {
+            return Ok(());
+        }
+        let subject = last.tbs_certificate.subject.to_string();
+        let url = match identity::ca_issuers(last).into_iter().next() {
+            Some(url) if download && certificates.len() < 5 => url,
+            _ => {
+                return Err(Error::InvalidSigningCertificate(format!(
+                    "the signing certificate chain is incomplete, the issuer of \"{subject}\" is missing; export the whole chain with the certificate or set `macos.downloadIssuerCertificates`"
+                )))
+            }
+        };
+        tracing::debug!("Downloading the issuer certificate of \"{subject}\" from {url}");
+        let issuer = util::download(&url).and_then(|der| Ok(Certificate::from_der(&der)?))?;
+        if !identity::is_issued_by(last, &issuer) {
+            return Err(Error::InvalidSigningCertificate(format!(
+                "the certificate downloaded from {url} is not the issuer of \"{subject}\""
+            )));
+        }
+        certificates.push(issuer);
+    }
+    Ok(())
+}
+
+#[tracing::instrument(level = "trace", skip(config))]
+pub fn try_sign(targets: Vec<SignTarget>, identity: &str, config: &Config) -> crate::Result<()> {
+    let signer = signer(identity, config)?;
+    let entitlements = config
+        .macos()
+        .and_then(|macos| macos.entitlements.as_ref())
+        .map(|path| macho::Entitlements::from_file(Path::new(path)))
+        .transpose()?;
+
+    // the main executables of the bundles are signed with their bundle
+    let executables = targets
+        .iter()
+        .filter(|t| t.path.is_dir())
+        .filter_map(|t| bundle::executable(&t.path).ok())
+        .collect::<HashSet<_>>();
+
+    for target in targets {
+        if executables.contains(&target.path) {
+            continue;
+        }
+        tracing::info!(
+            "Codesigning {} with identity \"{}\"",
+            target.path.display(),
+            identity
+        );
+        if target.path.is_dir() {
+            bundle::sign(
+                &target.path,
+                &signer,
+                target.is_native_binary,
+                entitlements.as_ref(),
+            )?;
+        } else {
+            let identifier = target
+                .path
+                .file_stem()
+                .ok_or_else(|| Error::FailedToExtractFilename(target.path.clone()))?
+                .to_string_lossy();
+            sign_file(
+                &target.path,
+                &macho::SignOptions {
+                    identifier: &identifier,
+                    runtime: target.is_native_binary,
+                    entitlements: entitlements.as_ref(),
+                    info_plist: None,
+                    code_resources: None,
+                },
+                &signer,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn sign_file(path: &Path, options: &macho::SignOptions, signer: &Signer) -> crate::Result<()> {
+    let data = fs::read(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+    let signed = macho::sign(path, &data, options, signer)?;
+    fs::write(path, signed).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))
+}
+
+#[tracing::instrument(level = "trace", skip(_config))]
+pub fn notarize(
+    app_bundle_path: PathBuf,
+    auth: MacOsNotarizationCredentials,
+    _config: &Config,
+) -> crate::Result<()> {
+    let MacOsNotarizationCredentials::ApiKey {
+        key_id,
+        key_path,
+        issuer,
+    } = auth
+    else {
+        return Err(Error::UnsupportedNotarizationCredentials);
+    };
+
+    let bundle_name = app_bundle_path
+        .file_name()
+        .ok_or_else(|| Error::FailedToExtractFilename(app_bundle_path.clone()))?
+        .to_string_lossy()
+        .into_owned();
+    let bundle_stem = app_bundle_path
+        .file_stem()
+        .ok_or_else(|| Error::FailedToExtractFilename(app_bundle_path.clone()))?;
+
+    let tmp_dir = tempfile::tempdir()?;
+    let zip_path = tmp_dir
+        .path()
+        .join(format!("{}.zip", bundle_stem.to_string_lossy()));
+    let zip_file = File::create(&zip_path).map_err(|e| Error::IoWithPath(zip_path.clone(), e))?;
+    crate::package::archive::create_zip(&app_bundle_path, &bundle_name, zip_file)?;
+
+    // the ticket is looked up by the hash of the main executable signature
+    let executable = bundle::executable(&app_bundle_path)?;
+    let data = fs::read(&executable).map_err(|e| Error::IoWithPath(executable.clone(), e))?;
+    let signature = macho::read_signature(&executable, &data)?
+        .ok_or_else(|| Error::UnsupportedMachO(executable.clone(), "not signed".into()))?;
+
+    tracing::info!("Notarizing {}", app_bundle_path.display());
+
+    notary::Client::new(
+        &key_id.to_string_lossy(),
+        &issuer.to_string_lossy(),
+        &key_path,
+    )?
+    .notarize(&zip_path, &app_bundle_path, &signature.cdhash)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use x509_cert::name::Name;
+
+    use super::*;
+    use crate::codesign::identity::tests::self_signed;
+
+    #[test]
+    fn requires_complete_chain() {
+        let root = self_signed("CN=Root").certificates;
+        let mut certificates = root.clone();
+        complete_chain(&mut certificates, false).unwrap();
+        assert_eq!(certificates, root);
+
+        let mut leaf = root[0].clone();
+        leaf.tbs_certificate.issuer = Name::from_str("CN=Intermediate").unwrap();
+        let mut certificates = vec![leaf];
+        assert!(complete_chain(&mut certificates, false).is_err());
+        assert_eq!(certificates.len(), 1);
+    }
+}
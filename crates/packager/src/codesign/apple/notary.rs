@@ -0,0 +1,536 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Client for the web API of the Apple notary service, the service behind `notarytool`,
+//! and for the ticket delivery service used to staple the notarization ticket.
+//!
+//! Submissions are authenticated with a JSON web token signed by an App Store Connect API key,
+//! and uploaded to the S3 bucket the notary service hands out.
+
+use std::{
+    fs,
+    io::Read,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use hmac::{Hmac, Mac};
+use p256::{
+    ecdsa::{signature::Signer, Signature, SigningKey},
+    pkcs8::DecodePrivateKey,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{util, Error};
+
+const API_URL: &str = "https://appstoreconnect.apple.com";
+const TICKET_URL: &str = "https://api.apple-cloudkit.com/database/1/com.apple.gk.ticket-delivery/production/public/records/lookup";
+const UPLOAD_REGION: &str = "us-west-2";
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Deserialize)]
+struct Response<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct NewSubmission {
+    id: String,
+    attributes: UploadCredentials,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UploadCredentials {
+    aws_access_key_id: String,
+    aws_secret_access_key: String,
+    aws_session_token: String,
+    bucket: String,
+    object: String,
+}
+
+#[derive(Deserialize)]
+struct Submission {
+    attributes: SubmissionStatus,
+}
+
+#[derive(Deserialize)]
+struct SubmissionStatus {
+    status: String,
+}
+
+#[derive(Deserialize)]
+struct SubmissionLog {
+    attributes: SubmissionLogUrl,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SubmissionLogUrl {
+    developer_log_url: String,
+}
+
+#[derive(Deserialize)]
+struct TicketLookup {
+    records: Vec<TicketRecord>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TicketRecord {
+    fields: Option<TicketFields>,
+    server_error_code: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TicketFields {
+    signed_ticket: TicketValue,
+}
+
+#[derive(Deserialize)]
+struct TicketValue {
+    value: String,
+}
+
+pub(super) struct Client {
+    agent: ureq::Agent,
+    key_id: String,
+    issuer: String,
+    key: SigningKey,
+    api_url: String,
+    upload_url: Option<String>,
+    ticket_url: String,
+    poll_interval: Duration,
+}
+
+impl Client {
+    /// Creates a client authenticated with the App Store Connect API key `key_id`
+    /// of `issuer`, whose private key is the `.p8` file at `key_path`.
+    pub(super) fn new(key_id: &str, issuer: &str, key_path: &Path) -> crate::Result<Self> {
+        let pem = fs::read_to_string(key_path)
+            .map_err(|e| Error::IoWithPath(key_path.to_path_buf(), e))?;
+        let key = SigningKey::from_pkcs8_pem(&pem)
+            .map_err(|e| Error::InvalidNotaryApiKey(e.to_string()))?;
+        Ok(Self {
+            agent: util::http_agent(),
+            key_id: key_id.into(),
+            issuer: issuer.into(),
+            key,
+            api_url: API_URL.into(),
+            upload_url: None,
+            ticket_url: TICKET_URL.into(),
+            poll_interval: POLL_INTERVAL,
+        })
+    }
+
+    /// Overrides the base URL of the notary API.
+    #[cfg(test)]
+    fn api_url(mut self, url: impl Into<String>) -> Self {
+        self.api_url = url.into();
+        self
+    }
+
+    /// Overrides the endpoint of the submissions storage, the bucket being its first path segment.
+    #[cfg(test)]
+    fn upload_url(mut self, url: impl Into<String>) -> Self {
+        self.upload_url.replace(url.into());
+        self
+    }
+
+    /// Overrides the URL of the ticket lookup service.
+    #[cfg(test)]
+    fn ticket_url(mut self, url: impl Into<String>) -> Self {
+        self.ticket_url = url.into();
+        self
+    }
+
+    #[cfg(test)]
+    fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Notarizes the archive at `path` then staples the ticket to `bundle`,
+    /// whose main executable signature has the given CodeDirectory hash.
+    pub(super) fn notarize(&self, path: &Path, bundle: &Path, cdhash: &[u8]) -> crate::Result<()> {
+        let id = self.submit(path)?;
+        let status = self.wait(&id)?;
+        let log_message = format!("Finished with status {status} for id {id}");
+        if status == "Accepted" {
+            tracing::info!("Notarizing {}", log_message);
+            self.staple(bundle, cdhash)
+        } else {
+            match self.log(&id) {
+                Ok(log) => Err(Error::NotarizeRejected(format!(
+                    "{log_message}\nLog:\n{log}"
+                ))),
+                Err(_) => Err(Error::NotarizeRejected(log_message)),
+            }
+        }
+    }
+
+    fn token(&self) -> crate::Result<String> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let header = serde_json::json!({ "alg": "ES256", "kid": self.key_id, "typ": "JWT" });
+        // the tokens of the notary API are valid for at most 20 minutes
+        let claims = serde_json::json!({
+            "iss": self.issuer,
+            "iat": now,
+            "exp": now + 20 * 60,
+            "aud": "appstoreconnect-v1",
+        });
+        let message = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let signature: Signature = self.key.sign(message.as_bytes());
+        Ok(format!(
+            "{message}.{}",
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        ))
+    }
+
+    fn api<T: for<'de> Deserialize<'de>>(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> crate::Result<T> {
+        let request = self
+            .agent
+            .request(method, &format!("{}{path}", self.api_url))
+            .set("Authorization", &format!("Bearer {}", self.token()?));
+        let response = match body {
+            Some(body) => request
+                .set("Content-Type", "application/json")
+                .send_string(&body.to_string()),
+            None => request.call(),
+        };
+        read_json(response)
+    }
+
+    fn submit(&self, path: &Path) -> crate::Result<String> {
+        let data = fs::read(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+        let name = path
+            .file_name()
+            .ok_or_else(|| Error::FailedToExtractFilename(path.to_path_buf()))?
+            .to_string_lossy();
+        let submission: Response<NewSubmission> = self.api(
+            "POST",
+            "/notary/v2/submissions",
+            Some(serde_json::json!({
+                "submissionName": name,
+                "sha256": hex::encode(Sha256::digest(&data)),
+            })),
+        )?;
+        tracing::debug!("Uploading submission {}", submission.data.id);
+        self.upload(&submission.data.attributes, &data)?;
+        Ok(submission.data.id)
+    }
+
+    /// Uploads the submission with a request signed with AWS Signature Version 4.
+    fn upload(&self, credentials: &UploadCredentials, data: &[u8]) -> crate::Result<()> {
+        let endpoint = match &self.upload_url {
+            Some(url) => format!("{url}/{}", credentials.bucket),
+            None => format!("https://{}.s3-accelerate.amazonaws.com", credentials.bucket),
+        };
+        let (scheme, rest) = endpoint.split_once("://").unwrap_or(("https", &endpoint));
+        let (host, base_path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        let object = credentials
+            .object
+            .split('/')
+            .map(uri_encode)
+            .collect::<Vec<_>>()
+            .join("/");
+        let path = format!("{base_path}/{object}");
+
+        let now = time::OffsetDateTime::now_utc();
+        let date = format!("{:04}{:02}{:02}", now.year(), now.month() as u8, now.day());
+        let timestamp = format!(
+            "{date}T{:02}{:02}{:02}Z",
+            now.hour(),
+            now.minute(),
+            now.second()
+        );
+        let payload_hash = hex::encode(Sha256::digest(data));
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date;x-amz-security-token";
+        let canonical_request = format!(
+            "PUT\n{path}\n\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{timestamp}\nx-amz-security-token:{}\n\n{signed_headers}\n{payload_hash}",
+            credentials.aws_session_token
+        );
+        let scope = format!("{date}/{UPLOAD_REGION}/s3/aws4_request");
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+            hex::encode(Sha256::digest(canonical_request))
+        );
+        let key = [date.as_str(), UPLOAD_REGION, "s3", "aws4_request"]
+            .iter()
+            .fold(
+                format!("AWS4{}", credentials.aws_secret_access_key).into_bytes(),
+                |key, data| hmac_sha256(&key, data.as_bytes()),
+            );
+        let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+
+        self.agent
+            .put(&format!("{scheme}://{host}{path}"))
+            .set("x-amz-content-sha256", &payload_hash)
+            .set("x-amz-date", &timestamp)
+            .set("x-amz-security-token", &credentials.aws_session_token)
+            .set(
+                "Authorization",
+                &format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+                    credentials.aws_access_key_id
+                ),
+            )
+            .send_bytes(data)
+            .map_err(api_error)?;
+        Ok(())
+    }
+
+    /// Waits for the submission to be processed, returning its status.
+    fn wait(&self, id: &str) -> crate::Result<String> {
+        loop {
+            let submission: Response<Submission> =
+                self.api("GET", &format!("/notary/v2/submissions/{id}"), None)?;
+            let status = submission.data.attributes.status;
+            if status != "In Progress" {
+                return Ok(status);
+            }
+            tracing::debug!("Waiting for submission {id} to be processed");
+            std::thread::sleep(self.poll_interval);
+        }
+    }
+
+    fn log(&self, id: &str) -> crate::Result<String> {
+        let log: Response<SubmissionLog> =
+            self.api("GET", &format!("/notary/v2/submissions/{id}/logs"), None)?;
+        let response = self
+            .agent
+            .get(&log.data.attributes.developer_log_url)
+            .call()
+            .map_err(api_error)?;
+        Ok(response.into_string()?)
+    }
+
+    /// Downloads the notarization ticket and stores it in the bundle,
+    /// like `stapler staple` does.
+    fn staple(&self, bundle: &Path, cdhash: &[u8]) -> crate::Result<()> {
+        let body = serde_json::json!({
+            "records": [{ "recordName": format!("2/2/{}", hex::encode(cdhash)) }]
+        });
+        let lookup: TicketLookup = read_json(
+            self.agent
+                .post(&self.ticket_url)
+                .set("Content-Type", "application/json")
+                .send_string(&body.to_string()),
+        )?;
+        let record = lookup
+            .records
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::NotaryApi("no notarization ticket found".into()))?;
+        let ticket = match (record.fields, record.server_error_code) {
+            (Some(fields), _) => STANDARD.decode(fields.signed_ticket.value)?,
+            (None, code) => {
+                return Err(Error::NotaryApi(format!(
+                    "failed to find the notarization ticket: {}",
+                    code.unwrap_or_default()
+                )))
+            }
+        };
+        let path = bundle.join("Contents/CodeResources");
+        fs::write(&path, ticket).map_err(|e| Error::IoWithPath(path, e))?;
+        Ok(())
+    }
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(
+    response: Result<ureq::Response, ureq::Error>,
+) -> crate::Result<T> {
+    let response = response.map_err(api_error)?;
+    let mut body = Vec::new();
+    response.into_reader().read_to_end(&mut body)?;
+    serde_json::from_slice(&body).map_err(|e| Error::NotaryApi(e.to_string()))
+}
+
+fn api_error(error: ureq::Error) -> Error {
+    match error {
+        ureq::Error::Status(code, response) => {
+            let url = response.get_url().to_string();
+            let body = response.into_string().unwrap_or_default();
+            Error::NotaryApi(format!("{url} returned {code}: {body}"))
+        }
+        error => Error::DownloadError(Box::new(error)),
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encodes a path segment, as required by the AWS canonical requests.
+fn uri_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        sync::mpsc,
+    };
+
+    use p256::{
+        ecdsa::{signature::Verifier, VerifyingKey},
+        pkcs8::{EncodePrivateKey, LineEnding},
+    };
+
+    use super::*;
+
+    struct Request {
+        method: String,
+        path: String,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    impl Request {
+        fn header(&self, name: &str) -> &str {
+            self.headers
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.as_str())
+                .unwrap_or_default()
+        }
+    }
+
+    /// Serves the given responses in order, sending back the requests it received.
+    fn mock_server(responses: Vec<&'static str>) -> (String, mpsc::Receiver<Request>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for body in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let mut parts = line.split_whitespace();
+                let method = parts.next().unwrap().to_string();
+                let path = parts.next().unwrap().to_string();
+                let mut headers = Vec::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    match line.trim_end().split_once(": ") {
+                        Some((name, value)) => headers.push((name.into(), value.into())),
+                        None => break,
+                    }
+                }
+                let mut request = Request {
+                    method,
+                    path,
+                    headers,
+                    body: Vec::new(),
+                };
+                let len = request.header("Content-Length").parse().unwrap_or(0);
+                request.body.resize(len, 0);
+                reader.read_exact(&mut request.body).unwrap();
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+                tx.send(request).unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    #[test]
+    fn notarizes_and_staples() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = p256::SecretKey::random(&mut rand::thread_rng());
+        let key_path = dir.path().join("AuthKey_KEY.p8");
+        fs::write(
+            &key_path,
+            key.to_pkcs8_pem(LineEnding::LF).unwrap().as_bytes(),
+        )
+        .unwrap();
+        let archive = dir.path().join("app.zip");
+        fs::write(&archive, "zip").unwrap();
+        let bundle = dir.path().join("app.app");
+        fs::create_dir_all(bundle.join("Contents")).unwrap();
+
+        let (url, requests) = mock_server(vec![
+            r#"{"data":{"id":"42","type":"newSubmissions","attributes":{"awsAccessKeyId":"AKID","awsSecretAccessKey":"secret","awsSessionToken":"token","bucket":"bucket","object":"prefix/app.zip"}}}"#,
+            "",
+            r#"{"data":{"id":"42","type":"submissions","attributes":{"status":"In Progress","name":"app.zip"}}}"#,
+            r#"{"data":{"id":"42","type":"submissions","attributes":{"status":"Accepted","name":"app.zip"}}}"#,
+            r#"{"records":[{"recordName":"2/2/0102","recordType":"DeveloperIDTicket","fields":{"signedTicket":{"type":"BYTES","value":"dGlja2V0"}}}]}"#,
+        ]);
+        let client = Client::new("KEY", "issuer", &key_path)
+            .unwrap()
+            .api_url(&url)
+            .upload_url(&url)
+            .ticket_url(format!("{url}/lookup"))
+            .poll_interval(Duration::ZERO);
+        client.notarize(&archive, &bundle, &[1, 2]).unwrap();
+
+        let submit = requests.recv().unwrap();
+        assert_eq!(submit.method, "POST");
+        assert_eq!(submit.path, "/notary/v2/submissions");
+        let body: serde_json::Value = serde_json::from_slice(&submit.body).unwrap();
+        assert_eq!(body["submissionName"], "app.zip");
+        assert_eq!(body["sha256"], hex::encode(Sha256::digest(b"zip")));
+        let token = submit
+            .header("Authorization")
+            .strip_prefix("Bearer ")
+            .unwrap();
+        let (message, signature) = token.rsplit_once('.').unwrap();
+        let signature = Signature::from_slice(&URL_SAFE_NO_PAD.decode(signature).unwrap()).unwrap();
+        VerifyingKey::from(key.public_key())
+            .verify(message.as_bytes(), &signature)
+            .unwrap();
+
+        let upload = requests.recv().unwrap();
+        assert_eq!(upload.method, "PUT");
+        assert_eq!(upload.path, "/bucket/prefix/app.zip");
+        assert_eq!(upload.body, b"zip");
+        assert_eq!(upload.header("x-amz-security-token"), "token");
+        assert!(upload
+            .header("Authorization")
+            .starts_with("AWS4-HMAC-SHA256 Credential=AKID/"));
+
+        assert_eq!(requests.recv().unwrap().path, "/notary/v2/submissions/42");
+        assert_eq!(requests.recv().unwrap().path, "/notary/v2/submissions/42");
+        let lookup = requests.recv().unwrap();
+        assert_eq!(lookup.path, "/lookup");
+        assert!(String::from_utf8_lossy(&lookup.body).contains(r#""recordName":"2/2/0102""#));
+        assert_eq!(
+            fs::read(bundle.join("Contents/CodeResources")).unwrap(),
+            b"ticket"
+        );
+    }
+}
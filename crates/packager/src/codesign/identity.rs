@@ -0,0 +1,341 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Code signing identities, and the CMS (PKCS#7) signed data they embed in code signatures.
+
+use cms::{
+    builder::{create_signing_time_attribute, SignedDataBuilder, SignerInfoBuilder},
+    cert::{CertificateChoices, IssuerAndSerialNumber},
+    content_info::{CmsVersion, ContentInfo},
+    signed_data::{EncapsulatedContentInfo, SignedData, SignerIdentifier, SignerInfos},
+};
+use der::{
+    asn1::{ObjectIdentifier, SetOfVec},
+    Any, Encode, EncodeValue, Tagged,
+};
+use rsa::{
+    pkcs1::EncodeRsaPublicKey,
+    pkcs1v15::{Signature, SigningKey},
+    RsaPrivateKey, RsaPublicKey,
+};
+use sha2::Sha256;
+use x509_cert::{attr::Attribute, spki::AlgorithmIdentifierOwned, Certificate};
+
+use super::timestamp;
+use crate::Error;
+
+#[cfg(not(target_os = "macos"))]
+pub(crate) const DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.1");
+pub(crate) const SIGNED_DATA: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.2");
+pub(crate) const SHA256: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.1");
+/// The unsigned attribute holding the RFC 3161 timestamp token of a signature.
+#[cfg(not(target_os = "macos"))]
+pub(crate) const TIMESTAMP_TOKEN: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.16.2.14");
+#[cfg(not(target_os = "macos"))]
+const CA_ISSUERS: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.5.5.7.48.2");
+
+pub(crate) const COMMON_NAME: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.3");
+#[cfg(not(target_os = "macos"))]
+pub(crate) const ORGANIZATIONAL_UNIT: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.11");
+
+/// The SHA-256 digest algorithm, with the NULL parameters code signatures are expected to have.
+pub(crate) fn sha256_algorithm() -> AlgorithmIdentifierOwned {
+    AlgorithmIdentifierOwned {
+        oid: SHA256,
+        parameters: Some(Any::null()),
+    }
+}
+
+pub(crate) fn is_issued_by(certificate: &Certificate, issuer: &Certificate) -> bool {
+    certificate.tbs_certificate.issuer == issuer.tbs_certificate.subject
+}
+
+/// Returns the value of the first subject attribute of `certificate` with the given type,
+/// e.g. [`COMMON_NAME`].
+pub(crate) fn subject_attribute(
+    certificate: &Certificate,
+    attribute: ObjectIdentifier,
+) -> Option<String> {
+    let value = &certificate
+        .tbs_certificate
+        .subject
+        .0
+        .iter()
+        .flat_map(|rdn| rdn.0.iter())
+        .find(|a| a.oid == attribute)?
+        .value;
+    Some(match value.decode_as::<der::asn1::BmpString>() {
+        Ok(value) => value.to_string(),
+        Err(_) => String::from_utf8_lossy(value.value()).into_owned(),
+    })
+}
+
+/// Returns the URLs where the certificate of the issuer of `certificate` can be downloaded,
+/// from its Authority Information Access extension.
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn ca_issuers(certificate: &Certificate) -> Vec<String> {
+    use x509_cert::ext::pkix::{name::GeneralName, AuthorityInfoAccessSyntax};
+
+    let Ok(Some((_, access))) = certificate
+        .tbs_certificate
+        .get::<AuthorityInfoAccessSyntax>()
+    else {
+        return Vec::new();
+    };
+    access
+        .0
+        .into_iter()
+        .filter(|description| description.access_method == CA_ISSUERS)
+        .filter_map(|description| match description.access_location {
+            GeneralName::UniformResourceIdentifier(uri) => Some(uri.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// An RFC 3161 time-stamping authority countersigning signatures.
+pub(crate) struct Timestamp<'a> {
+    pub url: &'a str,
+    /// The unsigned attribute storing the timestamp token, e.g. [`TIMESTAMP_TOKEN`].
+    pub attribute: ObjectIdentifier,
+}
+
+/// Creates a CMS attribute holding a single value.
+pub(crate) fn attribute(
+    id: ObjectIdentifier,
+    value: &(impl EncodeValue + Tagged),
+) -> crate::Result<Attribute> {
+    let mut values = SetOfVec::new();
+    values.insert(Any::encode_from(value)?)?;
+    Ok(Attribute { oid: id, values })
+}
+
+/// A private key and its certificate chain, starting with the signing certificate.
+pub(crate) struct SigningIdentity {
+    pub key: RsaPrivateKey,
+    pub certificates: Vec<Certificate>,
+}
+
+impl SigningIdentity {
+    /// Orders the certificates from the one matching the key up to the root.
+    pub(crate) fn new(
+        key: RsaPrivateKey,
+        mut certificates: Vec<Certificate>,
+    ) -> crate::Result<Self> {
+        let invalid = |message: String| Error::InvalidSigningCertificate(message);
+        let public_key = RsaPublicKey::from(&key)
+            .to_pkcs1_der()
+            .map_err(|e| invalid(e.to_string()))?;
+        let leaf = certificates
+            .iter()
+            .position(|c| {
+                c.tbs_certificate
+                    .subject_public_key_info
+                    .subject_public_key
+                    .raw_bytes()
+                    == public_key.as_bytes()
+            })
+            .ok_or_else(|| invalid("no certificate matches the private key".into()))?;
+
+        let mut chain = vec![certificates.remove(leaf)];
+        loop {
+            let last = chain.last().unwrap();
+            if is_issued_by(last, last) {
+                break;
+            }
+            match certificates.iter().position(|c| is_issued_by(last, c)) {
+                Some(index) => chain.push(certificates.remove(index)),
+                None => break,
+            }
+        }
+        Ok(Self {
+            key,
+            certificates: chain,
+        })
+    }
+
+    pub(crate) fn certificate(&self) -> &Certificate {
+        &self.certificates[0]
+    }
+
+    /// Creates a CMS SignedData over `content`, whose SHA-256 digest is `detached_digest`
+    /// when the content is not encapsulated.
+    ///
+    /// The signature is countersigned by the `timestamp` authority if any.
+    pub(crate) fn sign(
+        &self,
+        content: EncapsulatedContentInfo,
+        detached_digest: Option<&[u8]>,
+        attributes: Vec<Attribute>,
+        timestamp: Option<Timestamp>,
+    ) -> crate::Result<Vec<u8>> {
+        let failed = |e: cms::builder::Error| Error::CmsSignature(e.to_string());
+        let certificate = self.certificate();
+        let signer = SigningKey::<Sha256>::new(self.key.clone());
+        let sid = SignerIdentifier::IssuerAndSerialNumber(IssuerAndSerialNumber {
+            issuer: certificate.tbs_certificate.issuer.clone(),
+            serial_number: certificate.tbs_certificate.serial_number.clone(),
+        });
+        let mut signer_info =
+            SignerInfoBuilder::new(&signer, sid, sha256_algorithm(), &content, detached_digest)
+                .map_err(failed)?;
+        signer_info
+            .add_signed_attribute(create_signing_time_attribute().map_err(failed)?)
+            .map_err(failed)?;
+        for attribute in attributes {
+            signer_info
+                .add_signed_attribute(attribute)
+                .map_err(failed)?;
+        }
+
+        let mut builder = SignedDataBuilder::new(&content);
+        builder
+            .add_digest_algorithm(sha256_algorithm())
+            .map_err(failed)?;
+        for certificate in &self.certificates {
+            builder
+                .add_certificate(CertificateChoices::Certificate(certificate.clone()))
+                .map_err(failed)?;
+        }
+        let content_info = builder
+            .add_signer_info::<_, Signature>(signer_info)
+            .map_err(failed)?
+            .build()
+            .map_err(failed)?;
+
+        let mut signed_data = content_info.content.decode_as::<SignedData>()?;
+        // the builder raises the version for content types other than data,
+        // which Authenticode does not accept
+        signed_data.version = CmsVersion::V1;
+        if let Some(timestamp) = timestamp {
+            let mut signer_infos = signed_data.signer_infos.0.into_vec();
+            let token = timestamp::request(timestamp.url, signer_infos[0].signature.as_bytes())?;
+            signer_infos[0].unsigned_attrs = Some(SetOfVec::try_from(vec![attribute(
+                timestamp.attribute,
+                &token,
+            )?])?);
+            signed_data.signer_infos = SignerInfos(SetOfVec::try_from(signer_infos)?);
+        }
+
+        Ok(ContentInfo {
+            content_type: SIGNED_DATA,
+            content: Any::encode_from(&signed_data)?,
+        }
+        .to_der()?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{str::FromStr, time::Duration};
+
+    use cms::signed_data::SignerInfo;
+    use der::Decode;
+    use rsa::{pkcs1v15::VerifyingKey, signature::Verifier};
+    use sha2::Digest;
+    use x509_cert::{
+        builder::{Builder, CertificateBuilder, Profile},
+        name::Name,
+        serial_number::SerialNumber,
+        spki::SubjectPublicKeyInfoOwned,
+        time::Validity,
+    };
+
+    use super::*;
+
+    /// Creates an identity with a self-signed certificate for `subject`, e.g. `CN=Test`.
+    pub(crate) fn self_signed(subject: &str) -> SigningIdentity {
+        let key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+        let signer = SigningKey::<Sha256>::new(key.clone());
+        let certificate = CertificateBuilder::new(
+            Profile::Root,
+            SerialNumber::from(1u32),
+            Validity::from_now(Duration::from_secs(3600)).unwrap(),
+            Name::from_str(subject).unwrap(),
+            SubjectPublicKeyInfoOwned::from_key(RsaPublicKey::from(&key)).unwrap(),
+            &signer,
+        )
+        .unwrap()
+        .build::<Signature>()
+        .unwrap();
+        SigningIdentity::new(key, vec![certificate]).unwrap()
+    }
+
+    fn signer_info(signed: &[u8]) -> (SignedData, SignerInfo) {
+        let content_info = ContentInfo::from_der(signed).unwrap();
+        assert_eq!(content_info.content_type, SIGNED_DATA);
+        let signed_data = content_info.content.decode_as::<SignedData>().unwrap();
+        let signer_info = signed_data.signer_infos.0.get(0).unwrap().clone();
+        (signed_data, signer_info)
+    }
+
+    #[test]
+    fn signs_detached_content() {
+        let identity = self_signed("CN=Test,OU=Team");
+        let digest = Sha256::digest(b"content");
+        let content = EncapsulatedContentInfo {
+            econtent_type: ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.1"),
+            econtent: None,
+        };
+        let signed = identity.sign(content, Some(&digest), vec![], None).unwrap();
+
+        let (signed_data, signer_info) = signer_info(&signed);
+        assert_eq!(signed_data.version, CmsVersion::V1);
+        assert_eq!(signed_data.certificates.unwrap().0.len(), 1);
+        assert_eq!(signer_info.digest_alg, sha256_algorithm());
+        let signed_attrs = signer_info.signed_attrs.unwrap();
+        let message_digest = signed_attrs
+            .iter()
+            .find(|a| a.oid == ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.4"))
+            .unwrap();
+        assert_eq!(message_digest.values.get(0).unwrap().value(), &digest[..]);
+
+        // the signature covers the DER encoded signed attributes
+        VerifyingKey::<Sha256>::new(RsaPublicKey::from(&identity.key))
+            .verify(
+                &signed_attrs.to_der().unwrap(),
+                &Signature::try_from(signer_info.signature.as_bytes()).unwrap(),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn signs_encapsulated_content() {
+        let identity = self_signed("CN=Test");
+        let content_type = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.311.2.1.4");
+        let content = EncapsulatedContentInfo {
+            econtent_type: content_type,
+            econtent: Some(
+                Any::encode_from(&der::asn1::OctetString::new(b"content".to_vec()).unwrap())
+                    .unwrap(),
+            ),
+        };
+        let attribute = attribute(
+            ObjectIdentifier::new_unwrap("1.3.6.1.4.1.311.2.1.11"),
+            &der::asn1::Null,
+        )
+        .unwrap();
+        let signed = identity
+            .sign(content.clone(), None, vec![attribute.clone()], None)
+            .unwrap();
+
+        let (signed_data, signer_info) = signer_info(&signed);
+        // the version stays 1 for content types other than data
+        assert_eq!(signed_data.version, CmsVersion::V1);
+        assert_eq!(signed_data.encap_content_info, content);
+        let signed_attrs = signer_info.signed_attrs.unwrap();
+        assert!(signed_attrs.iter().any(|a| *a == attribute));
+        let message_digest = signed_attrs
+            .iter()
+            .find(|a| a.oid == ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.4"))
+            .unwrap();
+        // the digest of the value of the content, without its tag and length
+        assert_eq!(
+            message_digest.values.get(0).unwrap().value(),
+            &Sha256::digest(b"content")[..]
+        );
+    }
+}
@@ -4,7 +4,6 @@
 // SPDX-License-Identifier: MIT
 
 use std::{
-    cmp::Ordering,
     ffi::OsString,
     fs::File,
     io::prelude::*,
@@ -14,6 +13,7 @@ use std::{
 
 use serde::Deserialize;
 
+pub use super::{notarize_auth, SignTarget};
 use crate::{config::MacOsNotarizationCredentials, shell::CommandExt, Config, Error};
 
 const KEYCHAIN_ID: &str = "cargo-packager.keychain";
@@ -144,29 +144,6 @@ pub fn delete_keychain() {
         .output_ok();
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct SignTarget {
-    pub path: PathBuf,
-    pub is_native_binary: bool,
-}
-
-impl Ord for SignTarget {
-    fn cmp(&self, other: &Self) -> Ordering {
-        let self_count = self.path.components().count();
-        let other_count = other.path.components().count();
-
-        // Sort by path depth (note that we compare other to self, not self to other) so
-        // that longer paths have a smaller value!
-        other_count.cmp(&self_count)
-    }
-}
-
-impl PartialOrd for SignTarget {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
 #[tracing::instrument(level = "trace", skip(config))]
 pub fn try_sign(targets: Vec<SignTarget>, identity: &str, config: &Config) -> crate::Result<()> {
     let certificate_encoded = config
@@ -416,83 +393,3 @@ impl NotarytoolCmdExt for Command {
         }
     }
 }
-
-#[tracing::instrument(level = "trace")]
-pub fn notarize_auth() -> crate::Result<MacOsNotarizationCredentials> {
-    if let Some(keychain_profile) = std::env::var_os("APPLE_KEYCHAIN_PROFILE") {
-        Ok(MacOsNotarizationCredentials::KeychainProfile { keychain_profile })
-    } else {
-        match (
-            std::env::var_os("APPLE_ID"),
-            std::env::var_os("APPLE_PASSWORD"),
-            std::env::var_os("APPLE_TEAM_ID"),
-        ) {
-            (Some(apple_id), Some(password), Some(team_id)) => {
-                Ok(MacOsNotarizationCredentials::AppleId {
-                    apple_id,
-                    password,
-                    team_id,
-                })
-            }
-            _ => {
-                match (
-                    std::env::var_os("APPLE_API_KEY"),
-                    std::env::var_os("APPLE_API_ISSUER"),
-                    std::env::var("APPLE_API_KEY_PATH"),
-                ) {
-                    (Some(key_id), Some(issuer), Ok(key_path)) => {
-                        Ok(MacOsNotarizationCredentials::ApiKey {
-                            key_id,
-                            key_path: key_path.into(),
-                            issuer,
-                        })
-                    }
-                    (Some(key_id), Some(issuer), Err(_)) => {
-                        let mut api_key_file_name = OsString::from("AuthKey_");
-                        api_key_file_name.push(&key_id);
-                        api_key_file_name.push(".p8");
-                        let mut key_path = None;
-
-                        let mut search_paths = vec!["./private_keys".into()];
-                        if let Some(home_dir) = dirs::home_dir() {
-                            search_paths.push(home_dir.join("private_keys"));
-                            search_paths.push(home_dir.join(".private_keys"));
-                            search_paths.push(home_dir.join(".appstoreconnect/private_keys"));
-                        }
-
-                        for folder in search_paths {
-                            if let Some(path) = find_api_key(folder, &api_key_file_name) {
-                                key_path = Some(path);
-                                break;
-                            }
-                        }
-
-                        if let Some(key_path) = key_path {
-                            Ok(MacOsNotarizationCredentials::ApiKey {
-                                key_id,
-                                key_path,
-                                issuer,
-                            })
-                        } else {
-                            Err(Error::ApiKeyMissing {
-                                filename: api_key_file_name
-                                    .into_string()
-                                    .expect("failed to convert api_key_file_name to string"),
-                            })
-                        }
-                    }
-                    _ => Err(Error::MissingNotarizeAuthVars),
-                }
-            }
-        }
-    }
-}
-
-fn find_api_key(folder: PathBuf, file_name: &OsString) -> Option<PathBuf> {
-    let path = folder.join(file_name);
-    if path.exists() {
-        Some(path)
-    } else {
-        None
-    }
-}
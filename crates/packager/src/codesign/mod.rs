@@ -1,8 +1,167 @@
+// Copyright 2019-2023 Tauri Programme within The Commons Conservancy
 // Copyright 2023-2023 CrabNebula Ltd.
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-License-Identifier: MIT
 
+use std::{cmp::Ordering, ffi::OsString, path::PathBuf};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::{config::MacOsNotarizationCredentials, Config, Error};
+
+#[cfg(not(target_os = "macos"))]
+pub mod apple;
+mod authenticode;
+pub(crate) mod identity;
 #[cfg(target_os = "macos")]
 pub mod macos;
-
+mod pkcs12;
+mod timestamp;
 pub mod windows;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SignTarget {
+    pub path: PathBuf,
+    pub is_native_binary: bool,
+}
+
+impl Ord for SignTarget {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let self_count = self.path.components().count();
+        let other_count = other.path.components().count();
+
+        // Sort by path depth (note that we compare other to self, not self to other) so
+        // that longer paths have a smaller value!
+        other_count.cmp(&self_count)
+    }
+}
+
+impl PartialOrd for SignTarget {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Returns the base64 encoded PKCS#12 certificate and its password, if both are set.
+pub fn signing_certificate(config: &Config) -> Option<(OsString, OsString)> {
+    let certificate_encoded = config
+        .macos()
+        .and_then(|m| m.signing_certificate.clone())
+        .or_else(|| std::env::var_os("APPLE_CERTIFICATE"));
+
+    let certificate_password = config
+        .macos()
+        .and_then(|m| m.signing_certificate_password.clone())
+        .or_else(|| std::env::var_os("APPLE_CERTIFICATE_PASSWORD"));
+
+    certificate_encoded.zip(certificate_password)
+}
+
+/// Loads the `identity` from the PKCS#12 certificate of [`signing_certificate`], if it is set.
+pub(crate) fn signing_identity(
+    identity: &str,
+    config: &Config,
+) -> crate::Result<Option<identity::SigningIdentity>> {
+    let Some((certificate_encoded, certificate_password)) = signing_certificate(config) else {
+        return Ok(None);
+    };
+    let certificate_encoded = certificate_encoded
+        .to_string_lossy()
+        .split_whitespace()
+        .collect::<String>();
+    let signing_identity = pkcs12::find(
+        &STANDARD.decode(certificate_encoded)?,
+        &certificate_password.to_string_lossy(),
+        identity,
+    )?;
+
+    let common_name =
+        identity::subject_attribute(signing_identity.certificate(), identity::COMMON_NAME)
+            .unwrap_or_default();
+    if !common_name.contains(identity) {
+        tracing::warn!(
+            "The codesign certificate \"{common_name}\" does not match the signing identity \"{identity}\""
+        );
+    }
+    Ok(Some(signing_identity))
+}
+
+#[tracing::instrument(level = "trace")]
+pub fn notarize_auth() -> crate::Result<MacOsNotarizationCredentials> {
+    if let Some(keychain_profile) = std::env::var_os("APPLE_KEYCHAIN_PROFILE") {
+        Ok(MacOsNotarizationCredentials::KeychainProfile { keychain_profile })
+    } else {
+        match (
+            std::env::var_os("APPLE_ID"),
+            std::env::var_os("APPLE_PASSWORD"),
+            std::env::var_os("APPLE_TEAM_ID"),
+        ) {
+            (Some(apple_id), Some(password), Some(team_id)) => {
+                Ok(MacOsNotarizationCredentials::AppleId {
+                    apple_id,
+                    password,
+                    team_id,
+                })
+            }
+            _ => {
+                match (
+                    std::env::var_os("APPLE_API_KEY"),
+                    std::env::var_os("APPLE_API_ISSUER"),
+                    std::env::var("APPLE_API_KEY_PATH"),
+                ) {
+                    (Some(key_id), Some(issuer), Ok(key_path)) => {
+                        Ok(MacOsNotarizationCredentials::ApiKey {
+                            key_id,
+                            key_path: key_path.into(),
+                            issuer,
+                        })
+                    }
+                    (Some(key_id), Some(issuer), Err(_)) => {
+                        let mut api_key_file_name = OsString::from("AuthKey_");
+                        api_key_file_name.push(&key_id);
+                        api_key_file_name.push(".p8");
+                        let mut key_path = None;
+
+                        let mut search_paths = vec!["./private_keys".into()];
+                        if let Some(home_dir) = dirs::home_dir() {
+                            search_paths.push(home_dir.join("private_keys"));
+                            search_paths.push(home_dir.join(".private_keys"));
+                            search_paths.push(home_dir.join(".appstoreconnect/private_keys"));
+                        }
+
+                        for folder in search_paths {
+                            if let Some(path) = find_api_key(folder, &api_key_file_name) {
+                                key_path = Some(path);
+                                break;
+                            }
+                        }
+
+                        if let Some(key_path) = key_path {
+                            Ok(MacOsNotarizationCredentials::ApiKey {
+                                key_id,
+                                key_path,
+                                issuer,
+                            })
+                        } else {
+                            Err(Error::ApiKeyMissing {
+                                filename: api_key_file_name
+                                    .into_string()
+                                    .expect("failed to convert api_key_file_name to string"),
+                            })
+                        }
+                    }
+                    _ => Err(Error::MissingNotarizeAuthVars),
+                }
+            }
+        }
+    }
+}
+
+fn find_api_key(folder: PathBuf, file_name: &OsString) -> Option<PathBuf> {
+    let path = folder.join(file_name);
+    if path.exists() {
+        Some(path)
+    } else {
+        None
+    }
+}
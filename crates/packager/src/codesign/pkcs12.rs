@@ -0,0 +1,483 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Reader for PKCS#12 (`.p12` / `.pfx`) key stores holding a code signing identity.
+//!
+//! Supports the PBES2 encryption used by current OpenSSL and Keychain exports,
+//! and the legacy SHA-1 based 3DES and RC2 encryption schemes.
+
+use cbc::cipher::{block_padding::Pkcs7, BlockCipher, BlockDecryptMut, InnerIvInit, KeyInit};
+use cms::content_info::ContentInfo;
+use der::{
+    asn1::{ContextSpecific, ObjectIdentifier, OctetString},
+    Any, Decode, Encode, Header, Reader, Sequence, SliceReader, Tag, Tagged,
+};
+use pkcs12::{
+    cert_type::CertBag,
+    kdf::{derive_key_utf8, Pkcs12KeyType},
+    pbe_params::{EncryptedPrivateKeyInfo, Pkcs12PbeParams},
+    pfx::Pfx,
+    safe_bag::SafeContents,
+};
+use rsa::{
+    pkcs8::{pkcs5, DecodePrivateKey},
+    RsaPrivateKey,
+};
+use sha1::Sha1;
+use x509_cert::{spki::AlgorithmIdentifierOwned, Certificate};
+
+use super::identity::{self, SigningIdentity};
+use crate::Error;
+
+const DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.1");
+const ENCRYPTED_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.6");
+
+/// `EncryptedData`, keeping the encrypted content as is since the Keychain stores its implicitly
+/// tagged OCTET STRING in the constructed form.
+#[derive(Sequence)]
+struct EncryptedData {
+    version: u8,
+    content_info: EncryptedContentInfo,
+}
+
+#[derive(Sequence)]
+struct EncryptedContentInfo {
+    content_type: ObjectIdentifier,
+    algorithm: AlgorithmIdentifierOwned,
+    content: Any,
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::InvalidSigningCertificate(message.into())
+}
+
+fn wrong_password() -> Error {
+    invalid("failed to decrypt, is the password correct?")
+}
+
+/// Reads the RSA private key of a key store and its certificate chain.
+pub(crate) fn parse(data: &[u8], password: &str) -> crate::Result<SigningIdentity> {
+    let mut identities = identities(data, password)?;
+    match identities.len() {
+        1 => Ok(identities.remove(0)),
+        _ => Err(invalid("more than one private key found")),
+    }
+}
+
+/// Reads the identity of a key store whose certificate common name contains `name`,
+/// or its only identity.
+pub(crate) fn find(data: &[u8], password: &str, name: &str) -> crate::Result<SigningIdentity> {
+    let mut identities = identities(data, password)?;
+    if identities.len() == 1 {
+        return Ok(identities.remove(0));
+    }
+    identities
+        .into_iter()
+        .find(|identity| {
+            identity::subject_attribute(identity.certificate(), identity::COMMON_NAME)
+                .is_some_and(|common_name| common_name.contains(name))
+        })
+        .ok_or_else(|| invalid(format!("no certificate matching \"{name}\" found")))
+}
+
+/// Reads the RSA private keys of a key store, each with its certificate chain.
+fn identities(data: &[u8], password: &str) -> crate::Result<Vec<SigningIdentity>> {
+    let pfx = Pfx::from_der(&to_der(data)?)?;
+    if pfx.auth_safe.content_type != DATA {
+        return Err(invalid(
+            "public key protected PKCS#12 files are not supported",
+        ));
+    }
+    let auth_safe = pfx.auth_safe.content.decode_as::<OctetString>()?;
+
+    let mut keys = Vec::new();
+    let mut certificates = Vec::new();
+    for content_info in Vec::<ContentInfo>::from_der(&to_der(auth_safe.as_bytes())?)? {
+        let safe_contents = match content_info.content_type {
+            DATA => content_info
+                .content
+                .decode_as::<OctetString>()?
+                .into_bytes(),
+            ENCRYPTED_DATA => {
+                let encrypted = content_info
+                    .content
+                    .decode_as::<EncryptedData>()?
+                    .content_info;
+                let content = if encrypted.content.tag().is_constructed() {
+                    segments(encrypted.content.value())?
+                } else {
+                    encrypted.content.value().to_vec()
+                };
+                decrypt(&encrypted.algorithm, &content, password)?
+            }
+            _ => continue,
+        };
+        read_bags(
+            &to_der(&safe_contents)?,
+            password,
+            &mut keys,
+            &mut certificates,
+        )?;
+    }
+
+    if keys.is_empty() {
+        return Err(invalid("no private key found"));
+    }
+    keys.iter()
+        .map(|key| {
+            let key = RsaPrivateKey::from_pkcs8_der(key)
+                .map_err(|e| invalid(format!("unsupported private key: {e}")))?;
+            SigningIdentity::new(key, certificates.clone())
+        })
+        .collect()
+}
+
+fn read_bags(
+    safe_contents: &[u8],
+    password: &str,
+    keys: &mut Vec<Vec<u8>>,
+    certificates: &mut Vec<Certificate>,
+) -> crate::Result<()> {
+    for bag in SafeContents::from_der(safe_contents)? {
+        match bag.bag_id {
+            pkcs12::PKCS_12_KEY_BAG_OID => keys.push(explicit::<Any>(&bag.bag_value)?.to_der()?),
+            pkcs12::PKCS_12_PKCS8_KEY_BAG_OID => {
+                let key = explicit::<EncryptedPrivateKeyInfo>(&bag.bag_value)?;
+                keys.push(decrypt(
+                    &key.encryption_algorithm,
+                    key.encrypted_data.as_bytes(),
+                    password,
+                )?);
+            }
+            pkcs12::PKCS_12_CERT_BAG_OID => {
+                let certificate = explicit::<CertBag>(&bag.bag_value)?;
+                if certificate.cert_id == pkcs12::PKCS_12_X509_CERT_OID {
+                    certificates.push(Certificate::from_der(certificate.cert_value.as_bytes())?);
+                }
+            }
+            pkcs12::PKCS_12_SAFE_CONTENTS_BAG_OID => read_bags(
+                &explicit::<Any>(&bag.bag_value)?.to_der()?,
+                password,
+                keys,
+                certificates,
+            )?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Decodes the value of a bag, explicitly tagged with `[0]`.
+fn explicit<'a, T: Decode<'a>>(value: &'a [u8]) -> der::Result<T> {
+    Ok(ContextSpecific::<T>::from_der(value)?.value)
+}
+
+fn decrypt(
+    algorithm: &AlgorithmIdentifierOwned,
+    data: &[u8],
+    password: &str,
+) -> crate::Result<Vec<u8>> {
+    if algorithm.oid == pkcs5::pbes2::PBES2_OID {
+        let algorithm = algorithm.to_der()?;
+        return pkcs5::EncryptionScheme::try_from(algorithm.as_slice())?
+            .decrypt(password, data)
+            .map_err(|e| match e {
+                pkcs5::Error::DecryptFailed => wrong_password(),
+                e => invalid(e.to_string()),
+            });
+    }
+
+    let parameters = algorithm
+        .parameters
+        .as_ref()
+        .ok_or_else(|| invalid("missing encryption parameters"))?
+        .decode_as::<Pkcs12PbeParams>()?;
+    let derive = |id, len| {
+        derive_key_utf8::<Sha1>(
+            password,
+            parameters.salt.as_bytes(),
+            id,
+            parameters.iterations,
+            len,
+        )
+    };
+    let iv = derive(Pkcs12KeyType::Iv, 8)?;
+    match algorithm.oid {
+        pkcs12::PKCS_12_PBE_WITH_SHAAND3_KEY_TRIPLE_DES_CBC => {
+            let key = derive(Pkcs12KeyType::EncryptionKey, 24)?;
+            cbc_decrypt(des::TdesEde3::new_from_slice(&key), &iv, data)
+        }
+        pkcs12::PKCS_12_PBE_WITH_SHAAND128_BIT_RC2_CBC
+        | pkcs12::PKCS_12_PBEWITH_SHAAND40_BIT_RC2_CBC => {
+            let bits = if algorithm.oid == pkcs12::PKCS_12_PBEWITH_SHAAND40_BIT_RC2_CBC {
+                40
+            } else {
+                128
+            };
+            let key = derive(Pkcs12KeyType::EncryptionKey, bits / 8)?;
+            cbc_decrypt(
+                Ok::<_, ()>(rc2::Rc2::new_with_eff_key_len(&key, bits)),
+                &iv,
+                data,
+            )
+        }
+        id => Err(invalid(format!("unsupported encryption algorithm {id}"))),
+    }
+}
+
+fn cbc_decrypt<C: BlockCipher + BlockDecryptMut, E>(
+    cipher: Result<C, E>,
+    iv: &[u8],
+    data: &[u8],
+) -> crate::Result<Vec<u8>> {
+    let cipher = cipher.map_err(|_| invalid("invalid key length"))?;
+    cbc::Decryptor::inner_iv_slice_init(cipher, iv)
+        .map_err(|_| invalid("invalid initialization vector"))?
+        .decrypt_padded_vec_mut::<Pkcs7>(data)
+        .map_err(|_| wrong_password())
+}
+
+/// Concatenates the segments of an OCTET STRING in the constructed form.
+fn segments(content: &[u8]) -> der::Result<Vec<u8>> {
+    let mut reader = SliceReader::new(content)?;
+    let mut octets = Vec::new();
+    while !reader.is_finished() {
+        octets.extend_from_slice(reader.decode::<OctetString>()?.as_bytes());
+    }
+    Ok(octets)
+}
+
+/// Converts the BER encoding of Keychain exports to DER, making the indefinite lengths definite
+/// and the constructed OCTET STRINGs primitive.
+fn to_der(data: &[u8]) -> crate::Result<Vec<u8>> {
+    let mut der = Vec::with_capacity(data.len());
+    let mut input = data;
+    while !input.is_empty() {
+        input = convert(input, &mut der)?;
+    }
+    Ok(der)
+}
+
+const CONSTRUCTED_OCTET_STRING: u8 = 0x24;
+
+/// Converts the first element of `input` to DER, returning the rest of the input.
+fn convert<'a>(input: &'a [u8], der: &mut Vec<u8>) -> crate::Result<&'a [u8]> {
+    let truncated = || invalid("truncated BER data");
+    let (&tag, rest) = input.split_first().ok_or_else(truncated)?;
+    let (&length, mut rest) = rest.split_first().ok_or_else(truncated)?;
+    let length = match length {
+        0x80 => None,
+        0..=0x7f => Some(length as usize),
+        _ => {
+            let size = (length & 0x7f) as usize;
+            if size > std::mem::size_of::<usize>() {
+                return Err(invalid("BER length too large"));
+            }
+            let bytes = rest.get(..size).ok_or_else(truncated)?;
+            rest = &rest[size..];
+            Some(bytes.iter().fold(0, |len, b| len << 8 | *b as usize))
+        }
+    };
+
+    let (content, rest) = if tag & 0x20 == 0 {
+        let length = length.ok_or_else(|| invalid("indefinite length of a primitive element"))?;
+        let content = rest.get(..length).ok_or_else(truncated)?;
+        (content.to_vec(), &rest[length..])
+    } else {
+        let mut content = Vec::new();
+        let rest = match length {
+            Some(length) => {
+                let mut nested = rest.get(..length).ok_or_else(truncated)?;
+                while !nested.is_empty() {
+                    nested = convert(nested, &mut content)?;
+                }
+                &rest[length..]
+            }
+            // the nested elements are terminated by an end-of-contents element
+            None => {
+                let mut nested = rest;
+                while !nested.starts_with(&[0, 0]) {
+                    if nested.is_empty() {
+                        return Err(truncated());
+                    }
+                    nested = convert(nested, &mut content)?;
+                }
+                &nested[2..]
+            }
+        };
+        (content, rest)
+    };
+
+    let (tag, content) = match tag {
+        CONSTRUCTED_OCTET_STRING => (Tag::OctetString, segments(&content)?),
+        tag => (Tag::try_from(tag)?, content),
+    };
+    Header::new(tag, content.len())?.encode_to_vec(der)?;
+    der.extend_from_slice(&content);
+    Ok(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use cbc::cipher::{BlockEncryptMut, KeyIvInit};
+    use pkcs12::{pfx::Version, safe_bag::SafeBag};
+    use rsa::pkcs8::EncodePrivateKey;
+
+    use super::*;
+    use crate::codesign::identity::tests::self_signed;
+
+    fn bag(id: ObjectIdentifier, value: &impl Encode) -> SafeBag {
+        SafeBag {
+            bag_id: id,
+            // tagged with [0] when encoded, unlike the decoded value
+            bag_value: value.to_der().unwrap(),
+            bag_attributes: None,
+        }
+    }
+
+    /// Creates a key store holding PBES2 encrypted keys, and the certificates encrypted with
+    /// the legacy 3DES scheme.
+    fn key_store(keys: &[&RsaPrivateKey], certificates: &[Certificate], password: &str) -> Vec<u8> {
+        let scheme = pkcs5::EncryptionScheme::from(
+            pkcs5::pbes2::Parameters::pbkdf2_sha256_aes256cbc(2048, &[1; 8], &[2; 16]).unwrap(),
+        );
+        let keys = keys
+            .iter()
+            .map(|key| {
+                let key = EncryptedPrivateKeyInfo {
+                    encryption_algorithm: AlgorithmIdentifierOwned::from_der(
+                        &scheme.to_der().unwrap(),
+                    )
+                    .unwrap(),
+                    encrypted_data: OctetString::new(
+                        scheme
+                            .encrypt(password, key.to_pkcs8_der().unwrap().as_bytes())
+                            .unwrap(),
+                    )
+                    .unwrap(),
+                };
+                bag(pkcs12::PKCS_12_PKCS8_KEY_BAG_OID, &key)
+            })
+            .collect::<Vec<_>>();
+
+        let certificates = certificates
+            .iter()
+            .map(|certificate| {
+                let certificate = CertBag {
+                    cert_id: pkcs12::PKCS_12_X509_CERT_OID,
+                    cert_value: OctetString::new(certificate.to_der().unwrap()).unwrap(),
+                };
+                bag(pkcs12::PKCS_12_CERT_BAG_OID, &certificate)
+            })
+            .collect::<Vec<_>>();
+        let parameters = Pkcs12PbeParams {
+            salt: OctetString::new([3; 8]).unwrap(),
+            iterations: 2048,
+        };
+        let derive = |id, len| derive_key_utf8::<Sha1>(password, &[3; 8], id, 2048, len).unwrap();
+        let encrypted = cbc::Encryptor::<des::TdesEde3>::new_from_slices(
+            &derive(Pkcs12KeyType::EncryptionKey, 24),
+            &derive(Pkcs12KeyType::Iv, 8),
+        )
+        .unwrap()
+        .encrypt_padded_vec_mut::<Pkcs7>(&certificates.to_der().unwrap());
+        let encrypted = EncryptedData {
+            version: 0,
+            content_info: EncryptedContentInfo {
+                content_type: DATA,
+                algorithm: AlgorithmIdentifierOwned {
+                    oid: pkcs12::PKCS_12_PBE_WITH_SHAAND3_KEY_TRIPLE_DES_CBC,
+                    parameters: Some(Any::encode_from(&parameters).unwrap()),
+                },
+                content: Any::new(
+                    Tag::ContextSpecific {
+                        constructed: false,
+                        number: der::TagNumber::N0,
+                    },
+                    encrypted,
+                )
+                .unwrap(),
+            },
+        };
+
+        let auth_safe = vec![
+            ContentInfo {
+                content_type: DATA,
+                content: Any::encode_from(&OctetString::new(keys.to_der().unwrap()).unwrap())
+                    .unwrap(),
+            },
+            ContentInfo {
+                content_type: ENCRYPTED_DATA,
+                content: Any::encode_from(&encrypted).unwrap(),
+            },
+        ];
+        Pfx {
+            version: Version::V3,
+            auth_safe: ContentInfo {
+                content_type: DATA,
+                content: Any::encode_from(&OctetString::new(auth_safe.to_der().unwrap()).unwrap())
+                    .unwrap(),
+            },
+            mac_data: None,
+        }
+        .to_der()
+        .unwrap()
+    }
+
+    #[test]
+    fn reads_identity() {
+        let identity = self_signed("CN=Test");
+        let root = self_signed("CN=Root").certificates.remove(0);
+        let key_store = key_store(
+            &[&identity.key],
+            &[root, identity.certificate().clone()],
+            "secret",
+        );
+
+        let parsed = parse(&key_store, "secret").unwrap();
+        assert_eq!(parsed.key, identity.key);
+        // the signing certificate comes first, and the unrelated certificate is left out
+        assert_eq!(parsed.certificates, identity.certificates);
+
+        assert!(parse(&key_store, "wrong").is_err());
+    }
+
+    #[test]
+    fn finds_identity() {
+        let application = self_signed("CN=Developer ID Application: Test");
+        let installer = self_signed("CN=Developer ID Installer: Test");
+        let key_store = key_store(
+            &[&application.key, &installer.key],
+            &[
+                application.certificate().clone(),
+                installer.certificate().clone(),
+            ],
+            "secret",
+        );
+
+        assert!(parse(&key_store, "secret").is_err());
+        let found = find(&key_store, "secret", "Developer ID Installer").unwrap();
+        assert_eq!(found.key, installer.key);
+        assert_eq!(found.certificates, installer.certificates);
+        assert!(find(&key_store, "secret", "Apple Development").is_err());
+    }
+
+    #[test]
+    fn converts_ber_to_der() {
+        // an indefinite length SEQUENCE holding a constructed OCTET STRING with a segment
+        // of non minimal length, and an INTEGER
+        let ber = [
+            0x30, 0x80, 0x24, 0x80, 0x04, 0x02, 1, 2, 0x04, 0x81, 0x01, 3, 0, 0, 0x02, 0x01, 1, 0,
+            0,
+        ];
+        assert_eq!(
+            to_der(&ber).unwrap(),
+            [0x30, 0x08, 0x04, 0x03, 1, 2, 3, 0x02, 0x01, 1]
+        );
+
+        let der = [0x30, 0x03, 0x02, 0x01, 1];
+        assert_eq!(to_der(&der).unwrap(), der);
+        assert!(to_der(&[0x30, 0x80, 0x02, 0x01, 1]).is_err());
+    }
+}
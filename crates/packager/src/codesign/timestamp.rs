@@ -0,0 +1,131 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Client for RFC 3161 time-stamping authorities, used to countersign signatures
+//! so they remain valid after the signing certificate expires.
+
+use std::io::Read;
+
+use cms::content_info::ContentInfo;
+use der::{
+    asn1::{BitString, OctetString},
+    Decode, Encode, Sequence,
+};
+use sha2::{Digest, Sha256};
+use x509_cert::spki::AlgorithmIdentifierOwned;
+
+use super::identity::{self, SIGNED_DATA};
+use crate::{util, Error};
+
+/// `TimeStampReq`, without the policy and extensions.
+#[derive(Sequence)]
+struct TimeStampReq {
+    version: u8,
+    message_imprint: MessageImprint,
+    nonce: u64,
+    cert_req: bool,
+}
+
+#[derive(Sequence)]
+struct MessageImprint {
+    hash_algorithm: AlgorithmIdentifierOwned,
+    hashed_message: OctetString,
+}
+
+#[derive(Sequence)]
+struct TimeStampResp {
+    status: PkiStatusInfo,
+    #[asn1(optional = "true")]
+    time_stamp_token: Option<ContentInfo>,
+}
+
+#[derive(Sequence)]
+struct PkiStatusInfo {
+    status: u8,
+    #[asn1(optional = "true")]
+    status_string: Option<Vec<String>>,
+    #[asn1(optional = "true")]
+    fail_info: Option<BitString>,
+}
+
+/// Requests a timestamp token for `data`.
+#[tracing::instrument(level = "trace", skip(data))]
+pub(crate) fn request(url: &str, data: &[u8]) -> crate::Result<ContentInfo> {
+    let request = TimeStampReq {
+        version: 1,
+        message_imprint: MessageImprint {
+            hash_algorithm: identity::sha256_algorithm(),
+            hashed_message: OctetString::new(Sha256::digest(data).to_vec())?,
+        },
+        nonce: rand::random(),
+        // ask for the certificate of the authority to be included
+        cert_req: true,
+    }
+    .to_der()?;
+
+    tracing::debug!("Requesting timestamp from {url}");
+    let response = util::http_agent()
+        .post(url)
+        .set("Content-Type", "application/timestamp-query")
+        .send_bytes(&request)
+        .map_err(|e| Error::TimestampFailed(e.to_string()))?;
+    let mut body = Vec::new();
+    response.into_reader().read_to_end(&mut body)?;
+
+    parse_response(&body)
+}
+
+fn parse_response(response: &[u8]) -> crate::Result<ContentInfo> {
+    let response = TimeStampResp::from_der(response)?;
+    // granted, or granted with modifications
+    let status = response.status.status;
+    if status > 1 {
+        let mut message = format!("the request was rejected with status {status}");
+        if let Some(reason) = response.status.status_string {
+            message.push_str(&format!(": {}", reason.join(", ")));
+        }
+        return Err(Error::TimestampFailed(message));
+    }
+    response
+        .time_stamp_token
+        .filter(|token| token.content_type == SIGNED_DATA)
+        .ok_or_else(|| Error::TimestampFailed("invalid timestamp token".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use der::Any;
+
+    use super::*;
+
+    fn response(status: u8, token: Option<ContentInfo>) -> Vec<u8> {
+        TimeStampResp {
+            status: PkiStatusInfo {
+                status,
+                status_string: None,
+                fail_info: None,
+            },
+            time_stamp_token: token,
+        }
+        .to_der()
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_responses() {
+        let token = ContentInfo {
+            content_type: SIGNED_DATA,
+            content: Any::null(),
+        };
+        assert_eq!(
+            parse_response(&response(0, Some(token.clone()))).unwrap(),
+            token
+        );
+
+        assert!(matches!(
+            parse_response(&response(2, None)),
+            Err(Error::TimestampFailed(_))
+        ));
+    }
+}
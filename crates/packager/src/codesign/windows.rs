@@ -3,19 +3,22 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-License-Identifier: MIT
 
-use std::{fmt::Debug, path::Path, process::Command};
+use std::{
+    ffi::OsString,
+    fmt::{self, Debug},
+    path::{Path, PathBuf},
+    process::Command,
+};
 
 #[cfg(windows)]
 use once_cell::sync::Lazy;
-#[cfg(windows)]
-use std::path::PathBuf;
 
-use crate::{config::Config, shell::CommandExt, util};
+use super::authenticode;
+use crate::{config::Config, shell::CommandExt, util, Error};
 
 #[cfg(windows)]
 use crate::util::Bitness;
 
-#[derive(Debug)]
 #[allow(dead_code)]
 pub struct SignParams {
     pub product_name: String,
@@ -24,6 +27,24 @@ pub struct SignParams {
     pub timestamp_url: Option<String>,
     pub tsp: bool,
     pub sign_command: Option<String>,
+    pub signing_certificate: Option<PathBuf>,
+    pub signing_key: Option<PathBuf>,
+    pub signing_certificate_password: Option<OsString>,
+}
+
+impl Debug for SignParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignParams")
+            .field("product_name", &self.product_name)
+            .field("digest_algorithm", &self.digest_algorithm)
+            .field("certificate_thumbprint", &self.certificate_thumbprint)
+            .field("timestamp_url", &self.timestamp_url)
+            .field("tsp", &self.tsp)
+            .field("sign_command", &self.sign_command)
+            .field("signing_certificate", &self.signing_certificate)
+            .field("signing_key", &self.signing_key)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Config {
@@ -31,9 +52,23 @@ impl Config {
         self.windows()
             .and_then(|w| w.certificate_thumbprint.as_ref())
             .is_some()
+            || self
+                .windows()
+                .and_then(|w| w.signing_certificate.as_ref())
+                .is_some()
             || self.custom_sign_command()
     }
 
+    /// Whether files are signed in-process by the built-in Authenticode signer,
+    /// rather than by running a signing command.
+    pub(crate) fn builtin_sign(&self) -> bool {
+        !self.custom_sign_command()
+            && self
+                .windows()
+                .and_then(|w| w.signing_certificate.as_ref())
+                .is_some()
+    }
+
     pub(crate) fn custom_sign_command(&self) -> bool {
         self.windows()
             .and_then(|w| w.sign_command.as_ref())
@@ -55,6 +90,13 @@ impl Config {
             timestamp_url: windows.and_then(|w| w.timestamp_url.as_ref()).cloned(),
             tsp: windows.map(|w| w.tsp).unwrap_or_default(),
             sign_command: windows.and_then(|w| w.sign_command.as_ref()).cloned(),
+            signing_certificate: windows
+                .and_then(|w| w.signing_certificate.as_ref())
+                .cloned(),
+            signing_key: windows.and_then(|w| w.signing_key.as_ref()).cloned(),
+            signing_certificate_password: windows
+                .and_then(|w| w.signing_certificate_password.clone())
+                .or_else(|| std::env::var_os("WINDOWS_CERTIFICATE_PASSWORD")),
         }
     }
 }
@@ -238,10 +280,54 @@ pub fn sign_default<P: AsRef<Path> + Debug>(path: P, params: &SignParams) -> cra
     Ok(())
 }
 
+/// Signs a PE file with the built-in Authenticode signer, skipping other files.
+#[tracing::instrument(level = "trace")]
+pub fn sign_authenticode<P: AsRef<Path> + Debug>(
+    path: P,
+    params: &SignParams,
+) -> crate::Result<()> {
+    let path = path.as_ref();
+    let Some(certificate) = &params.signing_certificate else {
+        return Ok(());
+    };
+    if !params.digest_algorithm.eq_ignore_ascii_case("sha256") {
+        return Err(Error::UnsupportedDigestAlgorithm(
+            params.digest_algorithm.clone(),
+        ));
+    }
+
+    tracing::info!(
+        "Codesigning {} with certificate {}",
+        util::display_path(path),
+        util::display_path(certificate)
+    );
+
+    let identity = authenticode::identity(
+        certificate,
+        params.signing_key.as_deref(),
+        params.signing_certificate_password.as_deref(),
+    )?;
+    let signed = authenticode::sign_file(
+        path,
+        &identity,
+        &params.product_name,
+        params.timestamp_url.as_deref(),
+    )?;
+    if !signed {
+        tracing::warn!(
+            "Only executables can be signed with `signing_certificate`, you can specify a custom signing command in `config.windows.sign_command` to sign {}, for now, skipping signing it...",
+            util::display_path(path)
+        );
+    }
+
+    Ok(())
+}
+
 #[tracing::instrument(level = "trace")]
 pub fn sign<P: AsRef<Path> + Debug>(path: P, params: &SignParams) -> crate::Result<()> {
     match &params.sign_command {
         Some(custom_command) => sign_custom(path, custom_command),
+        None if params.signing_certificate.is_some() => sign_authenticode(path, params),
         #[cfg(windows)]
         None => sign_default(path, params),
 
@@ -251,6 +337,13 @@ pub fn sign<P: AsRef<Path> + Debug>(path: P, params: &SignParams) -> crate::Resu
     }
 }
 
+/// Warns that `subject` is not signed, as signing without a configured certificate
+/// or signing command is only supported on Windows hosts.
+#[cfg(not(windows))]
+pub(crate) fn warn_unsupported_host(subject: &str) {
+    tracing::warn!("Codesigning is only supported on Windows hosts by default, but you can specify a certificate in `config.windows.signing_certificate` or a custom signing command in `config.windows.sign_command`, for now, skipping signing {subject}...");
+}
+
 #[tracing::instrument(level = "trace", skip(config))]
 pub fn try_sign(
     file_path: &std::path::PathBuf,
@@ -3,8 +3,9 @@ use std::path::PathBuf;
 use crate::{Config, PackageFormat};
 
 use super::{
-    AppImageConfig, Binary, DebianConfig, FileAssociation, HookCommand, LogLevel, MacOsConfig,
-    NsisConfig, PacmanConfig, Resource, WindowsConfig, WixConfig,
+    ApkConfig, AppImageConfig, ArchiveConfig, Binary, DebianConfig, FileAssociation, FlatpakConfig,
+    FreeBsdConfig, HookCommand, LogLevel, MacOsConfig, MsixConfig, NsisConfig, PacmanConfig,
+    PkgConfig, Resource, RpmConfig, SnapConfig, WindowsConfig, WixConfig,
 };
 
 /// A builder type for [`Config`].
@@ -195,6 +196,18 @@ impl ConfigBuilder {
         self
     }
 
+    /// Set the [MSIX](Config::msix) specific configuration.
+    pub fn msix(mut self, msix: MsixConfig) -> Self {
+        self.0.msix.replace(msix);
+        self
+    }
+
+    /// Set the [pkg](Config::pkg) specific configuration.
+    pub fn pkg(mut self, pkg: PkgConfig) -> Self {
+        self.0.pkg.replace(pkg);
+        self
+    }
+
     /// Set the [Debian](Config::deb) specific configuration.
     pub fn deb(mut self, deb: DebianConfig) -> Self {
         self.0.deb.replace(deb);
@@ -212,4 +225,40 @@ impl ConfigBuilder {
         self.0.pacman.replace(pacman);
         self
     }
+
+    /// Set the [RPM](Config::rpm) specific configuration.
+    pub fn rpm(mut self, rpm: RpmConfig) -> Self {
+        self.0.rpm.replace(rpm);
+        self
+    }
+
+    /// Set the [Flatpak](Config::flatpak) specific configuration.
+    pub fn flatpak(mut self, flatpak: FlatpakConfig) -> Self {
+        self.0.flatpak.replace(flatpak);
+        self
+    }
+
+    /// Set the [snap](Config::snap) specific configuration.
+    pub fn snap(mut self, snap: SnapConfig) -> Self {
+        self.0.snap.replace(snap);
+        self
+    }
+
+    /// Set the [apk](Config::apk) specific configuration.
+    pub fn apk(mut self, apk: ApkConfig) -> Self {
+        self.0.apk.replace(apk);
+        self
+    }
+
+    /// Set the [FreeBSD](Config::freebsd) specific configuration.
+    pub fn freebsd(mut self, freebsd: FreeBsdConfig) -> Self {
+        self.0.freebsd.replace(freebsd);
+        self
+    }
+
+    /// Set the [archive](Config::archive) specific configuration.
+    pub fn archive(mut self, archive: ArchiveConfig) -> Self {
+        self.0.archive.replace(archive);
+        self
+    }
 }
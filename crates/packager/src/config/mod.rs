@@ -25,6 +25,9 @@ pub use category::AppCategory;
 
 pub use cargo_packager_utils::PackageFormat;
 
+/// The target triple of universal macOS binaries, see [`MacOsConfig::target_triples`].
+pub(crate) const UNIVERSAL_TARGET_TRIPLE: &str = "universal-apple-darwin";
+
 /// **macOS-only**. Corresponds to CFBundleTypeRole
 #[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
@@ -64,7 +67,7 @@ impl Display for BundleTypeRole {
 pub struct FileAssociation {
     /// File extensions to associate with this app. e.g. 'png'
     pub extensions: Vec<String>,
-    /// The mime-type e.g. 'image/png' or 'text/plain'. **Linux-only**.
+    /// The mime-type e.g. 'image/png' or 'text/plain'. **Linux and MSIX only**.
     #[serde(alias = "mime-type", alias = "mime_type")]
     pub mime_type: Option<String>,
     /// The association description. **Windows-only**. It is displayed on the `Type` column on Windows Explorer.
@@ -103,7 +106,7 @@ impl FileAssociation {
         self
     }
 
-    /// Set the mime-type e.g. 'image/png' or 'text/plain'. **Linux-only**.
+    /// Set the mime-type e.g. 'image/png' or 'text/plain'. **Linux and MSIX only**.
     pub fn mime_type<S: Into<String>>(mut self, mime_type: S) -> Self {
         self.mime_type.replace(mime_type.into());
         self
@@ -180,6 +183,39 @@ impl DeepLinkProtocol {
 pub struct DebianConfig {
     /// The list of Debian dependencies.
     pub depends: Option<Dependencies>,
+    /// List of Debian packages that must be installed and configured before this package is even unpacked.
+    ///
+    /// See : <https://www.debian.org/doc/debian-policy/ch-relationships.html#binary-dependencies-depends-recommends-suggests-enhances-pre-depends>
+    #[serde(alias = "pre-depends", alias = "pre_depends")]
+    pub pre_depends: Option<Dependencies>,
+    /// List of Debian packages that would be found together with this package in all but unusual installations.
+    ///
+    /// See : <https://www.debian.org/doc/debian-policy/ch-relationships.html#binary-dependencies-depends-recommends-suggests-enhances-pre-depends>
+    pub recommends: Option<Dependencies>,
+    /// List of Debian packages that are related to this package and may make it more useful.
+    ///
+    /// See : <https://www.debian.org/doc/debian-policy/ch-relationships.html#binary-dependencies-depends-recommends-suggests-enhances-pre-depends>
+    pub suggests: Option<Dependencies>,
+    /// List of Debian packages that this package can enhance the functionality of.
+    ///
+    /// See : <https://www.debian.org/doc/debian-policy/ch-relationships.html#binary-dependencies-depends-recommends-suggests-enhances-pre-depends>
+    pub enhances: Option<Dependencies>,
+    /// List of Debian packages that are broken by this package.
+    ///
+    /// See : <https://www.debian.org/doc/debian-policy/ch-relationships.html#packages-which-break-other-packages-breaks>
+    pub breaks: Option<Dependencies>,
+    /// List of Debian packages that conflict with this package and must be removed before it can be installed.
+    ///
+    /// See : <https://www.debian.org/doc/debian-policy/ch-relationships.html#conflicting-binary-packages-conflicts>
+    pub conflicts: Option<Dependencies>,
+    /// List of virtual packages that are provided by this package.
+    ///
+    /// See : <https://www.debian.org/doc/debian-policy/ch-relationships.html#virtual-packages-provides>
+    pub provides: Option<Dependencies>,
+    /// List of Debian packages whose files are overwritten or which are completely replaced by this package.
+    ///
+    /// See : <https://www.debian.org/doc/debian-policy/ch-relationships.html#overwriting-files-and-replacing-packages-replaces>
+    pub replaces: Option<Dependencies>,
     /// Path to a custom desktop file Handlebars template.
     ///
     /// Available variables: `categories`, `comment` (optional), `exec`, `icon` and `name`.
@@ -231,6 +267,56 @@ pub struct DebianConfig {
     /// Defaults to [`Config::product_name`] converted to kebab-case.
     #[serde(alias = "package-name", alias = "package_name")]
     pub package_name: Option<String>,
+    /// Whether to detect the shared libraries needed by the packaged binaries
+    /// and add the Debian packages providing them to the `Depends` field,
+    /// similar to `dpkg-shlibdeps`. By default, it is set to `false`.
+    ///
+    /// The `DT_NEEDED` entries of every ELF file in the package are resolved using
+    /// [`DebianConfig::shlibs_file`], then the `shlibs` files and the file lists of the dpkg database.
+    #[serde(default, alias = "shlib-depends", alias = "shlib_depends")]
+    pub shlib_depends: bool,
+    /// Path to a file mapping shared libraries to Debian dependencies, used by [`DebianConfig::shlib_depends`]
+    /// and taking precedence over the dpkg database. The file uses the `shlibs` format:
+    /// ```text
+    /// libfoo 1 libfoo1 (>= 1.2)
+    /// ```
+    ///
+    /// See : <https://www.debian.org/doc/debian-policy/ch-sharedlibs.html#the-shlibs-file-format>
+    #[serde(alias = "shlibs-file", alias = "shlibs_file")]
+    pub shlibs_file: Option<PathBuf>,
+    /// The compression to use for the `control` and `data` archives of the package.
+    /// By default, it is set to `gzip`.
+    pub compression: Option<DebianCompression>,
+    /// The compression level to use, its range depends on the [`DebianConfig::compression`]:
+    /// `0-9` for `gzip` and `xz`, `1-22` for `zstd`.
+    /// By default, each compression uses its default level.
+    #[serde(alias = "compression-level", alias = "compression_level")]
+    pub compression_level: Option<u32>,
+    /// The license of the package as a short name used in the generated `copyright` file,
+    /// ideally an SPDX identifier such as `MIT` or `Apache-2.0`.
+    /// The license text is read from [`Config::license_file`]. By default, it is set to `custom`.
+    ///
+    /// See : <https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/#license-specification>
+    pub license: Option<String>,
+    /// Path to the changelog of the app, which is compressed and installed
+    /// as `/usr/share/doc/<package>/changelog.gz`.
+    pub changelog: Option<PathBuf>,
+    /// The `preinst` maintainer script, run before the package is unpacked.
+    ///
+    /// See : <https://www.debian.org/doc/debian-policy/ch-maintainerscripts.html>
+    pub preinst: Option<MaintainerScript>,
+    /// The `postinst` maintainer script, run after the package is unpacked.
+    ///
+    /// See : <https://www.debian.org/doc/debian-policy/ch-maintainerscripts.html>
+    pub postinst: Option<MaintainerScript>,
+    /// The `prerm` maintainer script, run before the package is removed.
+    ///
+    /// See : <https://www.debian.org/doc/debian-policy/ch-maintainerscripts.html>
+    pub prerm: Option<MaintainerScript>,
+    /// The `postrm` maintainer script, run after the package is removed.
+    ///
+    /// See : <https://www.debian.org/doc/debian-policy/ch-maintainerscripts.html>
+    pub postrm: Option<MaintainerScript>,
 }
 
 impl DebianConfig {
@@ -261,6 +347,182 @@ impl DebianConfig {
         self
     }
 
+    /// Set the list of Debian packages for the `Pre-Depends` field directly using an iterator of strings.
+    pub fn pre_depends<I, S>(mut self, pre_depends: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.pre_depends.replace(Dependencies::List(
+            pre_depends.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    /// Set the list of Debian packages for the `Pre-Depends` field indirectly via a path to a file,
+    /// which must contain one package per line.
+    pub fn pre_depends_path<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.pre_depends.replace(Dependencies::Path(path.into()));
+        self
+    }
+
+    /// Set the list of Debian packages for the `Recommends` field directly using an iterator of strings.
+    pub fn recommends<I, S>(mut self, recommends: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.recommends.replace(Dependencies::List(
+            recommends.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    /// Set the list of Debian packages for the `Recommends` field indirectly via a path to a file,
+    /// which must contain one package per line.
+    pub fn recommends_path<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.recommends.replace(Dependencies::Path(path.into()));
+        self
+    }
+
+    /// Set the list of Debian packages for the `Suggests` field directly using an iterator of strings.
+    pub fn suggests<I, S>(mut self, suggests: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.suggests.replace(Dependencies::List(
+            suggests.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    /// Set the list of Debian packages for the `Suggests` field indirectly via a path to a file,
+    /// which must contain one package per line.
+    pub fn suggests_path<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.suggests.replace(Dependencies::Path(path.into()));
+        self
+    }
+
+    /// Set the list of Debian packages for the `Enhances` field directly using an iterator of strings.
+    pub fn enhances<I, S>(mut self, enhances: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.enhances.replace(Dependencies::List(
+            enhances.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    /// Set the list of Debian packages for the `Enhances` field indirectly via a path to a file,
+    /// which must contain one package per line.
+    pub fn enhances_path<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.enhances.replace(Dependencies::Path(path.into()));
+        self
+    }
+
+    /// Set the list of Debian packages for the `Breaks` field directly using an iterator of strings.
+    pub fn breaks<I, S>(mut self, breaks: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.breaks.replace(Dependencies::List(
+            breaks.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    /// Set the list of Debian packages for the `Breaks` field indirectly via a path to a file,
+    /// which must contain one package per line.
+    pub fn breaks_path<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.breaks.replace(Dependencies::Path(path.into()));
+        self
+    }
+
+    /// Set the list of Debian packages for the `Conflicts` field directly using an iterator of strings.
+    pub fn conflicts<I, S>(mut self, conflicts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.conflicts.replace(Dependencies::List(
+            conflicts.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    /// Set the list of Debian packages for the `Conflicts` field indirectly via a path to a file,
+    /// which must contain one package per line.
+    pub fn conflicts_path<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.conflicts.replace(Dependencies::Path(path.into()));
+        self
+    }
+
+    /// Set the list of Debian packages for the `Provides` field directly using an iterator of strings.
+    pub fn provides<I, S>(mut self, provides: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.provides.replace(Dependencies::List(
+            provides.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    /// Set the list of Debian packages for the `Provides` field indirectly via a path to a file,
+    /// which must contain one package per line.
+    pub fn provides_path<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.provides.replace(Dependencies::Path(path.into()));
+        self
+    }
+
+    /// Set the list of Debian packages for the `Replaces` field directly using an iterator of strings.
+    pub fn replaces<I, S>(mut self, replaces: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.replaces.replace(Dependencies::List(
+            replaces.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    /// Set the list of Debian packages for the `Replaces` field indirectly via a path to a file,
+    /// which must contain one package per line.
+    pub fn replaces_path<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.replaces.replace(Dependencies::Path(path.into()));
+        self
+    }
+
     /// Set the path to a custom desktop file Handlebars template.
     ///
     /// Available variables: `categories`, `comment` (optional), `exec`, `icon` and `name`.
@@ -315,6 +577,175 @@ impl DebianConfig {
         );
         self
     }
+
+    /// Set whether to detect the shared libraries needed by the packaged binaries
+    /// and add the Debian packages providing them to the `Depends` field.
+    pub fn shlib_depends(mut self, shlib_depends: bool) -> Self {
+        self.shlib_depends = shlib_depends;
+        self
+    }
+
+    /// Set the path to a file mapping shared libraries to Debian dependencies, in the `shlibs` format.
+    pub fn shlibs_file<P: Into<PathBuf>>(mut self, shlibs_file: P) -> Self {
+        self.shlibs_file.replace(shlibs_file.into());
+        self
+    }
+
+    /// Set the compression to use for the `control` and `data` archives of the package.
+    pub fn compression(mut self, compression: DebianCompression) -> Self {
+        self.compression.replace(compression);
+        self
+    }
+
+    /// Set the compression level to use.
+    pub fn compression_level(mut self, compression_level: u32) -> Self {
+        self.compression_level.replace(compression_level);
+        self
+    }
+
+    /// Set the license of the package as a short name used in the generated `copyright` file.
+    pub fn license<S: Into<String>>(mut self, license: S) -> Self {
+        self.license.replace(license.into());
+        self
+    }
+
+    /// Set the path to the changelog of the app.
+    pub fn changelog<P: Into<PathBuf>>(mut self, changelog: P) -> Self {
+        self.changelog.replace(changelog.into());
+        self
+    }
+
+    /// Set the `preinst` maintainer script, run before the package is unpacked.
+    pub fn preinst<S: Into<MaintainerScript>>(mut self, preinst: S) -> Self {
+        self.preinst.replace(preinst.into());
+        self
+    }
+
+    /// Set the `postinst` maintainer script, run after the package is unpacked.
+    pub fn postinst<S: Into<MaintainerScript>>(mut self, postinst: S) -> Self {
+        self.postinst.replace(postinst.into());
+        self
+    }
+
+    /// Set the `prerm` maintainer script, run before the package is removed.
+    pub fn prerm<S: Into<MaintainerScript>>(mut self, prerm: S) -> Self {
+        self.prerm.replace(prerm.into());
+        self
+    }
+
+    /// Set the `postrm` maintainer script, run after the package is removed.
+    pub fn postrm<S: Into<MaintainerScript>>(mut self, postrm: S) -> Self {
+        self.postrm.replace(postrm.into());
+        self
+    }
+}
+
+/// Compression algorithms used for the archives of the Debian package.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub enum DebianCompression {
+    /// Gzip compression (default), supported by all dpkg versions.
+    #[default]
+    Gzip,
+    /// Xz compression, gives better compression ratios than gzip but is slower.
+    Xz,
+    /// Zstandard compression, fast with good compression ratios. Requires dpkg 1.21.18 or newer.
+    Zstd,
+    /// Disable compression.
+    None,
+}
+
+/// A Debian, Alpine or FreeBSD maintainer script, specified as either a path to a script file
+/// or as an object with a path or the inline script contents.
+///
+/// When `template` is set to `true`, the script is rendered as a Handlebars template
+/// with the following variables available: `package_name`, `product_name`, `version`,
+/// `identifier`, `main_binary_name` and `publisher`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+#[non_exhaustive]
+pub enum MaintainerScript {
+    /// A path to the script file, which is used as-is.
+    Path(PathBuf),
+    /// A path to the script file, with options.
+    File {
+        /// The path to the script file.
+        path: PathBuf,
+        /// Whether to render the script as a Handlebars template.
+        #[serde(default)]
+        template: bool,
+    },
+    /// The script contents provided inline, with options.
+    Inline {
+        /// The script contents.
+        script: String,
+        /// Whether to render the script as a Handlebars template.
+        #[serde(default)]
+        template: bool,
+    },
+}
+
+impl From<PathBuf> for MaintainerScript {
+    fn from(path: PathBuf) -> Self {
+        Self::Path(path)
+    }
+}
+
+impl From<&Path> for MaintainerScript {
+    fn from(path: &Path) -> Self {
+        Self::Path(path.to_path_buf())
+    }
+}
+
+impl MaintainerScript {
+    /// Creates a maintainer script from the inline script contents.
+    pub fn inline<S: Into<String>>(script: S) -> Self {
+        Self::Inline {
+            script: script.into(),
+            template: false,
+        }
+    }
+
+    /// Creates a maintainer script from the inline Handlebars template contents.
+    pub fn inline_template<S: Into<String>>(script: S) -> Self {
+        Self::Inline {
+            script: script.into(),
+            template: true,
+        }
+    }
+
+    /// Creates a maintainer script from a path to a Handlebars template file.
+    pub fn template_path<P: Into<PathBuf>>(path: P) -> Self {
+        Self::File {
+            path: path.into(),
+            template: true,
+        }
+    }
+
+    /// Returns the script contents and whether it should be rendered as a template.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    pub(crate) fn contents(&self) -> crate::Result<(String, bool)> {
+        match self {
+            Self::Path(path) => Ok((
+                fs::read_to_string(path).map_err(|e| Error::IoWithPath(path.clone(), e))?,
+                false,
+            )),
+            Self::File { path, template } => Ok((
+                fs::read_to_string(path).map_err(|e| Error::IoWithPath(path.clone(), e))?,
+                *template,
+            )),
+            Self::Inline { script, template } => Ok((script.clone(), *template)),
+        }
+    }
 }
 
 /// A list of dependencies specified as either a list of Strings
@@ -387,6 +818,51 @@ pub struct AppImageConfig {
     /// For example, to exclude libnss3.so, you'd specify `libnss3*`
     #[serde(alias = "excluded-libraries", alias = "excluded_libraries")]
     pub excluded_libs: Option<Vec<String>>,
+    /// Whether to build the AppImage using [`linuxdeploy`](https://github.com/linuxdeploy/linuxdeploy)
+    /// instead of the built-in builder. Defaults to `false`.
+    ///
+    /// The built-in builder assembles the AppDir and creates the AppImage without
+    /// running external tools. Like `linuxdeploy`, it bundles the libraries the app depends on,
+    /// except for the ones expected on every system and the [`AppImageConfig::excluded_libs`].
+    /// `linuxdeploy` is always used if [`AppImageConfig::linuxdeploy_plugins`] is set,
+    /// or if no runtime release is pinned for the target architecture and
+    /// [`AppImageConfig::runtime`] is not set.
+    #[serde(default)]
+    pub linuxdeploy: bool,
+    /// Path to the [AppImage type 2 runtime](https://github.com/AppImage/type2-runtime)
+    /// used by the built-in builder. By default, the release pinned for the target architecture
+    /// is downloaded, verified against its SHA-256 and cached.
+    pub runtime: Option<PathBuf>,
+    /// The [update information](https://github.com/AppImage/AppImageSpec/blob/master/draft.md#update-information)
+    /// embedded in the AppImage, used by tools like `AppImageUpdate` to find and apply delta updates.
+    /// For example `zsync|https://example.com/app-latest-x86_64.AppImage.zsync`
+    /// or `gh-releases-zsync|owner|repo|latest|app-*x86_64.AppImage.zsync`.
+    ///
+    /// When set, a `.zsync` file is also generated next to the AppImage,
+    /// which must be uploaded alongside it. AppImages built with `linuxdeploy` get the update
+    /// information from its appimage plugin, through the `UPDATE_INFORMATION` environment variable.
+    #[serde(alias = "update-information", alias = "update_information")]
+    pub update_information: Option<String>,
+    /// Whether to embed an OpenPGP signature of the AppImage, signed with the
+    /// [`AppImageConfig::signing_key`], which can be checked by tools like `validate` and `AppImageUpdate`.
+    /// Defaults to `false`.
+    ///
+    /// Only AppImages created by the built-in builder can be signed.
+    #[serde(default)]
+    pub sign: bool,
+    /// Path to, or contents of, the ASCII-armored OpenPGP secret key used to sign the AppImage
+    /// when [`AppImageConfig::sign`] is enabled.
+    ///
+    /// Defaults to the `CARGO_PACKAGER_GPG_PRIVATE_KEY` environment variable.
+    #[serde(alias = "signing-key", alias = "signing_key")]
+    pub signing_key: Option<String>,
+    /// Password of the OpenPGP secret key.
+    ///
+    /// Defaults to the `CARGO_PACKAGER_GPG_PRIVATE_KEY_PASSWORD` environment variable.
+    ///
+    /// Note: this field cannot be specified via a config file or Cargo package metadata.
+    #[serde(skip)]
+    pub signing_key_password: Option<String>,
 }
 
 impl AppImageConfig {
@@ -456,6 +932,45 @@ impl AppImageConfig {
         );
         self
     }
+
+    /// Set whether to build the AppImage using [`linuxdeploy`](https://github.com/linuxdeploy/linuxdeploy)
+    /// instead of the built-in builder.
+    pub fn linuxdeploy(mut self, linuxdeploy: bool) -> Self {
+        self.linuxdeploy = linuxdeploy;
+        self
+    }
+
+    /// Set the path to the AppImage type 2 runtime used by the built-in builder.
+    pub fn runtime<P: Into<PathBuf>>(mut self, runtime: P) -> Self {
+        self.runtime.replace(runtime.into());
+        self
+    }
+
+    /// Set the update information embedded in the AppImage.
+    /// For example `zsync|https://example.com/app-latest-x86_64.AppImage.zsync`.
+    pub fn update_information<S: Into<String>>(mut self, update_information: S) -> Self {
+        self.update_information.replace(update_information.into());
+        self
+    }
+
+    /// Set whether to embed an OpenPGP signature of the AppImage.
+    pub fn sign(mut self, sign: bool) -> Self {
+        self.sign = sign;
+        self
+    }
+
+    /// Set the path to, or contents of, the ASCII-armored OpenPGP secret key
+    /// used to sign the AppImage.
+    pub fn signing_key<S: Into<String>>(mut self, signing_key: S) -> Self {
+        self.signing_key.replace(signing_key.into());
+        self
+    }
+
+    /// Set the password of the OpenPGP secret key.
+    pub fn signing_key_password<S: Into<String>>(mut self, password: S) -> Self {
+        self.signing_key_password.replace(password.into());
+        self
+    }
 }
 
 /// The Linux pacman configuration.
@@ -488,15 +1003,967 @@ pub struct PacmanConfig {
     /// Source of the package to be stored at PKGBUILD.
     /// PKGBUILD is a bash script, so version can be referred as ${pkgver}
     pub source: Option<Vec<String>>,
+    /// The licenses of the package, ideally as SPDX identifiers. By default, it is set to `custom`.
+    ///
+    /// See : <https://wiki.archlinux.org/title/PKGBUILD#license>
+    pub license: Option<Vec<String>>,
+    /// Optional dependencies of the app, each one as a package name
+    /// optionally followed by a short description, for example `libnotify: desktop notifications`.
+    ///
+    /// See : <https://wiki.archlinux.org/title/PKGBUILD#optdepends>
+    pub optdepends: Option<Vec<String>>,
+    /// Files that contain user made changes and should be preserved during upgrades
+    /// and removal of the package, as relative paths without a leading slash, for example `etc/app.conf`.
+    ///
+    /// See : <https://wiki.archlinux.org/title/PKGBUILD#backup>
+    pub backup: Option<Vec<String>>,
+    /// Path to the install script, defining functions like `post_install` and `pre_remove`
+    /// that are run by pacman on package operations.
+    ///
+    /// See : <https://wiki.archlinux.org/title/PKGBUILD#install>
+    pub install: Option<PathBuf>,
+    /// Whether to also generate a `.tar.gz` of the package contents and a `PKGBUILD`
+    /// that can be used with `makepkg`, next to the `.pkg.tar.zst` package. Defaults to `false`.
+    #[serde(default, alias = "generate-pkgbuild", alias = "generate_pkgbuild")]
+    pub generate_pkgbuild: bool,
+    /// Configuration to generate a `PKGBUILD` and `.SRCINFO` ready to be published to the
+    /// [AUR](https://aur.archlinux.org), in the `aur` directory next to the `.pkg.tar.zst` package.
+    pub aur: Option<AurConfig>,
 }
 
-impl PacmanConfig {
-    /// Creates a new [`PacmanConfig`].
-    pub fn new() -> Self {
-        Self::default()
+impl PacmanConfig {
+    /// Creates a new [`PacmanConfig`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Set the list of custom files to add to the pacman package.
+    /// Maps a dir/file to a dir/file inside the pacman package.
+    pub fn files<I, S, T>(mut self, files: I) -> Self
+    where
+        I: IntoIterator<Item = (S, T)>,
+        S: Into<String>,
+        T: Into<String>,
+    {
+        self.files.replace(
+            files
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        );
+        self
+    }
+
+    /// Set the list of pacman dependencies directly using an iterator of strings.
+    pub fn depends<I, S>(mut self, depends: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.depends.replace(Dependencies::List(
+            depends.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    /// Set the list of pacman dependencies indirectly via a path to a file,
+    /// which must contain one dependency (a package name) per line.
+    pub fn depends_path<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.depends.replace(Dependencies::Path(path.into()));
+        self
+    }
+
+    /// Set the list of additional packages that are provided by this app.
+    pub fn provides<I, S>(mut self, provides: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.provides
+            .replace(provides.into_iter().map(Into::into).collect());
+        self
+    }
+    /// Set the list of packages that conflict with the app.
+    pub fn conflicts<I, S>(mut self, conflicts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.conflicts
+            .replace(conflicts.into_iter().map(Into::into).collect());
+        self
+    }
+    /// Set the list of obsolete packages that are replaced by this package.
+    pub fn replaces<I, S>(mut self, replaces: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.replaces
+            .replace(replaces.into_iter().map(Into::into).collect());
+        self
+    }
+    /// Set the list of sources where the package will be stored.
+    pub fn source<I, S>(mut self, source: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.source
+            .replace(source.into_iter().map(Into::into).collect());
+        self
+    }
+    /// Set the licenses of the package.
+    pub fn license<I, S>(mut self, license: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.license
+            .replace(license.into_iter().map(Into::into).collect());
+        self
+    }
+    /// Set the list of optional dependencies of the app.
+    pub fn optdepends<I, S>(mut self, optdepends: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.optdepends
+            .replace(optdepends.into_iter().map(Into::into).collect());
+        self
+    }
+    /// Set the list of files that should be preserved during upgrades and removal of the package.
+    pub fn backup<I, S>(mut self, backup: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.backup
+            .replace(backup.into_iter().map(Into::into).collect());
+        self
+    }
+    /// Set the path to the install script.
+    pub fn install<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.install.replace(path.into());
+        self
+    }
+    /// Set whether to also generate a `.tar.gz` of the package contents and a `PKGBUILD`.
+    pub fn generate_pkgbuild(mut self, generate_pkgbuild: bool) -> Self {
+        self.generate_pkgbuild = generate_pkgbuild;
+        self
+    }
+    /// Set the configuration to generate a `PKGBUILD` and `.SRCINFO` for the AUR.
+    pub fn aur(mut self, aur: AurConfig) -> Self {
+        self.aur.replace(aur);
+        self
+    }
+}
+
+/// The [AUR](https://aur.archlinux.org) configuration.
+///
+/// The generated `PKGBUILD` downloads the `.tar.gz` of the package contents,
+/// generated next to the `.pkg.tar.zst` package, from [`AurConfig::source_url`],
+/// so it needs to be uploaded there, for example as a GitHub release asset.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[non_exhaustive]
+pub struct AurConfig {
+    /// The URL template of the `.tar.gz` of the package contents, for example
+    /// `https://github.com/user/app/releases/download/v{{version}}/{{filename}}`.
+    ///
+    /// The following variables are available:
+    /// - `version`: the app version.
+    /// - `pkgver`: the pacman package version.
+    /// - `arch`: the pacman package architecture.
+    /// - `filename`: the file name of the `.tar.gz` of the package contents.
+    #[serde(alias = "source-url", alias = "source_url")]
+    pub source_url: String,
+    /// The name of the AUR package. By default, it is set to the pacman package name with a `-bin` suffix.
+    #[serde(alias = "package-name", alias = "package_name")]
+    pub package_name: Option<String>,
+}
+
+impl AurConfig {
+    /// Creates a new [`AurConfig`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Set the URL template of the `.tar.gz` of the package contents.
+    pub fn source_url<S: Into<String>>(mut self, source_url: S) -> Self {
+        self.source_url = source_url.into();
+        self
+    }
+    /// Set the name of the AUR package.
+    pub fn package_name<S: Into<String>>(mut self, package_name: S) -> Self {
+        self.package_name.replace(package_name.into());
+        self
+    }
+}
+
+/// The Linux RPM configuration.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[non_exhaustive]
+pub struct RpmConfig {
+    /// List of custom files to add to the RPM package.
+    /// Maps a dir/file to a dir/file inside the RPM package.
+    pub files: Option<HashMap<String, String>>,
+    /// List of RPM dependencies, each entry is either a capability name
+    /// or a capability name followed by a version constraint, e.g. `glibc >= 2.28`.
+    ///
+    /// See : <https://rpm-software-management.github.io/rpm/manual/dependencies.html>
+    pub depends: Option<Dependencies>,
+    /// Additional capabilities that are provided by this app.
+    pub provides: Option<Vec<String>>,
+    /// Packages that conflict with the app.
+    pub conflicts: Option<Vec<String>>,
+    /// Obsolete packages that are replaced by this app.
+    /// For example, if you rename any package.
+    pub obsoletes: Option<Vec<String>>,
+    /// The package release number, by default set to `1`.
+    pub release: Option<String>,
+    /// The package epoch, by default no epoch is set.
+    pub epoch: Option<u32>,
+    /// The license of the package, ideally as an SPDX expression. By default, it is set to `Unknown`.
+    pub license: Option<String>,
+    /// Name to use for the `Name` tag of the RPM package.
+    /// Defaults to [`Config::product_name`] converted to kebab-case.
+    #[serde(alias = "package-name", alias = "package_name")]
+    pub package_name: Option<String>,
+    /// Path to a shell script that will be run before the package is installed (`%pre`).
+    #[serde(alias = "pre-install-script", alias = "pre_install_script")]
+    pub pre_install_script: Option<PathBuf>,
+    /// Path to a shell script that will be run after the package is installed (`%post`).
+    #[serde(alias = "post-install-script", alias = "post_install_script")]
+    pub post_install_script: Option<PathBuf>,
+    /// Path to a shell script that will be run before the package is removed (`%preun`).
+    #[serde(alias = "pre-remove-script", alias = "pre_remove_script")]
+    pub pre_remove_script: Option<PathBuf>,
+    /// Path to a shell script that will be run after the package is removed (`%postun`).
+    #[serde(alias = "post-remove-script", alias = "post_remove_script")]
+    pub post_remove_script: Option<PathBuf>,
+}
+
+impl RpmConfig {
+    /// Creates a new [`RpmConfig`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the list of custom files to add to the RPM package.
+    /// Maps a dir/file to a dir/file inside the RPM package.
+    pub fn files<I, S, T>(mut self, files: I) -> Self
+    where
+        I: IntoIterator<Item = (S, T)>,
+        S: Into<String>,
+        T: Into<String>,
+    {
+        self.files.replace(
+            files
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        );
+        self
+    }
+
+    /// Set the list of RPM dependencies directly using an iterator of strings.
+    pub fn depends<I, S>(mut self, depends: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.depends.replace(Dependencies::List(
+            depends.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    /// Set the list of RPM dependencies indirectly via a path to a file,
+    /// which must contain one dependency per line.
+    pub fn depends_path<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.depends.replace(Dependencies::Path(path.into()));
+        self
+    }
+
+    /// Set the list of additional capabilities that are provided by this app.
+    pub fn provides<I, S>(mut self, provides: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.provides
+            .replace(provides.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set the list of packages that conflict with the app.
+    pub fn conflicts<I, S>(mut self, conflicts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.conflicts
+            .replace(conflicts.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set the list of obsolete packages that are replaced by this package.
+    pub fn obsoletes<I, S>(mut self, obsoletes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.obsoletes
+            .replace(obsoletes.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set the package release number.
+    pub fn release<S: Into<String>>(mut self, release: S) -> Self {
+        self.release.replace(release.into());
+        self
+    }
+
+    /// Set the package epoch.
+    pub fn epoch(mut self, epoch: u32) -> Self {
+        self.epoch.replace(epoch);
+        self
+    }
+
+    /// Set the license of the package.
+    pub fn license<S: Into<String>>(mut self, license: S) -> Self {
+        self.license.replace(license.into());
+        self
+    }
+
+    /// Set the name to use for the `Name` tag of the RPM package.
+    pub fn package_name<S: Into<String>>(mut self, package_name: S) -> Self {
+        self.package_name.replace(package_name.into());
+        self
+    }
+
+    /// Set the path to a shell script that will be run before the package is installed.
+    pub fn pre_install_script<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.pre_install_script.replace(path.into());
+        self
+    }
+
+    /// Set the path to a shell script that will be run after the package is installed.
+    pub fn post_install_script<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.post_install_script.replace(path.into());
+        self
+    }
+
+    /// Set the path to a shell script that will be run before the package is removed.
+    pub fn pre_remove_script<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.pre_remove_script.replace(path.into());
+        self
+    }
+
+    /// Set the path to a shell script that will be run after the package is removed.
+    pub fn post_remove_script<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.post_remove_script.replace(path.into());
+        self
+    }
+}
+
+/// The Linux Flatpak configuration.
+///
+/// Building the Flatpak bundle requires `flatpak` and `flatpak-builder` to be installed,
+/// as well as the configured runtime and SDK.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[non_exhaustive]
+pub struct FlatpakConfig {
+    /// The Flatpak application ID. Defaults to [`Config::identifier`].
+    #[serde(alias = "app-id", alias = "app_id")]
+    pub app_id: Option<String>,
+    /// The runtime used by the app. Defaults to `org.freedesktop.Platform`.
+    pub runtime: Option<String>,
+    /// The version of the runtime. Defaults to `24.08`.
+    #[serde(alias = "runtime-version", alias = "runtime_version")]
+    pub runtime_version: Option<String>,
+    /// The SDK used to build the app. Defaults to `org.freedesktop.Sdk`.
+    pub sdk: Option<String>,
+    /// The arguments passed to `flatpak build-finish`, which set the app permissions.
+    /// Defaults to `["--share=ipc", "--socket=fallback-x11", "--socket=wayland", "--device=dri"]`.
+    ///
+    /// See: <https://docs.flatpak.org/en/latest/sandbox-permissions.html>
+    #[serde(alias = "finish-args", alias = "finish_args")]
+    pub finish_args: Option<Vec<String>>,
+    /// The branch of the app in the bundle. Defaults to `master`.
+    pub branch: Option<String>,
+    /// List of custom files to add to the Flatpak.
+    /// Maps a dir/file to a dir/file inside the `/app` directory of the Flatpak.
+    pub files: Option<HashMap<String, String>>,
+}
+
+impl FlatpakConfig {
+    /// Creates a new [`FlatpakConfig`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the Flatpak application ID.
+    pub fn app_id<S: Into<String>>(mut self, app_id: S) -> Self {
+        self.app_id.replace(app_id.into());
+        self
+    }
+
+    /// Set the runtime used by the app.
+    pub fn runtime<S: Into<String>>(mut self, runtime: S) -> Self {
+        self.runtime.replace(runtime.into());
+        self
+    }
+
+    /// Set the version of the runtime.
+    pub fn runtime_version<S: Into<String>>(mut self, runtime_version: S) -> Self {
+        self.runtime_version.replace(runtime_version.into());
+        self
+    }
+
+    /// Set the SDK used to build the app.
+    pub fn sdk<S: Into<String>>(mut self, sdk: S) -> Self {
+        self.sdk.replace(sdk.into());
+        self
+    }
+
+    /// Set the arguments passed to `flatpak build-finish`, which set the app permissions.
+    pub fn finish_args<I, S>(mut self, finish_args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.finish_args
+            .replace(finish_args.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set the branch of the app in the bundle.
+    pub fn branch<S: Into<String>>(mut self, branch: S) -> Self {
+        self.branch.replace(branch.into());
+        self
+    }
+
+    /// Set the list of custom files to add to the Flatpak.
+    /// Maps a dir/file to a dir/file inside the `/app` directory of the Flatpak.
+    pub fn files<I, S, T>(mut self, files: I) -> Self
+    where
+        I: IntoIterator<Item = (S, T)>,
+        S: Into<String>,
+        T: Into<String>,
+    {
+        self.files.replace(
+            files
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        );
+        self
+    }
+}
+
+/// The confinement level of a snap.
+///
+/// See <https://snapcraft.io/docs/snap-confinement>
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub enum SnapConfinement {
+    /// The app runs in a sandbox and can only access the resources granted by its plugs (default).
+    #[default]
+    Strict,
+    /// The app has full access to the system, like a traditionally packaged app.
+    /// Requires a manual review to be published on the Snap Store.
+    Classic,
+    /// The app runs in the strict sandbox but violations are only logged, for development.
+    Devmode,
+}
+
+impl SnapConfinement {
+    /// Returns the name of this confinement level in `snap.yaml`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SnapConfinement::Strict => "strict",
+            SnapConfinement::Classic => "classic",
+            SnapConfinement::Devmode => "devmode",
+        }
+    }
+}
+
+/// The quality grade of a snap.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub enum SnapGrade {
+    /// The snap can be released to all channels (default).
+    #[default]
+    Stable,
+    /// The snap can only be released to the `edge` and `beta` channels.
+    Devel,
+}
+
+impl SnapGrade {
+    /// Returns the name of this grade in `snap.yaml`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SnapGrade::Stable => "stable",
+            SnapGrade::Devel => "devel",
+        }
+    }
+}
+
+/// The Linux snap configuration.
+///
+/// The snap is built without `snapcraft`, so the libraries the app needs must be provided
+/// by the [base](SnapConfig::base) snap, or added with [`SnapConfig::files`].
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[non_exhaustive]
+pub struct SnapConfig {
+    /// The snap name. Defaults to the main binary name converted to kebab-case.
+    pub name: Option<String>,
+    /// A short summary of the snap, up to 78 characters.
+    /// Defaults to [`Config::description`] or [`Config::product_name`].
+    pub summary: Option<String>,
+    /// The base snap providing the run-time environment. Defaults to `core24`.
+    pub base: Option<String>,
+    /// The confinement level of the snap. Defaults to [`SnapConfinement::Strict`].
+    pub confinement: Option<SnapConfinement>,
+    /// The quality grade of the snap. Defaults to [`SnapGrade::Stable`].
+    pub grade: Option<SnapGrade>,
+    /// The interfaces the app connects to.
+    /// Defaults to `["desktop", "desktop-legacy", "wayland", "x11", "opengl", "home", "network"]`.
+    ///
+    /// See <https://snapcraft.io/docs/supported-interfaces>
+    pub plugs: Option<Vec<String>>,
+    /// The interfaces the app provides to other snaps.
+    pub slots: Option<Vec<String>>,
+    /// List of custom files to add to the snap.
+    /// Maps a dir/file to a dir/file inside the snap.
+    pub files: Option<HashMap<String, String>>,
+}
+
+impl SnapConfig {
+    /// Creates a new [`SnapConfig`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the snap name.
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name.replace(name.into());
+        self
+    }
+
+    /// Set the short summary of the snap.
+    pub fn summary<S: Into<String>>(mut self, summary: S) -> Self {
+        self.summary.replace(summary.into());
+        self
+    }
+
+    /// Set the base snap providing the run-time environment.
+    pub fn base<S: Into<String>>(mut self, base: S) -> Self {
+        self.base.replace(base.into());
+        self
+    }
+
+    /// Set the confinement level of the snap.
+    pub fn confinement(mut self, confinement: SnapConfinement) -> Self {
+        self.confinement.replace(confinement);
+        self
+    }
+
+    /// Set the quality grade of the snap.
+    pub fn grade(mut self, grade: SnapGrade) -> Self {
+        self.grade.replace(grade);
+        self
+    }
+
+    /// Set the interfaces the app connects to.
+    pub fn plugs<I, S>(mut self, plugs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.plugs
+            .replace(plugs.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set the interfaces the app provides to other snaps.
+    pub fn slots<I, S>(mut self, slots: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.slots
+            .replace(slots.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set the list of custom files to add to the snap.
+    /// Maps a dir/file to a dir/file inside the snap.
+    pub fn files<I, S, T>(mut self, files: I) -> Self
+    where
+        I: IntoIterator<Item = (S, T)>,
+        S: Into<String>,
+        T: Into<String>,
+    {
+        self.files.replace(
+            files
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        );
+        self
+    }
+}
+
+/// The Alpine Linux apk configuration.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[non_exhaustive]
+pub struct ApkConfig {
+    /// The name of the package, defaults to the kebab-case [`Config::product_name`].
+    #[serde(alias = "package-name", alias = "package_name")]
+    pub package_name: Option<String>,
+    /// The package release number (`pkgrel`), appended to the version as `-r<release>`. Defaults to `0`.
+    pub release: Option<u32>,
+    /// The license of the package, as an SPDX license expression. Defaults to `custom`.
+    pub license: Option<String>,
+    /// List of packages, shared objects (`so:libz.so.1`) or commands (`cmd:sh`)
+    /// that must be installed for the app to run.
+    ///
+    /// See : <https://wiki.alpinelinux.org/wiki/APKBUILD_Reference#depends>
+    pub depends: Option<Dependencies>,
+    /// Additional packages that are provided by this app.
+    ///
+    /// See : <https://wiki.alpinelinux.org/wiki/APKBUILD_Reference#provides>
+    pub provides: Option<Vec<String>>,
+    /// List of custom files to add to the apk package.
+    /// Maps a dir/file to a dir/file inside the apk package.
+    pub files: Option<HashMap<String, String>>,
+    /// The `.pre-install` script, run before the package is installed.
+    ///
+    /// See : <https://wiki.alpinelinux.org/wiki/APKBUILD_Reference#install>
+    #[serde(alias = "pre-install", alias = "pre_install")]
+    pub pre_install: Option<MaintainerScript>,
+    /// The `.post-install` script, run after the package is installed.
+    #[serde(alias = "post-install", alias = "post_install")]
+    pub post_install: Option<MaintainerScript>,
+    /// The `.pre-upgrade` script, run before the package is upgraded.
+    #[serde(alias = "pre-upgrade", alias = "pre_upgrade")]
+    pub pre_upgrade: Option<MaintainerScript>,
+    /// The `.post-upgrade` script, run after the package is upgraded.
+    #[serde(alias = "post-upgrade", alias = "post_upgrade")]
+    pub post_upgrade: Option<MaintainerScript>,
+    /// The `.pre-deinstall` script, run before the package is removed.
+    #[serde(alias = "pre-deinstall", alias = "pre_deinstall")]
+    pub pre_deinstall: Option<MaintainerScript>,
+    /// The `.post-deinstall` script, run after the package is removed.
+    #[serde(alias = "post-deinstall", alias = "post_deinstall")]
+    pub post_deinstall: Option<MaintainerScript>,
+    /// Path to, or contents of, the PEM encoded RSA private key used to sign the package,
+    /// as generated by `abuild-keygen`.
+    ///
+    /// Defaults to the `CARGO_PACKAGER_APK_PRIVATE_KEY` environment variable,
+    /// the package is not signed if neither is set.
+    #[serde(alias = "signing-key", alias = "signing_key")]
+    pub signing_key: Option<String>,
+    /// The file name of the public key that apk uses to verify the signature,
+    /// which must be installed in `/etc/apk/keys`, for example `alice@example.com-5f4e3d2c.rsa.pub`.
+    ///
+    /// Defaults to the file name of [`ApkConfig::signing_key`] followed by `.pub`
+    /// or `<package name>.rsa.pub` if the key is not a path.
+    /// The public key is written next to the package using this name.
+    #[serde(alias = "signing-key-name", alias = "signing_key_name")]
+    pub signing_key_name: Option<String>,
+}
+
+impl ApkConfig {
+    /// Creates a new [`ApkConfig`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the name of the package.
+    pub fn package_name<S: Into<String>>(mut self, package_name: S) -> Self {
+        self.package_name.replace(package_name.into());
+        self
+    }
+
+    /// Set the package release number.
+    pub fn release(mut self, release: u32) -> Self {
+        self.release.replace(release);
+        self
+    }
+
+    /// Set the license of the package.
+    pub fn license<S: Into<String>>(mut self, license: S) -> Self {
+        self.license.replace(license.into());
+        self
+    }
+
+    /// Set the list of apk dependencies directly using an iterator of strings.
+    pub fn depends<I, S>(mut self, depends: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.depends.replace(Dependencies::List(
+            depends.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    /// Set the list of apk dependencies indirectly via a path to a file,
+    /// which must contain one dependency per line.
+    pub fn depends_path<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.depends.replace(Dependencies::Path(path.into()));
+        self
+    }
+
+    /// Set the list of additional packages that are provided by this app.
+    pub fn provides<I, S>(mut self, provides: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.provides
+            .replace(provides.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set the list of custom files to add to the apk package.
+    /// Maps a dir/file to a dir/file inside the apk package.
+    pub fn files<I, S, T>(mut self, files: I) -> Self
+    where
+        I: IntoIterator<Item = (S, T)>,
+        S: Into<String>,
+        T: Into<String>,
+    {
+        self.files.replace(
+            files
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        );
+        self
+    }
+
+    /// Set the `.pre-install` script, run before the package is installed.
+    pub fn pre_install<S: Into<MaintainerScript>>(mut self, script: S) -> Self {
+        self.pre_install.replace(script.into());
+        self
+    }
+
+    /// Set the `.post-install` script, run after the package is installed.
+    pub fn post_install<S: Into<MaintainerScript>>(mut self, script: S) -> Self {
+        self.post_install.replace(script.into());
+        self
+    }
+
+    /// Set the `.pre-upgrade` script, run before the package is upgraded.
+    pub fn pre_upgrade<S: Into<MaintainerScript>>(mut self, script: S) -> Self {
+        self.pre_upgrade.replace(script.into());
+        self
+    }
+
+    /// Set the `.post-upgrade` script, run after the package is upgraded.
+    pub fn post_upgrade<S: Into<MaintainerScript>>(mut self, script: S) -> Self {
+        self.post_upgrade.replace(script.into());
+        self
+    }
+
+    /// Set the `.pre-deinstall` script, run before the package is removed.
+    pub fn pre_deinstall<S: Into<MaintainerScript>>(mut self, script: S) -> Self {
+        self.pre_deinstall.replace(script.into());
+        self
+    }
+
+    /// Set the `.post-deinstall` script, run after the package is removed.
+    pub fn post_deinstall<S: Into<MaintainerScript>>(mut self, script: S) -> Self {
+        self.post_deinstall.replace(script.into());
+        self
+    }
+
+    /// Set the path to, or contents of, the PEM encoded RSA private key used to sign the package.
+    pub fn signing_key<S: Into<String>>(mut self, signing_key: S) -> Self {
+        self.signing_key.replace(signing_key.into());
+        self
+    }
+
+    /// Set the file name of the public key that apk uses to verify the signature.
+    pub fn signing_key_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.signing_key_name.replace(name.into());
+        self
+    }
+}
+
+/// A dependency of the FreeBSD package.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[non_exhaustive]
+pub struct FreeBsdDependency {
+    /// The name of the package, for example `gtk3`.
+    pub name: String,
+    /// The origin of the package in the ports tree, for example `x11-toolkits/gtk30`.
+    pub origin: String,
+    /// The version of the package the app was built against, for example `3.24.43`.
+    pub version: String,
+}
+
+impl FreeBsdDependency {
+    /// Creates a new [`FreeBsdDependency`].
+    pub fn new<N, O, V>(name: N, origin: O, version: V) -> Self
+    where
+        N: Into<String>,
+        O: Into<String>,
+        V: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            origin: origin.into(),
+            version: version.into(),
+        }
+    }
+}
+
+/// Compression algorithms used for the FreeBSD package archive.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub enum FreeBsdCompression {
+    /// Zstandard compression (default), supported since pkg 1.17.
+    #[default]
+    Zstd,
+    /// Xz compression, supported by all pkg versions.
+    Xz,
+}
+
+/// The FreeBSD pkg configuration.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[non_exhaustive]
+pub struct FreeBsdConfig {
+    /// The name of the package, defaults to the kebab-case [`Config::product_name`].
+    #[serde(alias = "package-name", alias = "package_name")]
+    pub package_name: Option<String>,
+    /// The categories of the package in the ports tree. Defaults to `misc`.
+    pub categories: Option<Vec<String>>,
+    /// The origin of the package, defaults to `<first category>/<package name>`.
+    pub origin: Option<String>,
+    /// The ABI the package is built for, for example `FreeBSD:14:amd64`.
+    ///
+    /// Defaults to the FreeBSD version of the host when packaging on FreeBSD
+    /// or FreeBSD 14 otherwise, with the architecture of [`Config::target_triple`].
+    pub abi: Option<String>,
+    /// The licenses of the package, for example `MIT`. All of them apply if multiple are specified.
+    pub licenses: Option<Vec<String>>,
+    /// The packages that must be installed for the app to run.
+    pub depends: Option<Vec<FreeBsdDependency>>,
+    /// The compression of the package archive. Defaults to `zstd`.
+    pub compression: Option<FreeBsdCompression>,
+    /// List of custom files to add to the FreeBSD package.
+    /// Maps a dir/file to a dir/file inside the package, relative to the filesystem root.
+    pub files: Option<HashMap<String, String>>,
+    /// The `pre-install` script, run before the package is installed.
+    ///
+    /// See : <https://man.freebsd.org/cgi/man.cgi?query=pkg-create#MANIFEST_FILE_DETAILS>
+    #[serde(alias = "pre-install", alias = "pre_install")]
+    pub pre_install: Option<MaintainerScript>,
+    /// The `post-install` script, run after the package is installed.
+    #[serde(alias = "post-install", alias = "post_install")]
+    pub post_install: Option<MaintainerScript>,
+    /// The `pre-deinstall` script, run before the package is removed.
+    #[serde(alias = "pre-deinstall", alias = "pre_deinstall")]
+    pub pre_deinstall: Option<MaintainerScript>,
+    /// The `post-deinstall` script, run after the package is removed.
+    #[serde(alias = "post-deinstall", alias = "post_deinstall")]
+    pub post_deinstall: Option<MaintainerScript>,
+}
+
+impl FreeBsdConfig {
+    /// Creates a new [`FreeBsdConfig`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the name of the package.
+    pub fn package_name<S: Into<String>>(mut self, package_name: S) -> Self {
+        self.package_name.replace(package_name.into());
+        self
+    }
+
+    /// Set the categories of the package in the ports tree.
+    pub fn categories<I, S>(mut self, categories: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.categories
+            .replace(categories.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set the origin of the package.
+    pub fn origin<S: Into<String>>(mut self, origin: S) -> Self {
+        self.origin.replace(origin.into());
+        self
     }
-    /// Set the list of custom files to add to the pacman package.
-    /// Maps a dir/file to a dir/file inside the pacman package.
+
+    /// Set the ABI the package is built for.
+    pub fn abi<S: Into<String>>(mut self, abi: S) -> Self {
+        self.abi.replace(abi.into());
+        self
+    }
+
+    /// Set the licenses of the package.
+    pub fn licenses<I, S>(mut self, licenses: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.licenses
+            .replace(licenses.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set the packages that must be installed for the app to run.
+    pub fn depends<I: IntoIterator<Item = FreeBsdDependency>>(mut self, depends: I) -> Self {
+        self.depends.replace(depends.into_iter().collect());
+        self
+    }
+
+    /// Set the compression of the package archive.
+    pub fn compression(mut self, compression: FreeBsdCompression) -> Self {
+        self.compression.replace(compression);
+        self
+    }
+
+    /// Set the list of custom files to add to the FreeBSD package.
+    /// Maps a dir/file to a dir/file inside the package, relative to the filesystem root.
     pub fn files<I, S, T>(mut self, files: I) -> Self
     where
         I: IntoIterator<Item = (S, T)>,
@@ -512,66 +1979,97 @@ impl PacmanConfig {
         self
     }
 
-    /// Set the list of pacman dependencies directly using an iterator of strings.
-    pub fn depends<I, S>(mut self, depends: I) -> Self
-    where
-        I: IntoIterator<Item = S>,
-        S: Into<String>,
-    {
-        self.depends.replace(Dependencies::List(
-            depends.into_iter().map(Into::into).collect(),
-        ));
+    /// Set the `pre-install` script, run before the package is installed.
+    pub fn pre_install<S: Into<MaintainerScript>>(mut self, script: S) -> Self {
+        self.pre_install.replace(script.into());
         self
     }
 
-    /// Set the list of pacman dependencies indirectly via a path to a file,
-    /// which must contain one dependency (a package name) per line.
-    pub fn depends_path<P>(mut self, path: P) -> Self
-    where
-        P: Into<PathBuf>,
-    {
-        self.depends.replace(Dependencies::Path(path.into()));
+    /// Set the `post-install` script, run after the package is installed.
+    pub fn post_install<S: Into<MaintainerScript>>(mut self, script: S) -> Self {
+        self.post_install.replace(script.into());
         self
     }
 
-    /// Set the list of additional packages that are provided by this app.
-    pub fn provides<I, S>(mut self, provides: I) -> Self
-    where
-        I: IntoIterator<Item = S>,
-        S: Into<String>,
-    {
-        self.provides
-            .replace(provides.into_iter().map(Into::into).collect());
+    /// Set the `pre-deinstall` script, run before the package is removed.
+    pub fn pre_deinstall<S: Into<MaintainerScript>>(mut self, script: S) -> Self {
+        self.pre_deinstall.replace(script.into());
         self
     }
-    /// Set the list of packages that conflict with the app.
-    pub fn conflicts<I, S>(mut self, conflicts: I) -> Self
-    where
-        I: IntoIterator<Item = S>,
-        S: Into<String>,
-    {
-        self.conflicts
-            .replace(conflicts.into_iter().map(Into::into).collect());
+
+    /// Set the `post-deinstall` script, run after the package is removed.
+    pub fn post_deinstall<S: Into<MaintainerScript>>(mut self, script: S) -> Self {
+        self.post_deinstall.replace(script.into());
         self
     }
-    /// Set the list of obsolete packages that are replaced by this package.
-    pub fn replaces<I, S>(mut self, replaces: I) -> Self
-    where
-        I: IntoIterator<Item = S>,
-        S: Into<String>,
-    {
-        self.replaces
-            .replace(replaces.into_iter().map(Into::into).collect());
+}
+
+/// The format of the [portable archive](PackageFormat::Archive).
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub enum ArchiveFormat {
+    /// A gzip compressed tarball (.tar.gz), the default when not targeting Windows.
+    #[serde(rename = "tar.gz")]
+    TarGz,
+    /// A xz compressed tarball (.tar.xz).
+    #[serde(rename = "tar.xz")]
+    TarXz,
+    /// A Zstandard compressed tarball (.tar.zst).
+    #[serde(rename = "tar.zst")]
+    TarZst,
+    /// A zip archive (.zip), the default when targeting Windows.
+    #[serde(rename = "zip")]
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Returns the file extension of this archive format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::TarXz => "tar.xz",
+            ArchiveFormat::TarZst => "tar.zst",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+}
+
+/// The portable archive configuration.
+///
+/// The archive contains the app binaries, external binaries and resources
+/// next to each other, so the app can be run from wherever the archive is extracted.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[non_exhaustive]
+pub struct ArchiveConfig {
+    /// The archive format. Defaults to `zip` when targeting Windows and `tar.gz` otherwise.
+    pub format: Option<ArchiveFormat>,
+    /// Name of the directory containing the app files inside the archive.
+    /// Set it to an empty string to put the files at the root of the archive.
+    ///
+    /// Defaults to the archive file name without its extension,
+    /// `<main-binary-name>_<version>_<arch>`.
+    #[serde(alias = "top-level-dir", alias = "top_level_dir")]
+    pub top_level_dir: Option<String>,
+}
+
+impl ArchiveConfig {
+    /// Creates a new [`ArchiveConfig`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the archive format.
+    pub fn format(mut self, format: ArchiveFormat) -> Self {
+        self.format.replace(format);
         self
     }
-    /// Set the list of sources where the package will be stored.
-    pub fn source<I, S>(mut self, source: I) -> Self
-    where
-        I: IntoIterator<Item = S>,
-        S: Into<String>,
-    {
-        self.source
-            .replace(source.into_iter().map(Into::into).collect());
+
+    /// Set the name of the directory containing the app files inside the archive.
+    pub fn top_level_dir<S: Into<String>>(mut self, top_level_dir: S) -> Self {
+        self.top_level_dir.replace(top_level_dir.into());
         self
     }
 }
@@ -599,6 +2097,9 @@ pub struct Size {
 }
 
 /// The Apple Disk Image (.dmg) configuration.
+///
+/// On non-macOS hosts the image is generated without `hdiutil`, as an HFS+ volume
+/// compressed into a UDIF image, and it is not codesigned.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
@@ -659,6 +2160,68 @@ impl DmgConfig {
     }
 }
 
+/// The macOS installer package (.pkg) configuration.
+///
+/// The package is a product archive installing the `.app` bundle,
+/// so it can be deployed through MDM solutions.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[non_exhaustive]
+pub struct PkgConfig {
+    /// The folder the `.app` bundle is installed to. Defaults to `/Applications`.
+    #[serde(alias = "install-location", alias = "install_location")]
+    pub install_location: Option<String>,
+    /// Path to a script executed before the app is installed.
+    ///
+    /// The script receives the package path, the install location and the target volume
+    /// as arguments and a non-zero exit code aborts the installation.
+    #[serde(alias = "preinstall-script", alias = "preinstall_script")]
+    pub preinstall_script: Option<PathBuf>,
+    /// Path to a script executed after the app is installed.
+    #[serde(alias = "postinstall-script", alias = "postinstall_script")]
+    pub postinstall_script: Option<PathBuf>,
+    /// The `Developer ID Installer` identity signing the package,
+    /// e.g. `"Developer ID Installer: TEAM_NAME (TEAM_ID)"`.
+    ///
+    /// It is loaded from the PKCS#12 certificate of [`MacOsConfig::signing_certificate`]
+    /// (or the `APPLE_CERTIFICATE` and `APPLE_CERTIFICATE_PASSWORD` environment variables),
+    /// which may also hold the identity signing the app.
+    #[serde(alias = "signing-identity", alias = "signing_identity")]
+    pub signing_identity: Option<String>,
+}
+
+impl PkgConfig {
+    /// Creates a new [`PkgConfig`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the folder the `.app` bundle is installed to.
+    pub fn install_location<S: Into<String>>(mut self, install_location: S) -> Self {
+        self.install_location.replace(install_location.into());
+        self
+    }
+
+    /// Set the path to a script executed before the app is installed.
+    pub fn preinstall_script<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.preinstall_script.replace(path.into());
+        self
+    }
+
+    /// Set the path to a script executed after the app is installed.
+    pub fn postinstall_script<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.postinstall_script.replace(path.into());
+        self
+    }
+
+    /// Set the `Developer ID Installer` identity signing the package.
+    pub fn signing_identity<S: Into<String>>(mut self, signing_identity: S) -> Self {
+        self.signing_identity.replace(signing_identity.into());
+        self
+    }
+}
+
 /// Notarization authentication credentials.
 #[derive(Clone, Debug)]
 pub enum MacOsNotarizationCredentials {
@@ -726,6 +2289,9 @@ pub struct MacOsConfig {
     pub signing_identity: Option<String>,
     /// Codesign certificate (base64 encoded of the p12 file).
     ///
+    /// On other platforms than macOS, the app is signed with this certificate
+    /// without the Xcode command line tools, so it is required to sign there.
+    ///
     /// Note: this field cannot be specified via a config file or Cargo package metadata.
     #[serde(skip)]
     pub signing_certificate: Option<OsString>,
@@ -734,6 +2300,17 @@ pub struct MacOsConfig {
     /// Note: this field cannot be specified via a config file or Cargo package metadata.
     #[serde(skip)]
     pub signing_certificate_password: Option<OsString>,
+    /// Whether to download the missing issuer certificates of the [`MacOsConfig::signing_certificate`]
+    /// chain from the location in their Authority Information Access extension.
+    ///
+    /// Signatures created without the Xcode command line tools embed the whole chain up to the
+    /// root certificate, so when this is disabled the p12 file must contain all of it.
+    #[serde(
+        default,
+        alias = "download-issuer-certificates",
+        alias = "download_issuer_certificates"
+    )]
+    pub download_issuer_certificates: bool,
     /// Notarization authentication credentials.
     ///
     /// Note: this field cannot be specified via a config file or Cargo package metadata.
@@ -761,6 +2338,20 @@ pub struct MacOsConfig {
     /// Sets the `LSUIElement` flag in the macOS plist file.
     #[serde(default, alias = "background_app", alias = "background-app")]
     pub background_app: bool,
+    /// The target triples whose binaries are merged into universal binaries,
+    /// e.g. `["aarch64-apple-darwin", "x86_64-apple-darwin"]`.
+    ///
+    /// When set, [`Config::target_triple`] defaults to `universal-apple-darwin` on macOS hosts or when
+    /// only the `app`, `dmg` and `pkg` formats are packaged, and the
+    /// [`Config::binaries`] and [`Config::external_binaries`] of each target are merged into fat
+    /// Mach-O files, producing a single `universal` .app and DMG. The binaries of each target
+    /// are looked up in [`Config::target_binaries_dir`], while binaries with an absolute path
+    /// are copied as is.
+    ///
+    /// When not set, packaging for the `universal-apple-darwin` target copies the binaries
+    /// and external binaries built for it, which must already be universal.
+    #[serde(alias = "target-triples", alias = "target_triples")]
+    pub target_triples: Option<Vec<String>>,
 }
 
 impl MacOsConfig {
@@ -811,6 +2402,12 @@ impl MacOsConfig {
         self
     }
 
+    /// Whether to download the missing issuer certificates of the signing certificate chain.
+    pub fn download_issuer_certificates(mut self, download_issuer_certificates: bool) -> Self {
+        self.download_issuer_certificates = download_issuer_certificates;
+        self
+    }
+
     /// Provider short name for notarization.
     pub fn provider_short_name<S: Into<String>>(mut self, provider_short_name: S) -> Self {
         self.provider_short_name.replace(provider_short_name.into());
@@ -849,6 +2446,18 @@ impl MacOsConfig {
             .replace(embedded_apps.into_iter().map(Into::into).collect());
         self
     }
+
+    /// The target triples whose binaries are merged into universal binaries,
+    /// e.g. `["aarch64-apple-darwin", "x86_64-apple-darwin"]`.
+    pub fn target_triples<I, S>(mut self, target_triples: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.target_triples
+            .replace(target_triples.into_iter().map(Into::into).collect());
+        self
+    }
 }
 
 /// Linux configuration
@@ -890,6 +2499,10 @@ impl Default for WixLanguage {
 }
 
 /// The wix format configuration
+///
+/// On non-Windows hosts the MSI is generated without the WiX toolset,
+/// in which case [`Self::template`], the fragments, merge modules, element references
+/// and dialog images are not supported.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
@@ -1398,6 +3011,110 @@ impl NsisConfig {
     }
 }
 
+/// The MSIX configuration.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[non_exhaustive]
+pub struct MsixConfig {
+    /// The unique name of the package, defaults to [`Config::identifier`]
+    /// with the characters other than ASCII letters, digits, `.` and `-` replaced with `-`.
+    ///
+    /// See : <https://learn.microsoft.com/en-us/uwp/schemas/appxpackage/uapmanifestschema/element-identity>
+    #[serde(alias = "identity-name", alias = "identity_name")]
+    pub identity_name: Option<String>,
+    /// The publisher of the package, which must match the subject of the signing certificate,
+    /// for example `CN=Contoso Software, O=Contoso Corporation, C=US`.
+    ///
+    /// Defaults to `CN=<publisher>` using [`Config::publisher`].
+    pub publisher: Option<String>,
+    /// The publisher name displayed to users, defaults to [`Config::publisher`].
+    #[serde(alias = "publisher-display-name", alias = "publisher_display_name")]
+    pub publisher_display_name: Option<String>,
+    /// The minimum Windows version the package runs on. Defaults to `10.0.17763.0`.
+    #[serde(alias = "min-version", alias = "min_version")]
+    pub min_version: Option<String>,
+    /// The maximum Windows version the package was tested on. Defaults to `10.0.22621.0`.
+    #[serde(alias = "max-version-tested", alias = "max_version_tested")]
+    pub max_version_tested: Option<String>,
+    /// The languages supported by the app, as BCP-47 language tags. Defaults to `en-us`.
+    pub languages: Option<Vec<String>>,
+    /// The background color of the app tiles, as a color name or `#RRGGBB`. Defaults to `transparent`.
+    #[serde(alias = "background-color", alias = "background_color")]
+    pub background_color: Option<String>,
+    /// Additional capabilities requested by the app, for example `internetClient`.
+    /// The `runFullTrust` capability required by desktop apps is always requested.
+    ///
+    /// See : <https://learn.microsoft.com/en-us/windows/uwp/packaging/app-capability-declarations>
+    pub capabilities: Option<Vec<String>>,
+}
+
+impl MsixConfig {
+    /// Creates a new [`MsixConfig`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the unique name of the package.
+    pub fn identity_name<S: Into<String>>(mut self, identity_name: S) -> Self {
+        self.identity_name.replace(identity_name.into());
+        self
+    }
+
+    /// Set the publisher of the package, which must match the subject of the signing certificate.
+    pub fn publisher<S: Into<String>>(mut self, publisher: S) -> Self {
+        self.publisher.replace(publisher.into());
+        self
+    }
+
+    /// Set the publisher name displayed to users.
+    pub fn publisher_display_name<S: Into<String>>(mut self, publisher_display_name: S) -> Self {
+        self.publisher_display_name
+            .replace(publisher_display_name.into());
+        self
+    }
+
+    /// Set the minimum Windows version the package runs on.
+    pub fn min_version<S: Into<String>>(mut self, min_version: S) -> Self {
+        self.min_version.replace(min_version.into());
+        self
+    }
+
+    /// Set the maximum Windows version the package was tested on.
+    pub fn max_version_tested<S: Into<String>>(mut self, max_version_tested: S) -> Self {
+        self.max_version_tested.replace(max_version_tested.into());
+        self
+    }
+
+    /// Set the languages supported by the app.
+    pub fn languages<I, S>(mut self, languages: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.languages
+            .replace(languages.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set the background color of the app tiles.
+    pub fn background_color<S: Into<String>>(mut self, background_color: S) -> Self {
+        self.background_color.replace(background_color.into());
+        self
+    }
+
+    /// Set the additional capabilities requested by the app.
+    pub fn capabilities<I, S>(mut self, capabilities: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.capabilities
+            .replace(capabilities.into_iter().map(Into::into).collect());
+        self
+    }
+}
+
 /// The Windows configuration.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
@@ -1433,11 +3150,30 @@ pub struct WindowsConfig {
     /// This command needs to have a `%1` in it which is just a placeholder for the binary path,
     /// which we will detect and replace before calling the command.
     ///
-    /// By Default we use `signtool.exe` which can be found only on Windows so
-    /// if you are on another platform and want to cross-compile and sign you will
-    /// need to use another tool like `osslsigncode`.
+    /// By Default we use `signtool.exe` which can be found only on Windows, or the built-in
+    /// signer when [`WindowsConfig::signing_certificate`] is set. This command takes precedence
+    /// over both, e.g. to sign with a cloud HSM.
     #[serde(alias = "sign-command", alias = "sign_command")]
     pub sign_command: Option<String>,
+    /// Path to the code signing certificate, used to sign the binaries and installers
+    /// on any platform without `signtool.exe`.
+    ///
+    /// Either a PKCS#12 (`.pfx`) file, or a PEM file containing the certificate followed by
+    /// the intermediate certificates of its chain. Only RSA keys and the SHA-256
+    /// [`WindowsConfig::digest_algorithm`] are supported, and the [`WindowsConfig::timestamp_url`]
+    /// is always used as an RFC 3161 server.
+    #[serde(alias = "signing-certificate", alias = "signing_certificate")]
+    pub signing_certificate: Option<PathBuf>,
+    /// Path to a PEM file containing the RSA private key of the [`WindowsConfig::signing_certificate`],
+    /// if it is a PEM file which does not contain the key.
+    #[serde(alias = "signing-key", alias = "signing_key")]
+    pub signing_key: Option<PathBuf>,
+    /// Password of the [`WindowsConfig::signing_certificate`] PKCS#12 file.
+    /// Defaults to the `WINDOWS_CERTIFICATE_PASSWORD` environment variable.
+    ///
+    /// Note: this field cannot be specified via a config file or Cargo package metadata.
+    #[serde(skip)]
+    pub signing_certificate_password: Option<OsString>,
 }
 
 impl Default for WindowsConfig {
@@ -1449,6 +3185,9 @@ impl Default for WindowsConfig {
             tsp: false,
             allow_downgrades: true,
             sign_command: None,
+            signing_certificate: None,
+            signing_key: None,
+            signing_certificate_password: None,
         }
     }
 }
@@ -1494,6 +3233,28 @@ impl WindowsConfig {
         self.allow_downgrades = allow;
         self
     }
+
+    /// Set the path to the code signing certificate, used to sign the binaries and installers
+    /// on any platform without `signtool.exe`.
+    ///
+    /// Either a PKCS#12 (`.pfx`) file, or a PEM file containing the certificate followed by
+    /// the intermediate certificates of its chain.
+    pub fn signing_certificate<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.signing_certificate.replace(path.into());
+        self
+    }
+
+    /// Set the path to a PEM file containing the RSA private key of the signing certificate.
+    pub fn signing_key<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.signing_key.replace(path.into());
+        self
+    }
+
+    /// Set the password of the signing certificate PKCS#12 file.
+    pub fn signing_certificate_password<S: Into<OsString>>(mut self, password: S) -> Self {
+        self.signing_certificate_password.replace(password.into());
+        self
+    }
 }
 
 /// An enum representing the available verbosity levels of the logger.
@@ -1671,7 +3432,9 @@ pub struct Config {
     pub binaries_dir: Option<PathBuf>,
     /// The target triple we are packaging for.
     ///
-    /// Defaults to the current OS target triple.
+    /// Defaults to the current OS target triple, or `universal-apple-darwin`
+    /// if [`MacOsConfig::target_triples`] is set and either the host is macOS
+    /// or only the macOS formats are packaged.
     #[serde(alias = "target-triple", alias = "target_triple")]
     pub target_triple: Option<String>,
     /// The package's description.
@@ -1725,7 +3488,9 @@ pub struct Config {
     ///
     /// If you are building a universal binary for MacOS, the packager expects
     /// your external binary to also be universal, and named after the target triple,
-    /// e.g. `sqlite3-universal-apple-darwin`. See
+    /// e.g. `sqlite3-universal-apple-darwin`, unless [`MacOsConfig::target_triples`] is set,
+    /// in which case the external binaries of each target, e.g. `sqlite3-aarch64-apple-darwin`
+    /// and `sqlite3-x86_64-apple-darwin`, are merged. See
     /// <https://developer.apple.com/documentation/apple-silicon/building-a-universal-macos-binary>
     #[serde(alias = "external-binaries", alias = "external_binaries")]
     pub external_binaries: Option<Vec<PathBuf>>,
@@ -1741,12 +3506,28 @@ pub struct Config {
     pub appimage: Option<AppImageConfig>,
     /// Pacman configuration.
     pub pacman: Option<PacmanConfig>,
+    /// RPM configuration.
+    pub rpm: Option<RpmConfig>,
+    /// Flatpak configuration.
+    pub flatpak: Option<FlatpakConfig>,
+    /// Snap configuration.
+    pub snap: Option<SnapConfig>,
+    /// Alpine apk configuration.
+    pub apk: Option<ApkConfig>,
+    /// FreeBSD pkg configuration.
+    pub freebsd: Option<FreeBsdConfig>,
+    /// Portable archive configuration.
+    pub archive: Option<ArchiveConfig>,
     /// WiX configuration.
     pub wix: Option<WixConfig>,
     /// Nsis configuration.
     pub nsis: Option<NsisConfig>,
+    /// MSIX configuration.
+    pub msix: Option<MsixConfig>,
     /// Dmg configuration.
     pub dmg: Option<DmgConfig>,
+    /// macOS installer package configuration.
+    pub pkg: Option<PkgConfig>,
 }
 
 impl Config {
@@ -1780,6 +3561,11 @@ impl Config {
         self.wix.as_ref()
     }
 
+    /// Returns the [msix](Config::msix) specific configuration.
+    pub fn msix(&self) -> Option<&MsixConfig> {
+        self.msix.as_ref()
+    }
+
     /// Returns the [debian](Config::deb) specific configuration.
     pub fn deb(&self) -> Option<&DebianConfig> {
         self.deb.as_ref()
@@ -1795,18 +3581,85 @@ impl Config {
         self.pacman.as_ref()
     }
 
+    /// Returns the [rpm](Config::rpm) specific configuration.
+    pub fn rpm(&self) -> Option<&RpmConfig> {
+        self.rpm.as_ref()
+    }
+
+    /// Returns the [flatpak](Config::flatpak) specific configuration.
+    pub fn flatpak(&self) -> Option<&FlatpakConfig> {
+        self.flatpak.as_ref()
+    }
+
+    /// Returns the [snap](Config::snap) specific configuration.
+    pub fn snap(&self) -> Option<&SnapConfig> {
+        self.snap.as_ref()
+    }
+
+    /// Returns the [apk](Config::apk) specific configuration.
+    pub fn apk(&self) -> Option<&ApkConfig> {
+        self.apk.as_ref()
+    }
+
+    /// Returns the [FreeBSD](Config::freebsd) specific configuration.
+    pub fn freebsd(&self) -> Option<&FreeBsdConfig> {
+        self.freebsd.as_ref()
+    }
+
+    /// Returns the [archive](Config::archive) specific configuration.
+    pub fn archive(&self) -> Option<&ArchiveConfig> {
+        self.archive.as_ref()
+    }
+
     /// Returns the [dmg](Config::dmg) specific configuration.
     pub fn dmg(&self) -> Option<&DmgConfig> {
         self.dmg.as_ref()
     }
 
-    /// Returns the target triple of this config, if not set, fallsback to the current OS target triple.
+    /// Returns the [pkg](Config::pkg) specific configuration.
+    pub fn pkg(&self) -> Option<&PkgConfig> {
+        self.pkg.as_ref()
+    }
+
+    /// Returns the target triple of this config, if not set, fallsback to `universal-apple-darwin`
+    /// if [`MacOsConfig::target_triples`] is set and either the host is macOS or only the macOS
+    /// formats are packaged, or to the current OS target triple.
     pub fn target_triple(&self) -> String {
         self.target_triple.clone().unwrap_or_else(|| {
-            util::target_triple().expect("Failed to detect current target triple")
+            if self.uses_universal_target(self.formats.as_deref()) {
+                UNIVERSAL_TARGET_TRIPLE.into()
+            } else {
+                util::target_triple().expect("Failed to detect current target triple")
+            }
         })
     }
 
+    /// Whether `universal-apple-darwin` is the default target triple when packaging `formats`,
+    /// that is, [`MacOsConfig::target_triples`] is set and either the host is macOS
+    /// or `formats` only contains the macOS formats.
+    pub(crate) fn uses_universal_target(&self, formats: Option<&[PackageFormat]>) -> bool {
+        self.macos().is_some_and(|m| m.target_triples.is_some())
+            && (cfg!(target_os = "macos")
+                || formats.is_some_and(|formats| {
+                    !formats.is_empty()
+                        && formats.iter().all(|f| {
+                            matches!(
+                                f,
+                                PackageFormat::App | PackageFormat::Dmg | PackageFormat::Pkg
+                            )
+                        })
+                }))
+    }
+
+    /// Returns the target triples whose binaries are merged into universal binaries,
+    /// if packaging for the `universal-apple-darwin` target with [`MacOsConfig::target_triples`] set.
+    pub(crate) fn universal_target_triples(&self) -> Option<Vec<String>> {
+        if self.target_triple() != UNIVERSAL_TARGET_TRIPLE {
+            return None;
+        }
+        self.macos().and_then(|m| m.target_triples.clone())
+    }
+
     /// Returns the architecture for the package to be built (e.g. "arm", "x86" or "x86_64").
     pub fn target_arch(&self) -> crate::Result<&str> {
         let target = self.target_triple();
@@ -1834,6 +3687,22 @@ impl Config {
         }
     }
 
+    /// Returns the path to the executable of the given binary,
+    /// with the `.exe` suffix appended when packaging for a Windows target.
+    pub(crate) fn binary_executable_path(&self, binary: &Binary) -> PathBuf {
+        let path = self.binary_path(binary);
+        if !self.target_triple().contains("windows")
+            || path
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("exe"))
+        {
+            return path;
+        }
+        let mut path = path.into_os_string();
+        path.push(".exe");
+        path.into()
+    }
+
     /// Returns the package identifier. Defaults an empty string.
     pub fn identifier(&self) -> &str {
         self.identifier.as_deref().unwrap_or("")
@@ -1872,6 +3741,25 @@ impl Config {
         }
     }
 
+    /// Returns the directory of the binaries of the given target triple, when merging universal binaries.
+    ///
+    /// Following the Cargo layout, this is the `<target triple>` directory next to the profile
+    /// directory of [`Self::binaries_dir`], e.g. `target/x86_64-apple-darwin/release`
+    /// for `target/release` or `target/universal-apple-darwin/release`.
+    pub fn target_binaries_dir(&self, target_triple: &str) -> PathBuf {
+        let binaries_dir = self.binaries_dir();
+        let (Some(parent), Some(profile)) = (binaries_dir.parent(), binaries_dir.file_name())
+        else {
+            return binaries_dir.join(target_triple);
+        };
+        let target_dir = if parent.ends_with(self.target_triple()) {
+            parent.parent().unwrap_or(parent)
+        } else {
+            parent
+        };
+        target_dir.join(target_triple).join(profile)
+    }
+
     /// Returns the main binary.
     pub fn main_binary(&self) -> crate::Result<&Binary> {
         self.binaries
@@ -2037,19 +3925,29 @@ impl Config {
         if let Some(external_binaries) = &self.external_binaries {
             let cwd = std::env::current_dir()?;
             let target_triple = self.target_triple();
+            let universal_target_triples = self.universal_target_triples();
             for src in external_binaries {
                 let file_name = src
                     .file_name()
                     .ok_or_else(|| crate::Error::FailedToExtractFilename(src.clone()))?
                     .to_string_lossy();
-                #[cfg(windows)]
-                let src = src.with_file_name(format!("{file_name}-{target_triple}.exe"));
-                #[cfg(not(windows))]
-                let src = src.with_file_name(format!("{file_name}-{target_triple}"));
-                #[cfg(windows)]
-                let dest = path.join(format!("{file_name}.exe"));
-                #[cfg(not(windows))]
-                let dest = path.join(&*file_name);
+                if let Some(target_triples) = &universal_target_triples {
+                    let sources = target_triples
+                        .iter()
+                        .map(|t| src.with_file_name(format!("{file_name}-{t}")))
+                        .collect::<Vec<_>>();
+                    let dest = path.join(&*file_name);
+                    crate::package::universal::create(&sources, &dest)?;
+                    paths.push(dest);
+                    continue;
+                }
+                let extension = if target_triple.contains("windows") {
+                    ".exe"
+                } else {
+                    ""
+                };
+                let src = src.with_file_name(format!("{file_name}-{target_triple}{extension}"));
+                let dest = path.join(format!("{file_name}{extension}"));
                 fs::copy(&src, &dest).map_err(|e| Error::CopyFile(src.clone(), dest.clone(), e))?;
                 paths.push(dest);
             }
@@ -2072,3 +3970,74 @@ fn sanitize_path<P: AsRef<Path>>(path: P) -> PathBuf {
 fn default_true() -> bool {
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thin_macho(cputype: u32) -> Vec<u8> {
+        let mut data = 0xfeedfacf_u32.to_le_bytes().to_vec();
+        data.extend(cputype.to_le_bytes());
+        data.extend([0; 24]);
+        data
+    }
+
+    fn universal_config(dir: &Path) -> Config {
+        Config {
+            target_triple: Some(UNIVERSAL_TARGET_TRIPLE.into()),
+            external_binaries: Some(vec![dir.join("sidecar")]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn copies_prebuilt_universal_external_binaries() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let universal = b"prebuilt universal binary".to_vec();
+        fs::write(
+            dir.path().join("sidecar-universal-apple-darwin"),
+            &universal,
+        )
+        .unwrap();
+
+        let config = universal_config(dir.path());
+        assert_eq!(config.universal_target_triples(), None);
+
+        let paths = config.copy_external_binaries(out.path()).unwrap();
+        assert_eq!(paths, vec![out.path().join("sidecar")]);
+        assert_eq!(fs::read(&paths[0]).unwrap(), universal);
+    }
+
+    #[test]
+    fn merges_external_binaries_of_macos_target_triples() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let target_triples = ["aarch64-apple-darwin", "x86_64-apple-darwin"];
+        for (triple, cputype) in target_triples.iter().zip([0x0100000c, 0x01000007]) {
+            fs::write(
+                dir.path().join(format!("sidecar-{triple}")),
+                thin_macho(cputype),
+            )
+            .unwrap();
+        }
+
+        let mut config = universal_config(dir.path());
+        config.macos = Some(MacOsConfig::default().target_triples(target_triples));
+        assert_eq!(
+            config.universal_target_triples(),
+            Some(target_triples.map(String::from).to_vec())
+        );
+
+        let paths = config.copy_external_binaries(out.path()).unwrap();
+        assert_eq!(paths, vec![out.path().join("sidecar")]);
+        let merged = fs::read(&paths[0]).unwrap();
+        assert_eq!(merged[..4], 0xcafebabe_u32.to_be_bytes());
+        assert_eq!(
+            crate::package::universal::read_slices(&merged)
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+}
@@ -89,6 +89,12 @@ pub enum Error {
     /// create-dmg script error
     #[error("Error running create-dmg script: {0}")]
     CreateDmgFailed(std::io::Error),
+    /// A file name is too long to be stored in the DMG volume.
+    #[error("File name {0} is longer than the 255 UTF-16 code units allowed in a DMG volume")]
+    HfsNameTooLong(String),
+    /// The DMG volume has too many files for its catalog.
+    #[error("Too many files to fit in the DMG volume catalog")]
+    HfsCatalogTooLarge,
     /// signtool.exe error
     #[error("Error running signtool.exe: {0}")]
     SignToolFailed(std::io::Error),
@@ -108,7 +114,6 @@ pub enum Error {
     #[error("{0} `{1}` failed with exit code {2}")]
     HookCommandFailureWithExitCode(String, String, i32),
     /// Regex error.
-    #[cfg(windows)]
     #[error(transparent)]
     RegexError(#[from] regex::Error),
     /// Glob pattern error.
@@ -118,7 +123,6 @@ pub enum Error {
     #[error(transparent)]
     Glob(#[from] glob::GlobError),
     /// Unsupported WiX language
-    #[cfg(windows)]
     #[error("Wix language {0} not found. It must be one of {1}")]
     UnsupportedWixLanguage(String, String),
     /// Image crate errors.
@@ -210,9 +214,113 @@ pub enum Error {
     /// Path is not a directory.
     #[error("{0} is not a directory")]
     IsNotDirectory(PathBuf),
+    /// rpm crate errors.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    #[error(transparent)]
+    Rpm(#[from] rpm::Error),
+    /// squashfs creation errors.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    #[error(transparent)]
+    Squashfs(#[from] backhand::BackhandError),
+    /// OpenPGP errors.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    #[error(transparent)]
+    Pgp(#[from] pgp::errors::Error),
+    /// Invalid Debian package.
+    #[error("Invalid Debian package {0}: {1}")]
+    InvalidDebianPackage(PathBuf, String),
+    /// Invalid compression level for the Debian package archives.
+    #[error("Invalid {compression:?} compression level {level}, expected a level between {min} and {max}")]
+    InvalidCompressionLevel {
+        /// The compression.
+        compression: crate::config::DebianCompression,
+        /// The invalid level.
+        level: u32,
+        /// The lowest supported level.
+        min: u32,
+        /// The highest supported level.
+        max: u32,
+    },
+    /// Invalid pacman package.
+    #[error("Invalid pacman package {0}: {1}")]
+    InvalidPacmanPackage(PathBuf, String),
     /// Could not find a square icon to use as AppImage icon
     #[error("Could not find a square icon to use as AppImage icon")]
     AppImageSquareIcon,
+    /// flatpak-builder or flatpak error.
+    #[error("Error running {0}: {1}")]
+    FlatpakFailed(String, std::io::Error),
+    /// Invalid AppImage runtime.
+    #[error("Invalid AppImage runtime: {0}")]
+    InvalidAppImageRuntime(String),
+    /// AppImage signing is enabled without a key.
+    #[error("`appimage.sign` is enabled but neither `appimage.signingKey` nor the CARGO_PACKAGER_GPG_PRIVATE_KEY environment variable is set")]
+    MissingAppImageSigningKey,
+    /// AppImage signing is enabled for an AppImage built with linuxdeploy.
+    #[error("AppImages built with linuxdeploy cannot be signed, build it with the built-in builder (see `appimage.linuxdeploy` and `appimage.runtime`) or disable `appimage.sign`")]
+    AppImageLinuxdeploySigning,
+    /// RSA errors.
+    #[error(transparent)]
+    Rsa(#[from] rsa::Error),
+    /// Invalid apk signing key.
+    #[error("Invalid apk signing key: {0}")]
+    InvalidApkSigningKey(String),
+    /// DER encoding or decoding error.
+    #[error(transparent)]
+    Der(#[from] der::Error),
+    /// Failed to create a CMS signature.
+    #[error("Failed to create the signature: {0}")]
+    CmsSignature(String),
+    /// Invalid codesign certificate.
+    #[error("Invalid codesign certificate: {0}")]
+    InvalidSigningCertificate(String),
+    /// Failed to create a universal binary.
+    #[error("Failed to create universal binary {0}: {1}")]
+    UniversalBinaryFailed(PathBuf, String),
+    /// Unsupported Mach-O file.
+    #[error("Cannot sign Mach-O file {0}: {1}")]
+    UnsupportedMachO(PathBuf, String),
+    /// Unsupported PE file.
+    #[error("Cannot sign PE file {0}: {1}")]
+    UnsupportedPeFile(PathBuf, String),
+    /// Unsupported Authenticode digest algorithm.
+    #[error(
+        "Unsupported digest algorithm `{0}`, only sha256 can be used with `signing_certificate`"
+    )]
+    UnsupportedDigestAlgorithm(String),
+    /// Failed to timestamp a signature.
+    #[error("Failed to timestamp the signature: {0}")]
+    TimestampFailed(String),
+    /// Apple notary service error.
+    #[error("Notary service error: {0}")]
+    NotaryApi(String),
+    /// Invalid App Store Connect API key.
+    #[error("Invalid App Store Connect API key: {0}")]
+    InvalidNotaryApiKey(String),
+    /// Notarization credentials not supported on this platform.
+    #[error("Only App Store Connect API key credentials (APPLE_API_KEY & APPLE_API_ISSUER) can be used to notarize on this platform")]
+    UnsupportedNotarizationCredentials,
+    /// Could not find a PNG icon to generate the MSIX logos.
+    #[error("Could not find a PNG icon in `icons` to generate the MSIX logos")]
+    MsixIconNotFound,
     /// Base64 decoding error.
     #[error(transparent)]
     Base64DecodeError(#[from] base64::DecodeError),
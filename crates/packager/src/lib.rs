@@ -13,14 +13,24 @@
 //!
 //! - macOS
 //!   - DMG (.dmg)
+//!   - Installer package (.pkg)
 //!   - Bundle (.app)
 //! - Linux
 //!   - Debian package (.deb)
 //!   - AppImage (.AppImage)
-//!   - Pacman (.tar.gz and PKGBUILD)
+//!   - Pacman package (.pkg.tar.zst)
+//!   - RPM package (.rpm)
+//!   - Flatpak bundle (.flatpak)
+//!   - Snap package (.snap)
+//!   - Alpine package (.apk)
+//! - FreeBSD
+//!   - FreeBSD package (.pkg)
 //! - Windows
 //!   - NSIS (.exe)
+//!   - MSIX (.msix)
 //!   - MSI using WiX Toolset (.msi)
+//! - All platforms
+//!   - Portable archive (.tar.gz, .tar.xz, .tar.zst or .zip)
 //!
 //! ## CLI
 //!
@@ -80,6 +90,14 @@ use std::{io::Write, path::PathBuf};
 
 mod codesign;
 mod error;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod openpgp;
 mod package;
 mod shell;
 mod util;
@@ -88,6 +106,24 @@ mod util;
 #[cfg_attr(doc_cfg, doc(cfg(feature = "cli")))]
 pub mod cli;
 pub mod config;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+#[cfg_attr(
+    doc_cfg,
+    doc(cfg(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    )))
+)]
+pub mod repo;
 pub mod sign;
 
 pub use config::{Config, PackageFormat};
@@ -0,0 +1,71 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! OpenPGP signing utilities.
+
+use pgp::{
+    composed::{CleartextSignedMessage, Deserializable, DetachedSignature, SignedSecretKey},
+    crypto::hash::HashAlgorithm,
+    types::Password,
+};
+
+/// An OpenPGP secret key, decoded from its ASCII-armored form.
+pub(crate) struct SecretKey {
+    key: SignedSecretKey,
+    password: Password,
+}
+
+impl SecretKey {
+    /// Decodes an ASCII-armored OpenPGP secret key, unlocked with `password` if it is protected.
+    pub(crate) fn from_armored(armored: &str, password: Option<&str>) -> crate::Result<Self> {
+        let (key, _) = SignedSecretKey::from_string(armored)?;
+        key.verify_bindings()?;
+        Ok(Self {
+            key,
+            password: password.unwrap_or_default().into(),
+        })
+    }
+
+    /// Creates a cleartext signed message of `text`, like `gpg --clearsign`.
+    pub(crate) fn clearsign(&self, text: &str) -> crate::Result<String> {
+        let message = CleartextSignedMessage::sign(
+            rand::thread_rng(),
+            text,
+            &self.key.primary_key,
+            &self.password,
+        )?;
+        message
+            .to_armored_string(Default::default())
+            .map_err(Into::into)
+    }
+
+    /// Returns the ASCII-armored public key, like `gpg --armor --export`.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    pub(crate) fn public_key_armored(&self) -> crate::Result<String> {
+        self.key
+            .to_public_key()
+            .to_armored_string(Default::default())
+            .map_err(Into::into)
+    }
+
+    /// Creates an ASCII-armored detached signature of `data`, like `gpg --armor --detach-sign`.
+    pub(crate) fn detach_sign(&self, data: &[u8]) -> crate::Result<String> {
+        let signature = DetachedSignature::sign_binary_data(
+            rand::thread_rng(),
+            &self.key.primary_key,
+            &self.password,
+            HashAlgorithm::Sha256,
+            data,
+        )?;
+        signature
+            .to_armored_string(Default::default())
+            .map_err(Into::into)
+    }
+}
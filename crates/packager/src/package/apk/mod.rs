@@ -0,0 +1,443 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Alpine Linux packages, in the v2 format produced by `abuild`.
+//!
+//! A package is the concatenation of up to three gzip streams, which apk reads as a single tarball:
+//! the optional signature of the control stream, the control stream with the `.PKGINFO`
+//! and the install scripts, and the data stream with the package contents.
+//! See <https://wiki.alpinelinux.org/wiki/Apk_spec>
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use flate2::{write::GzEncoder, Compression};
+use heck::AsKebabCase;
+use rsa::{
+    pkcs1::DecodeRsaPrivateKey,
+    pkcs8::{DecodePrivateKey, EncodePublicKey, LineEnding},
+    Pkcs1v15Sign, RsaPrivateKey,
+};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use tar::HeaderMode;
+use walkdir::WalkDir;
+
+use super::{deb, metadata, Context};
+use crate::{config::Config, util, Error};
+
+/// The PAX header apk uses to validate the checksum of the installed files.
+const CHECKSUM_PAX_HEADER: &str = "APK-TOOLS.checksum.SHA1";
+
+/// Converts a semver version to an apk version, which only accepts
+/// the `_alpha`, `_beta`, `_pre` and `_rc` pre-release suffixes, followed by an optional number.
+///
+/// Other pre-release identifiers are mapped to `_pre` and the build metadata is dropped.
+fn apk_version(version: &str) -> String {
+    let Ok(version) = semver::Version::parse(version) else {
+        return version.replace('-', "_");
+    };
+
+    let mut apk_version = format!("{}.{}.{}", version.major, version.minor, version.patch);
+    if !version.pre.is_empty() {
+        let suffix = version
+            .pre
+            .split('.')
+            .find(|identifier| identifier.starts_with(|c: char| c.is_ascii_alphabetic()))
+            .map(|identifier| identifier.trim_end_matches(|c: char| c.is_ascii_digit()))
+            .filter(|identifier| matches!(*identifier, "alpha" | "beta" | "pre" | "rc"))
+            .unwrap_or("pre");
+        let number = version
+            .pre
+            .split(|c: char| !c.is_ascii_digit())
+            .find(|number| !number.is_empty())
+            .unwrap_or_default();
+        apk_version.push_str(&format!("_{suffix}{number}"));
+    }
+    apk_version
+}
+
+/// Returns the RSA private key used to sign the package and the file name of its public key, from
+/// [`crate::config::ApkConfig::signing_key`] or the `CARGO_PACKAGER_APK_PRIVATE_KEY` environment variable.
+fn signing_key(config: &Config, pkgname: &str) -> crate::Result<Option<(RsaPrivateKey, String)>> {
+    let apk = config.apk();
+    let Some(key) = apk
+        .and_then(|a| a.signing_key.clone())
+        .or_else(|| std::env::var("CARGO_PACKAGER_APK_PRIVATE_KEY").ok())
+    else {
+        return Ok(None);
+    };
+
+    let key_path = Path::new(&key);
+    let (key, default_name) = if key_path.exists() {
+        let name = format!(
+            "{}.pub",
+            key_path
+                .file_name()
+                .ok_or_else(|| Error::FailedToExtractFilename(key_path.to_path_buf()))?
+                .to_string_lossy()
+        );
+        let key = fs::read_to_string(key_path)
+            .map_err(|e| Error::IoWithPath(key_path.to_path_buf(), e))?;
+        (key, name)
+    } else {
+        (key, format!("{pkgname}.rsa.pub"))
+    };
+    let name = apk
+        .and_then(|a| a.signing_key_name.clone())
+        .unwrap_or(default_name);
+
+    // `abuild-keygen` generates PKCS#8 keys with OpenSSL 3 and PKCS#1 keys with older versions
+    let key = RsaPrivateKey::from_pkcs8_pem(&key)
+        .or_else(|_| RsaPrivateKey::from_pkcs1_pem(&key))
+        .map_err(|e| Error::InvalidApkSigningKey(e.to_string()))?;
+
+    Ok(Some((key, name)))
+}
+
+#[tracing::instrument(level = "trace", skip(ctx))]
+pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
+    let Context {
+        config,
+        intermediates_path,
+        ..
+    } = ctx;
+
+    let apk = config.apk();
+    let arch = match config.target_arch()? {
+        "arm" if config.target_triple().starts_with("armv7") => "armv7",
+        "arm" => "armhf",
+        other => other,
+    };
+    let pkgname = apk
+        .and_then(|a| a.package_name.clone())
+        .unwrap_or_else(|| AsKebabCase(&config.product_name).to_string());
+    let pkgver = format!(
+        "{}-r{}",
+        apk_version(&config.version),
+        apk.and_then(|a| a.release).unwrap_or(0)
+    );
+
+    let apk_name = format!("{pkgname}-{pkgver}.apk");
+    let apk_path = config.out_dir().join(&apk_name);
+
+    tracing::info!("Packaging {} ({})", apk_name, apk_path.display());
+
+    let intermediates_path = intermediates_path.join("apk");
+    util::create_clean_dir(&intermediates_path)?;
+
+    let pkg_dir = intermediates_path.join(&pkgname);
+    tracing::debug!("Generating data");
+    let _ = deb::generate_data(config, &pkg_dir)?;
+    tracing::debug!("Copying files specified in `apk.files`");
+    if let Some(files) = apk.and_then(|a| a.files.as_ref()) {
+        deb::copy_custom_files(files, &pkg_dir)?;
+    }
+
+    let builddate = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_secs();
+
+    tracing::debug!("Creating data stream");
+    let data = create_data_stream(&pkg_dir)?;
+
+    tracing::debug!("Generating .PKGINFO");
+    let pkginfo = generate_pkginfo(config, &pkgname, &pkgver, arch, builddate, &pkg_dir, &data)?;
+    let mut control_files = vec![(".PKGINFO".to_string(), pkginfo, 0o644)];
+
+    if let Some(apk) = apk {
+        let scripts = [
+            (".pre-install", &apk.pre_install),
+            (".post-install", &apk.post_install),
+            (".pre-upgrade", &apk.pre_upgrade),
+            (".post-upgrade", &apk.post_upgrade),
+            (".pre-deinstall", &apk.pre_deinstall),
+            (".post-deinstall", &apk.post_deinstall),
+        ];
+        for (name, script) in scripts {
+            if let Some(script) = script {
+                tracing::debug!("Generating {name}");
+                let contents = metadata::render_maintainer_script(config, &pkgname, name, script)?;
+                control_files.push((name.to_string(), contents.into_bytes(), 0o755));
+            }
+        }
+    }
+
+    tracing::debug!("Creating control stream");
+    let control = create_segment_stream(&control_files, builddate)?;
+
+    let mut outputs = Vec::new();
+    let mut apk_file = util::create_file(&apk_path)?;
+
+    if let Some((key, key_name)) = signing_key(config, &pkgname)? {
+        tracing::debug!("Signing the control stream with {key_name}");
+        let signature = key.sign(Pkcs1v15Sign::new::<Sha256>(), &Sha256::digest(&control))?;
+        let signature_files = [(format!(".SIGN.RSA256.{key_name}"), signature, 0o644)];
+        apk_file.write_all(&create_segment_stream(&signature_files, builddate)?)?;
+
+        let public_key_path = config.out_dir().join(&key_name);
+        let public_key = key
+            .to_public_key()
+            .to_public_key_pem(LineEnding::LF)
+            .map_err(|e| Error::InvalidApkSigningKey(e.to_string()))?;
+        fs::write(&public_key_path, public_key)
+            .map_err(|e| Error::IoWithPath(public_key_path.clone(), e))?;
+        outputs.push(public_key_path);
+    }
+
+    apk_file.write_all(&control)?;
+    apk_file.write_all(&data)?;
+    apk_file.flush()?;
+
+    outputs.insert(0, apk_path);
+    Ok(outputs)
+}
+
+/// Generates the `.PKGINFO` file describing the package to apk.
+/// For more information about the format of this file, see
+/// <https://wiki.alpinelinux.org/wiki/Apk_spec#PKGINFO_Format>
+fn generate_pkginfo(
+    config: &Config,
+    pkgname: &str,
+    pkgver: &str,
+    arch: &str,
+    builddate: u64,
+    pkg_dir: &Path,
+    data: &[u8],
+) -> crate::Result<Vec<u8>> {
+    let mut file = Vec::new();
+
+    writeln!(
+        file,
+        "# Generated by cargo-packager {}",
+        env!("CARGO_PKG_VERSION")
+    )?;
+    writeln!(file, "pkgname = {pkgname}")?;
+    writeln!(file, "pkgver = {pkgver}")?;
+    writeln!(
+        file,
+        "pkgdesc = {}",
+        metadata::single_line(config.description.as_deref().unwrap_or(""))
+    )?;
+    if let Some(homepage) = &config.homepage {
+        writeln!(file, "url = {homepage}")?;
+    }
+    writeln!(file, "builddate = {builddate}")?;
+    writeln!(file, "packager = {}", metadata::packager(config))?;
+    writeln!(file, "size = {}", deb::get_size(pkg_dir)?)?;
+    writeln!(file, "arch = {arch}")?;
+    writeln!(file, "origin = {pkgname}")?;
+
+    let apk = config.apk();
+    writeln!(
+        file,
+        "license = {}",
+        apk.and_then(|a| a.license.as_deref()).unwrap_or("custom")
+    )?;
+    let dependencies = apk
+        .and_then(|a| a.depends.as_ref())
+        .map_or_else(|| Ok(Vec::new()), |d| d.to_list())?;
+    for depend in dependencies {
+        writeln!(file, "depend = {depend}")?;
+    }
+    for provides in apk.and_then(|a| a.provides.as_ref()).into_iter().flatten() {
+        writeln!(file, "provides = {provides}")?;
+    }
+    writeln!(file, "datahash = {:x}", Sha256::digest(data))?;
+
+    Ok(file)
+}
+
+/// Creates a gzip stream of a tarball containing `files`, as `(name, contents, mode)` tuples.
+///
+/// The end-of-archive marker is omitted like `abuild-tar --cut` does,
+/// so apk keeps reading the following streams as part of the same tarball.
+fn create_segment_stream(files: &[(String, Vec<u8>, u32)], mtime: u64) -> crate::Result<Vec<u8>> {
+    let mut tar_builder = tar::Builder::new(Vec::new());
+    for (name, contents, mode) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(*mode);
+        header.set_mtime(mtime);
+        header.set_entry_type(tar::EntryType::file());
+        tar_builder.append_data(&mut header, name, contents.as_slice())?;
+    }
+
+    // `Builder::into_inner` appends the two zeroed 512 bytes blocks marking the end of the archive
+    let mut tar = tar_builder.into_inner()?;
+    tar.truncate(tar.len() - 1024);
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(&tar)?;
+    encoder.finish().map_err(Into::into)
+}
+
+/// Creates the gzip stream of the tarball with the contents of `pkg_dir`,
+/// adding the SHA-1 checksum of each file and symlink as a PAX header like `abuild-tar --hash` does.
+fn create_data_stream(pkg_dir: &Path) -> crate::Result<Vec<u8>> {
+    let encoder = GzEncoder::new(Vec::new(), Compression::best());
+    let mut tar_builder = tar::Builder::new(encoder);
+
+    for entry in WalkDir::new(pkg_dir).sort_by_file_name() {
+        let entry = entry?;
+        let src_path = entry.path();
+        if src_path == pkg_dir {
+            continue;
+        }
+        let dest_path = src_path.strip_prefix(pkg_dir)?;
+        let stat = fs::symlink_metadata(src_path)
+            .map_err(|e| Error::IoWithPath(src_path.to_path_buf(), e))?;
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata_in_mode(&stat, HeaderMode::Deterministic);
+        header.set_mtime(stat.mtime() as u64);
+        if stat.is_dir() {
+            tar_builder.append_data(&mut header, dest_path, &mut io::empty())?;
+        } else if stat.is_symlink() {
+            let target = fs::read_link(src_path)
+                .map_err(|e| Error::IoWithPath(src_path.to_path_buf(), e))?;
+            let checksum = hex::encode(Sha1::digest(target.as_os_str().as_encoded_bytes()));
+            tar_builder.append_pax_extensions([(CHECKSUM_PAX_HEADER, checksum.as_bytes())])?;
+            tar_builder.append_link(&mut header, dest_path, target)?;
+        } else {
+            let mut src_file =
+                File::open(src_path).map_err(|e| Error::IoWithPath(src_path.to_path_buf(), e))?;
+            let mut hasher = Sha1::new();
+            io::copy(&mut src_file, &mut hasher)?;
+            let checksum = hex::encode(hasher.finalize());
+            tar_builder.append_pax_extensions([(CHECKSUM_PAX_HEADER, checksum.as_bytes())])?;
+
+            let mut src_file =
+                File::open(src_path).map_err(|e| Error::IoWithPath(src_path.to_path_buf(), e))?;
+            tar_builder.append_data(&mut header, dest_path, &mut src_file)?;
+        }
+    }
+
+    tar_builder.into_inner()?.finish().map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::{GzDecoder, MultiGzDecoder};
+
+    use super::*;
+    use crate::config::ApkConfig;
+
+    #[test]
+    fn converts_semver_to_apk_version() {
+        assert_eq!(apk_version("1.2.3"), "1.2.3");
+        assert_eq!(apk_version("1.2.3-beta.1"), "1.2.3_beta1");
+        assert_eq!(apk_version("1.2.3-rc2"), "1.2.3_rc2");
+        assert_eq!(apk_version("1.2.3-alpha"), "1.2.3_alpha");
+        assert_eq!(apk_version("1.2.3-nightly.20240101"), "1.2.3_pre20240101");
+        assert_eq!(apk_version("1.2.3+build.5"), "1.2.3");
+    }
+
+    #[test]
+    fn generates_pkginfo() {
+        let mut config = Config::default();
+        config.product_name = "Test App".into();
+        config.description = Some("A test\n  application".into());
+        config.homepage = Some("https://example.com".into());
+        config.authors = Some(vec!["Alice".into(), "Bob".into()]);
+        config.apk = Some(
+            ApkConfig::new()
+                .license("MIT")
+                .depends(["musl", "gtk+3.0>=3.24"])
+                .provides(["test"]),
+        );
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("file"), [0; 42]).unwrap();
+
+        let pkginfo = generate_pkginfo(
+            &config,
+            "test-app",
+            "1.0.0-r0",
+            "x86_64",
+            1700000000,
+            dir.path(),
+            b"data",
+        )
+        .unwrap();
+        let pkginfo = String::from_utf8(pkginfo).unwrap();
+        let lines = pkginfo.lines().skip(1).collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "pkgname = test-app",
+                "pkgver = 1.0.0-r0",
+                "pkgdesc = A test application",
+                "url = https://example.com",
+                "builddate = 1700000000",
+                "packager = Alice, Bob",
+                "size = 42",
+                "arch = x86_64",
+                "origin = test-app",
+                "license = MIT",
+                "depend = musl",
+                "depend = gtk+3.0>=3.24",
+                "provides = test",
+                &format!("datahash = {:x}", Sha256::digest(b"data")),
+            ]
+        );
+    }
+
+    #[test]
+    fn concatenates_streams_into_one_tarball() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("usr/bin")).unwrap();
+        fs::write(dir.path().join("usr/bin/app"), "binary").unwrap();
+        std::os::unix::fs::symlink("app", dir.path().join("usr/bin/link")).unwrap();
+
+        let control_files = [
+            (".PKGINFO".to_string(), b"pkgname = app\n".to_vec(), 0o644),
+            (".post-install".to_string(), b"#!/bin/sh\n".to_vec(), 0o755),
+        ];
+        let control = create_segment_stream(&control_files, 0).unwrap();
+        let data = create_data_stream(dir.path()).unwrap();
+
+        // the control stream is cut before the end-of-archive marker
+        let mut tar = Vec::new();
+        GzDecoder::new(control.as_slice())
+            .read_to_end(&mut tar)
+            .unwrap();
+        assert_eq!(tar.len(), 4 * 512);
+
+        let stream = [control, data].concat();
+        let mut archive = tar::Archive::new(MultiGzDecoder::new(stream.as_slice()));
+        let mut entries = Vec::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().display().to_string();
+            let mode = entry.header().mode().unwrap();
+            let checksum = entry.pax_extensions().unwrap().and_then(|mut extensions| {
+                extensions
+                    .find(|e| e.as_ref().unwrap().key().unwrap() == CHECKSUM_PAX_HEADER)
+                    .map(|e| e.unwrap().value().unwrap().to_string())
+            });
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents).unwrap();
+            entries.push((path, mode, checksum, contents));
+        }
+
+        let sha1 = |data: &str| Some(hex::encode(Sha1::digest(data)));
+        assert_eq!(
+            entries,
+            [
+                (".PKGINFO".into(), 0o644, None, "pkgname = app\n".into()),
+                (".post-install".into(), 0o755, None, "#!/bin/sh\n".into()),
+                ("usr".into(), 0o755, None, String::new()),
+                ("usr/bin".into(), 0o755, None, String::new()),
+                ("usr/bin/app".into(), 0o644, sha1("binary"), "binary".into()),
+                ("usr/bin/link".into(), 0o755, sha1("app"), String::new()),
+            ]
+        );
+    }
+}
@@ -7,10 +7,12 @@
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use super::Context;
+use super::{universal, Context};
 use crate::Error;
 use crate::{config::Config, util};
 
+#[cfg(not(target_os = "macos"))]
+use crate::codesign::apple::{self as codesign, SignTarget};
 #[cfg(target_os = "macos")]
 use crate::{
     codesign::macos::{self as codesign, SignTarget},
@@ -44,7 +46,6 @@ pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
     let bin_dir = contents_directory.join("MacOS");
     fs::create_dir_all(&bin_dir).map_err(|e| Error::IoWithPath(bin_dir.clone(), e))?;
 
-    #[cfg(target_os = "macos")]
     let mut sign_paths = std::collections::BinaryHeap::new();
 
     let bundle_icon_file = util::create_icns_file(&resources_dir, config)?;
@@ -53,11 +54,10 @@ pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
     create_info_plist(&contents_directory, bundle_icon_file, config)?;
 
     tracing::debug!("Copying frameworks");
-    let _framework_paths = copy_frameworks_to_bundle(&contents_directory, config)?;
+    let framework_paths = copy_frameworks_to_bundle(&contents_directory, config)?;
 
-    #[cfg(target_os = "macos")]
     sign_paths.extend(
-        _framework_paths
+        framework_paths
             .into_iter()
             .filter(|p| {
                 let ext = p.extension();
@@ -81,11 +81,23 @@ pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
     tracing::debug!("Copying external binaries");
     config.copy_external_binaries(&bin_dir)?;
     tracing::debug!("Copying binaries");
+    let universal_target_triples = config.universal_target_triples();
     for bin in &config.binaries {
-        let bin_path = config.binary_path(bin);
         let dest_path = bin_dir.join(bin.path.file_name().unwrap());
-        fs::copy(&bin_path, &dest_path)
-            .map_err(|e| Error::CopyFile(bin_path.clone(), dest_path.clone(), e))?;
+        match &universal_target_triples {
+            Some(target_triples) if !bin.path.is_absolute() => {
+                let sources = target_triples
+                    .iter()
+                    .map(|t| config.target_binaries_dir(t).join(&bin.path))
+                    .collect::<Vec<_>>();
+                universal::create(&sources, &dest_path)?;
+            }
+            _ => {
+                let bin_path = config.binary_path(bin);
+                fs::copy(&bin_path, &dest_path)
+                    .map_err(|e| Error::CopyFile(bin_path.clone(), dest_path.clone(), e))?;
+            }
+        }
     }
 
     // All dylib files and native executables should be signed manually
@@ -135,18 +147,23 @@ pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
             continue;
         }
 
-        #[cfg(target_os = "macos")]
         sign_paths.push(SignTarget {
             path: file,
             is_native_binary: true,
         });
     }
 
-    #[cfg(target_os = "macos")]
     if let Some(identity) = config
         .macos()
         .and_then(|macos| macos.signing_identity.as_ref())
     {
+        // without the keychain, the identity can only be loaded from the certificate
+        #[cfg(not(target_os = "macos"))]
+        if codesign::signing_certificate(config).is_none() {
+            tracing::warn!("Skipping app codesigning, APPLE_CERTIFICATE and APPLE_CERTIFICATE_PASSWORD are required to sign on this platform");
+            return Ok(vec![app_bundle_path]);
+        }
+
         tracing::debug!("Codesigning {}", app_bundle_path.display());
         // Sign frameworks and sidecar binaries first, per apple, signing must be done inside out
         // https://developer.apple.com/forums/thread/701514
@@ -157,6 +174,7 @@ pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
 
         // Remove extra attributes, which could cause codesign to fail
         // https://developer.apple.com/library/archive/qa/qa1940/_index.html
+        #[cfg(target_os = "macos")]
         remove_extra_attr(&app_bundle_path)?;
 
         // sign application
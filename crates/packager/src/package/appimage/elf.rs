@@ -0,0 +1,54 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Access to the ELF sections reserved by the AppImage runtime,
+//! like `.upd_info` for the update information.
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{Seek, SeekFrom, Write},
+    ops::Range,
+    path::Path,
+};
+
+use crate::Error;
+
+/// Returns the byte range of the section named `name` in the AppImage runtime ELF.
+pub fn section_range(image: &[u8], name: &str) -> crate::Result<Range<usize>> {
+    let elf =
+        goblin::elf::Elf::parse(image).map_err(|e| Error::InvalidAppImageRuntime(e.to_string()))?;
+    elf.section_headers
+        .iter()
+        .find(|header| elf.shdr_strtab.get_at(header.sh_name) == Some(name))
+        .map(|header| header.sh_offset as usize..(header.sh_offset + header.sh_size) as usize)
+        .filter(|range| range.end <= image.len())
+        .ok_or_else(|| Error::InvalidAppImageRuntime(format!("missing `{name}` section")))
+}
+
+/// Writes `data` to the section named `name` of the AppImage at `path`,
+/// filling the rest of the section with zeros.
+#[tracing::instrument(level = "trace", skip(data))]
+pub fn write_section(path: &Path, name: &str, data: &[u8]) -> crate::Result<()> {
+    let image = fs::read(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+    let range = section_range(&image, name)?;
+    if data.len() > range.len() {
+        return Err(Error::InvalidAppImageRuntime(format!(
+            "`{name}` section is {} bytes but {} bytes are needed",
+            range.len(),
+            data.len()
+        )));
+    }
+
+    let mut contents = data.to_vec();
+    contents.resize(range.len(), 0);
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(path)
+        .map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+    file.seek(SeekFrom::Start(range.start as u64))?;
+    file.write_all(&contents)?;
+
+    Ok(())
+}
@@ -0,0 +1,223 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Bundling of the shared libraries the AppDir depends on, like `linuxdeploy` does:
+//! the `DT_NEEDED` entries of every ELF file are resolved against the host library paths
+//! and copied to `usr/lib`, except for the libraries every target system is expected to provide.
+
+use std::{
+    collections::{BTreeSet, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use walkdir::WalkDir;
+
+use crate::{util::is_elf, Error};
+
+/// The libraries that are never bundled, because they are part of the base system
+/// or depend on the host hardware, based on the AppImage
+/// [excludelist](https://github.com/AppImageCommunity/pkg2appimage/blob/master/excludelist)
+/// used by `linuxdeploy`.
+const EXCLUDED_LIBS: &[&str] = &[
+    "ld-linux.so.*",
+    "ld-linux-*.so.*",
+    "libanl.so.*",
+    "libBrokenLocale.so.*",
+    "libc.so.*",
+    "libcidn.so.*",
+    "libdl.so.*",
+    "libm.so.*",
+    "libmvec.so.*",
+    "libnsl.so.*",
+    "libnss_*.so.*",
+    "libpthread.so.*",
+    "libresolv.so.*",
+    "librt.so.*",
+    "libthread_db.so.*",
+    "libutil.so.*",
+    "libgcc_s.so.*",
+    "libz.so.*",
+    "libexpat.so.*",
+    "libcom_err.so.*",
+    "libgpg-error.so.*",
+    "libuuid.so.*",
+    "libusb-1.0.so.*",
+    "libasound.so.*",
+    "libjack.so.*",
+    "libpipewire-0.3.so.*",
+    "libfontconfig.so.*",
+    "libfreetype.so.*",
+    "libharfbuzz.so.*",
+    "libfribidi.so.*",
+    "libGL.so.*",
+    "libGLX.so.*",
+    "libGLdispatch.so.*",
+    "libOpenGL.so.*",
+    "libEGL.so.*",
+    "libGLESv2.so.*",
+    "libglapi.so.*",
+    "libgbm.so.*",
+    "libdrm.so.*",
+    "libvulkan.so.*",
+    "libxcb.so.*",
+    "libX11.so.*",
+    "libX11-xcb.so.*",
+    "libICE.so.*",
+    "libSM.so.*",
+    "libp11-kit.so.*",
+];
+
+/// Appends the directories listed in an `ld.so.conf` file, following its `include` directives.
+fn read_ld_so_conf(path: &Path, dirs: &mut Vec<PathBuf>) {
+    let Ok(contents) = fs::read_to_string(path) else {
+        return;
+    };
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if let Some(pattern) = line.strip_prefix("include") {
+            let pattern = Path::new("/etc").join(pattern.trim());
+            for include in glob::glob(&pattern.to_string_lossy())
+                .into_iter()
+                .flatten()
+                .flatten()
+            {
+                read_ld_so_conf(&include, dirs);
+            }
+        } else if !line.is_empty() {
+            dirs.push(PathBuf::from(line));
+        }
+    }
+}
+
+/// The directories searched for libraries, in the order used by the dynamic linker.
+fn search_dirs() -> Vec<PathBuf> {
+    let mut dirs = std::env::var_os("LD_LIBRARY_PATH")
+        .map(|paths| std::env::split_paths(&paths).collect::<Vec<_>>())
+        .unwrap_or_default();
+    read_ld_so_conf(Path::new("/etc/ld.so.conf"), &mut dirs);
+    dirs.extend(["/lib64", "/usr/lib64", "/lib", "/usr/lib"].map(PathBuf::from));
+    let mut seen = BTreeSet::new();
+    dirs.retain(|dir| dir.is_dir() && seen.insert(dir.clone()));
+    dirs
+}
+
+/// The ELF machine and class a library must have to be loaded by a binary.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Target {
+    machine: u16,
+    is_64: bool,
+}
+
+/// Copies the shared libraries needed by the ELF files of the AppDir, recursively,
+/// to its `usr/lib` directory. Libraries matching `excluded_libs` or the default
+/// exclude list, or already present in the AppDir, are not copied.
+#[tracing::instrument(level = "trace")]
+pub fn bundle_needed(app_dir: &Path, excluded_libs: &[String]) -> crate::Result<()> {
+    let excluded = EXCLUDED_LIBS
+        .iter()
+        .copied()
+        .chain(excluded_libs.iter().map(String::as_str))
+        .map(glob::Pattern::new)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut provided = BTreeSet::new();
+    let mut queue = VecDeque::new();
+    for entry in WalkDir::new(app_dir) {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        provided.insert(entry.file_name().to_string_lossy().into_owned());
+        if is_elf(entry.path()) {
+            queue.push_back(entry.into_path());
+        }
+    }
+
+    let lib_dir = app_dir.join("usr/lib");
+    let search_dirs = search_dirs();
+    while let Some(path) = queue.pop_front() {
+        let data = fs::read(&path).map_err(|e| Error::IoWithPath(path.clone(), e))?;
+        let elf = match goblin::elf::Elf::parse(&data) {
+            Ok(elf) => elf,
+            Err(e) => {
+                tracing::warn!("Failed to parse ELF file {}: {e}", path.display());
+                continue;
+            }
+        };
+        let target = Target {
+            machine: elf.header.e_machine,
+            is_64: elf.is_64,
+        };
+        // libraries next to the file, as found by a `$ORIGIN` relative runpath
+        let origin = path.parent().unwrap_or(app_dir);
+        let runpaths = elf
+            .runpaths
+            .iter()
+            .chain(&elf.rpaths)
+            .flat_map(|paths| paths.split(':'))
+            .map(|dir| PathBuf::from(dir.replace("$ORIGIN", &origin.to_string_lossy())))
+            .collect::<Vec<_>>();
+
+        for soname in &elf.libraries {
+            if provided.contains(*soname) || excluded.iter().any(|p| p.matches(soname)) {
+                continue;
+            }
+            let library = runpaths
+                .iter()
+                .chain(&search_dirs)
+                .map(|dir| dir.join(soname))
+                .find(|candidate| library_target(candidate) == Some(target));
+            let Some(library) = library else {
+                tracing::warn!(
+                    "Could not find {soname}, needed by {}, it won't be bundled",
+                    path.display()
+                );
+                continue;
+            };
+
+            fs::create_dir_all(&lib_dir).map_err(|e| Error::IoWithPath(lib_dir.clone(), e))?;
+            let dest = lib_dir.join(soname);
+            tracing::debug!("Copying {}", library.display());
+            fs::copy(&library, &dest).map_err(|e| Error::CopyFile(library, dest.clone(), e))?;
+            provided.insert(soname.to_string());
+            queue.push_back(dest);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the target of the library at `path`, if it is an ELF file.
+fn library_target(path: &Path) -> Option<Target> {
+    if !is_elf(path) {
+        return None;
+    }
+    let data = fs::read(path).ok()?;
+    let header = goblin::elf::Elf::parse_header(&data).ok()?;
+    Some(Target {
+        machine: header.e_machine,
+        is_64: header.e_ident[goblin::elf::header::EI_CLASS] == goblin::elf::header::ELFCLASS64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn excludes_base_system_libraries() {
+        let excluded = EXCLUDED_LIBS
+            .iter()
+            .map(|p| glob::Pattern::new(p).unwrap())
+            .collect::<Vec<_>>();
+        let is_excluded = |soname: &str| excluded.iter().any(|p| p.matches(soname));
+        assert!(is_excluded("libc.so.6"));
+        assert!(is_excluded("ld-linux-x86-64.so.2"));
+        assert!(is_excluded("libnss_files.so.2"));
+        assert!(is_excluded("libGL.so.1"));
+        assert!(!is_excluded("libwebkit2gtk-4.1.so.0"));
+        assert!(!is_excluded("libssl.so.3"));
+    }
+}
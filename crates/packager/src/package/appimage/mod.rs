@@ -4,7 +4,7 @@
 // SPDX-License-Identifier: MIT
 
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     fs,
     os::unix::fs::PermissionsExt,
     path::{Path, PathBuf},
@@ -12,9 +12,25 @@ use std::{
 };
 
 use handlebars::{to_json, Handlebars};
+use walkdir::WalkDir;
 
-use super::{deb, Context};
-use crate::{shell::CommandExt, util, Error};
+use super::{
+    deb,
+    squashfs::{self, Compressor},
+    Context,
+};
+use crate::{
+    config::Config,
+    openpgp::SecretKey,
+    shell::CommandExt,
+    util::{self, HashAlgorithm},
+    Error,
+};
+
+mod elf;
+mod libs;
+mod signature;
+mod zsync;
 
 #[tracing::instrument(level = "trace", skip(ctx))]
 fn donwload_dependencies(
@@ -68,35 +84,261 @@ fn donwload_dependencies(
     Ok(())
 }
 
+/// A pinned release of the [AppImage type 2 runtime](https://github.com/AppImage/type2-runtime).
+struct Runtime {
+    arch: &'static str,
+    release: &'static str,
+    sha256: &'static str,
+}
+
+/// The runtimes downloaded by the built-in builder, verified against their SHA-256.
+/// The moving `continuous` release is never used, so the built-in builder is only used by default
+/// for the architectures listed here, or when [`crate::config::AppImageConfig::runtime`] is set.
+const RUNTIMES: &[Runtime] = &[];
+
+/// Returns the pinned runtime release for `arch`.
+fn pinned_runtime(arch: &str) -> Option<&'static Runtime> {
+    RUNTIMES.iter().find(|r| r.arch == arch)
+}
+
+/// Returns the AppImage type 2 runtime, either from [`crate::config::AppImageConfig::runtime`]
+/// or downloaded from a pinned release and cached in the tools directory.
 #[tracing::instrument(level = "trace", skip(ctx))]
-pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
-    let Context {
-        config,
-        intermediates_path,
-        tools_path,
-        ..
-    } = ctx;
+fn runtime(ctx: &Context, arch: &str) -> crate::Result<Vec<u8>> {
+    if let Some(path) = ctx.config.appimage().and_then(|a| a.runtime.as_ref()) {
+        return fs::read(path).map_err(|e| Error::IoWithPath(path.clone(), e));
+    }
 
-    let mut config = config.clone();
-    let main_binary_name = config.main_binary_name()?;
+    let runtime = pinned_runtime(arch).ok_or_else(|| {
+        Error::InvalidAppImageRuntime(format!(
+            "no pinned runtime release for {arch}, set `appimage.runtime` to the path of a runtime"
+        ))
+    })?;
 
-    // if binary file name contains spaces, we must change it to kebab-case
-    if main_binary_name.contains(' ') {
-        let main_binary = config.main_binary_mut()?;
+    let appimage_tools_path = ctx.tools_path.join("AppImage");
+    fs::create_dir_all(&appimage_tools_path)
+        .map_err(|e| Error::IoWithPath(appimage_tools_path.clone(), e))?;
 
-        let main_binary_name_kebab = heck::AsKebabCase(main_binary_name).to_string();
-        let new_path = intermediates_path.join(&main_binary_name_kebab);
-        fs::copy(&main_binary.path, &new_path)?;
+    let path = appimage_tools_path.join(format!("runtime-{}-{arch}", runtime.release));
+    if path.exists() {
+        let data = fs::read(&path).map_err(|e| Error::IoWithPath(path.clone(), e))?;
+        if util::verify_hash(&data, runtime.sha256, HashAlgorithm::Sha256).is_ok() {
+            return Ok(data);
+        }
+        tracing::warn!("The cached AppImage runtime is corrupted. Redownloading it...");
+    }
 
-        main_binary.path = new_path;
+    let url = format!(
+        "https://github.com/AppImage/type2-runtime/releases/download/{}/runtime-{arch}",
+        runtime.release
+    );
+    let data = util::download_and_verify(&path, &url, runtime.sha256, HashAlgorithm::Sha256)?;
+    tracing::debug!("Writing {}", path.display());
+    fs::write(&path, &data).map_err(|e| Error::IoWithPath(path, e))?;
+    Ok(data)
+}
+
+/// Copies the libraries matching the [`crate::config::AppImageConfig::libs`] globs
+/// found in `/usr/lib*` to the same location inside the AppDir, similar to
+/// `find -L /usr/lib* -name <libname> -exec cp --parents {} <app_dir>`.
+///
+/// Returns the directories containing the copied libraries, relative to the AppDir.
+#[tracing::instrument(level = "trace", skip(libs))]
+fn copy_libs(libs: &[String], app_dir: &Path) -> crate::Result<BTreeSet<String>> {
+    let mut library_dirs = BTreeSet::new();
+    if libs.is_empty() {
+        return Ok(library_dirs);
     }
 
-    // generate the deb binary name
-    let (arch, linuxdeploy_arch) = match config.target_arch()? {
-        "x86" => ("i686", "i386"),
-        "arm" => ("armhf", "arm"),
-        other => (other, other),
+    let patterns = libs
+        .iter()
+        .map(|lib| glob::Pattern::new(lib))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let usr = Path::new("/usr");
+    let lib_dirs = fs::read_dir(usr)
+        .map_err(|e| Error::IoWithPath(usr.to_path_buf(), e))?
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with("lib"))
+        .map(|e| e.path());
+
+    for lib_dir in lib_dirs {
+        // unreadable directories and symlink loops are skipped like `find` does
+        for entry in WalkDir::new(lib_dir)
+            .follow_links(true)
+            .into_iter()
+            .flatten()
+        {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy();
+            if entry.file_type().is_dir() || !patterns.iter().any(|p| p.matches(&name)) {
+                continue;
+            }
+
+            let rel_path = path.strip_prefix("/")?;
+            let dest = app_dir.join(rel_path);
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| Error::IoWithPath(parent.to_path_buf(), e))?;
+            }
+            tracing::debug!("Copying {}", path.display());
+            fs::copy(path, &dest).map_err(|e| Error::CopyFile(path.to_path_buf(), dest, e))?;
+
+            if let Some(parent) = rel_path.parent() {
+                library_dirs.insert(parent.to_string_lossy().into_owned());
+            }
+        }
+    }
+
+    Ok(library_dirs)
+}
+
+/// Returns the OpenPGP secret key used to sign the AppImage if [`crate::config::AppImageConfig::sign`]
+/// is enabled, from [`crate::config::AppImageConfig::signing_key`] or the `CARGO_PACKAGER_GPG_PRIVATE_KEY`
+/// environment variable.
+fn signing_key(config: &Config) -> crate::Result<Option<SecretKey>> {
+    let Some(appimage) = config.appimage().filter(|a| a.sign) else {
+        return Ok(None);
     };
+    let key = appimage
+        .signing_key
+        .clone()
+        .or_else(|| std::env::var("CARGO_PACKAGER_GPG_PRIVATE_KEY").ok())
+        .ok_or(Error::MissingAppImageSigningKey)?;
+
+    let key = if Path::new(&key).exists() {
+        fs::read_to_string(&key).map_err(|e| Error::IoWithPath(PathBuf::from(&key), e))?
+    } else {
+        key
+    };
+    let password = appimage
+        .signing_key_password
+        .clone()
+        .or_else(|| std::env::var("CARGO_PACKAGER_GPG_PRIVATE_KEY_PASSWORD").ok());
+
+    SecretKey::from_armored(&key, password.as_deref()).map(Some)
+}
+
+/// Builds the AppImage without external tools, by assembling the AppDir
+/// and appending its squashfs image to the AppImage runtime.
+#[tracing::instrument(level = "trace", skip(ctx, config))]
+fn build_appimage(
+    ctx: &Context,
+    config: &Config,
+    arch: &str,
+    appimage_path: &Path,
+) -> crate::Result<()> {
+    let intermediates_path = ctx.intermediates_path.join("appimage");
+    util::create_clean_dir(&intermediates_path)?;
+
+    let main_binary_name = config.main_binary_name()?;
+    let app_dir_path = intermediates_path.join(format!("{main_binary_name}.AppDir"));
+
+    tracing::debug!("Generating data");
+    let icons = deb::generate_data(config, &app_dir_path)?;
+    tracing::debug!("Copying files specified in `appimage.files`");
+    if let Some(files) = config.appimage().and_then(|d| d.files.as_ref()) {
+        deb::copy_custom_files(files, &app_dir_path)?;
+    }
+
+    tracing::debug!("Copying libs");
+    let libs = config
+        .appimage()
+        .and_then(|c| c.libs.clone())
+        .unwrap_or_default();
+    let library_dirs = copy_libs(&libs, &app_dir_path)?;
+
+    tracing::debug!("Copying bins");
+    let bin_dir = app_dir_path.join("usr/bin");
+    for bin in config
+        .appimage()
+        .and_then(|c| c.bins.as_ref())
+        .into_iter()
+        .flatten()
+    {
+        let bin = Path::new(bin);
+        let file_name = bin
+            .file_name()
+            .ok_or_else(|| Error::FailedToExtractFilename(bin.to_path_buf()))?;
+        let dest = bin_dir.join(file_name);
+        fs::copy(bin, &dest).map_err(|e| Error::CopyFile(bin.to_path_buf(), dest, e))?;
+    }
+
+    tracing::debug!("Bundling needed libraries");
+    let excluded_libs = config
+        .appimage()
+        .and_then(|a| a.excluded_libs.clone())
+        .unwrap_or_default();
+    libs::bundle_needed(&app_dir_path, &excluded_libs)?;
+
+    tracing::debug!("Generating AppRun");
+    let mut data = BTreeMap::new();
+    data.insert("app_name", to_json(&main_binary_name));
+    data.insert("library_dirs", to_json(library_dirs));
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(handlebars::no_escape);
+    let app_run = handlebars.render_template(include_str!("AppRun"), &data)?;
+    let app_run_path = app_dir_path.join("AppRun");
+    fs::write(&app_run_path, app_run).map_err(|e| Error::IoWithPath(app_run_path.clone(), e))?;
+    fs::set_permissions(&app_run_path, fs::Permissions::from_mode(0o755))
+        .map_err(|e| Error::IoWithPath(app_run_path, e))?;
+
+    let larger_icon = icons
+        .iter()
+        .filter(|i| i.width == i.height)
+        .max_by_key(|i| i.width)
+        .ok_or(crate::Error::AppImageSquareIcon)?;
+    let larger_icon_path = larger_icon.path.strip_prefix(&app_dir_path)?;
+
+    let dir_icon_path = app_dir_path.join(".DirIcon");
+    fs::copy(&larger_icon.path, &dir_icon_path)
+        .map_err(|e| Error::CopyFile(larger_icon.path.clone(), dir_icon_path, e))?;
+
+    let links = [
+        (
+            larger_icon_path.to_path_buf(),
+            app_dir_path.join(format!("{main_binary_name}.png")),
+        ),
+        (
+            Path::new("usr/share/applications").join(format!("{main_binary_name}.desktop")),
+            app_dir_path.join(format!("{main_binary_name}.desktop")),
+        ),
+    ];
+    for (target, link) in links {
+        std::os::unix::fs::symlink(&target, &link).map_err(|e| Error::Symlink(target, link, e))?;
+    }
+
+    tracing::debug!("Creating AppImage");
+    let runtime = runtime(ctx, arch)?;
+    squashfs::create(&app_dir_path, Compressor::Zstd, &runtime, appimage_path)?;
+    fs::set_permissions(appimage_path, fs::Permissions::from_mode(0o755))
+        .map_err(|e| Error::IoWithPath(appimage_path.to_path_buf(), e))?;
+
+    if let Some(update_information) = config
+        .appimage()
+        .and_then(|a| a.update_information.as_ref())
+    {
+        tracing::debug!("Embedding update information");
+        elf::write_section(appimage_path, ".upd_info", update_information.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Builds the AppImage using `linuxdeploy`, see the `appimage` script.
+#[tracing::instrument(level = "trace", skip(ctx, config))]
+fn build_appimage_with_linuxdeploy(
+    ctx: &Context,
+    config: &Config,
+    arch: &str,
+    linuxdeploy_arch: &str,
+    appimage_path: &Path,
+) -> crate::Result<()> {
+    let Context {
+        intermediates_path,
+        tools_path,
+        ..
+    } = ctx;
 
     let appimage_tools_path = tools_path.join("AppImage");
     fs::create_dir_all(&appimage_tools_path)
@@ -109,7 +351,7 @@ pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
 
     // generate deb_folder structure
     tracing::debug!("Generating data");
-    let icons = deb::generate_data(&config, &appimage_deb_data_dir)?;
+    let icons = deb::generate_data(config, &appimage_deb_data_dir)?;
     tracing::debug!("Copying files specified in `appimage.files`");
     if let Some(files) = config.appimage().and_then(|d| d.files.as_ref()) {
         deb::copy_custom_files(files, &appimage_deb_data_dir)?;
@@ -119,8 +361,6 @@ pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
     let main_binary_name = config.main_binary_name()?;
     let upcase_app_name = main_binary_name.to_uppercase();
     let app_dir_path = intermediates_path.join(format!("{}.AppDir", main_binary_name));
-    let appimage_filename = format!("{}_{}_{}.AppImage", main_binary_name, config.version, arch);
-    let appimage_path = config.out_dir().join(&appimage_filename);
 
     fs::create_dir_all(&app_dir_path).map_err(|e| Error::IoWithPath(app_dir_path.clone(), e))?;
 
@@ -130,12 +370,11 @@ pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
     sh_map.insert("linuxdeploy_arch", to_json(linuxdeploy_arch));
     sh_map.insert("app_name", to_json(main_binary_name));
     sh_map.insert("app_name_uppercase", to_json(upcase_app_name));
-    sh_map.insert("appimage_path", to_json(&appimage_path));
+    sh_map.insert("appimage_path", to_json(appimage_path));
     sh_map.insert(
         "packager_tools_path",
         to_json(appimage_tools_path.display().to_string()),
     );
-
     let libs = config
         .appimage()
         .and_then(|c| c.libs.clone())
@@ -198,17 +437,129 @@ pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
     fs::set_permissions(&sh_file, fs::Permissions::from_mode(0o764))
         .map_err(|e| Error::IoWithPath(sh_file.clone(), e))?;
 
+    // execute the shell script to build the appimage.
+    let mut cmd = Command::new(&sh_file);
+    // embedded by the appimage plugin of linuxdeploy
+    if let Some(update_information) = config
+        .appimage()
+        .and_then(|a| a.update_information.as_ref())
+    {
+        cmd.env("UPDATE_INFORMATION", update_information);
+    }
+    cmd.current_dir(intermediates_path)
+        .output_ok()
+        .map_err(crate::Error::AppImageScriptFailed)?;
+
+    Ok(())
+}
+
+#[tracing::instrument(level = "trace", skip(ctx))]
+pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
+    let Context {
+        config,
+        intermediates_path,
+        ..
+    } = ctx;
+
+    let mut config = config.clone();
+    let main_binary_name = config.main_binary_name()?;
+
+    // if binary file name contains spaces, we must change it to kebab-case
+    if main_binary_name.contains(' ') {
+        let main_binary = config.main_binary_mut()?;
+
+        let main_binary_name_kebab = heck::AsKebabCase(main_binary_name).to_string();
+        let new_path = intermediates_path.join(&main_binary_name_kebab);
+        fs::copy(&main_binary.path, &new_path)?;
+
+        main_binary.path = new_path;
+    }
+
+    // generate the deb binary name
+    let (arch, linuxdeploy_arch) = match config.target_arch()? {
+        "x86" => ("i686", "i386"),
+        "arm" => ("armhf", "arm"),
+        other => (other, other),
+    };
+
+    let main_binary_name = config.main_binary_name()?;
+    let appimage_filename = format!("{}_{}_{}.AppImage", main_binary_name, config.version, arch);
+    let appimage_path = config.out_dir().join(&appimage_filename);
+
     tracing::info!(
         "Packaging {} ({})",
         appimage_filename,
         appimage_path.display()
     );
 
-    // execute the shell script to build the appimage.
-    Command::new(&sh_file)
-        .current_dir(intermediates_path)
-        .output_ok()
-        .map_err(crate::Error::AppImageScriptFailed)?;
+    let mut use_linuxdeploy = config.appimage().is_some_and(|a| {
+        a.linuxdeploy
+            || a.linuxdeploy_plugins
+                .as_ref()
+                .is_some_and(|p| !p.is_empty())
+    });
+    if !use_linuxdeploy
+        && pinned_runtime(arch).is_none()
+        && config.appimage().and_then(|a| a.runtime.as_ref()).is_none()
+    {
+        tracing::warn!("No AppImage runtime release is pinned for {arch}, building the AppImage with linuxdeploy instead, set `appimage.runtime` to the path of a runtime to use the built-in builder");
+        use_linuxdeploy = true;
+    }
+    // the key is loaded first so a missing or invalid key fails before building
+    let signing_key = signing_key(&config)?;
+    if use_linuxdeploy && signing_key.is_some() {
+        return Err(Error::AppImageLinuxdeploySigning);
+    }
 
-    Ok(vec![appimage_path])
+    if use_linuxdeploy {
+        build_appimage_with_linuxdeploy(ctx, &config, arch, linuxdeploy_arch, &appimage_path)?;
+    } else {
+        build_appimage(ctx, &config, arch, &appimage_path)?;
+    }
+
+    let mut outputs = vec![appimage_path.clone()];
+
+    // the signature covers the update information and must be embedded before generating the .zsync file
+    if let Some(key) = signing_key {
+        tracing::debug!("Signing {}", appimage_path.display());
+        signature::sign(&appimage_path, &key)?;
+    }
+
+    if config
+        .appimage()
+        .is_some_and(|a| a.update_information.is_some())
+    {
+        let zsync_path = config.out_dir().join(format!("{appimage_filename}.zsync"));
+        tracing::debug!("Generating {}", zsync_path.display());
+        zsync::generate(&appimage_path, &zsync_path)?;
+        outputs.push(zsync_path);
+    }
+
+    Ok(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppImageConfig;
+
+    #[test]
+    fn pinned_runtimes_are_verified_releases() {
+        for runtime in RUNTIMES {
+            assert_ne!(runtime.release, "continuous", "{}", runtime.arch);
+            assert_eq!(runtime.sha256.len(), 64, "{}", runtime.arch);
+            assert!(runtime.sha256.chars().all(|c| c.is_ascii_hexdigit()));
+            assert!(["x86_64", "aarch64", "i686", "armhf"].contains(&runtime.arch));
+        }
+    }
+
+    #[test]
+    fn signs_only_when_enabled() {
+        let mut config = Config::default();
+        config.appimage = Some(AppImageConfig::new().signing_key("invalid key"));
+        assert!(signing_key(&config).unwrap().is_none());
+
+        config.appimage = Some(AppImageConfig::new().sign(true).signing_key("invalid key"));
+        assert!(signing_key(&config).is_err());
+    }
 }
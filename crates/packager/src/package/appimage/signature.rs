@@ -0,0 +1,52 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Embedded AppImage signatures, stored in the `.sha256_sig` and `.sig_key` sections
+//! like `appimagetool --sign` does.
+//!
+//! The signed data is the hex-encoded SHA-256 digest of the AppImage,
+//! computed with both sections filled with zeros.
+
+use std::{fs, path::Path};
+
+use sha2::{Digest, Sha256};
+
+use super::elf;
+use crate::{openpgp::SecretKey, Error};
+
+const SIGNATURE_SECTION: &str = ".sha256_sig";
+const KEY_SECTION: &str = ".sig_key";
+
+/// Returns the hex-encoded SHA-256 digest of the AppImage, excluding the signature sections.
+fn digest(image: &[u8]) -> crate::Result<String> {
+    let mut skipped = [
+        elf::section_range(image, SIGNATURE_SECTION)?,
+        elf::section_range(image, KEY_SECTION)?,
+    ];
+    skipped.sort_by_key(|range| range.start);
+
+    let mut hasher = Sha256::new();
+    let mut position = 0;
+    for range in skipped {
+        hasher.update(&image[position..range.start]);
+        hasher.update(vec![0; range.len()]);
+        position = range.end;
+    }
+    hasher.update(&image[position..]);
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Signs the AppImage at `path` with `key`, embedding the signature and the public key.
+#[tracing::instrument(level = "trace", skip(key))]
+pub fn sign(path: &Path, key: &SecretKey) -> crate::Result<()> {
+    let image = fs::read(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+    let digest = digest(&image)?;
+
+    let signature = key.detach_sign(digest.as_bytes())?;
+    let public_key = key.public_key_armored()?;
+
+    elf::write_section(path, SIGNATURE_SECTION, signature.as_bytes())?;
+    elf::write_section(path, KEY_SECTION, public_key.as_bytes())
+}
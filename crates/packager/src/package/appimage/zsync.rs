@@ -0,0 +1,118 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! `.zsync` control files generation, compatible with the files generated by `zsyncmake` 0.6.2.
+//!
+//! See <http://zsync.moria.org.uk/>
+
+use std::{fs, io::Write, path::Path};
+
+use md4::{Digest, Md4};
+use sha1::Sha1;
+
+use crate::{util, Error};
+
+/// Returns the number of consecutive matching blocks required, the rolling checksum length
+/// and the strong checksum length, chosen like `zsyncmake` does
+/// to keep the probability of false matches low.
+fn hash_lengths(len: u64, blocksize: u64) -> (usize, usize, usize) {
+    let seq_matches = if len > blocksize { 2 } else { 1 };
+    let blocks = (1 + len / blocksize) as f64;
+    let len = len as f64;
+
+    let rsum_len = (((len.log2() + (blocksize as f64).log2()) - 8.6) / seq_matches as f64 / 8.0)
+        .ceil()
+        .clamp(2.0, 4.0) as usize;
+
+    let checksum_len = ((20.0 + len.log2() + blocks.log2()) / seq_matches as f64 / 8.0).ceil();
+    let min_checksum_len = ((7.9 + 20.0 + blocks.log2()) / 8.0).trunc();
+    let checksum_len = checksum_len.max(min_checksum_len).min(16.0) as usize;
+
+    (seq_matches, rsum_len, checksum_len)
+}
+
+/// The zsync rolling checksum of a block, in network byte order.
+fn rsum(block: &[u8]) -> [u8; 4] {
+    let mut a = 0u16;
+    let mut b = 0u16;
+    for (i, c) in block.iter().enumerate() {
+        let c = *c as u16;
+        a = a.wrapping_add(c);
+        b = b.wrapping_add(((block.len() - i) as u16).wrapping_mul(c));
+    }
+    let [a1, a2] = a.to_be_bytes();
+    let [b1, b2] = b.to_be_bytes();
+    [a1, a2, b1, b2]
+}
+
+/// Generates the `.zsync` control file of the file at `path`, written to `dest`.
+///
+/// The file is referenced by its file name, relative to the location of the `.zsync` file.
+#[tracing::instrument(level = "trace")]
+pub fn generate(path: &Path, dest: &Path) -> crate::Result<()> {
+    let data = fs::read(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+    let filename = path
+        .file_name()
+        .ok_or_else(|| Error::FailedToExtractFilename(path.to_path_buf()))?
+        .to_string_lossy();
+
+    let len = data.len() as u64;
+    let blocksize = if len < 100_000_000 { 2048 } else { 4096 };
+    let (seq_matches, rsum_len, checksum_len) = hash_lengths(len, blocksize as u64);
+
+    let format = time::format_description::parse(
+        "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] +0000",
+    )
+    .map_err(time::error::Error::from)?;
+    let modified = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+    let mtime = time::OffsetDateTime::from(modified)
+        .format(&format)
+        .map_err(time::error::Error::from)?;
+
+    let mut file = util::create_file(dest)?;
+    write!(
+        file,
+        "zsync: 0.6.2\n\
+         Filename: {filename}\n\
+         MTime: {mtime}\n\
+         Blocksize: {blocksize}\n\
+         Length: {len}\n\
+         Hash-Lengths: {seq_matches},{rsum_len},{checksum_len}\n\
+         URL: {filename}\n\
+         SHA-1: {}\n\n",
+        hex::encode(Sha1::digest(&data))
+    )?;
+
+    let mut block = vec![0; blocksize];
+    for chunk in data.chunks(blocksize) {
+        // the last block is padded with zeros
+        block.fill(0);
+        block[..chunk.len()].copy_from_slice(chunk);
+        file.write_all(&rsum(&block)[4 - rsum_len..])?;
+        file.write_all(&Md4::digest(&block)[..checksum_len])?;
+    }
+    file.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_hash_lengths() {
+        assert_eq!(hash_lengths(1000, 2048), (1, 2, 4));
+        assert_eq!(hash_lengths(10_000_000, 2048), (2, 2, 5));
+        assert_eq!(hash_lengths(200_000_000, 4096), (2, 2, 5));
+    }
+
+    #[test]
+    fn computes_rolling_checksum() {
+        assert_eq!(rsum(&[1, 2, 3]), [0, 6, 0, 10]);
+        assert_eq!(rsum(&[0xff; 512]), [0xfe, 0x00, 0xff, 0x00]);
+    }
+}
@@ -0,0 +1,245 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::{
+    fs::{self, File},
+    io::{self, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{write::GzEncoder, Compression};
+use liblzma::write::XzEncoder;
+use walkdir::WalkDir;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use super::Context;
+use crate::{
+    config::{ArchiveFormat, Config},
+    util, Error,
+};
+
+/// Copies the binaries, external binaries and resources to `data_dir`.
+#[tracing::instrument(level = "trace", skip(config))]
+fn generate_data(config: &Config, data_dir: &Path) -> crate::Result<()> {
+    tracing::debug!("Copying binaries");
+    fs::create_dir_all(data_dir).map_err(|e| Error::IoWithPath(data_dir.to_path_buf(), e))?;
+    for bin in &config.binaries {
+        let bin_path = config.binary_executable_path(bin);
+        let file_name = bin_path
+            .file_name()
+            .ok_or_else(|| Error::FailedToExtractFilename(bin_path.clone()))?;
+        let bin_out_path = data_dir.join(file_name);
+        fs::copy(&bin_path, &bin_out_path)
+            .map_err(|e| Error::CopyFile(bin_path.clone(), bin_out_path, e))?;
+    }
+
+    tracing::debug!("Copying external binaries");
+    config.copy_external_binaries(data_dir)?;
+
+    tracing::debug!("Copying resources");
+    config.copy_resources(data_dir)
+}
+
+/// Creates a tarball of `data_dir`, with its contents placed in `top_level_dir`.
+fn create_tar<W: Write>(data_dir: &Path, top_level_dir: &str, dest_file: W) -> crate::Result<W> {
+    let mut builder = tar::Builder::new(dest_file);
+    builder.follow_symlinks(false);
+    let top_level_dir = if top_level_dir.is_empty() {
+        "."
+    } else {
+        top_level_dir
+    };
+    builder.append_dir_all(top_level_dir, data_dir)?;
+    builder.into_inner().map_err(Into::into)
+}
+
+/// Creates a zip archive of `data_dir`, with its contents placed in `top_level_dir`.
+pub(crate) fn create_zip<W: Write + Seek>(
+    data_dir: &Path,
+    top_level_dir: &str,
+    dest_file: W,
+) -> crate::Result<W> {
+    let mut zip = ZipWriter::new(dest_file);
+
+    for entry in WalkDir::new(data_dir).sort_by_file_name() {
+        let entry = entry?;
+        let path = entry.path();
+        let rel_path = path.strip_prefix(data_dir)?;
+        // zip entries always use forward slashes
+        let name = Path::new(top_level_dir)
+            .join(rel_path)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if name.is_empty() {
+            continue;
+        }
+
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        #[cfg(unix)]
+        let options = {
+            use std::os::unix::fs::MetadataExt;
+            let metadata =
+                fs::symlink_metadata(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+            options.unix_permissions(metadata.mode() & 0o7777)
+        };
+
+        let file_type = entry.file_type();
+        if file_type.is_dir() {
+            zip.add_directory(name, options)?;
+        } else if file_type.is_symlink() {
+            let target =
+                fs::read_link(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+            zip.add_symlink(name, target.to_string_lossy(), options)?;
+        } else {
+            zip.start_file(name, options)?;
+            let mut file =
+                File::open(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+            io::copy(&mut file, &mut zip)?;
+        }
+    }
+
+    zip.finish().map_err(Into::into)
+}
+
+#[tracing::instrument(level = "trace", skip(ctx))]
+pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
+    let Context {
+        config,
+        intermediates_path,
+        ..
+    } = ctx;
+
+    let format = config.archive().and_then(|a| a.format).unwrap_or_else(|| {
+        if config.target_triple().contains("windows") {
+            ArchiveFormat::Zip
+        } else {
+            ArchiveFormat::TarGz
+        }
+    });
+
+    let name = format!(
+        "{}_{}_{}",
+        config.main_binary_name()?,
+        config.version,
+        config.target_arch()?
+    );
+    let archive_name = format!("{name}.{}", format.extension());
+    let archive_path = config.out_dir().join(&archive_name);
+
+    tracing::info!("Packaging {} ({})", archive_name, archive_path.display());
+
+    let data_dir = intermediates_path.join("archive");
+    util::create_clean_dir(&data_dir)?;
+    generate_data(config, &data_dir)?;
+
+    let top_level_dir = config
+        .archive()
+        .and_then(|a| a.top_level_dir.as_deref())
+        .unwrap_or(&name);
+
+    tracing::debug!("Creating {}", archive_path.display());
+    let dest_file = util::create_file(&archive_path)?;
+    let mut dest_file = match format {
+        ArchiveFormat::TarGz => {
+            let gzip_encoder = GzEncoder::new(dest_file, Compression::default());
+            create_tar(&data_dir, top_level_dir, gzip_encoder)?.finish()?
+        }
+        ArchiveFormat::TarXz => {
+            let xz_encoder = XzEncoder::new(dest_file, 6);
+            create_tar(&data_dir, top_level_dir, xz_encoder)?.finish()?
+        }
+        ArchiveFormat::TarZst => {
+            let zstd_encoder = zstd::Encoder::new(dest_file, 0)?;
+            create_tar(&data_dir, top_level_dir, zstd_encoder)?.finish()?
+        }
+        ArchiveFormat::Zip => create_zip(&data_dir, top_level_dir, dest_file)?,
+    };
+    dest_file.flush()?;
+
+    Ok(vec![archive_path])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Binary;
+
+    fn archive_config(out_dir: &Path, target_triple: &str) -> Config {
+        for name in ["app", "app.exe", "my.tool", "my.tool.exe"] {
+            fs::write(out_dir.join(name), name).unwrap();
+        }
+        let mut config = Config::default();
+        config.product_name = "App".into();
+        config.version = "1.0.0".into();
+        config.out_dir = out_dir.to_path_buf();
+        config.target_triple = Some(target_triple.into());
+        config.binaries = vec![Binary::new("app").main(true), Binary::new("my.tool")];
+        config
+    }
+
+    fn package_archive(config: Config) -> PathBuf {
+        let intermediates_path = config.out_dir().join(".cargo-packager");
+        let ctx = Context {
+            tools_path: intermediates_path.clone(),
+            intermediates_path,
+            config,
+        };
+        let mut paths = package(&ctx).unwrap();
+        assert_eq!(paths.len(), 1);
+        paths.remove(0)
+    }
+
+    #[test]
+    fn packages_windows_executables_in_zip() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = archive_config(dir.path(), "x86_64-pc-windows-msvc");
+        let path = package_archive(config);
+        assert_eq!(path.file_name().unwrap(), "app_1.0.0_x86_64.zip");
+
+        let mut zip = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+        let mut names = zip.file_names().map(String::from).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "app_1.0.0_x86_64/",
+                "app_1.0.0_x86_64/app.exe",
+                "app_1.0.0_x86_64/my.tool.exe"
+            ]
+        );
+        let mut contents = String::new();
+        io::Read::read_to_string(
+            &mut zip.by_name("app_1.0.0_x86_64/my.tool.exe").unwrap(),
+            &mut contents,
+        )
+        .unwrap();
+        assert_eq!(contents, "my.tool.exe");
+    }
+
+    #[test]
+    fn packages_unix_executables_in_tarball() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = archive_config(dir.path(), "aarch64-unknown-linux-gnu");
+        let path = package_archive(config);
+        assert_eq!(path.file_name().unwrap(), "app_1.0.0_aarch64.tar.gz");
+
+        let decoder = flate2::read::GzDecoder::new(File::open(&path).unwrap());
+        let mut names = tar::Archive::new(decoder)
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap().path().unwrap().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "app_1.0.0_aarch64/",
+                "app_1.0.0_aarch64/app",
+                "app_1.0.0_aarch64/my.tool"
+            ]
+        );
+    }
+}
@@ -12,22 +12,25 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use flate2::{write::GzEncoder, Compression};
+use flate2::{write::GzEncoder, Compression, GzBuilder};
 use handlebars::Handlebars;
 use heck::AsKebabCase;
 use image::{codecs::png::PngDecoder, ImageDecoder};
+use liblzma::write::XzEncoder;
 use relative_path::PathExt;
 use serde::Serialize;
 use tar::HeaderMode;
 use walkdir::WalkDir;
 
-use super::Context;
+use super::{metadata, Context};
 use crate::{
-    config::Config,
+    config::{Config, DebianCompression},
     util::{self, PathExt as UtilPathExt},
     Error,
 };
 
+mod shlibdeps;
+
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
 pub struct DebIcon {
     pub width: u32,
@@ -290,6 +293,14 @@ pub fn copy_custom_files(files: &HashMap<String, String>, data_dir: &Path) -> cr
     Ok(())
 }
 
+/// Returns the name to use for the `Package` field in the Debian Control file.
+fn package_name(config: &Config) -> String {
+    config
+        .deb()
+        .and_then(|deb| deb.package_name.clone())
+        .unwrap_or_else(|| AsKebabCase(&config.product_name).to_string())
+}
+
 /// Generates the debian control file and stores it under the `control_dir`.
 #[tracing::instrument(level = "trace", skip(config))]
 fn generate_control_file(
@@ -303,12 +314,7 @@ fn generate_control_file(
     let dest_path = control_dir.join("control");
     let mut file = util::create_file(&dest_path)?;
 
-    let pkg_name = config
-        .deb()
-        .and_then(|deb| deb.package_name.clone())
-        .unwrap_or_else(|| AsKebabCase(&config.product_name).to_string());
-
-    writeln!(file, "Package: {pkg_name}")?;
+    writeln!(file, "Package: {}", package_name(config))?;
     writeln!(file, "Version: {}", config.version)?;
     writeln!(file, "Architecture: {arch}")?;
     // Installed-Size must be divided by 1024, see https://www.debian.org/doc/debian-policy/ch-controlfields.html#installed-size
@@ -329,10 +335,44 @@ fn generate_control_file(
     if let Some(homepage) = &config.homepage {
         writeln!(file, "Homepage: {homepage}")?;
     }
-    if let Some(depends) = config.deb().and_then(|d| d.depends.as_ref()) {
-        let dependencies = depends.to_list()?;
-        if !dependencies.is_empty() {
-            writeln!(file, "Depends: {}", dependencies.join(", "))?;
+    if let Some(deb) = config.deb() {
+        let mut depends = deb
+            .depends
+            .as_ref()
+            .map_or_else(|| Ok(Vec::new()), |d| d.to_list())?;
+        if deb.shlib_depends {
+            tracing::debug!("Detecting shared library dependencies");
+            for dependency in shlibdeps::shlib_depends(config, arch, data_dir)? {
+                let package = dependency.split_whitespace().next();
+                if !depends
+                    .iter()
+                    .any(|d| d.split_whitespace().next() == package)
+                {
+                    depends.push(dependency);
+                }
+            }
+        }
+        if !depends.is_empty() {
+            writeln!(file, "Depends: {}", depends.join(", "))?;
+        }
+
+        let relationships = [
+            ("Pre-Depends", &deb.pre_depends),
+            ("Recommends", &deb.recommends),
+            ("Suggests", &deb.suggests),
+            ("Enhances", &deb.enhances),
+            ("Breaks", &deb.breaks),
+            ("Conflicts", &deb.conflicts),
+            ("Provides", &deb.provides),
+            ("Replaces", &deb.replaces),
+        ];
+        for (field, packages) in relationships {
+            if let Some(packages) = packages {
+                let packages = packages.to_list()?;
+                if !packages.is_empty() {
+                    writeln!(file, "{field}: {}", packages.join(", "))?;
+                }
+            }
         }
     }
 
@@ -358,6 +398,152 @@ fn generate_control_file(
     Ok(())
 }
 
+/// Generates the `copyright` and `changelog.gz` files required by the Debian policy
+/// and stores them under `usr/share/doc/<package>` in the `data_dir`,
+/// keeping the `copyright` file if one was already copied there.
+#[tracing::instrument(level = "trace", skip(config))]
+fn generate_doc_files(config: &Config, data_dir: &Path) -> crate::Result<()> {
+    let doc_dir = data_dir.join("usr/share/doc").join(package_name(config));
+
+    let copyright_path = doc_dir.join("copyright");
+    if copyright_path.exists() {
+        tracing::debug!(
+            "Keeping the copyright file at {}",
+            util::display_path(&copyright_path)
+        );
+    } else {
+        generate_copyright_file(config, &copyright_path)?;
+    }
+
+    if let Some(changelog) = config.deb().and_then(|d| d.changelog.as_ref()) {
+        let changelog_path = doc_dir.join("changelog.gz");
+        let mut src = File::open(changelog).map_err(|e| Error::IoWithPath(changelog.clone(), e))?;
+        // no file name and a zero mtime, like `gzip -9n`, so the package is reproducible
+        let mut gzip_encoder = GzBuilder::new()
+            .mtime(0)
+            .write(util::create_file(&changelog_path)?, Compression::best());
+        std::io::copy(&mut src, &mut gzip_encoder)?;
+        gzip_encoder.finish()?.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Writes the machine-readable `copyright` file of the package at `path`.
+fn generate_copyright_file(config: &Config, path: &Path) -> crate::Result<()> {
+    // For more information about the format of this file, see
+    // https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/
+    let mut file = util::create_file(path)?;
+
+    writeln!(
+        file,
+        "Format: https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/"
+    )?;
+    writeln!(file, "Upstream-Name: {}", config.product_name)?;
+    if let Some(authors) = &config.authors {
+        writeln!(file, "Upstream-Contact: {}", authors.join(", "))?;
+    }
+    if let Some(homepage) = &config.homepage {
+        writeln!(file, "Source: {homepage}")?;
+    }
+    writeln!(file)?;
+    writeln!(file, "Files: *")?;
+    let copyright = config
+        .copyright
+        .clone()
+        .or_else(|| config.authors.as_ref().map(|a| a.join(", ")))
+        .unwrap_or_else(|| config.publisher());
+    writeln!(file, "Copyright: {copyright}")?;
+    writeln!(
+        file,
+        "License: {}",
+        config
+            .deb()
+            .and_then(|d| d.license.as_deref())
+            .unwrap_or("custom")
+    )?;
+    if let Some(license_file) = &config.license_file {
+        let license = fs::read_to_string(license_file)
+            .map_err(|e| Error::IoWithPath(license_file.clone(), e))?;
+        for line in license.lines() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                writeln!(file, " .")?;
+            } else {
+                writeln!(file, " {line}")?;
+            }
+        }
+    }
+    file.flush()?;
+    Ok(())
+}
+
+/// Creates a `conffiles` file in the `control_dir` listing all the regular files
+/// installed under `/etc`, so dpkg preserves user modifications on upgrades.
+#[tracing::instrument(level = "trace")]
+fn generate_conffiles(control_dir: &Path, data_dir: &Path) -> crate::Result<()> {
+    let etc_dir = data_dir.join("etc");
+    if !etc_dir.exists() {
+        return Ok(());
+    }
+
+    let mut conffiles = Vec::new();
+    for entry in WalkDir::new(&etc_dir).sort_by_file_name() {
+        let entry = entry?;
+        // dpkg only tracks regular files as conffiles
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let rel_path = entry.path().strip_prefix(data_dir)?;
+        conffiles.push(format!("/{}", rel_path.display()));
+    }
+
+    if !conffiles.is_empty() {
+        let mut file = util::create_file(&control_dir.join("conffiles"))?;
+        for conffile in conffiles {
+            writeln!(file, "{conffile}")?;
+        }
+        file.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Writes the maintainer scripts configured in [`DebianConfig`](crate::config::DebianConfig)
+/// to the `control_dir`, rendering them as Handlebars templates if requested.
+#[tracing::instrument(level = "trace", skip(config))]
+fn generate_maintainer_scripts(config: &Config, control_dir: &Path) -> crate::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let Some(deb) = config.deb() else {
+        return Ok(());
+    };
+
+    let package_name = package_name(config);
+    let scripts = [
+        ("preinst", &deb.preinst),
+        ("postinst", &deb.postinst),
+        ("prerm", &deb.prerm),
+        ("postrm", &deb.postrm),
+    ];
+    for (name, script) in scripts {
+        let Some(script) = script else {
+            continue;
+        };
+
+        let contents = metadata::render_maintainer_script(config, &package_name, name, script)?;
+
+        let dest_path = control_dir.join(name);
+        let mut file = util::create_file(&dest_path)?;
+        file.write_all(contents.as_bytes())?;
+        file.flush()?;
+        fs::set_permissions(&dest_path, fs::Permissions::from_mode(0o755))
+            .map_err(|e| Error::IoWithPath(dest_path.clone(), e))?;
+    }
+
+    Ok(())
+}
+
 /// Creates an `md5sums` file in the `control_dir` containing the MD5 checksums
 /// for each file within the `data_dir`.
 #[tracing::instrument(level = "trace")]
@@ -418,12 +604,57 @@ fn create_tar_from_dir<P: AsRef<Path>, W: Write>(src_dir: P, dest_file: W) -> cr
 /// within the given directory's parent directory), then deletes the original
 /// directory and returns the path to the new file.
 pub fn tar_and_gzip_dir<P: AsRef<Path>>(src_dir: P) -> crate::Result<PathBuf> {
+    tar_and_compress_dir(src_dir, DebianCompression::Gzip, None)
+}
+
+/// Creates a `.tar` file compressed with the given `compression` from the given directory
+/// (placing the new file within the given directory's parent directory),
+/// and returns the path to the new file.
+pub fn tar_and_compress_dir<P: AsRef<Path>>(
+    src_dir: P,
+    compression: DebianCompression,
+    level: Option<u32>,
+) -> crate::Result<PathBuf> {
     let src_dir = src_dir.as_ref();
-    let dest_path = src_dir.with_additional_extension("tar.gz");
+    if let Some(level) = level {
+        let (min, max) = match compression {
+            DebianCompression::Xz | DebianCompression::Gzip => (0, 9),
+            DebianCompression::Zstd => (1, 22),
+            DebianCompression::None => (level, level),
+        };
+        if !(min..=max).contains(&level) {
+            return Err(Error::InvalidCompressionLevel {
+                compression,
+                level,
+                min,
+                max,
+            });
+        }
+    }
+    let extension = match compression {
+        DebianCompression::Xz => "tar.xz",
+        DebianCompression::Zstd => "tar.zst",
+        DebianCompression::None => "tar",
+        DebianCompression::Gzip => "tar.gz",
+    };
+    let dest_path = src_dir.with_additional_extension(extension);
     let dest_file = util::create_file(&dest_path)?;
-    let gzip_encoder = GzEncoder::new(dest_file, Compression::default());
-    let gzip_encoder = create_tar_from_dir(src_dir, gzip_encoder)?;
-    let mut dest_file = gzip_encoder.finish()?;
+    let mut dest_file = match compression {
+        DebianCompression::Xz => {
+            let xz_encoder = XzEncoder::new(dest_file, level.unwrap_or(6));
+            create_tar_from_dir(src_dir, xz_encoder)?.finish()?
+        }
+        DebianCompression::Zstd => {
+            let zstd_encoder = zstd::Encoder::new(dest_file, level.unwrap_or(0) as i32)?;
+            create_tar_from_dir(src_dir, zstd_encoder)?.finish()?
+        }
+        DebianCompression::None => create_tar_from_dir(src_dir, dest_file)?,
+        DebianCompression::Gzip => {
+            let level = level.map(Compression::new).unwrap_or_default();
+            let gzip_encoder = GzEncoder::new(dest_file, level);
+            create_tar_from_dir(src_dir, gzip_encoder)?.finish()?
+        }
+    };
     dest_file.flush()?;
     Ok(dest_path)
 }
@@ -475,10 +706,19 @@ pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
         copy_custom_files(files, &data_dir)?;
     }
 
+    tracing::debug!("Generating copyright and changelog files");
+    generate_doc_files(config, &data_dir)?;
+
     let control_dir = deb_dir.join("control");
     tracing::debug!("Generating control file");
     generate_control_file(config, arch, &control_dir, &data_dir)?;
 
+    tracing::debug!("Generating conffiles");
+    generate_conffiles(&control_dir, &data_dir)?;
+
+    tracing::debug!("Generating maintainer scripts");
+    generate_maintainer_scripts(config, &control_dir)?;
+
     tracing::debug!("Generating md5sums");
     generate_md5sums(&control_dir, &data_dir)?;
 
@@ -490,17 +730,175 @@ pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
     file.write_all(b"2.0\n")?;
     file.flush()?;
 
-    // Apply tar/gzip/ar to create the final package file.
-    tracing::debug!("Zipping control dir using tar and gzip");
-    let control_tar_gz_path = tar_and_gzip_dir(control_dir)?;
+    // Apply tar/compression/ar to create the final package file.
+    let compression = config.deb().and_then(|d| d.compression).unwrap_or_default();
+    let compression_level = config.deb().and_then(|d| d.compression_level);
+
+    tracing::debug!("Compressing control dir using tar and {compression:?}");
+    let control_tar_path = tar_and_compress_dir(control_dir, compression, compression_level)?;
 
-    tracing::debug!("Zipping data dir using tar and gzip");
-    let data_tar_gz_path = tar_and_gzip_dir(data_dir)?;
+    tracing::debug!("Compressing data dir using tar and {compression:?}");
+    let data_tar_path = tar_and_compress_dir(data_dir, compression, compression_level)?;
 
     tracing::debug!("Creating final archive: {}", deb_path.display());
     create_archive(
-        vec![debian_binary_path, control_tar_gz_path, data_tar_gz_path],
+        vec![debian_binary_path, control_tar_path, data_tar_path],
         &deb_path,
     )?;
     Ok(vec![deb_path])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Binary, DebianConfig, MaintainerScript};
+
+    #[test]
+    fn keeps_existing_copyright_file() {
+        let mut config = Config::default();
+        config.product_name = "Test App".into();
+        let dir = tempfile::tempdir().unwrap();
+
+        let generated = dir.path().join("generated");
+        generate_doc_files(&config, &generated).unwrap();
+        let copyright =
+            fs::read_to_string(generated.join("usr/share/doc/test-app/copyright")).unwrap();
+        assert!(copyright.contains("Upstream-Name: Test App\n"));
+
+        let supplied = dir.path().join("supplied");
+        let copyright_path = supplied.join("usr/share/doc/test-app/copyright");
+        fs::create_dir_all(copyright_path.parent().unwrap()).unwrap();
+        fs::write(&copyright_path, "custom copyright").unwrap();
+        generate_doc_files(&config, &supplied).unwrap();
+        assert_eq!(
+            fs::read_to_string(&copyright_path).unwrap(),
+            "custom copyright"
+        );
+    }
+
+    #[test]
+    fn lists_regular_files_as_conffiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().join("data");
+        let control_dir = dir.path().join("control");
+        fs::create_dir_all(data_dir.join("etc/test-app")).unwrap();
+        fs::create_dir_all(&control_dir).unwrap();
+        fs::write(data_dir.join("etc/test-app/app.conf"), "").unwrap();
+        fs::write(data_dir.join("etc/test-app.conf"), "").unwrap();
+        std::os::unix::fs::symlink("test-app.conf", data_dir.join("etc/link.conf")).unwrap();
+
+        generate_conffiles(&control_dir, &data_dir).unwrap();
+        assert_eq!(
+            fs::read_to_string(control_dir.join("conffiles")).unwrap(),
+            "/etc/test-app/app.conf\n/etc/test-app.conf\n"
+        );
+
+        // only symlinks under /etc, no conffiles
+        fs::remove_file(data_dir.join("etc/test-app/app.conf")).unwrap();
+        fs::remove_file(data_dir.join("etc/test-app.conf")).unwrap();
+        fs::remove_file(control_dir.join("conffiles")).unwrap();
+        generate_conffiles(&control_dir, &data_dir).unwrap();
+        assert!(!control_dir.join("conffiles").exists());
+    }
+
+    #[test]
+    fn packs_maintainer_scripts_into_control_archive() {
+        use std::{io::Read, os::unix::fs::PermissionsExt};
+
+        let mut config = Config::default();
+        config.product_name = "Test App".into();
+        config.version = "1.0.0".into();
+        config.binaries = vec![Binary::new("test-app").main(true)];
+        config.deb = Some(
+            DebianConfig::new()
+                .preinst(MaintainerScript::inline("#!/bin/sh\necho preinst\n"))
+                .postinst(MaintainerScript::inline_template(
+                    "#!/bin/sh\necho {{package_name}} {{version}}\n",
+                ))
+                .prerm(MaintainerScript::inline("#!/bin/sh\necho prerm\n"))
+                .postrm(MaintainerScript::inline("#!/bin/sh\necho postrm\n")),
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let control_dir = dir.path().join("control");
+        fs::create_dir_all(&control_dir).unwrap();
+        // a file generated earlier must keep its own mode
+        fs::write(control_dir.join("control"), "Package: test-app\n").unwrap();
+        fs::set_permissions(
+            control_dir.join("control"),
+            fs::Permissions::from_mode(0o644),
+        )
+        .unwrap();
+
+        generate_maintainer_scripts(&config, &control_dir).unwrap();
+        let control_tar =
+            tar_and_compress_dir(&control_dir, DebianCompression::None, None).unwrap();
+
+        let mut archive = tar::Archive::new(File::open(control_tar).unwrap());
+        let mut entries = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let path = entry.path().unwrap().to_string_lossy().into_owned();
+                let mode = entry.header().mode().unwrap();
+                let mut contents = String::new();
+                entry.read_to_string(&mut contents).unwrap();
+                (path, (mode, contents))
+            })
+            .collect::<Vec<_>>();
+        entries.sort();
+        assert_eq!(
+            entries,
+            [
+                ("control", (0o644, "Package: test-app\n")),
+                ("postinst", (0o755, "#!/bin/sh\necho test-app 1.0.0\n")),
+                ("postrm", (0o755, "#!/bin/sh\necho postrm\n")),
+                ("preinst", (0o755, "#!/bin/sh\necho preinst\n")),
+                ("prerm", (0o755, "#!/bin/sh\necho prerm\n")),
+            ]
+            .map(|(path, (mode, contents))| (path.to_string(), (mode, contents.to_string())))
+        );
+    }
+
+    #[test]
+    fn writes_relationship_fields_to_control_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let conflicts_path = dir.path().join("conflicts");
+        fs::write(&conflicts_path, "old-app\n\n  older-app (<< 1.0)  \n").unwrap();
+
+        let mut config = Config::default();
+        config.product_name = "Test App".into();
+        config.version = "1.0.0".into();
+        config.description = Some("A test application".into());
+        config.deb = Some(
+            DebianConfig::new()
+                .depends(["libc6 (>= 2.31)", "libgtk-3-0"])
+                .pre_depends(Vec::<String>::new())
+                .recommends(["xdg-utils"])
+                .conflicts_path(&conflicts_path)
+                .provides(["test-app-bin"])
+                .replaces(["old-app"]),
+        );
+        let data_dir = dir.path().join("data");
+        let control_dir = dir.path().join("control");
+        fs::create_dir_all(&data_dir).unwrap();
+
+        generate_control_file(&config, "amd64", &control_dir, &data_dir).unwrap();
+        assert_eq!(
+            fs::read_to_string(control_dir.join("control")).unwrap(),
+            "Package: test-app
+Version: 1.0.0
+Architecture: amd64
+Installed-Size: 0
+Priority: optional
+Depends: libc6 (>= 2.31), libgtk-3-0
+Recommends: xdg-utils
+Conflicts: old-app, older-app (<< 1.0)
+Provides: test-app-bin
+Replaces: old-app
+Description: A test application
+ (none)
+"
+        );
+    }
+}
@@ -0,0 +1,301 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Shared library dependency detection, similar to `dpkg-shlibdeps`.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use walkdir::WalkDir;
+
+use crate::{config::Config, util, Error};
+
+/// The default location of the dpkg database.
+const DPKG_ADMIN_DIR: &str = "/var/lib/dpkg";
+
+/// An entry of a `shlibs` file, mapping a library to a dependency.
+///
+/// See <https://www.debian.org/doc/debian-policy/ch-sharedlibs.html#the-shlibs-file-format>
+#[derive(Debug, PartialEq, Eq)]
+struct Shlib {
+    library: String,
+    version: String,
+    dependency: String,
+}
+
+/// Splits a library soname into its name and version the same way `dpkg-shlibdeps` does,
+/// for example `libfoo.so.1` becomes `("libfoo", "1")` and `libfoo-1.2.so` becomes `("libfoo", "1.2")`.
+fn split_soname(soname: &str) -> Option<(&str, &str)> {
+    if let Some((name, version)) = soname.split_once(".so.") {
+        return Some((name, version));
+    }
+
+    let name = soname.strip_suffix(".so")?;
+    let (name, version) = name.rsplit_once('-')?;
+    version
+        .starts_with(|c: char| c.is_ascii_digit())
+        .then_some((name, version))
+}
+
+/// Parses the contents of a `shlibs` file, ignoring comments and typed (e.g. `udeb:`) entries.
+fn parse_shlibs(contents: &str) -> Vec<Shlib> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, char::is_whitespace);
+            let library = parts.next()?;
+            if library.ends_with(':') {
+                return None;
+            }
+            let version = parts.next()?;
+            let dependency = parts.next()?.trim();
+            Some(Shlib {
+                library: library.into(),
+                version: version.into(),
+                dependency: dependency.into(),
+            })
+        })
+        .collect()
+}
+
+/// Parses the dpkg `status` file, returning the version of each installed package.
+fn parse_dpkg_status(contents: &str) -> HashMap<String, String> {
+    let mut versions = HashMap::new();
+    for paragraph in contents.split("\n\n") {
+        let mut package = None;
+        let mut version = None;
+        let mut installed = false;
+        for line in paragraph.lines() {
+            if let Some(value) = line.strip_prefix("Package:") {
+                package = Some(value.trim());
+            } else if let Some(value) = line.strip_prefix("Version:") {
+                version = Some(value.trim());
+            } else if let Some(value) = line.strip_prefix("Status:") {
+                installed = value.trim().ends_with(" installed");
+            }
+        }
+        if let (Some(package), Some(version), true) = (package, version, installed) {
+            versions.insert(package.to_string(), version.to_string());
+        }
+    }
+    versions
+}
+
+/// Returns the dpkg database directory, honoring the `DPKG_ADMINDIR` environment variable.
+fn dpkg_admin_dir() -> PathBuf {
+    std::env::var_os("DPKG_ADMINDIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DPKG_ADMIN_DIR))
+}
+
+/// Collects the `DT_NEEDED` entries of all ELF files under `data_dir`,
+/// excluding the libraries that are shipped in the package itself.
+#[tracing::instrument(level = "trace")]
+fn needed_libraries(data_dir: &Path) -> crate::Result<BTreeSet<String>> {
+    let mut needed = BTreeSet::new();
+    let mut provided = BTreeSet::new();
+
+    for entry in WalkDir::new(data_dir) {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let path = entry.path();
+        if !util::is_elf(path) {
+            continue;
+        }
+        let bytes = fs::read(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+
+        match goblin::elf::Elf::parse(&bytes) {
+            Ok(elf) => {
+                needed.extend(elf.libraries.iter().map(|l| l.to_string()));
+                if let Some(soname) = elf.soname {
+                    provided.insert(soname.to_string());
+                }
+                if let Some(name) = path.file_name() {
+                    provided.insert(name.to_string_lossy().into_owned());
+                }
+            }
+            Err(e) => tracing::warn!("Failed to parse ELF file {}: {e}", path.display()),
+        }
+    }
+
+    Ok(needed.difference(&provided).cloned().collect())
+}
+
+/// Maps each of the `sonames` to the packages owning a file with that name in a library directory,
+/// reading the file lists of the dpkg database in a single pass.
+fn library_owners(info_dir: &Path, sonames: &BTreeSet<&str>) -> HashMap<String, Vec<String>> {
+    let mut owners = HashMap::<String, Vec<String>>::new();
+    let Ok(entries) = fs::read_dir(info_dir) else {
+        return owners;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "list") {
+            continue;
+        }
+        let (Some(package), Ok(contents)) = (path.file_stem(), fs::read_to_string(&path)) else {
+            continue;
+        };
+        let package = package.to_string_lossy();
+        for line in contents.lines() {
+            let Some((dir, name)) = line.rsplit_once('/') else {
+                continue;
+            };
+            if sonames.contains(name) && dir.contains("/lib") {
+                let packages = owners.entry(name.to_string()).or_default();
+                if !packages.iter().any(|p| *p == package) {
+                    packages.push(package.to_string());
+                }
+            }
+        }
+    }
+    owners
+}
+
+/// Picks the package providing a library among the packages owning it,
+/// preferring the packages built for `arch`.
+fn owning_package(candidates: &[String], arch: &str) -> Option<String> {
+    let candidate = candidates
+        .iter()
+        .find(|c| c.split_once(':').is_some_and(|(_, a)| a == arch))
+        .or_else(|| candidates.iter().find(|c| !c.contains(':')))
+        .or_else(|| candidates.first())?;
+    Some(
+        candidate
+            .split_once(':')
+            .map_or(candidate.as_str(), |(p, _)| p)
+            .to_string(),
+    )
+}
+
+/// Detects the Debian dependencies of the ELF files under `data_dir`.
+///
+/// Each needed library is resolved against the user provided `shlibs` file,
+/// then the `shlibs` files of the dpkg database, and finally the file lists
+/// of the dpkg database, in which case the installed version is used as the minimum version.
+#[tracing::instrument(level = "trace", skip(config))]
+pub fn shlib_depends(config: &Config, arch: &str, data_dir: &Path) -> crate::Result<Vec<String>> {
+    let needed = needed_libraries(data_dir)?;
+    if needed.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut shlibs = Vec::new();
+    if let Some(path) = config.deb().and_then(|d| d.shlibs_file.as_ref()) {
+        let contents = fs::read_to_string(path).map_err(|e| Error::IoWithPath(path.clone(), e))?;
+        shlibs.extend(parse_shlibs(&contents));
+    }
+
+    let admin_dir = dpkg_admin_dir();
+    let info_dir = admin_dir.join("info");
+    if let Ok(entries) = fs::read_dir(&info_dir) {
+        let mut paths = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "shlibs"))
+            .collect::<Vec<_>>();
+        paths.sort();
+        for path in paths {
+            if let Ok(contents) = fs::read_to_string(&path) {
+                shlibs.extend(parse_shlibs(&contents));
+            }
+        }
+    }
+
+    let status = fs::read_to_string(admin_dir.join("status"))
+        .map(|s| parse_dpkg_status(&s))
+        .unwrap_or_default();
+
+    let find_shlib = |soname: &str| {
+        split_soname(soname).and_then(|(library, version)| {
+            shlibs
+                .iter()
+                .find(|s| s.library == library && s.version == version)
+        })
+    };
+
+    // the file lists are only read for the libraries without a shlibs entry
+    let unresolved = needed
+        .iter()
+        .map(String::as_str)
+        .filter(|soname| find_shlib(soname).is_none())
+        .collect::<BTreeSet<_>>();
+    let owners = if unresolved.is_empty() {
+        HashMap::new()
+    } else {
+        library_owners(&info_dir, &unresolved)
+    };
+
+    let mut depends = Vec::new();
+    for soname in &needed {
+        let dependency = match find_shlib(soname) {
+            Some(shlib) => Some(shlib.dependency.clone()),
+            None => owners
+                .get(soname)
+                .and_then(|candidates| owning_package(candidates, arch))
+                .map(|package| {
+                    match status.get(&package) {
+                        // strip the debian revision, keeping the epoch and upstream version
+                        Some(version) => {
+                            let version =
+                                version.rsplit_once('-').map_or(version.as_str(), |v| v.0);
+                            format!("{package} (>= {version})")
+                        }
+                        None => package,
+                    }
+                }),
+        };
+
+        match dependency {
+            Some(dependency) => {
+                tracing::debug!("Resolved {soname} to {dependency}");
+                if !depends.contains(&dependency) {
+                    depends.push(dependency);
+                }
+            }
+            None => tracing::warn!("Could not find a Debian package providing {soname}"),
+        }
+    }
+
+    Ok(depends)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_soname() {
+        assert_eq!(split_soname("libc.so.6"), Some(("libc", "6")));
+        assert_eq!(
+            split_soname("libgtk-3.so.0.2404.1"),
+            Some(("libgtk-3", "0.2404.1"))
+        );
+        assert_eq!(split_soname("libfoo-1.2.so"), Some(("libfoo", "1.2")));
+        assert_eq!(split_soname("libfoo.so"), None);
+    }
+
+    #[test]
+    fn parses_shlibs() {
+        let shlibs = parse_shlibs(
+            "# comment\nlibz 1 zlib1g (>= 1:1.1.4)\nudeb: libz 1 zlib1g-udeb (>= 1:1.1.4)\n",
+        );
+        assert_eq!(
+            shlibs,
+            vec![Shlib {
+                library: "libz".into(),
+                version: "1".into(),
+                dependency: "zlib1g (>= 1:1.1.4)".into(),
+            }]
+        );
+    }
+}
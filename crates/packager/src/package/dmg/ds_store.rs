@@ -0,0 +1,289 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Writer for the `.DS_Store` files used by the Finder to store the window layout,
+//! and for the alias records it uses to reference the background image.
+
+/// A `.DS_Store` record value.
+pub(super) enum Value {
+    Long(u32),
+    Blob(Vec<u8>),
+}
+
+/// A `.DS_Store` record, describing a property of the file `name` in the folder.
+pub(super) struct Record {
+    pub name: String,
+    pub code: [u8; 4],
+    pub value: Value,
+}
+
+impl Record {
+    pub(super) fn new(name: impl Into<String>, code: &[u8; 4], value: Value) -> Self {
+        Self {
+            name: name.into(),
+            code: *code,
+            value,
+        }
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        write_utf16(out, &self.name, 4);
+        out.extend_from_slice(&self.code);
+        match &self.value {
+            Value::Long(value) => {
+                out.extend_from_slice(b"long");
+                out.extend_from_slice(&value.to_be_bytes());
+            }
+            Value::Blob(data) => {
+                out.extend_from_slice(b"blob");
+                out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+                out.extend_from_slice(data);
+            }
+        }
+    }
+}
+
+/// Returns the `Iloc` value positioning an icon at `(x, y)`.
+pub(super) fn icon_location(x: u32, y: u32) -> Value {
+    let mut data = Vec::with_capacity(16);
+    data.extend_from_slice(&x.to_be_bytes());
+    data.extend_from_slice(&y.to_be_bytes());
+    data.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0, 0]);
+    Value::Blob(data)
+}
+
+/// The size of the single B-tree node holding the records.
+const PAGE_SIZE: usize = 0x1000;
+
+/// A buddy allocator over the `.DS_Store` file, where every block has a power of two size
+/// and is addressed by its offset combined with the base-2 logarithm of its size.
+struct Allocator {
+    free: Vec<Vec<u32>>,
+}
+
+impl Allocator {
+    fn new() -> Self {
+        // the first 32 bytes are taken by the file header
+        Self {
+            free: (0..32)
+                .map(|n| {
+                    if (5..31).contains(&n) {
+                        vec![1 << n]
+                    } else {
+                        vec![]
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    fn allocate(&mut self, size: usize) -> u32 {
+        let width = size.next_power_of_two().trailing_zeros().max(5) as usize;
+        let mut available = (width..32)
+            .find(|w| !self.free[*w].is_empty())
+            .expect("the .DS_Store file has no free space left");
+        let offset = self.free[available].remove(0);
+        // split the block, returning the upper halves to the free lists
+        while available > width {
+            available -= 1;
+            self.free[available].push(offset + (1 << available));
+            self.free[available].sort();
+        }
+        offset | width as u32
+    }
+}
+
+/// Writes a `.DS_Store` file containing the given records.
+pub(super) fn write(mut records: Vec<Record>) -> Vec<u8> {
+    records.sort_by_key(|r| (r.name.to_lowercase(), r.code));
+
+    let mut node = Vec::with_capacity(PAGE_SIZE);
+    // no next node, this is a leaf
+    node.extend_from_slice(&0u32.to_be_bytes());
+    node.extend_from_slice(&(records.len() as u32).to_be_bytes());
+    for record in &records {
+        record.write(&mut node);
+    }
+    debug_assert!(node.len() <= PAGE_SIZE);
+
+    let mut allocator = Allocator::new();
+    let root_address = allocator.allocate(2048);
+    let db_address = allocator.allocate(20);
+    let node_address = allocator.allocate(PAGE_SIZE);
+    // block IDs are indices in this list
+    let addresses = [root_address, db_address, node_address];
+
+    let mut db = Vec::with_capacity(20);
+    // root node ID, number of levels, records and nodes, and the page size
+    for value in [2, 0, records.len() as u32, 1, PAGE_SIZE as u32] {
+        db.extend_from_slice(&value.to_be_bytes());
+    }
+
+    let mut root = Vec::with_capacity(2048);
+    root.extend_from_slice(&(addresses.len() as u32).to_be_bytes());
+    root.extend_from_slice(&0u32.to_be_bytes());
+    for address in addresses {
+        root.extend_from_slice(&address.to_be_bytes());
+    }
+    // the address table is padded to a multiple of 256 entries
+    root.resize(8 + 256 * 4, 0);
+    // table of contents
+    root.extend_from_slice(&1u32.to_be_bytes());
+    root.push(4);
+    root.extend_from_slice(b"DSDB");
+    root.extend_from_slice(&1u32.to_be_bytes());
+    for free in &allocator.free {
+        root.extend_from_slice(&(free.len() as u32).to_be_bytes());
+        for offset in free {
+            root.extend_from_slice(&offset.to_be_bytes());
+        }
+    }
+
+    let block_range = |address: u32| {
+        let offset = (address & !0x1f) as usize;
+        (offset + 4, 1usize << (address & 0x1f))
+    };
+
+    let (root_offset, root_size) = block_range(root_address);
+    let end = addresses
+        .iter()
+        .map(|a| {
+            let (offset, size) = block_range(*a);
+            offset + size
+        })
+        .max()
+        .unwrap_or_default();
+
+    let mut file = vec![0; end];
+    file[0..4].copy_from_slice(&1u32.to_be_bytes());
+    file[4..8].copy_from_slice(b"Bud1");
+    file[8..12].copy_from_slice(&((root_offset - 4) as u32).to_be_bytes());
+    file[12..16].copy_from_slice(&(root_size as u32).to_be_bytes());
+    file[16..20].copy_from_slice(&((root_offset - 4) as u32).to_be_bytes());
+    for (address, data) in [(root_address, root), (db_address, db), (node_address, node)] {
+        let (offset, _) = block_range(address);
+        file[offset..offset + data.len()].copy_from_slice(&data);
+    }
+    file
+}
+
+/// Writes a string as its length followed by the UTF-16BE code units,
+/// the length being stored in `len_size` bytes.
+fn write_utf16(out: &mut Vec<u8>, value: &str, len_size: usize) {
+    let encoded = value.encode_utf16().collect::<Vec<_>>();
+    out.extend_from_slice(&(encoded.len() as u32).to_be_bytes()[4 - len_size..]);
+    for c in encoded {
+        out.extend_from_slice(&c.to_be_bytes());
+    }
+}
+
+/// A version 2 alias record pointing to a file on the disk image volume.
+pub(super) struct Alias<'a> {
+    pub volume_name: &'a str,
+    pub volume_created: u32,
+    /// The folder containing the file, directly under the volume root.
+    pub folder_name: &'a str,
+    pub folder_id: u32,
+    pub file_name: &'a str,
+    pub file_id: u32,
+}
+
+impl Alias<'_> {
+    pub(super) fn to_bytes(&self) -> Vec<u8> {
+        let mut alias = Vec::with_capacity(512);
+        // application info, record length (patched below) and version
+        alias.extend_from_slice(&[0; 4]);
+        alias.extend_from_slice(&0u16.to_be_bytes());
+        alias.extend_from_slice(&2u16.to_be_bytes());
+        // kind, a file
+        alias.extend_from_slice(&0u16.to_be_bytes());
+        write_pascal(&mut alias, self.volume_name, 28);
+        alias.extend_from_slice(&self.volume_created.to_be_bytes());
+        alias.extend_from_slice(b"H+");
+        // ejectable disk
+        alias.extend_from_slice(&5u16.to_be_bytes());
+        alias.extend_from_slice(&self.folder_id.to_be_bytes());
+        write_pascal(&mut alias, self.file_name, 64);
+        alias.extend_from_slice(&self.file_id.to_be_bytes());
+        alias.extend_from_slice(&self.volume_created.to_be_bytes());
+        // creator and type codes
+        alias.extend_from_slice(&[0; 8]);
+        // levels from and to
+        alias.extend_from_slice(&(-1i16).to_be_bytes());
+        alias.extend_from_slice(&(-1i16).to_be_bytes());
+        // volume attributes, file system ID and reserved bytes
+        alias.extend_from_slice(&[0; 16]);
+
+        let mut tag = |tag: i16, data: &[u8]| {
+            alias.extend_from_slice(&tag.to_be_bytes());
+            alias.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            alias.extend_from_slice(data);
+            if data.len() % 2 == 1 {
+                alias.push(0);
+            }
+        };
+        let utf16 = |value: &str| {
+            let mut out = Vec::new();
+            write_utf16(&mut out, value, 2);
+            out
+        };
+        tag(0, self.folder_name.as_bytes());
+        tag(1, &self.folder_id.to_be_bytes());
+        tag(
+            2,
+            format!(
+                "{}:{}:{}",
+                self.volume_name, self.folder_name, self.file_name
+            )
+            .as_bytes(),
+        );
+        tag(14, &utf16(self.file_name));
+        tag(15, &utf16(self.volume_name));
+        tag(
+            18,
+            format!("/{}/{}", self.folder_name, self.file_name).as_bytes(),
+        );
+        tag(19, format!("/Volumes/{}", self.volume_name).as_bytes());
+        tag(-1, &[]);
+
+        let len = alias.len() as u16;
+        alias[4..6].copy_from_slice(&len.to_be_bytes());
+        alias
+    }
+}
+
+/// Writes a fixed size Pascal string, truncating the value if needed.
+fn write_pascal(out: &mut Vec<u8>, value: &str, size: usize) {
+    let bytes = &value.as_bytes()[..value.len().min(size - 1)];
+    out.push(bytes.len() as u8);
+    out.extend_from_slice(bytes);
+    out.resize(out.len() + size - 1 - bytes.len(), 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_buddy_allocated_file() {
+        let file = write(vec![
+            Record::new("b.app", b"Iloc", icon_location(180, 170)),
+            Record::new(".", b"vSrn", Value::Long(1)),
+        ]);
+
+        assert_eq!(&file[0..8], b"\0\0\0\x01Bud1");
+        let root_offset = u32::from_be_bytes(file[8..12].try_into().unwrap()) as usize + 4;
+        let root = &file[root_offset..];
+        assert_eq!(u32::from_be_bytes(root[0..4].try_into().unwrap()), 3);
+
+        // the DSDB block points to the single leaf node, sorted by file name
+        let db_address = u32::from_be_bytes(root[12..16].try_into().unwrap());
+        let db = &file[(db_address & !0x1f) as usize + 4..];
+        assert_eq!(u32::from_be_bytes(db[8..12].try_into().unwrap()), 2);
+        let node_address = u32::from_be_bytes(root[16..20].try_into().unwrap());
+        let node = &file[(node_address & !0x1f) as usize + 4..];
+        assert_eq!(&node[0..8], &[0, 0, 0, 0, 0, 0, 0, 2]);
+        assert_eq!(&node[8..18], b"\0\0\0\x01\0.vSrn");
+    }
+}
@@ -0,0 +1,806 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! A minimal HFS+ volume writer, just enough to lay out the contents of a read-only disk image.
+//!
+//! The volume is written as HFSX with binary name ordering, so the catalog can be sorted
+//! without Apple's case folding tables. Every file is stored in a single contiguous extent,
+//! which keeps the extents overflow file empty, and there is no journal.
+//!
+//! See <https://developer.apple.com/library/archive/technotes/tn/tn1150.html>.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::Error;
+
+/// Finder flag hiding the file name extension.
+pub(super) const FINDER_FLAG_EXTENSION_HIDDEN: u16 = 0x0010;
+/// Finder flag telling the Finder to use the `Icon\r` file or `.VolumeIcon.icns` of a folder.
+pub(super) const FINDER_FLAG_HAS_CUSTOM_ICON: u16 = 0x0400;
+
+const BLOCK_SIZE: u64 = 4096;
+const EXTENTS_NODE_SIZE: usize = 4096;
+const CATALOG_NODE_SIZE: usize = 8192;
+
+/// Seconds between the HFS+ epoch (1904-01-01) and the unix epoch.
+const HFS_EPOCH_OFFSET: u64 = 2_082_844_800;
+
+const ROOT_PARENT_ID: u32 = 1;
+const ROOT_FOLDER_ID: u32 = 2;
+const FIRST_USER_CATALOG_NODE_ID: u32 = 16;
+
+const FOLDER_RECORD: u16 = 1;
+const FILE_RECORD: u16 = 2;
+const FOLDER_THREAD_RECORD: u16 = 3;
+const FILE_THREAD_RECORD: u16 = 4;
+
+/// `kHFSThreadExistsMask`
+const FILE_THREAD_EXISTS: u16 = 0x0002;
+/// `kHFSVolumeUnmountedBit`
+const VOLUME_UNMOUNTED: u32 = 1 << 8;
+
+/// User and group `unknown`, which macOS maps to the user accessing the volume.
+const UNKNOWN_ID: u32 = 99;
+
+const S_IFDIR: u16 = 0o040000;
+const S_IFREG: u16 = 0o100000;
+const S_IFLNK: u16 = 0o120000;
+
+const BTREE_LEAF_NODE: u8 = 0xff;
+const BTREE_INDEX_NODE: u8 = 0;
+const BTREE_HEADER_NODE: u8 = 1;
+const BTREE_NODE_DESCRIPTOR_SIZE: usize = 14;
+/// `kBTBigKeysMask`
+const BTREE_BIG_KEYS: u32 = 0x2;
+/// `kBTVariableIndexKeysMask`
+const BTREE_VARIABLE_INDEX_KEYS: u32 = 0x4;
+/// `kHFSBinaryCompare`
+const BINARY_COMPARE: u8 = 0xbc;
+
+enum Contents {
+    Path(PathBuf, u64),
+    Data(Vec<u8>),
+}
+
+impl Contents {
+    fn len(&self) -> u64 {
+        match self {
+            Self::Path(_, len) => *len,
+            Self::Data(data) => data.len() as u64,
+        }
+    }
+}
+
+enum NodeKind {
+    Folder,
+    File(Contents),
+    Symlink(Vec<u8>),
+}
+
+struct Node {
+    parent: u32,
+    name: Vec<u16>,
+    mode: u16,
+    finder_flags: u16,
+    kind: NodeKind,
+}
+
+/// An in-memory description of an HFS+ volume, written out by [`Volume::write`].
+pub(super) struct Volume {
+    name: Vec<u16>,
+    created: u32,
+    root_finder_flags: u16,
+    nodes: Vec<Node>,
+    children: HashMap<(u32, Vec<u16>), u32>,
+}
+
+impl Volume {
+    pub(super) fn new(name: &str) -> crate::Result<Self> {
+        let created = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs()
+            + HFS_EPOCH_OFFSET;
+        Ok(Self {
+            name: hfs_name(name)?,
+            created: created as u32,
+            root_finder_flags: 0,
+            nodes: Vec::new(),
+            children: HashMap::new(),
+        })
+    }
+
+    /// The volume creation date, in seconds since 1904-01-01.
+    pub(super) fn created(&self) -> u32 {
+        self.created
+    }
+
+    /// Returns the catalog node ID of the item at the `/` separated `path`.
+    pub(super) fn node_id(&self, path: &str) -> Option<u32> {
+        let mut id = ROOT_FOLDER_ID;
+        for component in path.split('/').filter(|c| !c.is_empty()) {
+            id = *self.children.get(&(id, hfs_name(component).ok()?))?;
+        }
+        Some(id)
+    }
+
+    /// Sets the Finder flags of the item at `path`, the empty path being the volume root.
+    pub(super) fn set_finder_flags(&mut self, path: &str, flags: u16) {
+        match self.node_id(path) {
+            Some(ROOT_FOLDER_ID) => self.root_finder_flags |= flags,
+            Some(id) => self.node_mut(id).finder_flags |= flags,
+            None => {}
+        }
+    }
+
+    /// Adds a folder at `path`, creating its parents as needed.
+    pub(super) fn add_folder(&mut self, path: &str) -> crate::Result<u32> {
+        let mut id = ROOT_FOLDER_ID;
+        for component in path.split('/').filter(|c| !c.is_empty()) {
+            let name = hfs_name(component)?;
+            id = match self.children.get(&(id, name.clone())) {
+                Some(child) => *child,
+                None => self.insert(id, name, S_IFDIR | 0o755, NodeKind::Folder),
+            };
+        }
+        Ok(id)
+    }
+
+    /// Adds a file with the given contents at `path`.
+    pub(super) fn add_data(&mut self, path: &str, data: Vec<u8>) -> crate::Result<u32> {
+        let (parent, name) = self.parent_of(path)?;
+        Ok(self.insert(
+            parent,
+            name,
+            S_IFREG | 0o644,
+            NodeKind::File(Contents::Data(data)),
+        ))
+    }
+
+    /// Adds the file at `source` to the volume at `path`.
+    pub(super) fn add_file(&mut self, source: &Path, path: &str) -> crate::Result<u32> {
+        let metadata =
+            fs::metadata(source).map_err(|e| Error::IoWithPath(source.to_path_buf(), e))?;
+        let (parent, name) = self.parent_of(path)?;
+        Ok(self.insert(
+            parent,
+            name,
+            S_IFREG | file_mode(&metadata),
+            NodeKind::File(Contents::Path(source.to_path_buf(), metadata.len())),
+        ))
+    }
+
+    /// Adds a symbolic link at `path` pointing to `target`.
+    pub(super) fn add_symlink(&mut self, path: &str, target: &str) -> crate::Result<u32> {
+        let (parent, name) = self.parent_of(path)?;
+        Ok(self.insert(
+            parent,
+            name,
+            S_IFLNK | 0o755,
+            NodeKind::Symlink(target.as_bytes().to_vec()),
+        ))
+    }
+
+    /// Recursively adds the `source` directory to the volume at `path`, preserving symlinks.
+    pub(super) fn add_dir_all(&mut self, source: &Path, path: &str) -> crate::Result<()> {
+        self.add_folder(path)?;
+        for entry in walkdir::WalkDir::new(source).min_depth(1) {
+            let entry = entry?;
+            let rel_path = entry.path().strip_prefix(source)?;
+            let dest = std::iter::once(path.to_string())
+                .chain(
+                    rel_path
+                        .components()
+                        .map(|c| c.as_os_str().to_string_lossy().into_owned()),
+                )
+                .collect::<Vec<_>>()
+                .join("/");
+            if entry.file_type().is_symlink() {
+                let target = fs::read_link(entry.path())
+                    .map_err(|e| Error::IoWithPath(entry.path().to_path_buf(), e))?;
+                self.add_symlink(&dest, &target.to_string_lossy().replace('\\', "/"))?;
+            } else if entry.file_type().is_dir() {
+                self.add_folder(&dest)?;
+            } else {
+                self.add_file(entry.path(), &dest)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn parent_of(&mut self, path: &str) -> crate::Result<(u32, Vec<u16>)> {
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        Ok((self.add_folder(parent)?, hfs_name(name)?))
+    }
+
+    fn insert(&mut self, parent: u32, name: Vec<u16>, mode: u16, kind: NodeKind) -> u32 {
+        let id = FIRST_USER_CATALOG_NODE_ID + self.nodes.len() as u32;
+        self.children.insert((parent, name.clone()), id);
+        self.nodes.push(Node {
+            parent,
+            name,
+            mode,
+            finder_flags: 0,
+            kind,
+        });
+        id
+    }
+
+    fn node_mut(&mut self, id: u32) -> &mut Node {
+        &mut self.nodes[(id - FIRST_USER_CATALOG_NODE_ID) as usize]
+    }
+
+    /// Returns the catalog records of the volume, sorted by key,
+    /// with the file contents starting at the `data_start` allocation block.
+    fn catalog_records(
+        &self,
+        extents: &[(u64, u32, u32)],
+        data_start: u32,
+    ) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut valences: HashMap<u32, u32> = HashMap::new();
+        for node in &self.nodes {
+            *valences.entry(node.parent).or_default() += 1;
+        }
+        let valence = |id| valences.get(&id).copied().unwrap_or_default();
+
+        let mut records = Vec::with_capacity(self.nodes.len() * 2 + 2);
+        records.push((
+            (ROOT_PARENT_ID, self.name.clone()),
+            folder_record(
+                ROOT_FOLDER_ID,
+                valence(ROOT_FOLDER_ID),
+                S_IFDIR | 0o755,
+                self.root_finder_flags,
+                self.created,
+            ),
+        ));
+        records.push((
+            (ROOT_FOLDER_ID, Vec::new()),
+            thread_record(FOLDER_THREAD_RECORD, ROOT_PARENT_ID, &self.name),
+        ));
+
+        for (i, (node, (len, start, blocks))) in self.nodes.iter().zip(extents).enumerate() {
+            let id = FIRST_USER_CATALOG_NODE_ID + i as u32;
+            let (record, thread_type) = match &node.kind {
+                NodeKind::Folder => (
+                    folder_record(id, valence(id), node.mode, node.finder_flags, self.created),
+                    FOLDER_THREAD_RECORD,
+                ),
+                NodeKind::File(_) | NodeKind::Symlink(_) => {
+                    let (file_type, creator) = match node.kind {
+                        NodeKind::Symlink(_) => (*b"slnk", *b"rhap"),
+                        _ => ([0; 4], [0; 4]),
+                    };
+                    let start = if *blocks == 0 { 0 } else { data_start + start };
+                    (
+                        file_record(
+                            id,
+                            node.mode,
+                            file_type,
+                            creator,
+                            node.finder_flags,
+                            self.created,
+                            &fork_data(*len, start, *blocks),
+                        ),
+                        FILE_THREAD_RECORD,
+                    )
+                }
+            };
+            records.push(((node.parent, node.name.clone()), record));
+            records.push((
+                (id, Vec::new()),
+                thread_record(thread_type, node.parent, &node.name),
+            ));
+        }
+
+        records.sort_by(|(a, _), (b, _)| a.cmp(b));
+        records
+            .into_iter()
+            .map(|((parent, name), data)| (catalog_key(parent, &name), data))
+            .collect()
+    }
+
+    /// Writes the volume image to `path`.
+    pub(super) fn write(&self, path: &Path) -> crate::Result<()> {
+        // allocate the file contents relative to the start of the data area
+        let mut data_blocks = 0u32;
+        let extents = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let len = match &node.kind {
+                    NodeKind::Folder => 0,
+                    NodeKind::File(contents) => contents.len(),
+                    NodeKind::Symlink(target) => target.len() as u64,
+                };
+                let blocks = len.div_ceil(BLOCK_SIZE) as u32;
+                let start = data_blocks;
+                data_blocks += blocks;
+                (len, start, blocks)
+            })
+            .collect::<Vec<_>>();
+
+        let btree = |records: &[(Vec<u8>, Vec<u8>)]| {
+            build_btree(
+                records,
+                CATALOG_NODE_SIZE,
+                516,
+                BTREE_BIG_KEYS | BTREE_VARIABLE_INDEX_KEYS,
+                BINARY_COMPARE,
+            )
+        };
+        // the catalog size does not depend on where the file contents are placed
+        let catalog_len = btree(&self.catalog_records(&extents, 0))?.len() as u64;
+        let extents_overflow = build_btree(&[], EXTENTS_NODE_SIZE, 10, BTREE_BIG_KEYS, 0)?;
+
+        // the first block holds the boot blocks and the volume header,
+        // and the last one the alternate volume header
+        let fixed_blocks =
+            2 + (extents_overflow.len() as u64 + catalog_len) / BLOCK_SIZE + data_blocks as u64;
+        let mut total_blocks = fixed_blocks + (fixed_blocks / 20).max(256);
+        let allocation_len = loop {
+            let len = total_blocks.div_ceil(8).div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+            if fixed_blocks + len / BLOCK_SIZE < total_blocks {
+                break len;
+            }
+            total_blocks += len / BLOCK_SIZE;
+        };
+
+        let allocation_start = 1;
+        let extents_start = allocation_start + allocation_len / BLOCK_SIZE;
+        let catalog_start = extents_start + extents_overflow.len() as u64 / BLOCK_SIZE;
+        let data_start = catalog_start + catalog_len / BLOCK_SIZE;
+        let next_allocation = data_start + data_blocks as u64;
+
+        let catalog = btree(&self.catalog_records(&extents, data_start as u32))?;
+        debug_assert_eq!(catalog.len() as u64, catalog_len);
+
+        let mut bitmap = vec![0u8; allocation_len as usize];
+        for block in (0..next_allocation).chain(std::iter::once(total_blocks - 1)) {
+            bitmap[(block / 8) as usize] |= 0x80 >> (block % 8);
+        }
+
+        let file_count = self
+            .nodes
+            .iter()
+            .filter(|n| !matches!(n.kind, NodeKind::Folder))
+            .count() as u32;
+        let folder_count = self.nodes.len() as u32 - file_count;
+        let next_catalog_id = FIRST_USER_CATALOG_NODE_ID + self.nodes.len() as u32;
+
+        let mut header = Vec::with_capacity(512);
+        header.extend_from_slice(b"HX");
+        header.extend_from_slice(&5u16.to_be_bytes());
+        header.extend_from_slice(&VOLUME_UNMOUNTED.to_be_bytes());
+        header.extend_from_slice(b"10.0");
+        // journal info block
+        header.extend_from_slice(&0u32.to_be_bytes());
+        // creation, modification, backup and checked dates
+        for date in [self.created, self.created, 0, self.created] {
+            header.extend_from_slice(&date.to_be_bytes());
+        }
+        header.extend_from_slice(&file_count.to_be_bytes());
+        header.extend_from_slice(&folder_count.to_be_bytes());
+        header.extend_from_slice(&(BLOCK_SIZE as u32).to_be_bytes());
+        header.extend_from_slice(&(total_blocks as u32).to_be_bytes());
+        header.extend_from_slice(&((total_blocks - next_allocation - 1) as u32).to_be_bytes());
+        header.extend_from_slice(&(next_allocation as u32).to_be_bytes());
+        // resource and data fork clump sizes
+        header.extend_from_slice(&65536u32.to_be_bytes());
+        header.extend_from_slice(&65536u32.to_be_bytes());
+        header.extend_from_slice(&next_catalog_id.to_be_bytes());
+        // write count
+        header.extend_from_slice(&1u32.to_be_bytes());
+        // encodings bitmap, only MacRoman
+        header.extend_from_slice(&1u64.to_be_bytes());
+        // Finder info, with the root folder opened when the volume is mounted
+        // and a random volume identifier in the last two words
+        let volume_id = rand::random::<u64>();
+        for info in [
+            0,
+            0,
+            ROOT_FOLDER_ID,
+            0,
+            0,
+            0,
+            (volume_id >> 32) as u32,
+            volume_id as u32,
+        ] {
+            header.extend_from_slice(&info.to_be_bytes());
+        }
+        for (start, len) in [
+            (allocation_start, allocation_len),
+            (extents_start, extents_overflow.len() as u64),
+            (catalog_start, catalog_len),
+        ] {
+            header.extend_from_slice(&fork_data(len, start as u32, (len / BLOCK_SIZE) as u32));
+        }
+        // attributes and startup files
+        header.extend_from_slice(&[0; 160]);
+        debug_assert_eq!(header.len(), 512);
+
+        let mut file = File::create(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+        file.set_len(total_blocks * BLOCK_SIZE)
+            .map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+
+        let mut write_at = |offset: u64, data: &[u8]| -> io::Result<()> {
+            file.seek(SeekFrom::Start(offset))?;
+            file.write_all(data)
+        };
+        write_at(1024, &header)
+            .and_then(|_| write_at(allocation_start * BLOCK_SIZE, &bitmap))
+            .and_then(|_| write_at(extents_start * BLOCK_SIZE, &extents_overflow))
+            .and_then(|_| write_at(catalog_start * BLOCK_SIZE, &catalog))
+            .and_then(|_| write_at(total_blocks * BLOCK_SIZE - 1024, &header))
+            .map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+
+        for (node, (_, start, _)) in self.nodes.iter().zip(&extents) {
+            let position = SeekFrom::Start((data_start + *start as u64) * BLOCK_SIZE);
+            match &node.kind {
+                NodeKind::Folder => {}
+                NodeKind::File(Contents::Path(source, _)) => {
+                    let mut source_file =
+                        File::open(source).map_err(|e| Error::IoWithPath(source.clone(), e))?;
+                    file.seek(position)
+                        .and_then(|_| io::copy(&mut source_file, &mut file))
+                        .map_err(|e| Error::IoWithPath(source.clone(), e))?;
+                }
+                NodeKind::File(Contents::Data(data)) | NodeKind::Symlink(data) => {
+                    file.seek(position)
+                        .and_then(|_| file.write_all(data))
+                        .map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Converts a file name to the decomposed UTF-16 form stored in the catalog.
+///
+/// The Finder shows `/` for the `:` of POSIX names and the catalog stores the Finder name.
+fn hfs_name(name: &str) -> crate::Result<Vec<u16>> {
+    let encoded = name
+        .nfd()
+        .map(|c| if c == ':' { '/' } else { c })
+        .collect::<String>()
+        .encode_utf16()
+        .collect::<Vec<_>>();
+    if encoded.len() > 255 {
+        return Err(Error::HfsNameTooLong(name.to_string()));
+    }
+    Ok(encoded)
+}
+
+#[cfg(unix)]
+fn file_mode(metadata: &fs::Metadata) -> u16 {
+    use std::os::unix::fs::PermissionsExt;
+    (metadata.permissions().mode() & 0o7777) as u16
+}
+
+#[cfg(not(unix))]
+fn file_mode(_metadata: &fs::Metadata) -> u16 {
+    0o755
+}
+
+fn catalog_key(parent: u32, name: &[u16]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(8 + name.len() * 2);
+    key.extend_from_slice(&(6 + name.len() as u16 * 2).to_be_bytes());
+    key.extend_from_slice(&parent.to_be_bytes());
+    key.extend_from_slice(&(name.len() as u16).to_be_bytes());
+    for c in name {
+        key.extend_from_slice(&c.to_be_bytes());
+    }
+    key
+}
+
+/// `HFSPlusBSDInfo`
+fn bsd_info(mode: u16) -> [u8; 16] {
+    let mut info = [0; 16];
+    info[0..4].copy_from_slice(&UNKNOWN_ID.to_be_bytes());
+    info[4..8].copy_from_slice(&UNKNOWN_ID.to_be_bytes());
+    info[10..12].copy_from_slice(&mode.to_be_bytes());
+    info
+}
+
+/// `HFSPlusCatalogFolder`
+fn folder_record(id: u32, valence: u32, mode: u16, finder_flags: u16, date: u32) -> Vec<u8> {
+    let mut record = Vec::with_capacity(88);
+    record.extend_from_slice(&FOLDER_RECORD.to_be_bytes());
+    // flags
+    record.extend_from_slice(&0u16.to_be_bytes());
+    record.extend_from_slice(&valence.to_be_bytes());
+    record.extend_from_slice(&id.to_be_bytes());
+    // creation, content modification, attribute modification, access and backup dates
+    for date in [date, date, date, date, 0] {
+        record.extend_from_slice(&date.to_be_bytes());
+    }
+    record.extend_from_slice(&bsd_info(mode));
+    // FolderInfo: window bounds, flags, location and a reserved field
+    record.extend_from_slice(&[0; 8]);
+    record.extend_from_slice(&finder_flags.to_be_bytes());
+    record.extend_from_slice(&[0; 6]);
+    // ExtendedFolderInfo, text encoding and folder count
+    record.extend_from_slice(&[0; 24]);
+    record
+}
+
+/// `HFSPlusCatalogFile`
+fn file_record(
+    id: u32,
+    mode: u16,
+    file_type: [u8; 4],
+    creator: [u8; 4],
+    finder_flags: u16,
+    date: u32,
+    data_fork: &[u8; 80],
+) -> Vec<u8> {
+    let mut record = Vec::with_capacity(248);
+    record.extend_from_slice(&FILE_RECORD.to_be_bytes());
+    record.extend_from_slice(&FILE_THREAD_EXISTS.to_be_bytes());
+    // reserved
+    record.extend_from_slice(&0u32.to_be_bytes());
+    record.extend_from_slice(&id.to_be_bytes());
+    for date in [date, date, date, date, 0] {
+        record.extend_from_slice(&date.to_be_bytes());
+    }
+    record.extend_from_slice(&bsd_info(mode));
+    // FileInfo: type, creator, flags, location and a reserved field
+    record.extend_from_slice(&file_type);
+    record.extend_from_slice(&creator);
+    record.extend_from_slice(&finder_flags.to_be_bytes());
+    record.extend_from_slice(&[0; 6]);
+    // ExtendedFileInfo, text encoding and a reserved field
+    record.extend_from_slice(&[0; 24]);
+    record.extend_from_slice(data_fork);
+    // resource fork
+    record.extend_from_slice(&[0; 80]);
+    record
+}
+
+/// `HFSPlusCatalogThread`
+fn thread_record(record_type: u16, parent: u32, name: &[u16]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(10 + name.len() * 2);
+    record.extend_from_slice(&record_type.to_be_bytes());
+    // reserved
+    record.extend_from_slice(&0u16.to_be_bytes());
+    record.extend_from_slice(&parent.to_be_bytes());
+    record.extend_from_slice(&(name.len() as u16).to_be_bytes());
+    for c in name {
+        record.extend_from_slice(&c.to_be_bytes());
+    }
+    record
+}
+
+/// `HFSPlusForkData` with a single extent.
+fn fork_data(len: u64, start: u32, blocks: u32) -> [u8; 80] {
+    let mut fork = [0; 80];
+    fork[0..8].copy_from_slice(&len.to_be_bytes());
+    // clump size is left as zero
+    fork[12..16].copy_from_slice(&blocks.to_be_bytes());
+    if blocks > 0 {
+        fork[16..20].copy_from_slice(&start.to_be_bytes());
+        fork[20..24].copy_from_slice(&blocks.to_be_bytes());
+    }
+    fork
+}
+
+/// Writes a B-tree node with the given descriptor and records.
+fn btree_node(
+    node_size: usize,
+    (next, previous): (u32, u32),
+    kind: u8,
+    height: u8,
+    records: &[Vec<u8>],
+) -> Vec<u8> {
+    let mut node = vec![0; node_size];
+    node[0..4].copy_from_slice(&next.to_be_bytes());
+    node[4..8].copy_from_slice(&previous.to_be_bytes());
+    node[8] = kind;
+    node[9] = height;
+    node[10..12].copy_from_slice(&(records.len() as u16).to_be_bytes());
+
+    // the record offsets are stored backwards from the end of the node,
+    // followed by the offset of the free space
+    let mut offset = BTREE_NODE_DESCRIPTOR_SIZE;
+    for (i, record) in records.iter().enumerate() {
+        node[offset..offset + record.len()].copy_from_slice(record);
+        let position = node_size - 2 * (i + 1);
+        node[position..position + 2].copy_from_slice(&(offset as u16).to_be_bytes());
+        offset += record.len();
+    }
+    let position = node_size - 2 * (records.len() + 1);
+    node[position..position + 2].copy_from_slice(&(offset as u16).to_be_bytes());
+    node
+}
+
+/// Splits the records into nodes, returning the records of each node.
+fn pack_nodes(records: Vec<Vec<u8>>, node_size: usize) -> Vec<Vec<Vec<u8>>> {
+    let mut nodes = Vec::new();
+    let mut current: Vec<Vec<u8>> = Vec::new();
+    let mut used = BTREE_NODE_DESCRIPTOR_SIZE + 2;
+    for record in records {
+        if !current.is_empty() && used + record.len() + 2 > node_size {
+            nodes.push(std::mem::take(&mut current));
+            used = BTREE_NODE_DESCRIPTOR_SIZE + 2;
+        }
+        used += record.len() + 2;
+        current.push(record);
+    }
+    if !current.is_empty() {
+        nodes.push(current);
+    }
+    nodes
+}
+
+/// Builds a B-tree file from the sorted `(key, data)` records.
+fn build_btree(
+    records: &[(Vec<u8>, Vec<u8>)],
+    node_size: usize,
+    max_key_len: u16,
+    attributes: u32,
+    key_compare_type: u8,
+) -> crate::Result<Vec<u8>> {
+    // node 0 is the header node, followed by the leaves and then the index levels
+    let mut levels: Vec<Vec<Vec<Vec<u8>>>> = vec![pack_nodes(
+        records
+            .iter()
+            .map(|(key, data)| [key.as_slice(), data.as_slice()].concat())
+            .collect(),
+        node_size,
+    )];
+    let mut first_keys = vec![pack_first_keys(records, &levels[0])];
+    let mut next_node = 1 + levels[0].len() as u32;
+    let mut level_start = vec![1u32];
+    while levels.last().map(|l| l.len()).unwrap_or_default() > 1 {
+        let children_start = *level_start.last().unwrap();
+        let keys = first_keys.last().unwrap();
+        let index_records = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| [key.as_slice(), &(children_start + i as u32).to_be_bytes()].concat())
+            .collect::<Vec<_>>();
+        let nodes = pack_nodes(index_records, node_size);
+        let mut keys_iter = keys.iter();
+        let mut level_keys = Vec::with_capacity(nodes.len());
+        for node in &nodes {
+            level_keys.push(keys_iter.next().unwrap().clone());
+            for _ in 1..node.len() {
+                keys_iter.next();
+            }
+        }
+        level_start.push(next_node);
+        next_node += nodes.len() as u32;
+        levels.push(nodes);
+        first_keys.push(level_keys);
+    }
+
+    let total_nodes = next_node;
+    let map_len = node_size - 256;
+    if total_nodes as usize > map_len * 8 {
+        return Err(Error::HfsCatalogTooLarge);
+    }
+
+    let leaf_count = levels[0].len() as u32;
+    let depth = if records.is_empty() {
+        0
+    } else {
+        levels.len() as u16
+    };
+    let root = if records.is_empty() { 0 } else { next_node - 1 };
+
+    let mut header = Vec::with_capacity(106);
+    header.extend_from_slice(&depth.to_be_bytes());
+    header.extend_from_slice(&root.to_be_bytes());
+    header.extend_from_slice(&(records.len() as u32).to_be_bytes());
+    // first and last leaf nodes
+    let (first_leaf, last_leaf) = if leaf_count == 0 {
+        (0u32, 0)
+    } else {
+        (1, leaf_count)
+    };
+    header.extend_from_slice(&first_leaf.to_be_bytes());
+    header.extend_from_slice(&last_leaf.to_be_bytes());
+    header.extend_from_slice(&(node_size as u16).to_be_bytes());
+    header.extend_from_slice(&max_key_len.to_be_bytes());
+    header.extend_from_slice(&total_nodes.to_be_bytes());
+    // free nodes
+    header.extend_from_slice(&0u32.to_be_bytes());
+    // reserved
+    header.extend_from_slice(&0u16.to_be_bytes());
+    // clump size
+    header.extend_from_slice(&(node_size as u32).to_be_bytes());
+    // B-tree type
+    header.push(0);
+    header.push(key_compare_type);
+    header.extend_from_slice(&attributes.to_be_bytes());
+    header.extend_from_slice(&[0; 64]);
+
+    let mut map = vec![0; map_len];
+    for node in 0..total_nodes as usize {
+        map[node / 8] |= 0x80 >> (node % 8);
+    }
+
+    let mut file = btree_node(
+        node_size,
+        (0, 0),
+        BTREE_HEADER_NODE,
+        0,
+        &[header, vec![0; 128], map],
+    );
+    for (height, (nodes, start)) in levels.iter().zip(&level_start).enumerate() {
+        let kind = if height == 0 {
+            BTREE_LEAF_NODE
+        } else {
+            BTREE_INDEX_NODE
+        };
+        let count = nodes.len() as u32;
+        for (i, node_records) in nodes.iter().enumerate() {
+            let i = i as u32;
+            let next = if i + 1 < count { start + i + 1 } else { 0 };
+            let previous = if i > 0 { start + i - 1 } else { 0 };
+            file.extend(btree_node(
+                node_size,
+                (next, previous),
+                kind,
+                height as u8 + 1,
+                node_records,
+            ));
+        }
+    }
+
+    Ok(file)
+}
+
+/// Returns the key of the first record of each node.
+fn pack_first_keys(records: &[(Vec<u8>, Vec<u8>)], nodes: &[Vec<Vec<u8>>]) -> Vec<Vec<u8>> {
+    let mut index = 0;
+    nodes
+        .iter()
+        .map(|node| {
+            let key = records[index].0.clone();
+            index += node.len();
+            key
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_multi_level_btrees() {
+        let records = (0..2000u32)
+            .map(|i| {
+                let name = format!("file-{i:04}").encode_utf16().collect::<Vec<_>>();
+                (catalog_key(ROOT_FOLDER_ID, &name), vec![0; 100])
+            })
+            .collect::<Vec<_>>();
+        let tree = build_btree(&records, 4096, 516, BTREE_BIG_KEYS, BINARY_COMPARE).unwrap();
+        assert_eq!(tree.len() % 4096, 0);
+
+        let header = &tree[BTREE_NODE_DESCRIPTOR_SIZE..];
+        let depth = u16::from_be_bytes([header[0], header[1]]);
+        let root = u32::from_be_bytes(header[2..6].try_into().unwrap()) as usize;
+        let leaf_records = u32::from_be_bytes(header[6..10].try_into().unwrap());
+        assert_eq!(depth, 2);
+        assert_eq!(leaf_records, 2000);
+
+        // the root is an index node pointing at every leaf in order
+        let root_node = &tree[root * 4096..(root + 1) * 4096];
+        assert_eq!(root_node[8], BTREE_INDEX_NODE);
+        assert_eq!(root_node[9], 2);
+        let count = u16::from_be_bytes([root_node[10], root_node[11]]) as usize;
+        assert_eq!(count, root - 1);
+    }
+}
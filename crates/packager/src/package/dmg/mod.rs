@@ -3,9 +3,12 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-License-Identifier: MIT
 
+#[cfg(target_os = "macos")]
 use std::{fs, os::unix::fs::PermissionsExt, path::PathBuf, process::Command};
 
+#[cfg(target_os = "macos")]
 use super::Context;
+#[cfg(target_os = "macos")]
 use crate::{
     codesign::macos as codesign,
     shell::CommandExt,
@@ -13,9 +16,23 @@ use crate::{
     Error,
 };
 
+#[cfg(not(target_os = "macos"))]
+mod ds_store;
+#[cfg(not(target_os = "macos"))]
+mod hfs;
+#[cfg(not(target_os = "macos"))]
+mod native;
+#[cfg(not(target_os = "macos"))]
+mod udif;
+
+#[cfg(not(target_os = "macos"))]
+pub(crate) use native::package;
+
+#[cfg(target_os = "macos")]
 const CREATE_DMG_URL: &str =
     "https://raw.githubusercontent.com/create-dmg/create-dmg/28867ba3563ddef62f55dcf130677103b4296c42/create-dmg";
 
+#[cfg(target_os = "macos")]
 #[tracing::instrument(level = "trace", skip(ctx))]
 pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
     let Context {
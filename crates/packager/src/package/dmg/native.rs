@@ -0,0 +1,241 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! DMG images built without `hdiutil`, for non-macOS hosts.
+//!
+//! The `.app` bundle, an `/Applications` symlink, the background image and the volume icon
+//! are laid out in an HFS+ volume together with a `.DS_Store` describing the window,
+//! which is then compressed into a UDIF image.
+
+use std::{fs, path::PathBuf};
+
+use base64::Engine;
+
+use super::{
+    ds_store::{self, Alias, Record, Value},
+    hfs, udif,
+};
+use crate::{package::Context, util, Error};
+
+const BACKGROUND_FOLDER: &str = ".background";
+
+/// The Finder window settings, a binary property list.
+fn window_settings(x: u32, y: u32, width: u32, height: u32) -> crate::Result<Vec<u8>> {
+    let mut settings = plist::Dictionary::new();
+    settings.insert(
+        "WindowBounds".into(),
+        format!("{{{{{x}, {y}}}, {{{width}, {height}}}}}").into(),
+    );
+    for key in [
+        "ContainerShowSidebar",
+        "PreviewPaneVisibility",
+        "ShowPathbar",
+        "ShowSidebar",
+        "ShowStatusBar",
+        "ShowTabView",
+        "ShowToolbar",
+    ] {
+        settings.insert(key.into(), false.into());
+    }
+    settings.insert("SidebarWidth".into(), 0.into());
+
+    let mut data = Vec::new();
+    plist::Value::Dictionary(settings).to_writer_binary(&mut data)?;
+    Ok(data)
+}
+
+/// The icon view options, a binary property list.
+fn icon_view_options(background_alias: Option<Vec<u8>>) -> crate::Result<Vec<u8>> {
+    let mut options = plist::Dictionary::new();
+    options.insert("viewOptionsVersion".into(), 1.into());
+    options.insert("arrangeBy".into(), "none".into());
+    options.insert("iconSize".into(), 128.0.into());
+    options.insert("textSize".into(), 16.0.into());
+    options.insert("gridSpacing".into(), 100.0.into());
+    options.insert("gridOffsetX".into(), 0.0.into());
+    options.insert("gridOffsetY".into(), 0.0.into());
+    options.insert("labelOnBottom".into(), true.into());
+    options.insert("showIconPreview".into(), true.into());
+    options.insert("showItemInfo".into(), false.into());
+    options.insert("backgroundColorRed".into(), 1.0.into());
+    options.insert("backgroundColorGreen".into(), 1.0.into());
+    options.insert("backgroundColorBlue".into(), 1.0.into());
+    match background_alias {
+        Some(alias) => {
+            options.insert("backgroundType".into(), 2.into());
+            options.insert("backgroundImageAlias".into(), plist::Value::Data(alias));
+        }
+        None => {
+            options.insert("backgroundType".into(), 0.into());
+        }
+    }
+
+    let mut data = Vec::new();
+    plist::Value::Dictionary(options).to_writer_binary(&mut data)?;
+    Ok(data)
+}
+
+/// The license agreement resources shown when the image is opened,
+/// from the same template used by `create-dmg`.
+fn license_resources(license: &[u8], rtf: bool) -> crate::Result<plist::Dictionary> {
+    let template = include_str!("eula-resources-template.xml")
+        .replace("${EULA_FORMAT}", if rtf { "RTF " } else { "TEXT" })
+        .replace(
+            "${EULA_DATA}",
+            &base64::engine::general_purpose::STANDARD.encode(license),
+        );
+    let resources = plist::Value::from_reader_xml(template.as_bytes())?;
+    Ok(resources.into_dictionary().unwrap_or_default())
+}
+
+#[tracing::instrument(level = "trace", skip(ctx))]
+pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
+    let Context {
+        config,
+        intermediates_path,
+        ..
+    } = ctx;
+
+    let out_dir = config.out_dir();
+    let intermediates_path = intermediates_path.join("dmg");
+    util::create_clean_dir(&intermediates_path)?;
+
+    let package_base_name = format!(
+        "{}_{}_{}",
+        config.product_name,
+        config.version,
+        match config.target_arch()? {
+            "x86_64" => "x64",
+            other => other,
+        }
+    );
+    let app_bundle_file_name = format!("{}.app", config.product_name);
+    let app_bundle_path = out_dir.join(&app_bundle_file_name);
+    let dmg_name = format!("{}.dmg", package_base_name);
+    let dmg_path = out_dir.join(&dmg_name);
+
+    tracing::info!("Packaging {} ({})", dmg_name, dmg_path.display());
+
+    if dmg_path.exists() {
+        fs::remove_file(&dmg_path).map_err(|e| Error::IoWithPath(dmg_path.clone(), e))?;
+    }
+
+    let dmg = config.dmg();
+
+    let app_position = dmg.and_then(|d| d.app_position);
+    let app_folder_position = dmg.and_then(|d| d.app_folder_position);
+    let window_position = dmg.and_then(|d| d.window_position);
+    let window_size = dmg.and_then(|d| d.window_size);
+
+    let mut volume = hfs::Volume::new(&config.product_name)?;
+
+    tracing::debug!("Copying {}", app_bundle_file_name);
+    volume.add_dir_all(&app_bundle_path, &app_bundle_file_name)?;
+    volume.set_finder_flags(&app_bundle_file_name, hfs::FINDER_FLAG_EXTENSION_HIDDEN);
+    volume.add_symlink("Applications", "/Applications")?;
+
+    tracing::debug!("Creating icns file");
+    if let Some(icon) = util::create_icns_file(&intermediates_path, config)? {
+        volume.add_file(&icon, ".VolumeIcon.icns")?;
+        volume.set_finder_flags("", hfs::FINDER_FLAG_HAS_CUSTOM_ICON);
+    }
+
+    let background_alias = match dmg.and_then(|d| d.background.as_ref()) {
+        Some(background) => {
+            let background_path = std::env::current_dir()?.join(background);
+            let file_name = format!(
+                "background.{}",
+                background_path
+                    .extension()
+                    .map(|e| e.to_string_lossy().to_lowercase())
+                    .unwrap_or_else(|| "png".into())
+            );
+            let file_id = volume.add_file(
+                &background_path,
+                &format!("{BACKGROUND_FOLDER}/{file_name}"),
+            )?;
+            let alias = Alias {
+                volume_name: &config.product_name,
+                volume_created: volume.created(),
+                folder_name: BACKGROUND_FOLDER,
+                folder_id: volume.node_id(BACKGROUND_FOLDER).unwrap_or_default(),
+                file_name: &file_name,
+                file_id,
+            };
+            Some(alias.to_bytes())
+        }
+        None => None,
+    };
+
+    tracing::debug!("Writing .DS_Store");
+    let (window_x, window_y) = window_position.map(|p| (p.x, p.y)).unwrap_or((10, 60));
+    let (window_width, window_height) = window_size
+        .map(|s| (s.width, s.height))
+        .unwrap_or((600, 400));
+    let ds_store = ds_store::write(vec![
+        Record::new(
+            ".",
+            b"bwsp",
+            Value::Blob(window_settings(
+                window_x,
+                window_y,
+                window_width,
+                window_height,
+            )?),
+        ),
+        Record::new(
+            ".",
+            b"icvp",
+            Value::Blob(icon_view_options(background_alias)?),
+        ),
+        Record::new(".", b"vSrn", Value::Long(1)),
+        Record::new(
+            &app_bundle_file_name,
+            b"Iloc",
+            app_position
+                .map(|p| ds_store::icon_location(p.x, p.y))
+                .unwrap_or_else(|| ds_store::icon_location(180, 170)),
+        ),
+        Record::new(
+            "Applications",
+            b"Iloc",
+            app_folder_position
+                .map(|p| ds_store::icon_location(p.x, p.y))
+                .unwrap_or_else(|| ds_store::icon_location(480, 170)),
+        ),
+    ]);
+    volume.add_data(".DS_Store", ds_store)?;
+
+    let volume_path = intermediates_path.join(format!("{package_base_name}.hfs"));
+    tracing::debug!("Writing HFS+ volume {}", volume_path.display());
+    volume.write(&volume_path)?;
+
+    let resources = match config.license_file.as_ref() {
+        Some(license) => {
+            let license_path = std::env::current_dir()?.join(license);
+            let license =
+                fs::read(&license_path).map_err(|e| Error::IoWithPath(license_path.clone(), e))?;
+            let rtf = license_path
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("rtf"));
+            license_resources(&license, rtf)?
+        }
+        None => plist::Dictionary::new(),
+    };
+
+    tracing::debug!("Compressing {}", dmg_path.display());
+    udif::write(&volume_path, &dmg_path, resources)?;
+
+    if config
+        .macos()
+        .and_then(|macos| macos.signing_identity.as_ref())
+        .is_some()
+    {
+        tracing::warn!(
+            "Codesigning DMG images is only supported on macOS hosts, skipping signing the DMG..."
+        );
+    }
+
+    Ok(vec![dmg_path])
+}
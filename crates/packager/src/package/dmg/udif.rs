@@ -0,0 +1,211 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Writer for zlib compressed UDIF disk images (`UDZO`), the format produced by `hdiutil`.
+//!
+//! The image is made of the compressed chunks of the raw volume, followed by an XML property
+//! list whose `blkx` resource maps the chunks back to sectors, and a `koly` trailer.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Write},
+    path::Path,
+};
+
+use flate2::{write::ZlibEncoder, Compression};
+
+use crate::Error;
+
+const SECTOR_SIZE: u64 = 512;
+/// The number of sectors compressed together, 1 MiB like `hdiutil`.
+const CHUNK_SECTORS: u64 = 2048;
+
+const CHUNK_RAW: u32 = 0x0000_0001;
+const CHUNK_IGNORE: u32 = 0x0000_0002;
+const CHUNK_ZLIB: u32 = 0x8000_0005;
+const CHUNK_TERMINATOR: u32 = 0xffff_ffff;
+
+const CHECKSUM_CRC32: u32 = 2;
+
+/// `UDIFChecksum` with a single CRC32 value.
+fn checksum(crc: u32) -> [u8; 136] {
+    let mut checksum = [0; 136];
+    checksum[0..4].copy_from_slice(&CHECKSUM_CRC32.to_be_bytes());
+    checksum[4..8].copy_from_slice(&32u32.to_be_bytes());
+    checksum[8..12].copy_from_slice(&crc.to_be_bytes());
+    checksum
+}
+
+struct Chunk {
+    kind: u32,
+    sector: u64,
+    sectors: u64,
+    offset: u64,
+    len: u64,
+}
+
+/// Compresses the raw volume image at `volume` into the disk image at `path`.
+///
+/// `resources` are added to the `resource-fork` of the image next to the `blkx` table,
+/// e.g. the license agreement resources.
+pub(super) fn write(volume: &Path, path: &Path, resources: plist::Dictionary) -> crate::Result<()> {
+    let mut input = File::open(volume).map_err(|e| Error::IoWithPath(volume.to_path_buf(), e))?;
+    let output = File::create(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+    let mut output = BufWriter::new(output);
+
+    let mut chunks = Vec::new();
+    let mut volume_crc = crc32fast::Hasher::new();
+    let mut data_crc = crc32fast::Hasher::new();
+    let mut offset = 0;
+    let mut sector = 0;
+    let mut buffer = vec![0; (CHUNK_SECTORS * SECTOR_SIZE) as usize];
+    loop {
+        let len = read_full(&mut input, &mut buffer)
+            .map_err(|e| Error::IoWithPath(volume.to_path_buf(), e))?;
+        if len == 0 {
+            break;
+        }
+        // the volume is always a multiple of the sector size
+        let data = &buffer[..len];
+        let sectors = len as u64 / SECTOR_SIZE;
+        volume_crc.update(data);
+
+        let (kind, compressed) = if data.iter().all(|b| *b == 0) {
+            (CHUNK_IGNORE, Vec::new())
+        } else {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(data)?;
+            let compressed = encoder.finish()?;
+            if compressed.len() < data.len() {
+                (CHUNK_ZLIB, compressed)
+            } else {
+                (CHUNK_RAW, data.to_vec())
+            }
+        };
+        output
+            .write_all(&compressed)
+            .map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+        data_crc.update(&compressed);
+
+        chunks.push(Chunk {
+            kind,
+            sector,
+            sectors,
+            offset,
+            len: compressed.len() as u64,
+        });
+        sector += sectors;
+        offset += compressed.len() as u64;
+    }
+    chunks.push(Chunk {
+        kind: CHUNK_TERMINATOR,
+        sector,
+        sectors: 0,
+        offset,
+        len: 0,
+    });
+    let sector_count = sector;
+    let volume_crc = volume_crc.finalize();
+
+    // BLKXTable
+    let mut blkx = Vec::with_capacity(204 + chunks.len() * 40);
+    blkx.extend_from_slice(b"mish");
+    blkx.extend_from_slice(&1u32.to_be_bytes());
+    // first sector, sector count and data offset
+    blkx.extend_from_slice(&0u64.to_be_bytes());
+    blkx.extend_from_slice(&sector_count.to_be_bytes());
+    blkx.extend_from_slice(&0u64.to_be_bytes());
+    // buffers needed, a bit more than the chunk size
+    blkx.extend_from_slice(&((CHUNK_SECTORS + 8) as u32).to_be_bytes());
+    // block descriptors, the partition index
+    blkx.extend_from_slice(&0u32.to_be_bytes());
+    blkx.extend_from_slice(&[0; 24]);
+    blkx.extend_from_slice(&checksum(volume_crc));
+    blkx.extend_from_slice(&(chunks.len() as u32).to_be_bytes());
+    for chunk in &chunks {
+        blkx.extend_from_slice(&chunk.kind.to_be_bytes());
+        // comment
+        blkx.extend_from_slice(&0u32.to_be_bytes());
+        blkx.extend_from_slice(&chunk.sector.to_be_bytes());
+        blkx.extend_from_slice(&chunk.sectors.to_be_bytes());
+        blkx.extend_from_slice(&chunk.offset.to_be_bytes());
+        blkx.extend_from_slice(&chunk.len.to_be_bytes());
+    }
+
+    let name = "whole disk (Apple_HFSX : 0)";
+    let mut partition = plist::Dictionary::new();
+    partition.insert("Attributes".into(), "0x0050".into());
+    partition.insert("CFName".into(), name.into());
+    partition.insert("Data".into(), plist::Value::Data(blkx));
+    partition.insert("ID".into(), "-1".into());
+    partition.insert("Name".into(), name.into());
+
+    let mut resource_fork = resources;
+    resource_fork.insert(
+        "blkx".into(),
+        plist::Value::Array(vec![plist::Value::Dictionary(partition)]),
+    );
+    let mut properties = plist::Dictionary::new();
+    properties.insert(
+        "resource-fork".into(),
+        plist::Value::Dictionary(resource_fork),
+    );
+    let mut xml = Vec::new();
+    plist::Value::Dictionary(properties).to_writer_xml(&mut xml)?;
+
+    let xml_offset = offset;
+    output
+        .write_all(&xml)
+        .map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+
+    // the master checksum covers the checksums of every blkx table
+    let master_crc = crc32fast::hash(&volume_crc.to_be_bytes());
+
+    // UDIFResourceFile
+    let mut koly = Vec::with_capacity(512);
+    koly.extend_from_slice(b"koly");
+    // version, header size and flags (flattened)
+    koly.extend_from_slice(&4u32.to_be_bytes());
+    koly.extend_from_slice(&512u32.to_be_bytes());
+    koly.extend_from_slice(&1u32.to_be_bytes());
+    // running data fork offset, data fork offset and length
+    koly.extend_from_slice(&0u64.to_be_bytes());
+    koly.extend_from_slice(&0u64.to_be_bytes());
+    koly.extend_from_slice(&xml_offset.to_be_bytes());
+    // resource fork offset and length
+    koly.extend_from_slice(&[0; 16]);
+    // segment number, count and ID
+    koly.extend_from_slice(&1u32.to_be_bytes());
+    koly.extend_from_slice(&1u32.to_be_bytes());
+    koly.extend_from_slice(uuid::Uuid::new_v4().as_bytes());
+    koly.extend_from_slice(&checksum(data_crc.finalize()));
+    koly.extend_from_slice(&xml_offset.to_be_bytes());
+    koly.extend_from_slice(&(xml.len() as u64).to_be_bytes());
+    koly.extend_from_slice(&[0; 120]);
+    koly.extend_from_slice(&checksum(master_crc));
+    // image variant, a device image
+    koly.extend_from_slice(&1u32.to_be_bytes());
+    koly.extend_from_slice(&sector_count.to_be_bytes());
+    koly.extend_from_slice(&[0; 12]);
+    debug_assert_eq!(koly.len(), 512);
+
+    output
+        .write_all(&koly)
+        .and_then(|_| output.flush())
+        .map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+
+    Ok(())
+}
+
+/// Reads until the buffer is full or the end of the file is reached.
+fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut len = 0;
+    while len < buffer.len() {
+        match reader.read(&mut buffer[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    Ok(len)
+}
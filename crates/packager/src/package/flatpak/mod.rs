@@ -0,0 +1,213 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use handlebars::Handlebars;
+use relative_path::PathExt;
+use serde::Serialize;
+
+use super::{deb, Context};
+use crate::{config::Config, shell::CommandExt, util, Error};
+
+/// The default arguments passed to `flatpak build-finish`,
+/// giving access to the display servers and GPU.
+const DEFAULT_FINISH_ARGS: &[&str] = &[
+    "--share=ipc",
+    "--socket=fallback-x11",
+    "--socket=wayland",
+    "--device=dri",
+];
+
+/// A flatpak-builder manifest.
+///
+/// See <https://docs.flatpak.org/en/latest/flatpak-builder-command-reference.html#flatpak-manifest>
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Manifest<'a> {
+    id: &'a str,
+    runtime: &'a str,
+    runtime_version: &'a str,
+    sdk: &'a str,
+    command: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rename_desktop_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rename_icon: Option<&'a str>,
+    finish_args: Vec<&'a str>,
+    modules: Vec<Module<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Module<'a> {
+    name: &'a str,
+    buildsystem: &'a str,
+    build_commands: Vec<&'a str>,
+    sources: Vec<Source>,
+}
+
+#[derive(Serialize)]
+struct Source {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    path: PathBuf,
+}
+
+#[derive(Serialize)]
+struct MimeType<'a> {
+    mime_type: &'a str,
+    comment: Option<&'a str>,
+    extensions: &'a [String],
+}
+
+/// Generates the shared-mime-info definitions of the [`Config::file_associations`]
+/// that have a mime type, so the file types are registered when the app is installed.
+#[tracing::instrument(level = "trace", skip(config))]
+fn generate_mime_types(config: &Config, app_id: &str, app_dir: &Path) -> crate::Result<()> {
+    let mime_types = config
+        .file_associations
+        .iter()
+        .flatten()
+        .filter_map(|association| {
+            association.mime_type.as_deref().map(|mime_type| MimeType {
+                mime_type,
+                comment: association
+                    .description
+                    .as_deref()
+                    .or(association.name.as_deref()),
+                extensions: &association.extensions,
+            })
+        })
+        .collect::<Vec<_>>();
+    if mime_types.is_empty() {
+        return Ok(());
+    }
+
+    let mut data = serde_json::Map::new();
+    data.insert("mime_types".into(), serde_json::to_value(mime_types)?);
+    let mime_xml = Handlebars::new().render_template(include_str!("mime.xml"), &data)?;
+
+    let mime_dir = app_dir.join("share/mime/packages");
+    fs::create_dir_all(&mime_dir).map_err(|e| Error::IoWithPath(mime_dir.clone(), e))?;
+    let mime_path = mime_dir.join(format!("{app_id}.xml"));
+    fs::write(&mime_path, mime_xml).map_err(|e| Error::IoWithPath(mime_path, e))?;
+
+    Ok(())
+}
+
+#[tracing::instrument(level = "trace", skip(ctx))]
+pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
+    let Context {
+        config,
+        intermediates_path,
+        ..
+    } = ctx;
+
+    let flatpak = config.flatpak();
+    let app_id = flatpak
+        .and_then(|f| f.app_id.as_deref())
+        .unwrap_or_else(|| config.identifier());
+    let branch = flatpak
+        .and_then(|f| f.branch.as_deref())
+        .unwrap_or("master");
+    let arch = match config.target_arch()? {
+        "x86" => "i386",
+        other => other,
+    };
+
+    let main_binary_name = config.main_binary_name()?;
+    let bundle_name = format!("{}_{}_{}.flatpak", main_binary_name, config.version, arch);
+    let bundle_path = config.out_dir().join(&bundle_name);
+    let manifest_path = config.out_dir().join(format!("{app_id}.json"));
+
+    tracing::info!("Packaging {} ({})", bundle_name, bundle_path.display());
+
+    let intermediates_path = intermediates_path.join("flatpak");
+    util::create_clean_dir(&intermediates_path)?;
+
+    // generate the deb data and use its `usr` directory as the `/app` directory
+    tracing::debug!("Generating data");
+    let data_dir = intermediates_path.join("data");
+    let icons = deb::generate_data(config, &data_dir)?;
+    let app_dir = data_dir.join("usr");
+    tracing::debug!("Copying files specified in `flatpak.files`");
+    if let Some(files) = flatpak.and_then(|f| f.files.as_ref()) {
+        deb::copy_custom_files(files, &app_dir)?;
+    }
+    generate_mime_types(config, app_id, &app_dir)?;
+
+    tracing::debug!("Generating {}", manifest_path.display());
+    let generate_desktop_entry = config
+        .linux()
+        .is_none_or(|linux| linux.generate_desktop_entry);
+    let manifest = Manifest {
+        id: app_id,
+        runtime: flatpak
+            .and_then(|f| f.runtime.as_deref())
+            .unwrap_or("org.freedesktop.Platform"),
+        runtime_version: flatpak
+            .and_then(|f| f.runtime_version.as_deref())
+            .unwrap_or("24.08"),
+        sdk: flatpak
+            .and_then(|f| f.sdk.as_deref())
+            .unwrap_or("org.freedesktop.Sdk"),
+        command: &main_binary_name,
+        // exported files must be named after the app id
+        rename_desktop_file: generate_desktop_entry.then(|| format!("{main_binary_name}.desktop")),
+        rename_icon: (!icons.is_empty()).then_some(main_binary_name.as_str()),
+        finish_args: flatpak
+            .and_then(|f| f.finish_args.as_ref())
+            .map(|args| args.iter().map(String::as_str).collect())
+            .unwrap_or_else(|| DEFAULT_FINISH_ARGS.to_vec()),
+        modules: vec![Module {
+            name: &main_binary_name,
+            buildsystem: "simple",
+            build_commands: vec!["cp -a . /app/"],
+            sources: vec![Source {
+                kind: "dir",
+                // relative to the manifest, so it doesn't depend on where it was generated
+                path: dunce::canonicalize(&app_dir)
+                    .map_err(|e| Error::IoWithPath(app_dir.clone(), e))?
+                    .relative_to(config.out_dir())?
+                    .to_path(""),
+            }],
+        }],
+    };
+    let manifest_file = util::create_file(&manifest_path)?;
+    serde_json::to_writer_pretty(manifest_file, &manifest)?;
+
+    tracing::debug!("Running flatpak-builder");
+    let repo_path = intermediates_path.join("repo");
+    Command::new("flatpak-builder")
+        .arg("--force-clean")
+        .arg("--disable-rofiles-fuse")
+        .arg(format!("--arch={arch}"))
+        .arg(format!("--default-branch={branch}"))
+        .arg("--state-dir")
+        .arg(intermediates_path.join(".flatpak-builder"))
+        .arg("--repo")
+        .arg(&repo_path)
+        .arg(intermediates_path.join("build"))
+        .arg(&manifest_path)
+        .output_ok()
+        .map_err(|e| Error::FlatpakFailed("flatpak-builder".into(), e))?;
+
+    tracing::debug!("Running flatpak build-bundle");
+    Command::new("flatpak")
+        .arg("build-bundle")
+        .arg(format!("--arch={arch}"))
+        .arg(&repo_path)
+        .arg(&bundle_path)
+        .arg(app_id)
+        .arg(branch)
+        .output_ok()
+        .map_err(|e| Error::FlatpakFailed("flatpak build-bundle".into(), e))?;
+
+    Ok(vec![bundle_path, manifest_path])
+}
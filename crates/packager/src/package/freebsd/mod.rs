@@ -0,0 +1,504 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! FreeBSD packages, in the format produced by `pkg create`.
+//!
+//! A package is a compressed tarball starting with the `+COMPACT_MANIFEST` and `+MANIFEST` files,
+//! followed by the package contents stored with their absolute paths.
+//! See <https://man.freebsd.org/cgi/man.cgi?query=pkg-create>
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, Read, Write},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use heck::AsKebabCase;
+use liblzma::write::XzEncoder;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tar::HeaderMode;
+use walkdir::WalkDir;
+
+use super::{deb, metadata, Context};
+use crate::{
+    config::{Config, FreeBsdCompression},
+    util, Error,
+};
+
+/// The installation prefix of the package.
+const PREFIX: &str = "/usr/local";
+
+/// The FreeBSD major version used in the default ABI when not packaging on FreeBSD.
+const DEFAULT_OS_VERSION: &str = "14";
+
+/// The package manifest, written as JSON which is a subset of the UCL format read by pkg.
+/// For more information about the format of this file, see
+/// <https://man.freebsd.org/cgi/man.cgi?query=pkg-create#MANIFEST_FILE_DETAILS>
+#[derive(Serialize)]
+struct Manifest<'a> {
+    name: &'a str,
+    origin: &'a str,
+    version: &'a str,
+    comment: String,
+    maintainer: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    www: Option<&'a str>,
+    abi: &'a str,
+    prefix: &'a str,
+    flatsize: u64,
+    licenselogic: &'a str,
+    licenses: &'a [String],
+    desc: &'a str,
+    categories: &'a [String],
+    deps: BTreeMap<&'a str, Dependency<'a>>,
+    /// The package files and directories, only in the `+MANIFEST`.
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    contents: Option<Contents>,
+}
+
+#[derive(Serialize)]
+struct Dependency<'a> {
+    origin: &'a str,
+    version: &'a str,
+}
+
+#[derive(Serialize)]
+struct Contents {
+    /// Maps the absolute path of each file to its checksum.
+    files: BTreeMap<String, String>,
+    /// The directories owned by the package, removed on uninstall if empty.
+    directories: BTreeMap<String, &'static str>,
+    scripts: BTreeMap<&'static str, String>,
+}
+
+/// Converts a semver version to a pkg version, which can't contain `-`
+/// since it separates the version from the package name.
+///
+/// Pre-release identifiers are appended with a `.`, so `1.0.0-beta.1` becomes `1.0.0.beta1`,
+/// which pkg orders before `1.0.0`, and the build metadata is dropped.
+fn pkg_version(version: &str) -> String {
+    let Ok(version) = semver::Version::parse(version) else {
+        return version.replace('-', ".");
+    };
+
+    let mut pkg_version = format!("{}.{}.{}", version.major, version.minor, version.patch);
+    if !version.pre.is_empty() {
+        pkg_version.push('.');
+        pkg_version.push_str(&version.pre.replace(['.', '-'], ""));
+    }
+    pkg_version
+}
+
+/// Returns the default ABI of the package, using the FreeBSD version of the host when packaging on FreeBSD.
+fn default_abi(arch: &str) -> String {
+    let os_version = cfg!(target_os = "freebsd")
+        .then(|| Command::new("uname").arg("-r").output().ok())
+        .flatten()
+        .and_then(|output| {
+            let release = String::from_utf8_lossy(&output.stdout);
+            release
+                .split(['.', '-'])
+                .next()
+                .filter(|major| !major.trim().is_empty())
+                .map(|major| major.trim().to_string())
+        })
+        .unwrap_or_else(|| DEFAULT_OS_VERSION.to_string());
+    format!("FreeBSD:{os_version}:{arch}")
+}
+
+/// Returns the pkg checksum of `data`, a SHA-256 hex digest prefixed with its hash type.
+fn checksum(mut data: impl Read) -> crate::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut data, &mut hasher)?;
+    Ok(format!("1${:x}", hasher.finalize()))
+}
+
+#[tracing::instrument(level = "trace", skip(ctx))]
+pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
+    let Context {
+        config,
+        intermediates_path,
+        ..
+    } = ctx;
+
+    let freebsd = config.freebsd();
+    let arch = match config.target_arch()? {
+        "x86_64" => "amd64",
+        "x86" => "i386",
+        "arm" => "armv7",
+        other => other,
+    };
+    let name = freebsd
+        .and_then(|f| f.package_name.clone())
+        .unwrap_or_else(|| AsKebabCase(&config.product_name).to_string());
+    let version = pkg_version(&config.version);
+
+    let pkg_name = format!("{name}-{version}.pkg");
+    let pkg_path = config.out_dir().join(&pkg_name);
+
+    tracing::info!("Packaging {} ({})", pkg_name, pkg_path.display());
+
+    let intermediates_path = intermediates_path.join("freebsd");
+    util::create_clean_dir(&intermediates_path)?;
+
+    // generate the deb data and move its `usr` directory to the prefix
+    tracing::debug!("Generating data");
+    let data_dir = intermediates_path.join("data");
+    let _ = deb::generate_data(config, &data_dir)?;
+    let root_dir = intermediates_path.join(&name);
+    let prefix_dir = root_dir.join(PREFIX.trim_start_matches('/'));
+    let prefix_parent = prefix_dir
+        .parent()
+        .ok_or_else(|| Error::ParentDirNotFound(prefix_dir.clone()))?;
+    fs::create_dir_all(prefix_parent)
+        .map_err(|e| Error::IoWithPath(prefix_parent.to_path_buf(), e))?;
+    let usr_dir = data_dir.join("usr");
+    fs::rename(&usr_dir, &prefix_dir).map_err(|e| Error::IoWithPath(usr_dir, e))?;
+
+    tracing::debug!("Copying files specified in `freebsd.files`");
+    if let Some(files) = freebsd.and_then(|f| f.files.as_ref()) {
+        deb::copy_custom_files(files, &root_dir)?;
+    }
+
+    tracing::debug!("Generating manifest");
+    let owned_dir = Path::new(PREFIX)
+        .join("lib")
+        .join(config.main_binary_name()?);
+    let (compact_manifest, manifest) =
+        generate_manifests(config, &name, &version, arch, &root_dir, &owned_dir)?;
+
+    tracing::debug!("Creating package archive");
+    let dest_file = util::create_file(&pkg_path)?;
+    match freebsd.and_then(|f| f.compression).unwrap_or_default() {
+        FreeBsdCompression::Zstd => {
+            let encoder = zstd::Encoder::new(dest_file, 0)?;
+            let mut dest_file = create_package_archive(
+                encoder,
+                &compact_manifest,
+                &manifest,
+                &root_dir,
+                &owned_dir,
+            )?
+            .finish()?;
+            dest_file.flush()?;
+        }
+        FreeBsdCompression::Xz => {
+            let encoder = XzEncoder::new(dest_file, 6);
+            let mut dest_file = create_package_archive(
+                encoder,
+                &compact_manifest,
+                &manifest,
+                &root_dir,
+                &owned_dir,
+            )?
+            .finish()?;
+            dest_file.flush()?;
+        }
+    }
+
+    Ok(vec![pkg_path])
+}
+
+/// Generates the `+COMPACT_MANIFEST` and `+MANIFEST` of the package installing the contents
+/// of `root_dir`, which owns the directories under `owned_dir`.
+fn generate_manifests(
+    config: &Config,
+    name: &str,
+    version: &str,
+    arch: &str,
+    root_dir: &Path,
+    owned_dir: &Path,
+) -> crate::Result<(Vec<u8>, Vec<u8>)> {
+    let freebsd = config.freebsd();
+
+    let mut files = BTreeMap::new();
+    let mut directories = BTreeMap::new();
+    for entry in WalkDir::new(root_dir).sort_by_file_name() {
+        let entry = entry?;
+        let path = entry.path();
+        let install_path = Path::new("/").join(path.strip_prefix(root_dir)?);
+        let install_path_str = install_path.to_string_lossy().into_owned();
+        if entry.file_type().is_dir() {
+            if install_path.starts_with(owned_dir) {
+                directories.insert(install_path_str, "y");
+            }
+        } else if entry.file_type().is_symlink() {
+            let target =
+                fs::read_link(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+            let checksum = checksum(target.as_os_str().as_encoded_bytes())?;
+            files.insert(install_path_str, checksum);
+        } else {
+            let file = File::open(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+            files.insert(install_path_str, checksum(file)?);
+        }
+    }
+
+    let mut scripts = BTreeMap::new();
+    if let Some(freebsd) = freebsd {
+        for (script_name, script) in [
+            ("pre-install", &freebsd.pre_install),
+            ("post-install", &freebsd.post_install),
+            ("pre-deinstall", &freebsd.pre_deinstall),
+            ("post-deinstall", &freebsd.post_deinstall),
+        ] {
+            if let Some(script) = script {
+                let contents =
+                    metadata::render_maintainer_script(config, name, script_name, script)?;
+                scripts.insert(script_name, contents);
+            }
+        }
+    }
+
+    let categories = freebsd
+        .and_then(|f| f.categories.clone())
+        .filter(|c| !c.is_empty())
+        .unwrap_or_else(|| vec!["misc".into()]);
+    let origin = freebsd
+        .and_then(|f| f.origin.clone())
+        .unwrap_or_else(|| format!("{}/{name}", categories[0]));
+    let abi = freebsd
+        .and_then(|f| f.abi.clone())
+        .unwrap_or_else(|| default_abi(arch));
+    let licenses = freebsd
+        .and_then(|f| f.licenses.as_deref())
+        .unwrap_or_default();
+    let description = config
+        .long_description
+        .as_deref()
+        .or(config.description.as_deref())
+        .unwrap_or(config.product_name.as_str());
+
+    let mut manifest = Manifest {
+        name,
+        origin: &origin,
+        version,
+        comment: metadata::single_line(
+            config
+                .description
+                .as_deref()
+                .unwrap_or(config.product_name.as_str()),
+        ),
+        maintainer: metadata::packager(config),
+        www: config.homepage.as_deref(),
+        abi: &abi,
+        prefix: PREFIX,
+        flatsize: deb::get_size(root_dir)?,
+        licenselogic: if licenses.len() > 1 { "and" } else { "single" },
+        licenses,
+        desc: description,
+        categories: &categories,
+        deps: freebsd
+            .and_then(|f| f.depends.as_ref())
+            .into_iter()
+            .flatten()
+            .map(|d| {
+                (
+                    d.name.as_str(),
+                    Dependency {
+                        origin: &d.origin,
+                        version: &d.version,
+                    },
+                )
+            })
+            .collect(),
+        contents: None,
+    };
+    let compact_manifest = serde_json::to_vec(&manifest)?;
+    manifest.contents = Some(Contents {
+        files,
+        directories,
+        scripts,
+    });
+    let manifest = serde_json::to_vec(&manifest)?;
+
+    Ok((compact_manifest, manifest))
+}
+
+/// Appends an entry stored at the absolute `path`, set in a PAX header
+/// as [`tar::Builder`] only accepts relative paths.
+fn append_entry<W: Write, R: Read>(
+    tar_builder: &mut tar::Builder<W>,
+    header: &mut tar::Header,
+    path: &str,
+    link: Option<&str>,
+    data: R,
+) -> crate::Result<()> {
+    let mut extensions = vec![("path", path.as_bytes())];
+    if let Some(link) = link {
+        extensions.push(("linkpath", link.as_bytes()));
+    }
+    tar_builder.append_pax_extensions(extensions)?;
+
+    // readers without PAX support get the relative path, truncated to fit the header
+    let relative = path.trim_start_matches('/').as_bytes();
+    let name = &mut header.as_old_mut().name;
+    let len = relative.len().min(name.len());
+    name[..len].copy_from_slice(&relative[..len]);
+    header.set_cksum();
+    tar_builder.append(header, data)?;
+    Ok(())
+}
+
+/// Creates the package archive with the manifests first, followed by the contents of `root_dir`.
+/// Only the directories owned by the package, in `owned_dir`, are stored.
+fn create_package_archive<W: Write>(
+    writer: W,
+    compact_manifest: &[u8],
+    manifest: &[u8],
+    root_dir: &Path,
+    owned_dir: &Path,
+) -> crate::Result<W> {
+    let mut tar_builder = tar::Builder::new(writer);
+
+    for (name, contents) in [
+        ("+COMPACT_MANIFEST", compact_manifest),
+        ("+MANIFEST", manifest),
+    ] {
+        let mut header = tar::Header::new_ustar();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_entry_type(tar::EntryType::file());
+        header.set_username("root")?;
+        header.set_groupname("wheel")?;
+        tar_builder.append_data(&mut header, name, contents)?;
+    }
+
+    for entry in WalkDir::new(root_dir).sort_by_file_name() {
+        let entry = entry?;
+        let src_path = entry.path();
+        let install_path = Path::new("/").join(src_path.strip_prefix(root_dir)?);
+        let stat = fs::symlink_metadata(src_path)
+            .map_err(|e| Error::IoWithPath(src_path.to_path_buf(), e))?;
+        let mut header = tar::Header::new_ustar();
+        header.set_metadata_in_mode(&stat, HeaderMode::Deterministic);
+        header.set_mtime(stat.mtime() as u64);
+        header.set_username("root")?;
+        header.set_groupname("wheel")?;
+        let path = install_path.to_string_lossy();
+        if stat.is_dir() {
+            if install_path.starts_with(owned_dir) {
+                append_entry(&mut tar_builder, &mut header, &path, None, io::empty())?;
+            }
+        } else if stat.is_symlink() {
+            let target = fs::read_link(src_path)
+                .map_err(|e| Error::IoWithPath(src_path.to_path_buf(), e))?;
+            append_entry(
+                &mut tar_builder,
+                &mut header,
+                &path,
+                Some(&target.to_string_lossy()),
+                io::empty(),
+            )?;
+        } else {
+            let src_file =
+                File::open(src_path).map_err(|e| Error::IoWithPath(src_path.to_path_buf(), e))?;
+            append_entry(&mut tar_builder, &mut header, &path, None, src_file)?;
+        }
+    }
+
+    tar_builder.into_inner().map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::config::{Binary, FreeBsdConfig, FreeBsdDependency, MaintainerScript};
+
+    #[test]
+    fn converts_semver_to_pkg_version() {
+        assert_eq!(pkg_version("1.2.3"), "1.2.3");
+        assert_eq!(pkg_version("1.2.3-beta.1"), "1.2.3.beta1");
+        assert_eq!(pkg_version("1.2.3-rc1"), "1.2.3.rc1");
+        assert_eq!(pkg_version("1.2.3+build.5"), "1.2.3");
+    }
+
+    #[test]
+    fn generates_manifests() {
+        let mut config = Config::default();
+        config.product_name = "Test App".into();
+        config.description = Some("A test\napplication".into());
+        config.homepage = Some("https://example.com".into());
+        config.authors = Some(vec!["Alice".into()]);
+        config.binaries = vec![Binary::new("test-app").main(true)];
+        config.freebsd = Some(
+            FreeBsdConfig::new()
+                .categories(["devel"])
+                .licenses(["MIT", "APACHE20"])
+                .depends([FreeBsdDependency::new(
+                    "gtk3",
+                    "x11-toolkits/gtk30",
+                    "3.24.43",
+                )])
+                .post_install(MaintainerScript::inline_template("echo {{package_name}}")),
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let bin_dir = dir.path().join("usr/local/bin");
+        let lib_dir = dir.path().join("usr/local/lib/test-app/data");
+        fs::create_dir_all(&bin_dir).unwrap();
+        fs::create_dir_all(&lib_dir).unwrap();
+        fs::write(bin_dir.join("test-app"), "binary").unwrap();
+        std::os::unix::fs::symlink("test-app", bin_dir.join("link")).unwrap();
+
+        let (compact_manifest, manifest) = generate_manifests(
+            &config,
+            "test-app",
+            "1.0.0",
+            "amd64",
+            dir.path(),
+            Path::new("/usr/local/lib/test-app"),
+        )
+        .unwrap();
+
+        let sha256 = |data: &str| format!("1${:x}", Sha256::digest(data));
+        let mut expected = json!({
+            "name": "test-app",
+            "origin": "devel/test-app",
+            "version": "1.0.0",
+            "comment": "A test application",
+            "maintainer": "Alice",
+            "www": "https://example.com",
+            "abi": default_abi("amd64"),
+            "prefix": "/usr/local",
+            // `deb::get_size` follows the symlink, so the binary is counted twice
+            "flatsize": 12,
+            "licenselogic": "and",
+            "licenses": ["MIT", "APACHE20"],
+            "desc": "A test\napplication",
+            "categories": ["devel"],
+            "deps": {
+                "gtk3": { "origin": "x11-toolkits/gtk30", "version": "3.24.43" }
+            },
+        });
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&compact_manifest).unwrap(),
+            expected
+        );
+
+        let contents = json!({
+            "files": {
+                "/usr/local/bin/link": sha256("test-app"),
+                "/usr/local/bin/test-app": sha256("binary"),
+            },
+            "directories": {
+                "/usr/local/lib/test-app": "y",
+                "/usr/local/lib/test-app/data": "y",
+            },
+            "scripts": { "post-install": "echo test-app" },
+        });
+        expected
+            .as_object_mut()
+            .unwrap()
+            .extend(contents.as_object().unwrap().clone());
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&manifest).unwrap(),
+            expected
+        );
+    }
+}
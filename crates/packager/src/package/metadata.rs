@@ -0,0 +1,66 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Package metadata shared by the Linux and BSD package formats.
+
+use handlebars::Handlebars;
+use serde::Serialize;
+
+use crate::config::{Config, MaintainerScript};
+
+/// Returns the packager string used in the package metadata.
+pub(crate) fn packager(config: &Config) -> String {
+    config
+        .authors
+        .as_ref()
+        .map(|authors| authors.join(", "))
+        .unwrap_or_else(|| "Unknown Packager".into())
+}
+
+/// Collapses `value` into a single line so it can be used as a metadata value.
+pub(crate) fn single_line(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Returns the contents of a maintainer script of the package named `package_name`,
+/// rendering it as a Handlebars template if requested.
+pub(crate) fn render_maintainer_script(
+    config: &Config,
+    package_name: &str,
+    name: &str,
+    script: &MaintainerScript,
+) -> crate::Result<String> {
+    #[derive(Serialize)]
+    struct MaintainerScriptParams<'a> {
+        package_name: &'a str,
+        product_name: &'a str,
+        version: &'a str,
+        identifier: &'a str,
+        main_binary_name: &'a str,
+        publisher: &'a str,
+    }
+
+    let (contents, template) = script.contents()?;
+    if !template {
+        return Ok(contents);
+    }
+
+    let main_binary_name = config.main_binary_name()?;
+    let publisher = config.publisher();
+    let params = MaintainerScriptParams {
+        package_name,
+        product_name: &config.product_name,
+        version: &config.version,
+        identifier: config.identifier(),
+        main_binary_name: &main_binary_name,
+        publisher: &publisher,
+    };
+
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(handlebars::no_escape);
+    handlebars
+        .register_template_string(name, contents)
+        .map_err(Box::new)?;
+    handlebars.render(name, &params).map_err(Into::into)
+}
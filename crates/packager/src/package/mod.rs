@@ -8,6 +8,14 @@ use crate::{config, shell::CommandExt, util, Config, PackageFormat};
 
 use self::context::Context;
 
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod apk;
 mod app;
 #[cfg(any(
     target_os = "linux",
@@ -17,6 +25,7 @@ mod app;
     target_os = "openbsd"
 ))]
 mod appimage;
+pub(crate) mod archive;
 #[cfg(any(
     target_os = "linux",
     target_os = "dragonfly",
@@ -25,8 +34,32 @@ mod appimage;
     target_os = "openbsd"
 ))]
 mod deb;
-#[cfg(target_os = "macos")]
 mod dmg;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod flatpak;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod freebsd;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod metadata;
+mod msix;
 mod nsis;
 #[cfg(any(
     target_os = "linux",
@@ -36,7 +69,32 @@ mod nsis;
     target_os = "openbsd"
 ))]
 mod pacman;
-#[cfg(windows)]
+mod pkg;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod rpm;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod snap;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod squashfs;
+pub(crate) mod universal;
 mod wix;
 
 mod context;
@@ -104,7 +162,6 @@ pub fn package(config: &Config) -> crate::Result<Vec<PackageOutput>> {
 
         let paths = match format {
             PackageFormat::App => app::package(&ctx),
-            #[cfg(target_os = "macos")]
             PackageFormat::Dmg => {
                 // PackageFormat::App is required for the DMG bundle
                 if !packages
@@ -119,9 +176,23 @@ pub fn package(config: &Config) -> crate::Result<Vec<PackageOutput>> {
                 }
                 dmg::package(&ctx)
             }
-            #[cfg(target_os = "windows")]
+            PackageFormat::Pkg => {
+                // PackageFormat::App is required for the pkg installer
+                if !packages
+                    .iter()
+                    .any(|b: &PackageOutput| b.format == PackageFormat::App)
+                {
+                    let paths = app::package(&ctx)?;
+                    packages.push(PackageOutput {
+                        format: PackageFormat::App,
+                        paths,
+                    });
+                }
+                pkg::package(&ctx)
+            }
             PackageFormat::Wix => wix::package(&ctx),
             PackageFormat::Nsis => nsis::package(&ctx),
+            PackageFormat::Msix => msix::package(&ctx),
             #[cfg(any(
                 target_os = "linux",
                 target_os = "dragonfly",
@@ -146,6 +217,47 @@ pub fn package(config: &Config) -> crate::Result<Vec<PackageOutput>> {
                 target_os = "openbsd"
             ))]
             PackageFormat::Pacman => pacman::package(&ctx),
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            PackageFormat::Rpm => rpm::package(&ctx),
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            PackageFormat::Flatpak => flatpak::package(&ctx),
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            PackageFormat::Snap => snap::package(&ctx),
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            PackageFormat::Apk => apk::package(&ctx),
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            PackageFormat::FreeBsd => freebsd::package(&ctx),
+            PackageFormat::Archive => archive::package(&ctx),
 
             _ => {
                 tracing::warn!("ignoring {}", format.short_name());
@@ -159,28 +271,23 @@ pub fn package(config: &Config) -> crate::Result<Vec<PackageOutput>> {
         });
     }
 
-    #[cfg(target_os = "macos")]
-    {
-        // Clean up .app if only building dmg
-        if !formats.contains(&PackageFormat::App) {
-            if let Some(app_bundle_paths) = packages
-                .iter()
-                .position(|b| b.format == PackageFormat::App)
-                .map(|i| packages.remove(i))
-                .map(|b| b.paths)
-            {
-                for p in &app_bundle_paths {
-                    use crate::Error;
-                    use std::fs;
+    // Clean up .app if only building dmg or pkg
+    if !formats.contains(&PackageFormat::App) {
+        if let Some(app_bundle_paths) = packages
+            .iter()
+            .position(|b| b.format == PackageFormat::App)
+            .map(|i| packages.remove(i))
+            .map(|b| b.paths)
+        {
+            for p in &app_bundle_paths {
+                use crate::Error;
+                use std::fs;
 
-                    tracing::debug!("Cleaning {}", p.display());
-                    match p.is_dir() {
-                        true => {
-                            fs::remove_dir_all(p).map_err(|e| Error::IoWithPath(p.clone(), e))?
-                        }
-                        false => fs::remove_file(p).map_err(|e| Error::IoWithPath(p.clone(), e))?,
-                    };
-                }
+                tracing::debug!("Cleaning {}", p.display());
+                match p.is_dir() {
+                    true => fs::remove_dir_all(p).map_err(|e| Error::IoWithPath(p.clone(), e))?,
+                    false => fs::remove_file(p).map_err(|e| Error::IoWithPath(p.clone(), e))?,
+                };
             }
         }
     }
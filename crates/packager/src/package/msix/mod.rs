@@ -0,0 +1,372 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! MSIX packages, built without the Windows SDK.
+//!
+//! The payload is laid out in a directory next to a generated `AppxManifest.xml`
+//! and then zipped together with the block map and the content types,
+//! see [`writer::PackageWriter`].
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use handlebars::{to_json, Handlebars};
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use super::Context;
+use crate::{
+    codesign::windows::{self as codesign},
+    config::Config,
+    util, Error,
+};
+
+mod writer;
+
+/// The logos referenced by the manifest and their size in pixels.
+const LOGOS: &[(&str, u32)] = &[
+    ("StoreLogo.png", 50),
+    ("Square44x44Logo.png", 44),
+    ("Square150x150Logo.png", 150),
+];
+
+const DEFAULT_MIN_VERSION: &str = "10.0.17763.0";
+const DEFAULT_MAX_VERSION_TESTED: &str = "10.0.22621.0";
+
+#[derive(Serialize)]
+struct FileAssociationData {
+    name: String,
+    display_name: Option<String>,
+    content_type: Option<String>,
+    extensions: Vec<String>,
+}
+
+/// Converts a semver version into the `Major.Minor.Build.Revision` form required by MSIX.
+fn msix_version(version: &str) -> crate::Result<String> {
+    let version = semver::Version::parse(version)?;
+    for (name, part) in [
+        ("major", version.major),
+        ("minor", version.minor),
+        ("patch", version.patch),
+    ] {
+        if part > 65535 {
+            return Err(Error::InvalidAppVersion(format!(
+                "{name} number cannot be greater than 65535"
+            )));
+        }
+    }
+
+    let revision = if version.build.is_empty() {
+        0
+    } else {
+        match version.build.parse::<u64>() {
+            Ok(build) if build <= 65535 => build,
+            _ => {
+                return Err(Error::NonNumericBuildMetadata(Some(
+                    "and cannot be greater than 65535 for msix target".into(),
+                )))
+            }
+        }
+    };
+
+    Ok(format!(
+        "{}.{}.{}.{}",
+        version.major, version.minor, version.patch, revision
+    ))
+}
+
+/// Replaces the characters that are not allowed in a package identity name with `-`.
+fn identity_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// File type association names must be lowercase and only contain letters, digits, `.`, `_` and `-`.
+fn association_name(name: &str) -> String {
+    name.trim_start_matches('.')
+        .to_ascii_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+fn file_associations_data(config: &Config) -> Vec<FileAssociationData> {
+    config
+        .file_associations
+        .iter()
+        .flatten()
+        .filter(|association| !association.extensions.is_empty())
+        .map(|association| FileAssociationData {
+            name: association_name(
+                association
+                    .name
+                    .as_deref()
+                    .unwrap_or(&association.extensions[0]),
+            ),
+            display_name: association.description.clone(),
+            content_type: association.mime_type.clone(),
+            extensions: association
+                .extensions
+                .iter()
+                .map(|ext| format!(".{}", ext.trim_start_matches('.').to_ascii_lowercase()))
+                .collect(),
+        })
+        .collect()
+}
+
+/// Generates the logos referenced by the manifest from the largest PNG in [`Config::icons`].
+fn generate_logos(config: &Config, assets_dir: &Path) -> crate::Result<()> {
+    let mut largest: Option<(PathBuf, u32)> = None;
+    for icon_path in config.icons()?.into_iter().flatten() {
+        if icon_path.extension().and_then(|e| e.to_str()) != Some("png") {
+            continue;
+        }
+        let (width, height) = image::image_dimensions(&icon_path)?;
+        let size = width.min(height);
+        if largest.as_ref().is_none_or(|(_, s)| size > *s) {
+            largest = Some((icon_path, size));
+        }
+    }
+
+    let (icon_path, _) = largest.ok_or(Error::MsixIconNotFound)?;
+    tracing::debug!("Generating logos from {}", icon_path.display());
+    let icon = image::open(&icon_path)?;
+
+    fs::create_dir_all(assets_dir).map_err(|e| Error::IoWithPath(assets_dir.to_path_buf(), e))?;
+    for (name, size) in LOGOS {
+        icon.resize_exact(*size, *size, image::imageops::FilterType::Lanczos3)
+            .save(assets_dir.join(name))?;
+    }
+
+    Ok(())
+}
+
+#[tracing::instrument(level = "trace", skip(ctx))]
+pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
+    let Context {
+        config,
+        intermediates_path,
+        ..
+    } = ctx;
+
+    let arch = match config.target_arch()? {
+        "x86_64" => "x64",
+        "x86" => "x86",
+        "aarch64" => "arm64",
+        "arm" => "arm",
+        target => return Err(Error::UnsupportedArch("msix".into(), target.into())),
+    };
+
+    let main_binary = config.main_binary()?;
+    let main_binary_name = config.main_binary_name()?;
+    let main_binary_path = config.binary_executable_path(main_binary);
+
+    if config.can_sign() {
+        tracing::debug!("Codesigning {}", main_binary_path.display());
+        codesign::try_sign(&main_binary_path, config)?;
+    } else {
+        #[cfg(not(target_os = "windows"))]
+        codesign::warn_unsupported_host("the main binary");
+    }
+
+    let version = msix_version(&config.version)?;
+
+    let msix_name = format!("{}_{}_{}.msix", main_binary_name, config.version, arch);
+    let msix_path = config.out_dir().join(&msix_name);
+
+    tracing::info!(
+        "Packaging {} ({})",
+        msix_name,
+        util::display_path(&msix_path)
+    );
+
+    let intermediates_path = intermediates_path.join("msix").join(arch);
+    util::create_clean_dir(&intermediates_path)?;
+    let data_dir = intermediates_path.join("data");
+
+    tracing::debug!("Copying binaries");
+    fs::create_dir_all(&data_dir).map_err(|e| Error::IoWithPath(data_dir.clone(), e))?;
+    for bin in &config.binaries {
+        let bin_path = config.binary_executable_path(bin);
+        let file_name = bin_path
+            .file_name()
+            .ok_or_else(|| Error::FailedToExtractFilename(bin_path.clone()))?;
+        let dest = data_dir.join(file_name);
+        fs::copy(&bin_path, &dest).map_err(|e| Error::CopyFile(bin_path.clone(), dest, e))?;
+    }
+
+    tracing::debug!("Copying external binaries");
+    config.copy_external_binaries(&data_dir)?;
+
+    tracing::debug!("Copying resources");
+    config.copy_resources(&data_dir)?;
+
+    generate_logos(config, &data_dir.join("Assets"))?;
+
+    let msix = config.msix();
+    let publisher = config.publisher();
+
+    let mut data = BTreeMap::new();
+    data.insert(
+        "identity_name",
+        to_json(
+            msix.and_then(|m| m.identity_name.clone())
+                .unwrap_or_else(|| identity_name(config.identifier())),
+        ),
+    );
+    data.insert(
+        "publisher",
+        to_json(
+            msix.and_then(|m| m.publisher.clone())
+                .unwrap_or_else(|| format!("CN={publisher}")),
+        ),
+    );
+    data.insert(
+        "publisher_display_name",
+        to_json(
+            msix.and_then(|m| m.publisher_display_name.clone())
+                .unwrap_or(publisher),
+        ),
+    );
+    data.insert("version", to_json(version));
+    data.insert("arch", to_json(arch));
+    data.insert("display_name", to_json(&config.product_name));
+    data.insert(
+        "description",
+        to_json(
+            config
+                .description
+                .as_deref()
+                .unwrap_or(&config.product_name),
+        ),
+    );
+    data.insert("executable", to_json(format!("{main_binary_name}.exe")));
+    data.insert(
+        "min_version",
+        to_json(
+            msix.and_then(|m| m.min_version.as_deref())
+                .unwrap_or(DEFAULT_MIN_VERSION),
+        ),
+    );
+    data.insert(
+        "max_version_tested",
+        to_json(
+            msix.and_then(|m| m.max_version_tested.as_deref())
+                .unwrap_or(DEFAULT_MAX_VERSION_TESTED),
+        ),
+    );
+    data.insert(
+        "languages",
+        to_json(
+            msix.and_then(|m| m.languages.clone())
+                .unwrap_or_else(|| vec!["en-us".into()]),
+        ),
+    );
+    data.insert(
+        "background_color",
+        to_json(
+            msix.and_then(|m| m.background_color.as_deref())
+                .unwrap_or("transparent"),
+        ),
+    );
+    data.insert(
+        "capabilities",
+        to_json(
+            msix.and_then(|m| m.capabilities.as_ref())
+                .into_iter()
+                .flatten()
+                .filter(|c| *c != "runFullTrust")
+                .collect::<Vec<_>>(),
+        ),
+    );
+
+    let file_associations = file_associations_data(config);
+    let protocols = config
+        .deep_link_protocols
+        .iter()
+        .flatten()
+        .flat_map(|p| &p.schemes)
+        .map(|s| s.to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    data.insert(
+        "has_extensions",
+        to_json(!file_associations.is_empty() || !protocols.is_empty()),
+    );
+    data.insert("file_associations", to_json(file_associations));
+    data.insert("protocols", to_json(protocols));
+
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(writer::xml_escape);
+    handlebars
+        .register_template_string("AppxManifest.xml", include_str!("./AppxManifest.xml"))
+        .map_err(Box::new)?;
+    let manifest = handlebars.render("AppxManifest.xml", &data)?;
+
+    let manifest_path = intermediates_path.join("AppxManifest.xml");
+    fs::write(&manifest_path, &manifest).map_err(|e| Error::IoWithPath(manifest_path, e))?;
+
+    tracing::debug!("Writing {}", msix_path.display());
+    let file = File::create(&msix_path).map_err(|e| Error::IoWithPath(msix_path.clone(), e))?;
+    let mut package = writer::PackageWriter::new(BufWriter::new(file));
+    let mut entries = WalkDir::new(&data_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.retain(|entry| entry.file_type().is_file());
+    for entry in entries {
+        let path = entry.path();
+        let name = path
+            .strip_prefix(&data_dir)?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let contents = fs::read(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+        package.add_file(&name, &contents)?;
+    }
+    package.add_file("AppxManifest.xml", manifest.as_bytes())?;
+    package.finish()?.into_inner().map_err(|e| e.into_error())?;
+
+    if config.builtin_sign() {
+        tracing::warn!("MSIX packages can't be signed with `config.windows.signing_certificate`, but you can specify a custom signing command in `config.windows.sign_command`, for now, skipping signing the package...");
+    } else if config.can_sign() {
+        tracing::debug!("Codesigning {}", msix_path.display());
+        codesign::try_sign(&msix_path, config)?;
+    } else {
+        #[cfg(not(target_os = "windows"))]
+        codesign::warn_unsupported_host("the package");
+    }
+
+    Ok(vec![msix_path])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_msix_versions() {
+        assert_eq!(msix_version("1.2.3").unwrap(), "1.2.3.0");
+        assert_eq!(msix_version("1.2.3+42").unwrap(), "1.2.3.42");
+        assert!(msix_version("1.2.3+abc").is_err());
+        assert!(msix_version("70000.0.0").is_err());
+    }
+}
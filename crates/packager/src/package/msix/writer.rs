@@ -0,0 +1,376 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Writes the MSIX package, a ZIP archive containing the payload files followed by the
+//! `AppxBlockMap.xml` listing the hashes of each 64 KiB block of the files
+//! and the `[Content_Types].xml` describing the content type of each file.
+//!
+//! The ZIP archive is written directly since the block map references the size of
+//! the local file headers and of each compressed block, which the deflate stream of a file
+//! must be flushed at so the blocks can be decompressed independently.
+//!
+//! See <https://learn.microsoft.com/en-us/uwp/schemas/blockmapschema/app-package-block-map>
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use flate2::{Compress, Compression, Crc, FlushCompress, Status};
+use sha2::{Digest, Sha256};
+
+/// The size of the blocks hashed in the block map.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x04034b50;
+const CENTRAL_DIRECTORY_HEADER_SIGNATURE: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+/// ZIP 2.0, which supports deflate.
+const ZIP_VERSION: u16 = 20;
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+/// The MS-DOS date of the entries, 1980-01-01, so packages are reproducible.
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// Returns the content type of a file with the given lowercase extension.
+fn content_type(extension: &str) -> &'static str {
+    match extension {
+        "exe" | "dll" => "application/x-msdownload",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "ico" => "image/vnd.microsoft.icon",
+        "svg" => "image/svg+xml",
+        "xml" => "text/xml",
+        "txt" => "text/plain",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" => "application/javascript",
+        "json" => "application/json",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Percent-encodes a ZIP item name to the part name used in the package,
+/// keeping the characters allowed in the part name segments and the `/` separators.
+fn part_name(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@/".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+/// Escapes the XML special characters of an attribute value.
+pub(super) fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Compresses `data` as a raw deflate stream, flushed at the end of each block
+/// so the blocks can be decompressed independently.
+/// Returns the compressed data and the compressed size of each block.
+fn deflate_blocks(data: &[u8]) -> io::Result<(Vec<u8>, Vec<usize>)> {
+    let mut compress = Compress::new(Compression::default(), false);
+    let mut output = Vec::with_capacity(data.len() / 2);
+    let mut block_sizes = Vec::new();
+
+    let block_count = data.len().div_ceil(BLOCK_SIZE);
+    for (i, block) in data.chunks(BLOCK_SIZE).enumerate() {
+        let flush = if i + 1 == block_count {
+            FlushCompress::Finish
+        } else {
+            FlushCompress::Full
+        };
+        let start = output.len();
+        let mut input = block;
+        loop {
+            output.reserve(input.len() + 1024);
+            let total_in = compress.total_in();
+            let status = compress.compress_vec(input, &mut output, flush)?;
+            input = &input[(compress.total_in() - total_in) as usize..];
+            // the flush is complete once the output buffer is not filled
+            let flushed = input.is_empty() && output.len() < output.capacity();
+            if status == Status::StreamEnd || (flush == FlushCompress::Full && flushed) {
+                break;
+            }
+        }
+        block_sizes.push(output.len() - start);
+    }
+
+    Ok((output, block_sizes))
+}
+
+/// Converts a ZIP field to the 32 bits it is stored in, as ZIP64 is not supported.
+fn zip32(value: usize) -> io::Result<u32> {
+    u32::try_from(value)
+        .map_err(|_| io::Error::other("MSIX packages larger than 4 GiB are not supported"))
+}
+
+/// A file in the block map.
+struct BlockMapFile {
+    name: String,
+    size: usize,
+    local_file_header_size: usize,
+    /// The base64 SHA-256 hash of each block and its compressed size.
+    blocks: Vec<(String, usize)>,
+}
+
+/// An entry of the ZIP central directory.
+struct CentralDirectoryEntry {
+    name: String,
+    method: u16,
+    crc: u32,
+    compressed_size: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// Writes an MSIX package to `W`.
+pub struct PackageWriter<W: Write> {
+    writer: W,
+    offset: usize,
+    entries: Vec<CentralDirectoryEntry>,
+    block_map: Vec<BlockMapFile>,
+    /// Maps the extensions of the files to their content type.
+    defaults: BTreeMap<String, &'static str>,
+    /// Maps the part names of the files that have no extension to their content type.
+    overrides: BTreeMap<String, &'static str>,
+}
+
+impl<W: Write> PackageWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            offset: 0,
+            entries: Vec::new(),
+            block_map: Vec::new(),
+            defaults: BTreeMap::new(),
+            overrides: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a file to the package, `name` being its path using `/` separators.
+    pub fn add_file(&mut self, name: &str, data: &[u8]) -> io::Result<()> {
+        let part_name = part_name(name);
+        let extension = name
+            .rsplit_once('/')
+            .map_or(name, |(_, file_name)| file_name)
+            .rsplit_once('.')
+            .map(|(_, extension)| extension.to_lowercase());
+        match extension {
+            Some(extension) => {
+                let content_type = content_type(&extension);
+                self.defaults.entry(extension).or_insert(content_type);
+            }
+            None => {
+                self.overrides
+                    .insert(format!("/{part_name}"), "application/octet-stream");
+            }
+        }
+
+        let local_file_header_size = 30 + part_name.len();
+        let blocks = if data.is_empty() {
+            self.write_entry(&part_name, METHOD_STORED, data, data)?;
+            Vec::new()
+        } else {
+            let (compressed, block_sizes) = deflate_blocks(data)?;
+            self.write_entry(&part_name, METHOD_DEFLATED, data, &compressed)?;
+            data.chunks(BLOCK_SIZE)
+                .zip(block_sizes)
+                .map(|(block, size)| (STANDARD.encode(Sha256::digest(block)), size))
+                .collect()
+        };
+
+        self.block_map.push(BlockMapFile {
+            name: name.replace('/', "\\"),
+            size: data.len(),
+            local_file_header_size,
+            blocks,
+        });
+
+        Ok(())
+    }
+
+    /// Writes the block map, the content types and the ZIP central directory, returning the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut block_map = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\r\n\
+            <BlockMap xmlns=\"http://schemas.microsoft.com/appx/2010/blockmap\" \
+            HashMethod=\"http://www.w3.org/2001/04/xmlenc#sha256\">",
+        );
+        for file in &self.block_map {
+            block_map.push_str(&format!(
+                "<File Name=\"{}\" Size=\"{}\" LfhSize=\"{}\">",
+                xml_escape(&file.name),
+                file.size,
+                file.local_file_header_size
+            ));
+            for (hash, size) in &file.blocks {
+                block_map.push_str(&format!("<Block Hash=\"{hash}\" Size=\"{size}\"/>"));
+            }
+            block_map.push_str("</File>");
+        }
+        block_map.push_str("</BlockMap>");
+        let (compressed, _) = deflate_blocks(block_map.as_bytes())?;
+        self.write_entry(
+            "AppxBlockMap.xml",
+            METHOD_DEFLATED,
+            block_map.as_bytes(),
+            &compressed,
+        )?;
+
+        let mut content_types = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\r\n\
+            <Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">",
+        );
+        for (extension, content_type) in &self.defaults {
+            content_types.push_str(&format!(
+                "<Default Extension=\"{}\" ContentType=\"{content_type}\"/>",
+                xml_escape(extension)
+            ));
+        }
+        self.overrides.insert(
+            "/AppxManifest.xml".into(),
+            "application/vnd.ms-appx.manifest+xml",
+        );
+        self.overrides.insert(
+            "/AppxBlockMap.xml".into(),
+            "application/vnd.ms-appx.blockmap+xml",
+        );
+        for (part_name, content_type) in &self.overrides {
+            content_types.push_str(&format!(
+                "<Override PartName=\"{}\" ContentType=\"{content_type}\"/>",
+                xml_escape(part_name)
+            ));
+        }
+        content_types.push_str("</Types>");
+        let (compressed, _) = deflate_blocks(content_types.as_bytes())?;
+        self.write_entry(
+            "[Content_Types].xml",
+            METHOD_DEFLATED,
+            content_types.as_bytes(),
+            &compressed,
+        )?;
+
+        let central_directory_offset = zip32(self.offset)?;
+        let mut central_directory = Vec::new();
+        for entry in &self.entries {
+            central_directory.extend_from_slice(&CENTRAL_DIRECTORY_HEADER_SIGNATURE.to_le_bytes());
+            central_directory.extend_from_slice(&ZIP_VERSION.to_le_bytes()); // version made by
+            central_directory.extend_from_slice(&ZIP_VERSION.to_le_bytes()); // version needed
+            central_directory.extend_from_slice(&0u16.to_le_bytes()); // flags
+            central_directory.extend_from_slice(&entry.method.to_le_bytes());
+            central_directory.extend_from_slice(&0u16.to_le_bytes()); // time
+            central_directory.extend_from_slice(&DOS_DATE.to_le_bytes());
+            central_directory.extend_from_slice(&entry.crc.to_le_bytes());
+            central_directory.extend_from_slice(&entry.compressed_size.to_le_bytes());
+            central_directory.extend_from_slice(&entry.size.to_le_bytes());
+            central_directory.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            central_directory.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+            central_directory.extend_from_slice(&0u16.to_le_bytes()); // comment length
+            central_directory.extend_from_slice(&0u16.to_le_bytes()); // disk number
+            central_directory.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+            central_directory.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+            central_directory.extend_from_slice(&entry.offset.to_le_bytes());
+            central_directory.extend_from_slice(entry.name.as_bytes());
+        }
+        self.writer.write_all(&central_directory)?;
+
+        let entry_count = u16::try_from(self.entries.len()).map_err(|_| {
+            io::Error::other("MSIX packages with more than 65535 files are not supported")
+        })?;
+        let mut end = Vec::new();
+        end.extend_from_slice(&END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // disk number
+        end.extend_from_slice(&0u16.to_le_bytes()); // disk with the central directory
+        end.extend_from_slice(&entry_count.to_le_bytes());
+        end.extend_from_slice(&entry_count.to_le_bytes());
+        end.extend_from_slice(&zip32(central_directory.len())?.to_le_bytes());
+        end.extend_from_slice(&central_directory_offset.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        self.writer.write_all(&end)?;
+
+        Ok(self.writer)
+    }
+
+    /// Writes the local file header and the data of an entry.
+    fn write_entry(
+        &mut self,
+        name: &str,
+        method: u16,
+        data: &[u8],
+        compressed: &[u8],
+    ) -> io::Result<()> {
+        let mut crc = Crc::new();
+        crc.update(data);
+        let entry = CentralDirectoryEntry {
+            name: name.to_string(),
+            method,
+            crc: crc.sum(),
+            compressed_size: zip32(compressed.len())?,
+            size: zip32(data.len())?,
+            offset: zip32(self.offset)?,
+        };
+
+        let mut header = Vec::with_capacity(30 + name.len());
+        header.extend_from_slice(&LOCAL_FILE_HEADER_SIGNATURE.to_le_bytes());
+        header.extend_from_slice(&ZIP_VERSION.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes()); // flags
+        header.extend_from_slice(&method.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes()); // time
+        header.extend_from_slice(&DOS_DATE.to_le_bytes());
+        header.extend_from_slice(&entry.crc.to_le_bytes());
+        header.extend_from_slice(&entry.compressed_size.to_le_bytes());
+        header.extend_from_slice(&entry.size.to_le_bytes());
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+        header.extend_from_slice(name.as_bytes());
+
+        self.writer.write_all(&header)?;
+        self.writer.write_all(compressed)?;
+        self.offset += header.len() + compressed.len();
+        self.entries.push(entry);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn encodes_part_names() {
+        assert_eq!(part_name("Assets/Logo.png"), "Assets/Logo.png");
+        assert_eq!(part_name("my file [1].txt"), "my%20file%20%5B1%5D.txt");
+        assert_eq!(part_name("café"), "caf%C3%A9");
+    }
+
+    #[test]
+    fn deflates_independent_blocks() {
+        let data = (0..BLOCK_SIZE * 2 + 100)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        let (compressed, block_sizes) = deflate_blocks(&data).unwrap();
+        assert_eq!(block_sizes.len(), 3);
+        assert_eq!(block_sizes.iter().sum::<usize>(), compressed.len());
+
+        let mut decompressed = Vec::new();
+        flate2::read::DeflateDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, data);
+    }
+}
@@ -139,6 +139,27 @@ fn generate_binaries_data(config: &Config) -> crate::Result<BinariesMap> {
     Ok(binaries)
 }
 
+/// Signs copies of the binaries, which may be external binaries that are not ours to modify.
+#[tracing::instrument(level = "trace", skip(config))]
+fn sign_binaries(
+    binaries: BinariesMap,
+    intermediates_path: &Path,
+    config: &Config,
+) -> crate::Result<BinariesMap> {
+    let signed_path = intermediates_path.join("binaries");
+    fs::create_dir_all(&signed_path).map_err(|e| Error::IoWithPath(signed_path.clone(), e))?;
+
+    let mut signed = BinariesMap::new();
+    for (path, file_name) in binaries {
+        let signed_binary_path = signed_path.join(&file_name);
+        fs::copy(&path, &signed_binary_path).map_err(|e| Error::IoWithPath(path.clone(), e))?;
+        tracing::debug!("Codesigning {}", signed_binary_path.display());
+        codesign::try_sign(&signed_binary_path, config)?;
+        signed.insert(signed_binary_path, file_name);
+    }
+    Ok(signed)
+}
+
 #[tracing::instrument(level = "trace")]
 fn get_lang_data(
     lang: &str,
@@ -334,6 +355,47 @@ fn get_and_extract_nsis(
     Ok(())
 }
 
+/// Returns the shell command copying `src` to `dest`, run by makensis
+/// through the `!uninstfinalize` command.
+fn copy_command<S: AsRef<Path>, D: AsRef<Path>>(src: S, dest: D) -> String {
+    #[cfg(target_os = "windows")]
+    let copy = "copy /Y";
+    #[cfg(not(target_os = "windows"))]
+    let copy = "cp";
+    format!(
+        "{copy} \"{}\" \"{}\"",
+        src.as_ref().display(),
+        dest.as_ref().display()
+    )
+}
+
+/// Compiles the installer script at `installer_nsi_path` with the `makensis` executable.
+fn makensis(
+    config: &Config,
+    makensis: &Path,
+    installer_nsi_path: &Path,
+    intermediates_path: &Path,
+) -> crate::Result<()> {
+    let mut nsis_cmd = Command::new(makensis);
+
+    if let Some(level) = config.log_level {
+        nsis_cmd.arg(match level {
+            LogLevel::Error => "-V1",
+            LogLevel::Warn | LogLevel::Info => "-V2",
+            LogLevel::Debug => "-V3",
+            _ => "-V4",
+        });
+    }
+
+    nsis_cmd
+        .arg(installer_nsi_path)
+        .current_dir(intermediates_path)
+        .output_ok()
+        .map_err(Error::NsisFailed)?;
+
+    Ok(())
+}
+
 #[tracing::instrument(level = "trace", skip(ctx))]
 fn build_nsis_app_installer(ctx: &Context, nsis_path: &Path) -> crate::Result<Vec<PathBuf>> {
     let Context {
@@ -358,7 +420,7 @@ fn build_nsis_app_installer(ctx: &Context, nsis_path: &Path) -> crate::Result<Ve
         codesign::try_sign(&main_binary_path, config)?;
     } else {
         #[cfg(not(target_os = "windows"))]
-        tracing::warn!("Codesigning is by default is only supported on Windows hosts, but you can specify a custom signing command in `config.windows.sign_command`, for now, skipping signing the main binary...");
+        tracing::warn!("Codesigning is by default is only supported on Windows hosts, but you can specify a certificate in `config.windows.signing_certificate` or a custom signing command in `config.windows.sign_command`, for now, skipping signing the main binary...");
     }
 
     let intermediates_path = intermediates_path.join("nsis").join(arch);
@@ -391,7 +453,7 @@ fn build_nsis_app_installer(ctx: &Context, nsis_path: &Path) -> crate::Result<Ve
         to_json(config.windows().map(|w| w.allow_downgrades)),
     );
 
-    if config.can_sign() {
+    if config.can_sign() && !config.builtin_sign() {
         let sign_cmd = format!("{:?}", codesign::sign_command("%1", &config.sign_params())?);
         data.insert("uninstaller_sign_cmd", to_json(sign_cmd));
     }
@@ -503,7 +565,10 @@ fn build_nsis_app_installer(ctx: &Context, nsis_path: &Path) -> crate::Result<Ve
     data.insert("resources_dirs", to_json(resources_dirs));
     data.insert("resources", to_json(&resources));
 
-    let binaries = generate_binaries_data(config)?;
+    let mut binaries = generate_binaries_data(config)?;
+    if config.can_sign() {
+        binaries = sign_binaries(binaries, &intermediates_path, config)?;
+    }
     data.insert("binaries", to_json(&binaries));
 
     let estimated_size =
@@ -545,18 +610,51 @@ fn build_nsis_app_installer(ctx: &Context, nsis_path: &Path) -> crate::Result<Ve
         include_str!("./FileAssociation.nsh"),
     )?;
 
-    let installer_nsi_path = intermediates_path.join("installer.nsi");
-    write_ut16_le_with_bom(
-        &installer_nsi_path,
-        handlebars.render("installer.nsi", &data)?.as_str(),
-    )?;
-
     for (lang, data) in languages_data.iter() {
         if let Some(content) = data {
             write_ut16_le_with_bom(intermediates_path.join(lang).with_extension("nsh"), content)?;
         }
     }
 
+    let installer_nsi_path = intermediates_path.join("installer.nsi");
+    #[cfg(target_os = "windows")]
+    let makensis_path = nsis_path.join("makensis.exe");
+    #[cfg(not(target_os = "windows"))]
+    let makensis_path = PathBuf::from("makensis");
+
+    // the built-in signer can't be run by makensis, so the uninstaller is copied out
+    // in a first pass, signed, and then copied back over the unsigned one in a second pass
+    if config.builtin_sign() {
+        let uninstaller_path = intermediates_path.join("uninstall.exe");
+        data.insert(
+            "uninstaller_sign_cmd",
+            to_json(copy_command("%1", &uninstaller_path)),
+        );
+        write_ut16_le_with_bom(
+            &installer_nsi_path,
+            handlebars.render("installer.nsi", &data)?.as_str(),
+        )?;
+        tracing::debug!("Running makensis to produce the uninstaller");
+        makensis(
+            config,
+            &makensis_path,
+            &installer_nsi_path,
+            &intermediates_path,
+        )?;
+
+        tracing::debug!("Codesigning {}", uninstaller_path.display());
+        codesign::try_sign(&uninstaller_path, config)?;
+        data.insert(
+            "uninstaller_sign_cmd",
+            to_json(copy_command(&uninstaller_path, "%1")),
+        );
+    }
+
+    write_ut16_le_with_bom(
+        &installer_nsi_path,
+        handlebars.render("installer.nsi", &data)?.as_str(),
+    )?;
+
     let nsis_output_path = intermediates_path.join(out_file);
 
     let installer_path = config.out_dir().join(format!(
@@ -574,25 +672,12 @@ fn build_nsis_app_installer(ctx: &Context, nsis_path: &Path) -> crate::Result<Ve
         "Running makensis.exe to produce {}",
         util::display_path(&installer_path)
     );
-    #[cfg(target_os = "windows")]
-    let mut nsis_cmd = Command::new(nsis_path.join("makensis.exe"));
-    #[cfg(not(target_os = "windows"))]
-    let mut nsis_cmd = Command::new("makensis");
-
-    if let Some(level) = config.log_level {
-        nsis_cmd.arg(match level {
-            LogLevel::Error => "-V1",
-            LogLevel::Warn | LogLevel::Info => "-V2",
-            LogLevel::Debug => "-V3",
-            _ => "-V4",
-        });
-    }
-
-    nsis_cmd
-        .arg(installer_nsi_path)
-        .current_dir(intermediates_path)
-        .output_ok()
-        .map_err(Error::NsisFailed)?;
+    makensis(
+        config,
+        &makensis_path,
+        &installer_nsi_path,
+        &intermediates_path,
+    )?;
 
     fs::rename(&nsis_output_path, &installer_path)
         .map_err(|e| Error::RenameFile(nsis_output_path, installer_path.clone(), e))?;
@@ -602,7 +687,7 @@ fn build_nsis_app_installer(ctx: &Context, nsis_path: &Path) -> crate::Result<Ve
         codesign::try_sign(&installer_path, config)?;
     } else {
         #[cfg(not(target_os = "windows"))]
-        tracing::warn!("Codesigning is by default is only supported on Windows hosts, but you can specify a custom signing command in `config.windows.sign_command`, for now, skipping signing the installer...");
+        tracing::warn!("Codesigning is by default is only supported on Windows hosts, but you can specify a certificate in `config.windows.signing_certificate` or a custom signing command in `config.windows.sign_command`, for now, skipping signing the installer...");
     }
 
     Ok(vec![installer_path])
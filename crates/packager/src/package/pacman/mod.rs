@@ -2,15 +2,25 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-License-Identifier: MIT
 
-use super::deb;
+use super::{deb, metadata};
 use crate::{config::Config, package::Context, util, Error};
+use flate2::{write::GzEncoder, Compression};
+use handlebars::Handlebars;
 use heck::AsKebabCase;
-use sha2::{Digest, Sha512};
+use sha2::{Digest, Sha256, Sha512};
 use std::{
+    collections::BTreeMap,
     fs::{self, File},
-    io::{self, Write},
+    io::{self, Read, Write},
+    os::unix::fs::MetadataExt,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
+use tar::HeaderMode;
+use walkdir::WalkDir;
+
+/// The package release number.
+const PKGREL: &str = "1";
 
 #[tracing::instrument(level = "trace", skip(ctx))]
 pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
@@ -21,20 +31,23 @@ pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
     } = ctx;
 
     let arch = match config.target_arch()? {
-        "x86" => "i386",
-        "arm" => "armhf",
+        "x86" => "i686",
+        "arm" => "armv7h",
         other => other,
     };
 
     let intermediates_path = intermediates_path.join("pacman");
     util::create_clean_dir(&intermediates_path)?;
 
+    let pkgname = AsKebabCase(&config.product_name).to_string();
+    // pacman versions can't contain `-` as it separates the version from the release
+    let pkgver = config.version.replace('-', "_");
+
     let package_base_name = format!("{}_{}_{}", config.main_binary_name()?, config.version, arch);
-    let package_name = format!("{package_base_name}.tar.gz");
+    let package_name = format!("{pkgname}-{pkgver}-{PKGREL}-{arch}.pkg.tar.zst");
 
     let pkg_dir = intermediates_path.join(&package_base_name);
     let pkg_path = config.out_dir().join(&package_name);
-    let pkgbuild_path = pkg_path.with_file_name("PKGBUILD");
 
     tracing::info!("Packaging {} ({})", package_name, pkg_path.display());
 
@@ -46,97 +59,569 @@ pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
         deb::copy_custom_files(files, &pkg_dir)?;
     }
 
-    // Apply tar/gzip to create the final package file.
-    tracing::debug!("Creating package archive using tar and gzip");
-    let data_tar_gz_path = deb::tar_and_gzip_dir(pkg_dir)?;
-    fs::copy(&data_tar_gz_path, &pkg_path)
-        .map_err(|e| Error::CopyFile(data_tar_gz_path, pkg_path.clone(), e))?;
+    let builddate = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_secs();
+
+    tracing::debug!("Generating .PKGINFO");
+    let pkginfo = generate_pkginfo(config, &pkgname, &pkgver, arch, builddate, &pkg_dir)?;
+
+    tracing::debug!("Generating .BUILDINFO");
+    let buildinfo = generate_buildinfo(config, &pkgname, &pkgver, arch, builddate);
+
+    let mut metadata_files = vec![(".PKGINFO", pkginfo), (".BUILDINFO", buildinfo)];
+
+    if let Some(install) = config.pacman().and_then(|p| p.install.as_ref()) {
+        tracing::debug!("Copying install script");
+        let contents = fs::read(install).map_err(|e| Error::IoWithPath(install.clone(), e))?;
+        metadata_files.push((".INSTALL", contents));
+    }
+
+    tracing::debug!("Generating .MTREE");
+    let mtree = generate_mtree(&metadata_files, &pkg_dir, builddate)?;
+    metadata_files.push((".MTREE", mtree));
+
+    tracing::debug!("Creating package archive using tar and zstd");
+    create_package_archive(&metadata_files, &pkg_dir, &pkg_path, builddate)?;
+
+    let mut outputs = vec![pkg_path];
+
+    let aur = config.pacman().and_then(|p| p.aur.as_ref());
+    if config.pacman().is_some_and(|p| p.generate_pkgbuild) || aur.is_some() {
+        let tar_gz_path = config.out_dir().join(format!("{package_base_name}.tar.gz"));
+
+        tracing::debug!("Creating source archive using tar and gzip");
+        let data_tar_gz_path = deb::tar_and_gzip_dir(pkg_dir)?;
+        fs::copy(&data_tar_gz_path, &tar_gz_path)
+            .map_err(|e| Error::CopyFile(data_tar_gz_path, tar_gz_path.clone(), e))?;
+
+        if config.pacman().is_some_and(|p| p.generate_pkgbuild) {
+            let pkgbuild_path = tar_gz_path.with_file_name("PKGBUILD");
+            tracing::info!("Generating PKGBUILD: {}", pkgbuild_path.display());
+            let source = config.pacman().and_then(|p| p.source.clone());
+            let pkgbuild =
+                generate_pkgbuild(config, &pkgname, &pkgver, arch, &tar_gz_path, source)?;
+            write_pkgbuild_files(config, &pkgbuild, &pkgbuild_path, false)?;
+        }
+
+        if let Some(aur) = aur {
+            let aur_pkgname = aur
+                .package_name
+                .clone()
+                .unwrap_or_else(|| format!("{pkgname}-bin"));
+            let pkgbuild_path = config.out_dir().join("aur").join("PKGBUILD");
+            tracing::info!("Generating AUR PKGBUILD: {}", pkgbuild_path.display());
+            let source = aur_source(aur, config, &pkgver, arch, &tar_gz_path)?;
+            let pkgbuild = generate_pkgbuild(
+                config,
+                &aur_pkgname,
+                &pkgver,
+                arch,
+                &tar_gz_path,
+                Some(vec![source]),
+            )?;
+            write_pkgbuild_files(config, &pkgbuild, &pkgbuild_path, true)?;
+        }
 
-    tracing::info!("Generating PKGBUILD: {}", pkgbuild_path.display());
-    generate_pkgbuild_file(config, arch, pkgbuild_path.as_path(), pkg_path.as_path())?;
+        outputs.push(tar_gz_path);
+    }
 
-    Ok(vec![pkg_path])
+    Ok(outputs)
 }
 
-/// Generates the pacman PKGBUILD file.
+/// Generates the `.PKGINFO` file describing the package to pacman.
 /// For more information about the format of this file, see
-/// <https://wiki.archlinux.org/title/PKGBUILD>
-fn generate_pkgbuild_file(
+/// <https://gitlab.archlinux.org/pacman/pacman/-/blob/master/scripts/makepkg.sh.in>
+fn generate_pkginfo(
     config: &Config,
+    pkgname: &str,
+    pkgver: &str,
     arch: &str,
-    dest_dir: &Path,
-    package_path: &Path,
-) -> crate::Result<()> {
-    let pkgbuild_path = dest_dir.with_file_name("PKGBUILD");
-    let mut file = util::create_file(&pkgbuild_path)?;
+    builddate: u64,
+    pkg_dir: &Path,
+) -> crate::Result<Vec<u8>> {
+    let mut file = Vec::new();
 
-    if let Some(authors) = &config.authors {
-        writeln!(file, "# Maintainer: {}", authors.join(", "))?;
-    }
-    writeln!(file, "pkgname={}", AsKebabCase(&config.product_name))?;
-    writeln!(file, "pkgver={}", config.version)?;
-    writeln!(file, "pkgrel=1")?;
-    writeln!(file, "epoch=")?;
     writeln!(
         file,
-        "pkgdesc=\"{}\"",
-        config.description.as_deref().unwrap_or("")
+        "# Generated by cargo-packager {}",
+        env!("CARGO_PKG_VERSION")
+    )?;
+    writeln!(file, "pkgname = {pkgname}")?;
+    writeln!(file, "pkgbase = {pkgname}")?;
+    writeln!(file, "xdata = pkgtype=pkg")?;
+    writeln!(file, "pkgver = {pkgver}-{PKGREL}")?;
+    writeln!(
+        file,
+        "pkgdesc = {}",
+        metadata::single_line(config.description.as_deref().unwrap_or(""))
     )?;
-    writeln!(file, "arch=('{arch}')")?;
-
     if let Some(homepage) = &config.homepage {
-        writeln!(file, "url=\"{homepage}\"")?;
+        writeln!(file, "url = {homepage}")?;
     }
+    writeln!(file, "builddate = {builddate}")?;
+    writeln!(file, "packager = {}", metadata::packager(config))?;
+    writeln!(file, "size = {}", deb::get_size(pkg_dir)?)?;
+    writeln!(file, "arch = {arch}")?;
 
-    let dependencies = config
-        .pacman()
+    let pacman = config.pacman();
+    for license in licenses(config) {
+        writeln!(file, "license = {license}")?;
+    }
+    for replaces in pacman
+        .and_then(|p| p.replaces.as_ref())
+        .into_iter()
+        .flatten()
+    {
+        writeln!(file, "replaces = {replaces}")?;
+    }
+    for conflict in pacman
+        .and_then(|p| p.conflicts.as_ref())
+        .into_iter()
+        .flatten()
+    {
+        writeln!(file, "conflict = {conflict}")?;
+    }
+    for provides in pacman
+        .and_then(|p| p.provides.as_ref())
+        .into_iter()
+        .flatten()
+    {
+        writeln!(file, "provides = {provides}")?;
+    }
+    let dependencies = pacman
         .and_then(|d| d.depends.as_ref())
         .map_or_else(|| Ok(Vec::new()), |d| d.to_list())?;
-    writeln!(file, "depends=({})", dependencies.join(" \n"))?;
+    for depend in dependencies {
+        writeln!(file, "depend = {depend}")?;
+    }
+    for optdepend in pacman
+        .and_then(|p| p.optdepends.as_ref())
+        .into_iter()
+        .flatten()
+    {
+        writeln!(file, "optdepend = {optdepend}")?;
+    }
+    for backup in pacman.and_then(|p| p.backup.as_ref()).into_iter().flatten() {
+        writeln!(file, "backup = {backup}")?;
+    }
 
-    let provides = config
-        .pacman()
-        .and_then(|d| d.provides.clone())
-        .unwrap_or_default();
-    writeln!(file, "provides=({})", provides.join(" \n"))?;
+    Ok(file)
+}
 
-    let conflicts = config
-        .pacman()
-        .and_then(|d| d.conflicts.clone())
-        .unwrap_or_default();
-    writeln!(file, "conflicts=({})", conflicts.join(" \n"))?;
+/// Generates the `.BUILDINFO` file describing the environment the package was built in.
+/// For more information about the format of this file, see
+/// <https://man.archlinux.org/man/BUILDINFO.5>
+fn generate_buildinfo(
+    config: &Config,
+    pkgname: &str,
+    pkgver: &str,
+    arch: &str,
+    builddate: u64,
+) -> Vec<u8> {
+    format!(
+        "format = 2\n\
+        pkgname = {pkgname}\n\
+        pkgbase = {pkgname}\n\
+        pkgver = {pkgver}-{PKGREL}\n\
+        pkgarch = {arch}\n\
+        packager = {}\n\
+        builddate = {builddate}\n\
+        buildtool = cargo-packager\n\
+        buildtoolver = {}\n",
+        metadata::packager(config),
+        env!("CARGO_PKG_VERSION"),
+    )
+    .into_bytes()
+}
 
-    let replaces = config
-        .pacman()
-        .and_then(|d| d.replaces.clone())
-        .unwrap_or_default();
-    writeln!(file, "replaces=({})", replaces.join(" \n"))?;
+/// Generates the gzipped `.MTREE` file listing the package contents and their checksums,
+/// used by pacman to validate the installed files.
+/// For more information about the format of this file, see
+/// <https://man.archlinux.org/man/mtree.5>
+fn generate_mtree(
+    metadata_files: &[(&str, Vec<u8>)],
+    pkg_dir: &Path,
+    builddate: u64,
+) -> crate::Result<Vec<u8>> {
+    let mut mtree = GzEncoder::new(Vec::new(), Compression::default());
+
+    writeln!(mtree, "#mtree")?;
+    writeln!(mtree, "/set type=file uid=0 gid=0 mode=644")?;
+
+    for (name, contents) in metadata_files {
+        writeln!(
+            mtree,
+            "./{name} time={builddate}.0 size={} md5digest={:x} sha256digest={:x}",
+            contents.len(),
+            md5::compute(contents),
+            Sha256::digest(contents),
+        )?;
+    }
+
+    for entry in WalkDir::new(pkg_dir).sort_by_file_name() {
+        let entry = entry?;
+        let path = entry.path();
+        if path == pkg_dir {
+            continue;
+        }
+
+        let rel_path = path.strip_prefix(pkg_dir)?;
+        let rel_path = mtree_escape(&rel_path.to_string_lossy());
+        let metadata =
+            fs::symlink_metadata(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+        let time = metadata.mtime();
+
+        if metadata.is_dir() {
+            writeln!(mtree, "./{rel_path} time={time}.0 mode=755 type=dir")?;
+        } else if metadata.is_symlink() {
+            let target =
+                fs::read_link(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+            writeln!(
+                mtree,
+                "./{rel_path} time={time}.0 mode=777 type=link link={}",
+                mtree_escape(&target.to_string_lossy())
+            )?;
+        } else {
+            let mut md5 = md5::Context::new();
+            let mut sha256 = Sha256::new();
+            let mut file =
+                File::open(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+            let mut buf = [0; 8192];
+            loop {
+                let n = file.read(&mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                md5.consume(&buf[..n]);
+                sha256.update(&buf[..n]);
+            }
+
+            let mode = if metadata.mode() & 0o100 == 0o100 {
+                " mode=755"
+            } else {
+                ""
+            };
+            writeln!(
+                mtree,
+                "./{rel_path} time={time}.0{mode} size={} md5digest={:x} sha256digest={:x}",
+                metadata.len(),
+                md5.finalize(),
+                sha256.finalize(),
+            )?;
+        }
+    }
+
+    mtree.finish().map_err(Into::into)
+}
+
+/// Creates the zstd compressed package archive with the metadata files first,
+/// followed by the contents of `pkg_dir`.
+fn create_package_archive(
+    metadata_files: &[(&str, Vec<u8>)],
+    pkg_dir: &Path,
+    dest: &Path,
+    builddate: u64,
+) -> crate::Result<()> {
+    let dest_file = util::create_file(dest)?;
+    let encoder = zstd::Encoder::new(dest_file, 0)?;
+    let mut tar_builder = tar::Builder::new(encoder);
+
+    for (name, contents) in metadata_files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(builddate);
+        header.set_entry_type(tar::EntryType::file());
+        tar_builder.append_data(&mut header, name, contents.as_slice())?;
+    }
 
-    writeln!(file, "options=(!lto)")?;
-    let source = config
+    for entry in WalkDir::new(pkg_dir).sort_by_file_name() {
+        let entry = entry?;
+        let src_path = entry.path();
+        if src_path == pkg_dir {
+            continue;
+        }
+        let dest_path = src_path.strip_prefix(pkg_dir)?;
+        let stat = fs::symlink_metadata(src_path)
+            .map_err(|e| Error::IoWithPath(src_path.to_path_buf(), e))?;
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata_in_mode(&stat, HeaderMode::Deterministic);
+        header.set_mtime(stat.mtime() as u64);
+        if stat.is_dir() {
+            tar_builder.append_data(&mut header, dest_path, &mut io::empty())?;
+        } else if stat.is_symlink() {
+            let target = fs::read_link(src_path)
+                .map_err(|e| Error::IoWithPath(src_path.to_path_buf(), e))?;
+            tar_builder.append_link(&mut header, dest_path, target)?;
+        } else {
+            let mut src_file =
+                File::open(src_path).map_err(|e| Error::IoWithPath(src_path.to_path_buf(), e))?;
+            tar_builder.append_data(&mut header, dest_path, &mut src_file)?;
+        }
+    }
+
+    let mut dest_file = tar_builder.into_inner()?.finish()?;
+    dest_file.flush()?;
+    Ok(())
+}
+
+/// Returns the licenses of the package, `custom` if none is specified.
+fn licenses(config: &Config) -> Vec<String> {
+    config
         .pacman()
-        .and_then(|d| d.source.clone())
-        .unwrap_or_default();
+        .and_then(|p| p.license.clone())
+        .filter(|l| !l.is_empty())
+        .unwrap_or_else(|| vec!["custom".into()])
+}
+
+/// Escapes a path to be used in an mtree file, encoding whitespace, non-ASCII
+/// and the special `\`, `#` and `=` characters as octal escapes.
+fn mtree_escape(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte <= b' ' || byte >= 0x7f || matches!(byte, b'\\' | b'#' | b'=') {
+            escaped.push_str(&format!("\\{byte:03o}"));
+        } else {
+            escaped.push(byte as char);
+        }
+    }
+    escaped
+}
+
+/// A value of a PKGBUILD variable.
+enum PkgbuildValue {
+    String(String),
+    Array(Vec<String>),
+}
+
+/// A PKGBUILD for a single package.
+/// For more information about the format of this file, see
+/// <https://wiki.archlinux.org/title/PKGBUILD>
+struct Pkgbuild {
+    pkgname: String,
+    maintainer: Option<String>,
+    /// The variables in the order `makepkg --printsrcinfo` writes them, empty values are skipped.
+    variables: Vec<(&'static str, PkgbuildValue)>,
+    package_function: String,
+}
+
+impl Pkgbuild {
+    /// Renders the PKGBUILD bash script.
+    fn to_pkgbuild(&self) -> String {
+        let mut pkgbuild = String::new();
+        if let Some(maintainer) = &self.maintainer {
+            pkgbuild.push_str(&format!("# Maintainer: {maintainer}\n"));
+        }
+        pkgbuild.push_str(&format!("pkgname={}\n", bash_quote(&self.pkgname)));
+        for (name, value) in &self.variables {
+            match value {
+                PkgbuildValue::String(value) => {
+                    pkgbuild.push_str(&format!("{name}={}\n", bash_quote(value)))
+                }
+                PkgbuildValue::Array(values) => {
+                    let values = values.iter().map(|v| bash_quote(v)).collect::<Vec<_>>();
+                    pkgbuild.push_str(&format!("{name}=({})\n", values.join(" ")));
+                }
+            }
+        }
+        pkgbuild.push_str(&format!("\npackage() {{\n{}}}\n", self.package_function));
+        pkgbuild
+    }
 
-    if source.is_empty() {
-        writeln!(file, "source=({:?})", package_path.file_name().unwrap())?;
-    } else {
-        writeln!(file, "source=({})", source.join(" \n"))?;
+    /// Renders the `.SRCINFO` file, the output of `makepkg --printsrcinfo`.
+    /// For more information about the format of this file, see
+    /// <https://wiki.archlinux.org/title/.SRCINFO>
+    fn to_srcinfo(&self) -> String {
+        let mut srcinfo = format!("pkgbase = {}\n", self.pkgname);
+        for (name, value) in &self.variables {
+            let values = match value {
+                PkgbuildValue::String(value) => std::slice::from_ref(value),
+                PkgbuildValue::Array(values) => values.as_slice(),
+            };
+            for value in values {
+                srcinfo.push_str(&format!("\t{name} = {value}\n"));
+            }
+        }
+        srcinfo.push_str(&format!("\npkgname = {}\n", self.pkgname));
+        srcinfo
     }
+}
+
+/// Quotes `value` to be used as a single word in a bash script.
+fn bash_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Renders the [`AurConfig::source_url`](crate::config::AurConfig::source_url) template
+/// into a PKGBUILD source, keeping the file name of `tar_gz_path` for the downloaded file.
+fn aur_source(
+    aur: &crate::config::AurConfig,
+    config: &Config,
+    pkgver: &str,
+    arch: &str,
+    tar_gz_path: &Path,
+) -> crate::Result<String> {
+    let filename = tar_gz_path
+        .file_name()
+        .ok_or_else(|| Error::FailedToExtractFilename(tar_gz_path.to_path_buf()))?
+        .to_string_lossy();
+
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(handlebars::no_escape);
+    let mut data = BTreeMap::new();
+    data.insert("version", config.version.as_str());
+    data.insert("pkgver", pkgver);
+    data.insert("arch", arch);
+    data.insert("filename", &filename);
+    let url = handlebars.render_template(&aur.source_url, &data)?;
+
+    Ok(format!("{filename}::{url}"))
+}
+
+/// Generates the PKGBUILD installing the contents of the `.tar.gz` at `tar_gz_path`,
+/// downloaded from `source` or used from the PKGBUILD directory if not set.
+fn generate_pkgbuild(
+    config: &Config,
+    pkgname: &str,
+    pkgver: &str,
+    arch: &str,
+    tar_gz_path: &Path,
+    source: Option<Vec<String>>,
+) -> crate::Result<Pkgbuild> {
+    let pacman = config.pacman();
+    let list =
+        |values: Option<&Vec<String>>| PkgbuildValue::Array(values.cloned().unwrap_or_default());
+
+    let filename = tar_gz_path
+        .file_name()
+        .ok_or_else(|| Error::FailedToExtractFilename(tar_gz_path.to_path_buf()))?
+        .to_string_lossy()
+        .into_owned();
 
     // Generate SHA512 sum of the package
     let mut sha_file =
-        File::open(package_path).map_err(|e| Error::IoWithPath(package_path.to_path_buf(), e))?;
+        File::open(tar_gz_path).map_err(|e| Error::IoWithPath(tar_gz_path.to_path_buf(), e))?;
     let mut sha512 = Sha512::new();
     io::copy(&mut sha_file, &mut sha512)?;
-    let sha_hash = sha512.finalize();
+    let sha_hash = format!("{:x}", sha512.finalize());
 
-    writeln!(file, "sha512sums=(\"{sha_hash:x}\")")?;
-    writeln!(
-        file,
-        "package() {{\n\tcp -r \"${{srcdir}}\"/* \"${{pkgdir}}\"/\n}}"
-    )?;
+    // custom sources are copied as is, otherwise the archive is extracted to the package directory
+    let (sha512sums, source, noextract, package_function) = match source.filter(|s| !s.is_empty()) {
+        Some(source) if !source.iter().any(|s| s.starts_with(&filename)) => (
+            vec!["SKIP".to_string(); source.len()],
+            source,
+            vec![],
+            "\tcp -r \"${srcdir}\"/* \"${pkgdir}\"/\n".to_string(),
+        ),
+        source => (
+            vec![sha_hash],
+            source.unwrap_or_else(|| vec![filename.clone()]),
+            vec![filename.clone()],
+            format!("\ttar -xzf \"${{srcdir}}/{filename}\" -C \"${{pkgdir}}\"\n"),
+        ),
+    };
+
+    let depends = pacman
+        .and_then(|p| p.depends.as_ref())
+        .map_or_else(|| Ok(Vec::new()), |d| d.to_list())?;
+    let install = pacman
+        .and_then(|p| p.install.as_ref())
+        .map(|_| format!("{pkgname}.install"))
+        .unwrap_or_default();
+
+    let variables = vec![
+        (
+            "pkgdesc",
+            PkgbuildValue::String(metadata::single_line(
+                config.description.as_deref().unwrap_or(""),
+            )),
+        ),
+        ("pkgver", PkgbuildValue::String(pkgver.into())),
+        ("pkgrel", PkgbuildValue::String(PKGREL.into())),
+        (
+            "url",
+            PkgbuildValue::String(config.homepage.clone().unwrap_or_default()),
+        ),
+        ("install", PkgbuildValue::String(install)),
+        ("arch", PkgbuildValue::Array(vec![arch.into()])),
+        ("license", PkgbuildValue::Array(licenses(config))),
+        ("depends", PkgbuildValue::Array(depends)),
+        (
+            "optdepends",
+            list(pacman.and_then(|p| p.optdepends.as_ref())),
+        ),
+        ("provides", list(pacman.and_then(|p| p.provides.as_ref()))),
+        ("conflicts", list(pacman.and_then(|p| p.conflicts.as_ref()))),
+        ("replaces", list(pacman.and_then(|p| p.replaces.as_ref()))),
+        ("noextract", PkgbuildValue::Array(noextract)),
+        ("options", PkgbuildValue::Array(vec!["!lto".into()])),
+        ("backup", list(pacman.and_then(|p| p.backup.as_ref()))),
+        ("source", PkgbuildValue::Array(source)),
+        ("sha512sums", PkgbuildValue::Array(sha512sums)),
+    ]
+    .into_iter()
+    .filter(|(_, value)| match value {
+        PkgbuildValue::String(value) => !value.is_empty(),
+        PkgbuildValue::Array(values) => !values.is_empty(),
+    })
+    .collect();
+
+    Ok(Pkgbuild {
+        pkgname: pkgname.into(),
+        maintainer: config.authors.as_ref().map(|a| a.join(", ")),
+        variables,
+        package_function,
+    })
+}
+
+/// Writes the PKGBUILD at `pkgbuild_path`, along with the install script,
+/// and the `.SRCINFO` file if `srcinfo` is `true`.
+fn write_pkgbuild_files(
+    config: &Config,
+    pkgbuild: &Pkgbuild,
+    pkgbuild_path: &Path,
+    srcinfo: bool,
+) -> crate::Result<()> {
+    let dir = pkgbuild_path
+        .parent()
+        .ok_or_else(|| Error::ParentDirNotFound(pkgbuild_path.to_path_buf()))?;
+    fs::create_dir_all(dir).map_err(|e| Error::IoWithPath(dir.to_path_buf(), e))?;
+
+    fs::write(pkgbuild_path, pkgbuild.to_pkgbuild())
+        .map_err(|e| Error::IoWithPath(pkgbuild_path.to_path_buf(), e))?;
+
+    if srcinfo {
+        let srcinfo_path = dir.join(".SRCINFO");
+        fs::write(&srcinfo_path, pkgbuild.to_srcinfo())
+            .map_err(|e| Error::IoWithPath(srcinfo_path, e))?;
+    }
+
+    if let Some(install) = config.pacman().and_then(|p| p.install.as_ref()) {
+        let dest = dir.join(format!("{}.install", pkgbuild.pkgname));
+        fs::copy(install, &dest).map_err(|e| Error::CopyFile(install.clone(), dest, e))?;
+    }
 
-    file.flush()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_mtree_paths() {
+        assert_eq!(mtree_escape("usr/bin/app"), "usr/bin/app");
+        assert_eq!(
+            mtree_escape("usr/share/My App/a=b#c"),
+            "usr/share/My\\040App/a\\075b\\043c"
+        );
+        assert_eq!(mtree_escape("é"), "\\303\\251");
+    }
+
+    #[test]
+    fn quotes_bash_words() {
+        assert_eq!(bash_quote("glibc"), "'glibc'");
+        assert_eq!(bash_quote("it's"), "'it'\\''s'");
+    }
+}
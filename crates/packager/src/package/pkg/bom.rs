@@ -0,0 +1,365 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Writer for the bill of materials (`Bom`) listing the files installed by a package,
+//! which the installer uses to write its receipts.
+//!
+//! A `Bom` is a `BOMStore`: numbered blocks followed by the table of their locations
+//! and a table of named variables pointing to the blocks at the root of each B-tree.
+
+use std::collections::HashMap;
+
+const PATHS_BLOCK_SIZE: usize = 4096;
+/// The block size of the empty trees, like `mkbom`.
+const SMALL_BLOCK_SIZE: usize = 128;
+const PATHS_PER_NODE: usize = 256;
+
+const TYPE_FILE: u8 = 1;
+const TYPE_DIRECTORY: u8 = 2;
+const TYPE_SYMLINK: u8 = 3;
+
+/// A file of the payload.
+pub(super) struct Entry {
+    /// The path of the file in the payload, either `.` or starting with `./`.
+    pub path: String,
+    /// The `st_mode` of the file, with its type and permissions.
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub mtime: u32,
+    pub size: u32,
+    /// The `cksum` checksum of the file contents or symlink target.
+    pub checksum: u32,
+    /// The target of a symlink.
+    pub link: Option<String>,
+}
+
+impl Entry {
+    fn name(&self) -> &str {
+        self.path
+            .rsplit_once('/')
+            .map(|(_, name)| name)
+            .unwrap_or(&self.path)
+    }
+
+    fn parent(&self) -> Option<&str> {
+        self.path.rsplit_once('/').map(|(parent, _)| parent)
+    }
+
+    /// `BOMPathInfo2`
+    fn path_info(&self) -> Vec<u8> {
+        let kind = match self.mode & 0o170000 {
+            0o040000 => TYPE_DIRECTORY,
+            0o120000 => TYPE_SYMLINK,
+            _ => TYPE_FILE,
+        };
+        let mut info = Vec::with_capacity(31);
+        info.push(kind);
+        info.push(1);
+        // architecture
+        info.extend_from_slice(&0u16.to_be_bytes());
+        info.extend_from_slice(&(self.mode as u16).to_be_bytes());
+        info.extend_from_slice(&self.uid.to_be_bytes());
+        info.extend_from_slice(&self.gid.to_be_bytes());
+        info.extend_from_slice(&self.mtime.to_be_bytes());
+        info.extend_from_slice(&self.size.to_be_bytes());
+        info.push(1);
+        info.extend_from_slice(&self.checksum.to_be_bytes());
+        match &self.link {
+            Some(link) => {
+                info.extend_from_slice(&(link.len() as u32 + 1).to_be_bytes());
+                info.extend_from_slice(link.as_bytes());
+                info.push(0);
+            }
+            None => info.extend_from_slice(&0u32.to_be_bytes()),
+        }
+        info
+    }
+}
+
+/// The checksum computed by the POSIX `cksum` utility.
+pub(super) fn cksum(data: &[u8]) -> u32 {
+    fn update(crc: u32, byte: u8) -> u32 {
+        let mut crc = crc ^ ((byte as u32) << 24);
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    }
+
+    let mut crc = data.iter().fold(0, |crc, byte| update(crc, *byte));
+    let mut len = data.len();
+    while len != 0 {
+        crc = update(crc, len as u8);
+        len >>= 8;
+    }
+    !crc
+}
+
+/// The blocks of the store, block 0 being reserved as the null block.
+struct Store {
+    blocks: Vec<Vec<u8>>,
+    vars: Vec<(&'static str, u32)>,
+}
+
+impl Store {
+    fn add(&mut self, data: Vec<u8>) -> u32 {
+        self.blocks.push(data);
+        self.blocks.len() as u32
+    }
+
+    /// Adds a B-tree whose leaves hold the given `(value, key)` block pairs
+    /// and returns the block of its `BOMTree` header.
+    fn add_tree(&mut self, entries: &[(u32, u32)], block_size: usize) -> u32 {
+        let mut level = Vec::new();
+        let first_node = self.blocks.len() as u32 + 1;
+        let chunks = entries.chunks(PATHS_PER_NODE).collect::<Vec<_>>();
+        let node_count = chunks.len().max(1) as u32;
+        for i in 0..node_count {
+            let chunk = chunks.get(i as usize).copied().unwrap_or_default();
+            let id = first_node + i;
+            let forward = if i + 1 < node_count { id + 1 } else { 0 };
+            let backward = if i > 0 { id - 1 } else { 0 };
+            self.add(tree_node(true, forward, backward, chunk, block_size));
+            level.push((id, chunk.last().map(|(_, key)| *key).unwrap_or_default()));
+        }
+
+        // index nodes pointing to the nodes of the level below and the last key they hold
+        while level.len() > 1 {
+            level = level
+                .chunks(PATHS_PER_NODE)
+                .map(|children| {
+                    let id = self.add(tree_node(false, 0, 0, children, block_size));
+                    (id, children.last().map(|(_, key)| *key).unwrap_or_default())
+                })
+                .collect();
+        }
+
+        let mut tree = Vec::with_capacity(21);
+        tree.extend_from_slice(b"tree");
+        tree.extend_from_slice(&1u32.to_be_bytes());
+        tree.extend_from_slice(&level[0].0.to_be_bytes());
+        tree.extend_from_slice(&(block_size as u32).to_be_bytes());
+        tree.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+        tree.push(0);
+        self.add(tree)
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        // the header is followed by the blocks, their table and the variables
+        let mut out = vec![0; 512];
+        let mut table = Vec::with_capacity(self.blocks.len() + 1);
+        table.push((0, 0));
+        for block in &self.blocks {
+            table.push((out.len() as u32, block.len() as u32));
+            out.extend_from_slice(block);
+        }
+
+        let index_offset = out.len() as u32;
+        out.extend_from_slice(&(table.len() as u32).to_be_bytes());
+        for (address, len) in &table {
+            out.extend_from_slice(&address.to_be_bytes());
+            out.extend_from_slice(&len.to_be_bytes());
+        }
+        // free list, with two empty pointers
+        out.extend_from_slice(&2u32.to_be_bytes());
+        out.extend_from_slice(&[0; 16]);
+        let index_len = out.len() as u32 - index_offset;
+
+        let vars_offset = out.len() as u32;
+        out.extend_from_slice(&(self.vars.len() as u32).to_be_bytes());
+        for (name, id) in &self.vars {
+            out.extend_from_slice(&id.to_be_bytes());
+            out.push(name.len() as u8);
+            out.extend_from_slice(name.as_bytes());
+        }
+        let vars_len = out.len() as u32 - vars_offset;
+
+        out[0..8].copy_from_slice(b"BOMStore");
+        out[8..12].copy_from_slice(&1u32.to_be_bytes());
+        out[12..16].copy_from_slice(&(self.blocks.len() as u32).to_be_bytes());
+        out[16..20].copy_from_slice(&index_offset.to_be_bytes());
+        out[20..24].copy_from_slice(&index_len.to_be_bytes());
+        out[24..28].copy_from_slice(&vars_offset.to_be_bytes());
+        out[28..32].copy_from_slice(&vars_len.to_be_bytes());
+        out
+    }
+}
+
+/// `BOMPaths`, a B-tree node.
+fn tree_node(
+    leaf: bool,
+    forward: u32,
+    backward: u32,
+    indices: &[(u32, u32)],
+    block_size: usize,
+) -> Vec<u8> {
+    let mut node = Vec::with_capacity(block_size);
+    node.extend_from_slice(&(leaf as u16).to_be_bytes());
+    node.extend_from_slice(&(indices.len() as u16).to_be_bytes());
+    node.extend_from_slice(&forward.to_be_bytes());
+    node.extend_from_slice(&backward.to_be_bytes());
+    for (value, key) in indices {
+        node.extend_from_slice(&value.to_be_bytes());
+        node.extend_from_slice(&key.to_be_bytes());
+    }
+    node.resize(node.len().max(block_size), 0);
+    node
+}
+
+/// Writes the `Bom` of the given payload entries, which must include the `.` root.
+pub(super) fn write(entries: &[Entry]) -> Vec<u8> {
+    let mut children = HashMap::<&str, Vec<&Entry>>::new();
+    for entry in entries {
+        if let Some(parent) = entry.parent() {
+            children.entry(parent).or_default().push(entry);
+        }
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_by(|a, b| a.name().cmp(b.name()));
+    }
+
+    // the paths are numbered breadth first so the tree is sorted by parent ID and name
+    let mut ordered = entries.iter().filter(|e| e.path == ".").collect::<Vec<_>>();
+    let mut i = 0;
+    while i < ordered.len() {
+        if let Some(siblings) = children.get(ordered[i].path.as_str()) {
+            ordered.extend(siblings);
+        }
+        i += 1;
+    }
+
+    let mut store = Store {
+        blocks: Vec::new(),
+        vars: Vec::new(),
+    };
+
+    let mut ids = HashMap::new();
+    let mut paths = Vec::with_capacity(ordered.len());
+    for (id, entry) in (1u32..).zip(&ordered) {
+        ids.insert(entry.path.as_str(), id);
+        let parent = entry
+            .parent()
+            .and_then(|p| ids.get(p).copied())
+            .unwrap_or_default();
+
+        let info = store.add(entry.path_info());
+        // BOMPathInfo1
+        let mut info1 = Vec::with_capacity(8);
+        info1.extend_from_slice(&id.to_be_bytes());
+        info1.extend_from_slice(&info.to_be_bytes());
+        let info1 = store.add(info1);
+        // BOMFile
+        let mut file = Vec::with_capacity(entry.name().len() + 5);
+        file.extend_from_slice(&parent.to_be_bytes());
+        file.extend_from_slice(entry.name().as_bytes());
+        file.push(0);
+        let file = store.add(file);
+
+        paths.push((info1, file));
+    }
+
+    let mut info = Vec::with_capacity(28);
+    for value in [1, ordered.len() as u32, 1, 0, 0, 0, 0] {
+        info.extend_from_slice(&value.to_be_bytes());
+    }
+    let info = store.add(info);
+    let paths = store.add_tree(&paths, PATHS_BLOCK_SIZE);
+    let hard_links = store.add_tree(&[], PATHS_BLOCK_SIZE);
+    let size64 = store.add_tree(&[], SMALL_BLOCK_SIZE);
+
+    // BOMVIndex
+    let v_tree = store.add_tree(&[], SMALL_BLOCK_SIZE);
+    let mut v_index = Vec::with_capacity(13);
+    v_index.extend_from_slice(&1u32.to_be_bytes());
+    v_index.extend_from_slice(&v_tree.to_be_bytes());
+    v_index.extend_from_slice(&0u32.to_be_bytes());
+    v_index.push(0);
+    let v_index = store.add(v_index);
+
+    store.vars = vec![
+        ("BomInfo", info),
+        ("Paths", paths),
+        ("HLIndex", hard_links),
+        ("VIndex", v_index),
+        ("Size64", size64),
+    ];
+    store.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn be32(data: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn computes_cksum() {
+        assert_eq!(cksum(b""), 4294967295);
+        assert_eq!(cksum(b"123456789"), 930766865);
+    }
+
+    #[test]
+    fn writes_paths_breadth_first() {
+        let entry = |path: &str, mode| Entry {
+            path: path.into(),
+            mode,
+            uid: 0,
+            gid: 0,
+            mtime: 0,
+            size: 0,
+            checksum: 0,
+            link: None,
+        };
+        let bom = write(&[
+            entry(".", 0o40755),
+            entry("./a.app", 0o40755),
+            entry("./a.app/z", 0o100644),
+            entry("./a.app/Contents", 0o40755),
+            entry("./b", 0o100644),
+        ]);
+        assert_eq!(&bom[0..8], b"BOMStore");
+
+        let index = be32(&bom, 16) as usize;
+        let block = |id: u32| {
+            let address = be32(&bom, index + 4 + id as usize * 8) as usize;
+            let len = be32(&bom, index + 8 + id as usize * 8) as usize;
+            &bom[address..address + len]
+        };
+
+        let vars = be32(&bom, 24) as usize;
+        assert_eq!(be32(&bom, vars), 5);
+        // after the `BomInfo` variable
+        assert_eq!(&bom[vars + 21..vars + 26], b"Paths");
+        let tree = block(be32(&bom, vars + 16));
+        assert_eq!(&tree[0..4], b"tree");
+        assert_eq!(be32(tree, 16), 5);
+
+        let node = block(be32(tree, 8));
+        assert_eq!(&node[0..4], &[0, 1, 0, 5]);
+        let names = (0..5)
+            .map(|i| {
+                let file = block(be32(node, 16 + i * 8));
+                let name = &file[4..file.len() - 1];
+                (be32(file, 0), String::from_utf8(name.to_vec()).unwrap())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                (0, ".".into()),
+                (1, "a.app".into()),
+                (1, "b".into()),
+                (2, "Contents".into()),
+                (2, "z".into()),
+            ]
+        );
+    }
+}
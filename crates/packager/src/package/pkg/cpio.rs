@@ -0,0 +1,99 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Writer for the portable ASCII (`odc`) cpio archives used by the
+//! `Payload` and `Scripts` of installer packages.
+
+use std::io::{self, Write};
+
+const MAGIC: &str = "070707";
+const TRAILER: &str = "TRAILER!!!";
+
+pub(super) struct CpioWriter<W: Write> {
+    out: W,
+    inode: u32,
+}
+
+impl<W: Write> CpioWriter<W> {
+    pub(super) fn new(out: W) -> Self {
+        Self { out, inode: 0 }
+    }
+
+    /// Adds an entry, `data` being the contents of a file or the target of a symlink.
+    pub(super) fn add(
+        &mut self,
+        path: &str,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+        mtime: u64,
+        data: &[u8],
+    ) -> io::Result<()> {
+        self.inode += 1;
+        let nlink = if mode & 0o170000 == 0o040000 { 2 } else { 1 };
+        self.write_header(path, mode, uid, gid, nlink, mtime, data.len() as u64)?;
+        self.out.write_all(data)
+    }
+
+    /// Writes the trailer entry and returns the underlying writer.
+    pub(super) fn finish(mut self) -> io::Result<W> {
+        self.inode = 0;
+        self.write_header(TRAILER, 0, 0, 0, 1, 0, 0)?;
+        Ok(self.out)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn write_header(
+        &mut self,
+        path: &str,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+        nlink: u32,
+        mtime: u64,
+        size: u64,
+    ) -> io::Result<()> {
+        // the inode numbers only need to be unique to tell hard links apart
+        let inode = self.inode & 0o777777;
+        write!(
+            self.out,
+            "{MAGIC}{:06o}{inode:06o}{mode:06o}{uid:06o}{gid:06o}{nlink:06o}{:06o}{mtime:011o}{:06o}{size:011o}",
+            0,
+            0,
+            path.len() + 1,
+        )?;
+        self.out.write_all(path.as_bytes())?;
+        self.out.write_all(&[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_odc_entries() {
+        let mut cpio = CpioWriter::new(Vec::new());
+        cpio.add("./a", 0o100644, 0, 80, 0, b"hi").unwrap();
+        let archive = cpio.finish().unwrap();
+
+        let header = [
+            "070707",
+            "000000",
+            "000001",
+            "100644",
+            "000000",
+            "000120",
+            "000001",
+            "000000",
+            "00000000000",
+            "000004",
+            "00000000002",
+        ]
+        .concat();
+        assert_eq!(&archive[..76], header.as_bytes());
+        assert_eq!(&archive[76..82], b"./a\0hi");
+        assert!(archive.ends_with(b"TRAILER!!!\0"));
+    }
+}
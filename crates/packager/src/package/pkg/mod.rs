@@ -0,0 +1,354 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! macOS installer packages (.pkg), generated without `pkgbuild` and `productbuild`
+//! so they can also be produced on other hosts.
+//!
+//! The package is a product archive: a xar archive holding the `Distribution` script
+//! and a component package installing the `.app` bundle, made of its `PackageInfo`,
+//! its bill of materials, the gzip compressed cpio `Payload` and the optional `Scripts`.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use der::Encode;
+use flate2::{write::GzEncoder, Compression};
+use handlebars::{to_json, Handlebars};
+use walkdir::WalkDir;
+
+use super::Context;
+use crate::{codesign, util, Config, Error};
+
+mod bom;
+mod cpio;
+mod xar;
+
+const DEFAULT_INSTALL_LOCATION: &str = "/Applications";
+/// The `admin` group, owning the root of the payload like `pkgbuild` does for `/Applications`.
+const ADMIN_GID: u32 = 80;
+
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+#[cfg(unix)]
+fn permissions(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn permissions(_metadata: &fs::Metadata) -> u32 {
+    0o755
+}
+
+fn signer(config: &Config) -> crate::Result<Option<xar::Signer>> {
+    let Some(identity) = config.pkg().and_then(|pkg| pkg.signing_identity.as_ref()) else {
+        return Ok(None);
+    };
+    let Some(signing_identity) = codesign::signing_identity(identity, config)? else {
+        tracing::warn!("Skipping pkg signing, APPLE_CERTIFICATE and APPLE_CERTIFICATE_PASSWORD are required to sign the package");
+        return Ok(None);
+    };
+
+    let certificates = signing_identity
+        .certificates
+        .iter()
+        .map(|certificate| certificate.to_der())
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Some(xar::Signer::new(signing_identity.key, certificates)))
+}
+
+/// Writes a gzip compressed cpio archive of `dir`, stored as `prefix` in the archive,
+/// and returns its entries for the bill of materials.
+fn write_payload(dir: &Path, prefix: &str, path: &Path) -> crate::Result<Vec<bom::Entry>> {
+    let file = File::create(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+    let mut cpio =
+        cpio::CpioWriter::new(GzEncoder::new(BufWriter::new(file), Compression::default()));
+
+    let mut entries = Vec::new();
+    let mut add = |entry: bom::Entry, data: &[u8]| -> crate::Result<()> {
+        cpio.add(
+            &entry.path,
+            entry.mode,
+            entry.uid,
+            entry.gid,
+            entry.mtime as u64,
+            data,
+        )
+        .map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+        entries.push(entry);
+        Ok(())
+    };
+
+    let root_metadata = fs::metadata(dir).map_err(|e| Error::IoWithPath(dir.to_path_buf(), e))?;
+    add(
+        bom::Entry {
+            path: ".".into(),
+            mode: S_IFDIR | 0o775,
+            uid: 0,
+            gid: ADMIN_GID,
+            mtime: root_metadata
+                .modified()?
+                .duration_since(UNIX_EPOCH)?
+                .as_secs() as u32,
+            size: 0,
+            checksum: 0,
+            link: None,
+        },
+        &[],
+    )?;
+
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry?;
+        let source = entry.path();
+        let relative = source.strip_prefix(dir)?;
+        let name = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>();
+        let path = std::iter::once(".")
+            .chain((!prefix.is_empty()).then_some(prefix))
+            .chain(name.iter().map(|c| c.as_ref()))
+            .collect::<Vec<_>>()
+            .join("/");
+        if path == "." {
+            continue;
+        }
+
+        let metadata = entry.metadata()?;
+        let mtime = metadata.modified()?.duration_since(UNIX_EPOCH)?.as_secs() as u32;
+        let (mode, data, checksum, link) = if entry.file_type().is_dir() {
+            (S_IFDIR | permissions(&metadata), Vec::new(), 0, None)
+        } else if entry.file_type().is_symlink() {
+            let target = fs::read_link(source)
+                .map_err(|e| Error::IoWithPath(source.to_path_buf(), e))?
+                .to_string_lossy()
+                .replace('\\', "/");
+            let data = target.as_bytes().to_vec();
+            (
+                S_IFLNK | 0o755,
+                data,
+                bom::cksum(target.as_bytes()),
+                Some(target),
+            )
+        } else {
+            let data = fs::read(source).map_err(|e| Error::IoWithPath(source.to_path_buf(), e))?;
+            let checksum = bom::cksum(&data);
+            (S_IFREG | permissions(&metadata), data, checksum, None)
+        };
+
+        add(
+            bom::Entry {
+                path,
+                mode,
+                uid: 0,
+                gid: 0,
+                mtime,
+                size: data.len() as u32,
+                checksum,
+                link,
+            },
+            &data,
+        )?;
+    }
+
+    cpio.finish()
+        .and_then(|gz| gz.finish())
+        .and_then(|mut out| out.flush())
+        .map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+
+    Ok(entries)
+}
+
+#[tracing::instrument(level = "trace", skip(ctx))]
+pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
+    let Context {
+        config,
+        intermediates_path,
+        ..
+    } = ctx;
+
+    let host_architectures = match config.target_arch()? {
+        "x86_64" => "x86_64",
+        "aarch64" => "arm64",
+        "universal" => "arm64,x86_64",
+        target => return Err(Error::UnsupportedArch("pkg".into(), target.into())),
+    };
+
+    let out_dir = config.out_dir();
+    let intermediates_path = intermediates_path.join("pkg");
+    util::create_clean_dir(&intermediates_path)?;
+
+    let package_base_name = format!(
+        "{}_{}_{}",
+        config.product_name,
+        config.version,
+        match config.target_arch()? {
+            "x86_64" => "x64",
+            other => other,
+        }
+    );
+    let app_bundle_file_name = format!("{}.app", config.product_name);
+    let app_bundle_path = out_dir.join(&app_bundle_file_name);
+    let pkg_name = format!("{}.pkg", package_base_name);
+    let pkg_path = out_dir.join(&pkg_name);
+
+    tracing::info!("Packaging {} ({})", pkg_name, pkg_path.display());
+
+    let pkg = config.pkg();
+    let signer = signer(config)?;
+    let identifier = config.identifier();
+    let component = format!("{identifier}.pkg");
+
+    tracing::debug!("Writing Payload");
+    let payload_path = intermediates_path.join("Payload");
+    // the payload holds the .app bundle directory itself, extracted to the install location
+    let payload = write_payload(&app_bundle_path, &app_bundle_file_name, &payload_path)?;
+    let install_kbytes = payload
+        .iter()
+        .map(|e| e.size as u64)
+        .sum::<u64>()
+        .div_ceil(1024);
+
+    tracing::debug!("Writing Bom");
+    let bom_path = intermediates_path.join("Bom");
+    fs::write(&bom_path, bom::write(&payload))
+        .map_err(|e| Error::IoWithPath(bom_path.clone(), e))?;
+
+    let preinstall = pkg.and_then(|p| p.preinstall_script.as_ref());
+    let postinstall = pkg.and_then(|p| p.postinstall_script.as_ref());
+    let scripts_path = intermediates_path.join("Scripts");
+    let has_scripts = preinstall.is_some() || postinstall.is_some();
+    if has_scripts {
+        tracing::debug!("Writing Scripts");
+        let scripts_dir = intermediates_path.join("scripts");
+        fs::create_dir_all(&scripts_dir).map_err(|e| Error::IoWithPath(scripts_dir.clone(), e))?;
+        for (name, script) in [("preinstall", preinstall), ("postinstall", postinstall)] {
+            if let Some(script) = script {
+                let dest = scripts_dir.join(name);
+                fs::copy(script, &dest)
+                    .map_err(|e| Error::CopyFile(script.clone(), dest.clone(), e))?;
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    fs::set_permissions(&dest, fs::Permissions::from_mode(0o755))
+                        .map_err(|e| Error::IoWithPath(dest.clone(), e))?;
+                }
+            }
+        }
+        write_payload(&scripts_dir, "", &scripts_path)?;
+    }
+
+    let info_plist_path = app_bundle_path.join("Contents/Info.plist");
+    let info_plist = plist::Value::from_file(&info_plist_path)?;
+    let info_plist = info_plist.as_dictionary();
+    let info_plist_string = |key: &str, default: &str| {
+        info_plist
+            .and_then(|d| d.get(key))
+            .and_then(|v| v.as_string())
+            .unwrap_or(default)
+            .to_string()
+    };
+
+    let install_location = pkg
+        .and_then(|p| p.install_location.as_deref())
+        .unwrap_or(DEFAULT_INSTALL_LOCATION);
+    let license = config
+        .license_file
+        .as_ref()
+        .map(|license| -> crate::Result<_> {
+            let file_name = license
+                .file_name()
+                .ok_or_else(|| Error::FailedToExtractFilename(license.clone()))?
+                .to_string_lossy()
+                .to_string();
+            Ok((license, file_name))
+        })
+        .transpose()?;
+
+    let mut data = BTreeMap::new();
+    data.insert("identifier", to_json(identifier));
+    data.insert("version", to_json(&config.version));
+    data.insert("install_location", to_json(install_location));
+    data.insert("number_of_files", to_json(payload.len()));
+    data.insert("install_kbytes", to_json(install_kbytes));
+    data.insert("app", to_json(&app_bundle_file_name));
+    data.insert(
+        "bundle_identifier",
+        to_json(info_plist_string("CFBundleIdentifier", identifier)),
+    );
+    data.insert(
+        "bundle_short_version",
+        to_json(info_plist_string(
+            "CFBundleShortVersionString",
+            &config.version,
+        )),
+    );
+    data.insert(
+        "bundle_version",
+        to_json(info_plist_string("CFBundleVersion", &config.version)),
+    );
+    data.insert("has_scripts", to_json(has_scripts));
+    data.insert("preinstall", to_json(preinstall.is_some()));
+    data.insert("postinstall", to_json(postinstall.is_some()));
+    data.insert("title", to_json(&config.product_name));
+    data.insert("host_architectures", to_json(host_architectures));
+    data.insert(
+        "min_os_version",
+        to_json(
+            config
+                .macos()
+                .and_then(|macos| macos.minimum_system_version.as_deref()),
+        ),
+    );
+    data.insert(
+        "license",
+        to_json(license.as_ref().map(|(_, file_name)| file_name)),
+    );
+    data.insert("component", to_json(&component));
+
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(xar::xml_escape);
+    handlebars
+        .register_template_string("PackageInfo", include_str!("./PackageInfo.xml"))
+        .map_err(Box::new)?;
+    handlebars
+        .register_template_string("Distribution", include_str!("./Distribution.xml"))
+        .map_err(Box::new)?;
+
+    let package_info_path = intermediates_path.join("PackageInfo");
+    fs::write(&package_info_path, handlebars.render("PackageInfo", &data)?)
+        .map_err(|e| Error::IoWithPath(package_info_path.clone(), e))?;
+    let distribution_path = intermediates_path.join("Distribution");
+    fs::write(
+        &distribution_path,
+        handlebars.render("Distribution", &data)?,
+    )
+    .map_err(|e| Error::IoWithPath(distribution_path.clone(), e))?;
+
+    let mut archive = xar::XarWriter::new();
+    archive.add_file("Distribution", &distribution_path)?;
+    if let Some((license, file_name)) = &license {
+        archive.add_file(&format!("Resources/{file_name}"), license)?;
+    }
+    archive.add_file(&format!("{component}/PackageInfo"), &package_info_path)?;
+    archive.add_file(&format!("{component}/Bom"), &bom_path)?;
+    archive.add_file(&format!("{component}/Payload"), &payload_path)?;
+    if has_scripts {
+        archive.add_file(&format!("{component}/Scripts"), &scripts_path)?;
+    }
+
+    if signer.is_some() {
+        tracing::debug!("Signing {}", pkg_name);
+    }
+    archive.write(&pkg_path, signer.as_ref())?;
+
+    Ok(vec![pkg_path])
+}
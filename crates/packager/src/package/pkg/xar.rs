@@ -0,0 +1,299 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Writer for xar archives, the container of flat installer packages.
+//!
+//! The archive is made of a header, the zlib compressed XML table of contents describing
+//! the files, and the heap holding the SHA-1 checksum of the compressed table of contents,
+//! the optional RSA signature of that checksum, and the contents of the files.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use flate2::{write::ZlibEncoder, Compression};
+use rsa::{traits::PublicKeyParts, Pkcs1v15Sign, RsaPrivateKey};
+use sha1::{Digest, Sha1};
+
+use crate::Error;
+
+const MAGIC: u32 = 0x78617221;
+const HEADER_SIZE: u16 = 28;
+const CHECKSUM_SHA1: u32 = 1;
+const CHECKSUM_SIZE: u64 = 20;
+
+/// Escapes the XML special characters of a text or attribute value.
+pub(super) fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The key and certificate chain used to sign the archive, like `productsign`.
+pub(super) struct Signer {
+    key: RsaPrivateKey,
+    /// The DER encoded certificates, starting with the signing certificate.
+    certificates: Vec<Vec<u8>>,
+}
+
+impl Signer {
+    pub(super) fn new(key: RsaPrivateKey, certificates: Vec<Vec<u8>>) -> Self {
+        Self { key, certificates }
+    }
+}
+
+struct ArchivedFile {
+    source: PathBuf,
+    len: u64,
+    checksum: String,
+}
+
+/// A node of the archive tree, with the index of its contents in the heap for files.
+struct Node {
+    name: String,
+    file: Option<usize>,
+    children: Vec<Node>,
+}
+
+pub(super) struct XarWriter {
+    files: Vec<ArchivedFile>,
+    root: Vec<Node>,
+}
+
+impl XarWriter {
+    pub(super) fn new() -> Self {
+        Self {
+            files: Vec::new(),
+            root: Vec::new(),
+        }
+    }
+
+    /// Adds the file at `source` to the archive, `path` being `/` separated.
+    /// The parent directories are created as needed.
+    pub(super) fn add_file(&mut self, path: &str, source: &Path) -> crate::Result<()> {
+        let mut hasher = Sha1::new();
+        let mut input =
+            BufReader::new(File::open(source).map_err(|e| Error::IoWithPath(source.into(), e))?);
+        let len = io::copy(&mut input, &mut hasher)
+            .map_err(|e| Error::IoWithPath(source.to_path_buf(), e))?;
+        self.files.push(ArchivedFile {
+            source: source.to_path_buf(),
+            len,
+            checksum: hex::encode(hasher.finalize()),
+        });
+
+        let mut nodes = &mut self.root;
+        let mut components = path.split('/').peekable();
+        while let Some(name) = components.next() {
+            let file = components.peek().is_none().then_some(self.files.len() - 1);
+            let directory = nodes
+                .iter()
+                .position(|n| file.is_none() && n.file.is_none() && n.name == name);
+            let index = match directory {
+                Some(index) => index,
+                None => {
+                    nodes.push(Node {
+                        name: name.into(),
+                        file,
+                        children: Vec::new(),
+                    });
+                    nodes.len() - 1
+                }
+            };
+            nodes = &mut nodes[index].children;
+        }
+        Ok(())
+    }
+
+    fn toc(&self, signer: Option<&Signer>) -> crate::Result<String> {
+        let format =
+            time::format_description::parse("[year]-[month]-[day]T[hour]:[minute]:[second]")
+                .map_err(time::error::Error::from)?;
+        let creation_time = time::OffsetDateTime::now_utc()
+            .format(&format)
+            .map_err(time::error::Error::from)?;
+
+        let signature_size = signer.map(|s| s.key.size() as u64);
+        let mut toc = String::new();
+        toc.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<xar>\n <toc>\n");
+        toc.push_str(&format!(
+            "  <checksum style=\"sha1\">\n   <offset>0</offset>\n   <size>{CHECKSUM_SIZE}</size>\n  </checksum>\n"
+        ));
+        toc.push_str(&format!(
+            "  <creation-time>{creation_time}</creation-time>\n"
+        ));
+        if let Some(signer) = signer {
+            toc.push_str(&format!(
+                "  <signature style=\"RSA\">\n   <offset>{CHECKSUM_SIZE}</offset>\n   <size>{}</size>\n",
+                signer.key.size()
+            ));
+            toc.push_str(
+                "   <KeyInfo xmlns=\"http://www.w3.org/2000/09/xmldsig#\">\n    <X509Data>\n",
+            );
+            for certificate in &signer.certificates {
+                toc.push_str(&format!(
+                    "     <X509Certificate>{}</X509Certificate>\n",
+                    STANDARD.encode(certificate)
+                ));
+            }
+            toc.push_str("    </X509Data>\n   </KeyInfo>\n  </signature>\n");
+        }
+
+        // the files are stored in the order they were added, after the checksum and signature
+        let mut offsets = Vec::with_capacity(self.files.len());
+        let mut offset = CHECKSUM_SIZE + signature_size.unwrap_or_default();
+        for file in &self.files {
+            offsets.push(offset);
+            offset += file.len;
+        }
+
+        let mut id = 0;
+        for node in &self.root {
+            self.write_node(&mut toc, node, &offsets, &mut id, 2);
+        }
+        toc.push_str(" </toc>\n</xar>\n");
+        Ok(toc)
+    }
+
+    fn write_node(
+        &self,
+        toc: &mut String,
+        node: &Node,
+        offsets: &[u64],
+        id: &mut u32,
+        depth: usize,
+    ) {
+        let indent = " ".repeat(depth);
+        *id += 1;
+        toc.push_str(&format!("{indent}<file id=\"{id}\">\n"));
+        toc.push_str(&format!(
+            "{indent} <name>{}</name>\n",
+            xml_escape(&node.name)
+        ));
+        match node.file {
+            Some(index) => {
+                let file = &self.files[index];
+                toc.push_str(&format!("{indent} <type>file</type>\n"));
+                toc.push_str(&format!("{indent} <mode>0644</mode>\n"));
+                toc.push_str(&format!(
+                    "{indent} <data>\n{indent}  <length>{len}</length>\n{indent}  <offset>{offset}</offset>\n{indent}  <size>{len}</size>\n",
+                    len = file.len,
+                    offset = offsets[index],
+                ));
+                toc.push_str(&format!(
+                    "{indent}  <encoding style=\"application/octet-stream\"/>\n"
+                ));
+                toc.push_str(&format!(
+                    "{indent}  <extracted-checksum style=\"sha1\">{0}</extracted-checksum>\n{indent}  <archived-checksum style=\"sha1\">{0}</archived-checksum>\n",
+                    file.checksum
+                ));
+                toc.push_str(&format!("{indent} </data>\n"));
+            }
+            None => {
+                toc.push_str(&format!("{indent} <type>directory</type>\n"));
+                toc.push_str(&format!("{indent} <mode>0755</mode>\n"));
+                for child in &node.children {
+                    self.write_node(toc, child, offsets, id, depth + 1);
+                }
+            }
+        }
+        toc.push_str(&format!("{indent}</file>\n"));
+    }
+
+    /// Writes the archive to `path`, signed with `signer` if any.
+    pub(super) fn write(self, path: &Path, signer: Option<&Signer>) -> crate::Result<()> {
+        let toc = self.toc(signer)?;
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(toc.as_bytes())?;
+        let compressed_toc = encoder.finish()?;
+        let checksum = Sha1::digest(&compressed_toc);
+
+        let file = File::create(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+        let mut out = BufWriter::new(file);
+        let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+        header.extend_from_slice(&MAGIC.to_be_bytes());
+        header.extend_from_slice(&HEADER_SIZE.to_be_bytes());
+        // version
+        header.extend_from_slice(&1u16.to_be_bytes());
+        header.extend_from_slice(&(compressed_toc.len() as u64).to_be_bytes());
+        header.extend_from_slice(&(toc.len() as u64).to_be_bytes());
+        header.extend_from_slice(&CHECKSUM_SHA1.to_be_bytes());
+        out.write_all(&header)
+            .and_then(|_| out.write_all(&compressed_toc))
+            .and_then(|_| out.write_all(&checksum))
+            .map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+
+        if let Some(signer) = signer {
+            // the checksum is signed as a SHA-1 digest
+            let signature = signer.key.sign(Pkcs1v15Sign::new::<Sha1>(), &checksum)?;
+            out.write_all(&signature)
+                .map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+        }
+
+        for file in &self.files {
+            let mut input =
+                File::open(&file.source).map_err(|e| Error::IoWithPath(file.source.clone(), e))?;
+            io::copy(&mut input, &mut out).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+        }
+        out.flush()
+            .map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Read};
+
+    use flate2::read::ZlibDecoder;
+    use rsa::RsaPublicKey;
+
+    use super::*;
+
+    #[test]
+    fn writes_signed_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let distribution = dir.path().join("Distribution");
+        fs::write(&distribution, "<installer-gui-script/>").unwrap();
+        let payload = dir.path().join("Payload");
+        fs::write(&payload, "payload").unwrap();
+
+        let key = RsaPrivateKey::new(&mut rand::thread_rng(), 512).unwrap();
+        let public_key = RsaPublicKey::from(&key);
+        let signer = Signer::new(key, vec![vec![1, 2, 3]]);
+
+        let mut archive = XarWriter::new();
+        archive.add_file("Distribution", &distribution).unwrap();
+        archive.add_file("a.pkg/Payload", &payload).unwrap();
+        let path = dir.path().join("a.pkg");
+        archive.write(&path, Some(&signer)).unwrap();
+
+        let data = fs::read(&path).unwrap();
+        assert_eq!(&data[0..8], b"xar!\0\x1c\0\x01");
+        let toc_len = u64::from_be_bytes(data[8..16].try_into().unwrap()) as usize;
+        let compressed_toc = &data[28..28 + toc_len];
+        let mut toc = String::new();
+        ZlibDecoder::new(compressed_toc)
+            .read_to_string(&mut toc)
+            .unwrap();
+        assert!(toc.contains("<X509Certificate>AQID</X509Certificate>"));
+        assert!(toc.contains("<name>a.pkg</name>\n   <type>directory</type>"));
+        assert!(toc.contains("<offset>107</offset>"));
+
+        let heap = &data[28 + toc_len..];
+        let checksum = Sha1::digest(compressed_toc);
+        assert_eq!(&heap[0..20], checksum.as_slice());
+        public_key
+            .verify(Pkcs1v15Sign::new::<Sha1>(), &checksum, &heap[20..84])
+            .unwrap();
+        assert_eq!(&heap[84..107], b"<installer-gui-script/>");
+        assert_eq!(&heap[107..], b"payload");
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use heck::AsKebabCase;
+use walkdir::WalkDir;
+
+use super::{deb, Context};
+use crate::{
+    config::{Config, RpmConfig},
+    util, Error,
+};
+
+/// Parses a dependency string like `glibc >= 2.28` into an RPM [`rpm::Dependency`].
+///
+/// Strings without a recognized version operator are treated as plain capability names.
+fn parse_dependency(dep: &str) -> rpm::Dependency {
+    let parts = dep.split_whitespace().collect::<Vec<_>>();
+    match parts.as_slice() {
+        [name, "=" | "==", version] => rpm::Dependency::eq(*name, *version),
+        [name, "<", version] => rpm::Dependency::less(*name, *version),
+        [name, "<=", version] => rpm::Dependency::less_eq(*name, *version),
+        [name, ">", version] => rpm::Dependency::greater(*name, *version),
+        [name, ">=", version] => rpm::Dependency::greater_eq(*name, *version),
+        _ => rpm::Dependency::any(dep.trim()),
+    }
+}
+
+/// Converts the app version into a valid RPM version.
+///
+/// RPM versions can't contain `-`, so semver pre-release separators are replaced
+/// with `~` which RPM sorts before the release version.
+fn rpm_version(version: &str) -> String {
+    version.replace('-', "~")
+}
+
+/// Adds all files under `data_dir` to the package builder.
+#[tracing::instrument(level = "trace", skip(builder))]
+fn add_data_files(
+    builder: &mut rpm::PackageBuilder,
+    data_dir: &Path,
+    resource_dir: &Path,
+) -> crate::Result<()> {
+    for entry in WalkDir::new(data_dir).sort_by_file_name() {
+        let entry = entry?;
+        let path = entry.path();
+        if path == data_dir {
+            continue;
+        }
+
+        let rel_path = path.strip_prefix(data_dir)?;
+        let rel_path = rel_path.to_str().ok_or_else(|| {
+            let msg = format!("Non-UTF-8 path: {rel_path:?}");
+            std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+        })?;
+        let dest = format!("/{rel_path}");
+
+        let file_type = entry.file_type();
+        if file_type.is_dir() {
+            // only own the directories specific to this app,
+            // system directories like `/usr/bin` belong to the `filesystem` package.
+            if path.starts_with(resource_dir) {
+                builder.with_dir_entry(rpm::FileOptions::dir(dest))?;
+            }
+        } else if file_type.is_symlink() {
+            let target =
+                fs::read_link(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+            builder.with_symlink(rpm::FileOptions::symlink(dest, target.to_string_lossy()))?;
+        } else {
+            let options = rpm::FileOptions::new(dest.as_str());
+            let options = if dest.starts_with("/etc/") {
+                options.config().noreplace()
+            } else {
+                options
+            };
+            builder.with_file(path, options)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the scriptlet at `path`.
+fn read_scriptlet(path: &Path) -> crate::Result<String> {
+    fs::read_to_string(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))
+}
+
+/// Sets the package relationships and scriptlets from the [`RpmConfig`].
+#[tracing::instrument(level = "trace", skip(builder))]
+fn apply_rpm_config(builder: &mut rpm::PackageBuilder, rpm: &RpmConfig) -> crate::Result<()> {
+    if let Some(depends) = &rpm.depends {
+        for dep in depends.to_list()? {
+            builder.requires(parse_dependency(&dep));
+        }
+    }
+    for dep in rpm.provides.iter().flatten() {
+        builder.provides(parse_dependency(dep));
+    }
+    for dep in rpm.conflicts.iter().flatten() {
+        builder.conflicts(parse_dependency(dep));
+    }
+    for dep in rpm.obsoletes.iter().flatten() {
+        builder.obsoletes(parse_dependency(dep));
+    }
+
+    if let Some(epoch) = rpm.epoch {
+        builder.epoch(epoch);
+    }
+
+    if let Some(script) = &rpm.pre_install_script {
+        builder.pre_install_script(read_scriptlet(script)?);
+    }
+    if let Some(script) = &rpm.post_install_script {
+        builder.post_install_script(read_scriptlet(script)?);
+    }
+    if let Some(script) = &rpm.pre_remove_script {
+        builder.pre_uninstall_script(read_scriptlet(script)?);
+    }
+    if let Some(script) = &rpm.post_remove_script {
+        builder.post_uninstall_script(read_scriptlet(script)?);
+    }
+
+    Ok(())
+}
+
+/// Creates the package builder with the metadata from the [`Config`].
+fn package_builder(
+    config: &Config,
+    name: &str,
+    version: &str,
+    release: &str,
+    arch: &str,
+) -> rpm::PackageBuilder {
+    let license = config
+        .rpm()
+        .and_then(|r| r.license.as_deref())
+        .unwrap_or("Unknown");
+    let summary = config
+        .description
+        .as_deref()
+        .unwrap_or(&config.product_name);
+
+    let mut builder = rpm::PackageBuilder::new(name, version, license, arch, summary);
+    builder.release(release);
+    builder.description(
+        config
+            .long_description
+            .as_deref()
+            .or(config.description.as_deref())
+            .unwrap_or(summary),
+    );
+    builder.vendor(config.publisher());
+    if let Some(homepage) = &config.homepage {
+        builder.url(homepage);
+    }
+    if let Some(authors) = &config.authors {
+        builder.packager(authors.join(", "));
+    }
+    builder
+}
+
+#[tracing::instrument(level = "trace", skip(ctx))]
+pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
+    let Context {
+        config,
+        intermediates_path,
+        ..
+    } = ctx;
+
+    let arch = match config.target_arch()? {
+        "x86" => "i686",
+        "arm" => "armv7hl",
+        other => other,
+    };
+
+    let intermediates_path = intermediates_path.join("rpm");
+    util::create_clean_dir(&intermediates_path)?;
+
+    let name = config
+        .rpm()
+        .and_then(|rpm| rpm.package_name.clone())
+        .unwrap_or_else(|| AsKebabCase(&config.product_name).to_string());
+    let version = rpm_version(&config.version);
+    let release = config
+        .rpm()
+        .and_then(|rpm| rpm.release.as_deref())
+        .unwrap_or("1");
+
+    let rpm_name = format!("{name}-{version}-{release}.{arch}.rpm");
+    let rpm_path = config.out_dir().join(&rpm_name);
+
+    tracing::info!("Packaging {} ({})", rpm_name, rpm_path.display());
+
+    tracing::debug!("Generating data");
+    let data_dir = intermediates_path.join("data");
+    let _ = deb::generate_data(config, &data_dir)?;
+
+    tracing::debug!("Copying files specified in `rpm.files`");
+    if let Some(files) = config.rpm().and_then(|r| r.files.as_ref()) {
+        deb::copy_custom_files(files, &data_dir)?;
+    }
+
+    let mut builder = package_builder(config, &name, &version, release, arch);
+
+    tracing::debug!("Adding files to the package");
+    let resource_dir = data_dir.join("usr/lib").join(config.main_binary_name()?);
+    add_data_files(&mut builder, &data_dir, &resource_dir)?;
+
+    if let Some(rpm) = config.rpm() {
+        apply_rpm_config(&mut builder, rpm)?;
+    }
+
+    tracing::debug!("Creating final package: {}", rpm_path.display());
+    let package = builder.build()?;
+    let parent = rpm_path
+        .parent()
+        .ok_or_else(|| Error::ParentDirNotFound(rpm_path.clone()))?;
+    fs::create_dir_all(parent).map_err(|e| Error::IoWithPath(parent.to_path_buf(), e))?;
+    package.write_file(&rpm_path)?;
+
+    Ok(vec![rpm_path])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dependencies() {
+        assert_eq!(parse_dependency("glibc"), rpm::Dependency::any("glibc"));
+        assert_eq!(
+            parse_dependency("glibc >= 2.28"),
+            rpm::Dependency::greater_eq("glibc", "2.28")
+        );
+        assert_eq!(
+            parse_dependency("openssl-libs = 1:3.0.7"),
+            rpm::Dependency::eq("openssl-libs", "1:3.0.7")
+        );
+        assert_eq!(
+            parse_dependency("libfoo < 2"),
+            rpm::Dependency::less("libfoo", "2")
+        );
+    }
+
+    #[test]
+    fn converts_version() {
+        assert_eq!(rpm_version("1.2.3"), "1.2.3");
+        assert_eq!(rpm_version("1.2.3-beta.1"), "1.2.3~beta.1");
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use handlebars::{to_json, Handlebars};
+use heck::AsKebabCase;
+
+use super::{
+    deb,
+    squashfs::{self, Compressor},
+    Context,
+};
+use crate::{util, Error};
+
+/// The default interfaces the app connects to,
+/// giving access to the display servers, GPU, home directory and network.
+const DEFAULT_PLUGS: &[&str] = &[
+    "desktop",
+    "desktop-legacy",
+    "wayland",
+    "x11",
+    "opengl",
+    "home",
+    "network",
+];
+
+/// Quotes `value` as a YAML double-quoted scalar, which uses the same escapes as JSON strings.
+fn yaml_quote(value: &str) -> String {
+    serde_json::Value::from(value).to_string()
+}
+
+/// Returns the arguments of a desktop entry `Exec` key value, without the program.
+fn exec_args(exec: &str) -> &str {
+    let program_end = match exec.strip_prefix('"') {
+        Some(quoted) => quoted.find('"').map(|i| i + 2),
+        None => exec.find(' '),
+    };
+    program_end
+        .map(|i| exec[i..].trim_start())
+        .unwrap_or_default()
+}
+
+#[tracing::instrument(level = "trace", skip(ctx))]
+pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
+    let Context {
+        config,
+        intermediates_path,
+        ..
+    } = ctx;
+
+    let snap = config.snap();
+    let main_binary_name = config.main_binary_name()?;
+    let app_name = AsKebabCase(&main_binary_name).to_string();
+    let snap_name = snap
+        .and_then(|s| s.name.clone())
+        .unwrap_or_else(|| app_name.clone());
+    let arch = match config.target_arch()? {
+        "x86" => "i386",
+        "x86_64" => "amd64",
+        "arm" => "armhf",
+        "aarch64" => "arm64",
+        other => other,
+    };
+
+    let snap_filename = format!("{}_{}_{}.snap", snap_name, config.version, arch);
+    let snap_path = config.out_dir().join(&snap_filename);
+
+    tracing::info!("Packaging {} ({})", snap_filename, snap_path.display());
+
+    let intermediates_path = intermediates_path.join("snap");
+    util::create_clean_dir(&intermediates_path)?;
+
+    let snap_dir = intermediates_path.join(&snap_name);
+    tracing::debug!("Generating data");
+    let icons = deb::generate_data(config, &snap_dir)?;
+    tracing::debug!("Copying files specified in `snap.files`");
+    if let Some(files) = snap.and_then(|s| s.files.as_ref()) {
+        deb::copy_custom_files(files, &snap_dir)?;
+    }
+
+    let gui_dir = snap_dir.join("meta/gui");
+    fs::create_dir_all(&gui_dir).map_err(|e| Error::IoWithPath(gui_dir.clone(), e))?;
+
+    // the largest square icon is used as the snap icon
+    let icon = icons
+        .iter()
+        .filter(|i| i.width == i.height)
+        .max_by_key(|i| i.width);
+    if let Some(icon) = icon {
+        let dest = gui_dir.join("icon.png");
+        fs::copy(&icon.path, &dest).map_err(|e| Error::CopyFile(icon.path.clone(), dest, e))?;
+    }
+
+    // snapd only exports the desktop entries in `meta/gui`, which must run the app through its snap command
+    let desktop_path = snap_dir
+        .join("usr/share/applications")
+        .join(format!("{main_binary_name}.desktop"));
+    if desktop_path.exists() {
+        tracing::debug!("Generating desktop file");
+        let command = if app_name == snap_name {
+            snap_name.clone()
+        } else {
+            format!("{snap_name}.{app_name}")
+        };
+        let desktop = fs::read_to_string(&desktop_path)
+            .map_err(|e| Error::IoWithPath(desktop_path.clone(), e))?;
+        let desktop = desktop
+            .lines()
+            .map(|line| {
+                if let Some(exec) = line.strip_prefix("Exec=") {
+                    format!("Exec={command} {}", exec_args(exec))
+                        .trim_end()
+                        .to_string()
+                } else if line.starts_with("Icon=") && icon.is_some() {
+                    "Icon=${SNAP}/meta/gui/icon.png".to_string()
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let dest = gui_dir.join(format!("{app_name}.desktop"));
+        fs::write(&dest, desktop + "\n").map_err(|e| Error::IoWithPath(dest, e))?;
+    }
+
+    tracing::debug!("Generating meta/snap.yaml");
+    let description = config
+        .long_description
+        .as_deref()
+        .or(config.description.as_deref())
+        .unwrap_or(config.product_name.as_str());
+    let summary = snap
+        .and_then(|s| s.summary.as_deref())
+        .or(config.description.as_deref())
+        .unwrap_or(config.product_name.as_str());
+    let quote_all = |values: &[String]| values.iter().map(|v| yaml_quote(v)).collect::<Vec<_>>();
+    let plugs = snap
+        .and_then(|s| s.plugs.clone())
+        .unwrap_or_else(|| DEFAULT_PLUGS.iter().map(ToString::to_string).collect());
+
+    let mut data = BTreeMap::new();
+    data.insert("name", to_json(yaml_quote(&snap_name)));
+    data.insert("version", to_json(yaml_quote(&config.version)));
+    data.insert("summary", to_json(yaml_quote(summary)));
+    data.insert("description", to_json(yaml_quote(description)));
+    data.insert(
+        "base",
+        to_json(yaml_quote(
+            snap.and_then(|s| s.base.as_deref()).unwrap_or("core24"),
+        )),
+    );
+    data.insert(
+        "confinement",
+        to_json(
+            snap.and_then(|s| s.confinement)
+                .unwrap_or_default()
+                .as_str(),
+        ),
+    );
+    data.insert(
+        "grade",
+        to_json(snap.and_then(|s| s.grade).unwrap_or_default().as_str()),
+    );
+    data.insert("arch", to_json(yaml_quote(arch)));
+    data.insert("app_name", to_json(yaml_quote(&app_name)));
+    data.insert(
+        "command",
+        to_json(yaml_quote(&format!("usr/bin/{main_binary_name}"))),
+    );
+    data.insert("plugs", to_json(quote_all(&plugs)));
+    data.insert(
+        "slots",
+        to_json(quote_all(
+            snap.and_then(|s| s.slots.as_deref()).unwrap_or_default(),
+        )),
+    );
+
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(handlebars::no_escape);
+    let snap_yaml = handlebars.render_template(include_str!("snap.yaml"), &data)?;
+    let snap_yaml_path = snap_dir.join("meta/snap.yaml");
+    fs::write(&snap_yaml_path, snap_yaml).map_err(|e| Error::IoWithPath(snap_yaml_path, e))?;
+
+    tracing::debug!("Creating {}", snap_path.display());
+    squashfs::create(&snap_dir, Compressor::Xz, &[], &snap_path)?;
+
+    Ok(vec![snap_path])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_exec_program() {
+        assert_eq!(exec_args("my-app %U"), "%U");
+        assert_eq!(exec_args("\"my app\" --flag %F"), "--flag %F");
+        assert_eq!(exec_args("my-app"), "");
+    }
+}
@@ -0,0 +1,72 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! squashfs images creation, used by the AppImage and snap packages.
+
+use std::{
+    fs,
+    io::{Seek, SeekFrom, Write},
+    os::unix::fs::MetadataExt,
+    path::Path,
+};
+
+pub use backhand::compression::Compressor;
+use backhand::{FilesystemCompressor, FilesystemWriter, NodeHeader};
+use walkdir::WalkDir;
+
+use crate::{util, Error};
+
+/// Creates the squashfs image of `src_dir`, compressed with `compressor`,
+/// and writes it to `dest` after the `header` bytes.
+///
+/// All files are owned by root in the image.
+#[tracing::instrument(level = "trace", skip(header))]
+pub fn create(
+    src_dir: &Path,
+    compressor: Compressor,
+    header: &[u8],
+    dest: &Path,
+) -> crate::Result<()> {
+    let mut squashfs = FilesystemWriter::default();
+    squashfs.set_compressor(FilesystemCompressor::new(compressor, None)?);
+    squashfs.set_only_root_id();
+    squashfs.set_root_mode(0o755);
+    squashfs.set_current_time();
+
+    for entry in WalkDir::new(src_dir).sort_by_file_name() {
+        let entry = entry?;
+        let path = entry.path();
+        if path == src_dir {
+            continue;
+        }
+
+        let rel_path = path.strip_prefix(src_dir)?;
+        let metadata =
+            fs::symlink_metadata(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+        let header = NodeHeader::new(
+            (metadata.mode() & 0o7777) as u16,
+            0,
+            0,
+            metadata.mtime() as u32,
+        );
+
+        if metadata.is_dir() {
+            squashfs.push_dir(rel_path, header)?;
+        } else if metadata.is_symlink() {
+            let target =
+                fs::read_link(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+            squashfs.push_symlink(target, rel_path, header)?;
+        } else {
+            squashfs.push_file_from_path(path, rel_path, header)?;
+        }
+    }
+
+    let mut file = util::create_file(dest)?;
+    file.write_all(header)?;
+    file.seek(SeekFrom::End(0))?;
+    squashfs.write_with_offset(&mut file, header.len() as u64)?;
+    file.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,211 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Universal binaries, merging the Mach-O binaries built for each macOS architecture
+//! into a single fat file like `lipo -create` does.
+
+use std::{fs, path::Path};
+
+use crate::Error;
+
+const FAT_MAGIC: u32 = 0xcafebabe;
+const MH_MAGIC: u32 = 0xfeedface;
+const MH_MAGIC_64: u32 = 0xfeedfacf;
+const CPU_TYPE_ARM: u32 = 12;
+/// The ABI bits of the CPU type.
+const CPU_ARCH_MASK: u32 = 0xff000000;
+/// The capability bits of the CPU subtype, ignored when comparing architectures.
+const CPU_SUBTYPE_MASK: u32 = 0xff000000;
+
+/// An architecture of a universal binary.
+pub(crate) struct Slice<'a> {
+    pub cputype: u32,
+    pub cpusubtype: u32,
+    /// The alignment of the slice in the fat file, as a power of 2.
+    pub align: u32,
+    pub data: &'a [u8],
+}
+
+/// Merges the thin or universal binaries at `sources` into the universal binary at `dest`.
+pub(crate) fn create<P: AsRef<Path>>(sources: &[P], dest: &Path) -> crate::Result<()> {
+    let binaries = sources
+        .iter()
+        .map(|source| {
+            let source = source.as_ref();
+            fs::read(source).map_err(|e| Error::IoWithPath(source.to_path_buf(), e))
+        })
+        .collect::<crate::Result<Vec<_>>>()?;
+
+    let mut slices: Vec<Slice> = Vec::new();
+    for (source, data) in sources.iter().zip(&binaries) {
+        let source = source.as_ref();
+        for slice in read_slices(data).map_err(|e| universal_error(source, e))? {
+            if slices.iter().any(|s| {
+                s.cputype == slice.cputype
+                    && s.cpusubtype & !CPU_SUBTYPE_MASK == slice.cpusubtype & !CPU_SUBTYPE_MASK
+            }) {
+                return Err(universal_error(
+                    source,
+                    format!("duplicate architecture with cputype {:#x}", slice.cputype),
+                ));
+            }
+            slices.push(slice);
+        }
+    }
+
+    let data = write_fat(slices).map_err(|e| universal_error(dest, e))?;
+    fs::write(dest, data).map_err(|e| Error::IoWithPath(dest.to_path_buf(), e))?;
+
+    // keep the permissions of the binaries, they are usually executables
+    if let Some(source) = sources.first() {
+        let source = source.as_ref();
+        let permissions = fs::metadata(source)
+            .map_err(|e| Error::IoWithPath(source.to_path_buf(), e))?
+            .permissions();
+        fs::set_permissions(dest, permissions)
+            .map_err(|e| Error::IoWithPath(dest.to_path_buf(), e))?;
+    }
+
+    Ok(())
+}
+
+fn universal_error(path: &Path, message: impl Into<String>) -> Error {
+    Error::UniversalBinaryFailed(path.to_path_buf(), message.into())
+}
+
+/// Returns whether `data` is a universal binary.
+pub(crate) fn is_fat(data: &[u8]) -> bool {
+    data.get(..4).is_some_and(|b| b == FAT_MAGIC.to_be_bytes())
+}
+
+/// Returns the architectures of a thin or universal binary.
+pub(crate) fn read_slices(data: &[u8]) -> Result<Vec<Slice<'_>>, String> {
+    let read = |offset: usize, big_endian: bool| {
+        data.get(offset..offset + 4)
+            .map(|b| {
+                let b = b.try_into().unwrap();
+                if big_endian {
+                    u32::from_be_bytes(b)
+                } else {
+                    u32::from_le_bytes(b)
+                }
+            })
+            .ok_or_else(|| "truncated file".to_string())
+    };
+
+    if is_fat(data) {
+        let count = read(4, true)? as usize;
+        let mut slices = Vec::with_capacity(count);
+        for i in 0..count {
+            let base = 8 + i * 20;
+            let offset = read(base + 8, true)? as usize;
+            let size = read(base + 12, true)? as usize;
+            let data = data
+                .get(offset..offset + size)
+                .ok_or_else(|| "truncated file".to_string())?;
+            slices.push(Slice {
+                cputype: read(base, true)?,
+                cpusubtype: read(base + 4, true)?,
+                align: read(base + 16, true)?,
+                data,
+            });
+        }
+        return Ok(slices);
+    }
+
+    match read(0, false)? {
+        MH_MAGIC | MH_MAGIC_64 => {
+            let cputype = read(4, false)?;
+            Ok(vec![Slice {
+                cputype,
+                cpusubtype: read(8, false)?,
+                // arm64 uses 16K pages
+                align: if cputype & !CPU_ARCH_MASK == CPU_TYPE_ARM {
+                    14
+                } else {
+                    12
+                },
+                data,
+            }])
+        }
+        _ => Err("not a little endian Mach-O binary".into()),
+    }
+}
+
+/// Writes the fat header followed by the aligned architectures.
+pub(crate) fn write_fat(mut slices: Vec<Slice>) -> Result<Vec<u8>, String> {
+    // like lipo, the architectures with the smallest alignment come first
+    slices.sort_by_key(|s| s.align);
+
+    let mut offsets = Vec::with_capacity(slices.len());
+    let mut offset = 8 + 20 * slices.len();
+    for slice in &slices {
+        offset = offset.div_ceil(1 << slice.align) * (1 << slice.align);
+        offsets.push(offset);
+        offset += slice.data.len();
+    }
+    if offset > u32::MAX as usize {
+        return Err("universal binaries larger than 4 GiB are not supported".into());
+    }
+
+    let mut out = Vec::with_capacity(offset);
+    out.extend_from_slice(&FAT_MAGIC.to_be_bytes());
+    out.extend_from_slice(&(slices.len() as u32).to_be_bytes());
+    for (slice, offset) in slices.iter().zip(&offsets) {
+        for value in [
+            slice.cputype,
+            slice.cpusubtype,
+            *offset as u32,
+            slice.data.len() as u32,
+            slice.align,
+        ] {
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+    for (slice, offset) in slices.iter().zip(offsets) {
+        out.resize(offset, 0);
+        out.extend_from_slice(slice.data);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thin(cputype: u32, cpusubtype: u32, len: usize) -> Vec<u8> {
+        let mut data = vec![0xab; len];
+        data[0..4].copy_from_slice(&MH_MAGIC_64.to_le_bytes());
+        data[4..8].copy_from_slice(&cputype.to_le_bytes());
+        data[8..12].copy_from_slice(&cpusubtype.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn merges_architectures() {
+        let arm64 = thin(0x0100000c, 0, 100);
+        let x86_64 = thin(0x01000007, 0x80000003, 50);
+        let mut slices = read_slices(&arm64).unwrap();
+        slices.extend(read_slices(&x86_64).unwrap());
+        let fat = write_fat(slices).unwrap();
+
+        assert_eq!(
+            fat[..48],
+            [
+                0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 2, //
+                1, 0, 0, 7, 0x80, 0, 0, 3, 0, 0, 0x10, 0, 0, 0, 0, 50, 0, 0, 0, 12, //
+                1, 0, 0, 12, 0, 0, 0, 0, 0, 0, 0x40, 0, 0, 0, 0, 100, 0, 0, 0, 14,
+            ]
+        );
+        assert_eq!(fat[0x1000..0x1000 + 50], x86_64);
+        assert_eq!(fat[0x4000..], arm64);
+
+        // merging a universal binary again keeps its architectures
+        let slices = read_slices(&fat).unwrap();
+        assert_eq!(slices.len(), 2);
+        assert_eq!(write_fat(slices).unwrap(), fat);
+
+        assert!(read_slices(b"\x7fELF").is_err());
+    }
+}
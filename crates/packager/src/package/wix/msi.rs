@@ -0,0 +1,1538 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Builds MSI installers without the WiX toolset, which only runs on Windows.
+//!
+//! The installer mirrors the one described by `main.wxs`: the database tables are
+//! written directly and the files are stored in an MSZIP cabinet embedded in the package.
+//! The WiX UI dialogs, custom templates, fragments and merge modules are only available
+//! when building on Windows, the generated installer shows the basic progress UI instead.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+};
+
+use cab::{CabinetBuilder, CompressionType};
+use msi::{CodePage, Column, Insert, Language, Package, PackageType, Value};
+use regex::Regex;
+use sha1::{Digest, Sha1};
+use uuid::Uuid;
+
+use super::{
+    convert_version, generate_binaries_data, generate_guid, generate_package_guid,
+    generate_resource_data, generate_upgrade_code, LanguageMetadata, ResourceDirectory,
+};
+use crate::{
+    codesign::windows as codesign,
+    config::{Config, WixLanguage},
+    package::Context,
+    util, Error,
+};
+
+/// The name of the cabinet stream embedded in the package.
+const CABINET_NAME: &str = "app.cab";
+
+/// The `PropertyKey` of `System.AppUserModel.ID`.
+const APP_USER_MODEL_ID_KEY: &str = "{9F4C2855-9F79-4B39-A8D0-E1D42DE1D5F3}, 5";
+
+// Registry roots.
+const HKCU: i32 = 1;
+const HKLM: i32 = 2;
+
+// Component attributes.
+const COMPONENT_REGISTRY_KEY_PATH: i32 = 4;
+const COMPONENT_64BIT: i32 = 256;
+
+// File attributes.
+const FILE_VITAL: i32 = 512;
+
+// Feature attributes.
+const FEATURE_DISALLOW_ADVERTISE: i32 = 8;
+const FEATURE_UI_DISALLOW_ABSENT: i32 = 16;
+
+// Upgrade attributes.
+const UPGRADE_MIGRATE_FEATURES: i32 = 1;
+const UPGRADE_ONLY_DETECT: i32 = 2;
+const UPGRADE_VERSION_MIN_INCLUSIVE: i32 = 256;
+const UPGRADE_VERSION_MAX_INCLUSIVE: i32 = 512;
+
+// RemoveFile install modes.
+const REMOVE_ON_UNINSTALL: i32 = 2;
+
+/// Custom action type that sets a property from a formatted string.
+const CUSTOM_ACTION_SET_PROPERTY: i32 = 51;
+
+/// Locator type that reads the raw registry value.
+const LOCATOR_RAW_VALUE: i32 = 2;
+
+const INSTALL_UI_SEQUENCE: &[(&str, Option<&str>, i32)] = &[
+    ("LaunchConditions", None, 100),
+    ("FindRelatedProducts", None, 200),
+    ("AppSearch", None, 400),
+    ("CostInitialize", None, 800),
+    ("FileCost", None, 900),
+    ("CostFinalize", None, 1000),
+    ("MigrateFeatureStates", None, 1200),
+    ("ExecuteAction", None, 1300),
+];
+
+const INSTALL_EXECUTE_SEQUENCE: &[(&str, Option<&str>, i32)] = &[
+    ("LaunchConditions", None, 100),
+    ("FindRelatedProducts", None, 200),
+    ("AppSearch", None, 400),
+    ("CostInitialize", None, 800),
+    ("FileCost", None, 900),
+    ("CostFinalize", None, 1000),
+    ("SetARPINSTALLLOCATION", None, 1001),
+    ("MigrateFeatureStates", None, 1200),
+    ("InstallValidate", None, 1400),
+    ("InstallInitialize", None, 1500),
+    ("RemoveExistingProducts", None, 1501),
+    ("ProcessComponents", None, 1600),
+    ("UnpublishFeatures", None, 1800),
+    ("RemoveRegistryValues", None, 2600),
+    (
+        "RemoveShortcuts",
+        Some("Installed AND NOT UPGRADINGPRODUCTCODE"),
+        3200,
+    ),
+    ("RemoveFiles", None, 3500),
+    ("RemoveFolders", None, 3600),
+    ("CreateFolders", None, 3700),
+    ("InstallFiles", None, 4000),
+    ("CreateShortcuts", None, 4500),
+    ("WriteRegistryValues", None, 5000),
+    ("RegisterUser", None, 6000),
+    ("RegisterProduct", None, 6100),
+    ("PublishFeatures", None, 6300),
+    ("PublishProduct", None, 6400),
+    ("InstallFinalize", None, 6600),
+];
+
+const ADMIN_UI_SEQUENCE: &[(&str, Option<&str>, i32)] = &[
+    ("CostInitialize", None, 800),
+    ("FileCost", None, 900),
+    ("CostFinalize", None, 1000),
+    ("ExecuteAction", None, 1300),
+];
+
+const ADMIN_EXECUTE_SEQUENCE: &[(&str, Option<&str>, i32)] = &[
+    ("CostInitialize", None, 800),
+    ("FileCost", None, 900),
+    ("CostFinalize", None, 1000),
+    ("InstallValidate", None, 1400),
+    ("InstallInitialize", None, 1500),
+    ("InstallAdminPackage", None, 3900),
+    ("InstallFiles", None, 4000),
+    ("InstallFinalize", None, 6600),
+];
+
+const ADVT_EXECUTE_SEQUENCE: &[(&str, Option<&str>, i32)] = &[
+    ("CostInitialize", None, 800),
+    ("CostFinalize", None, 1000),
+    ("InstallValidate", None, 1400),
+    ("InstallInitialize", None, 1500),
+    ("CreateShortcuts", None, 4500),
+    ("PublishFeatures", None, 6300),
+    ("PublishProduct", None, 6400),
+    ("InstallFinalize", None, 6600),
+];
+
+/// The columns of the tables used by the installer, as defined by the Windows Installer SDK.
+fn table_columns(table: &str) -> Vec<Column> {
+    match table {
+        "Property" => vec![
+            Column::build("Property").primary_key().id_string(72),
+            Column::build("Value").localizable().text_string(0),
+        ],
+        "Directory" => vec![
+            Column::build("Directory").primary_key().id_string(72),
+            Column::build("Directory_Parent")
+                .nullable()
+                .foreign_key("Directory", 1)
+                .id_string(72),
+            Column::build("DefaultDir")
+                .localizable()
+                .category(msi::Category::DefaultDir)
+                .string(255),
+        ],
+        "Component" => vec![
+            Column::build("Component").primary_key().id_string(72),
+            Column::build("ComponentId")
+                .nullable()
+                .category(msi::Category::Guid)
+                .string(38),
+            Column::build("Directory_")
+                .foreign_key("Directory", 1)
+                .id_string(72),
+            Column::build("Attributes").int16(),
+            Column::build("Condition")
+                .nullable()
+                .category(msi::Category::Condition)
+                .string(255),
+            Column::build("KeyPath").nullable().id_string(72),
+        ],
+        "Feature" => vec![
+            Column::build("Feature").primary_key().id_string(38),
+            Column::build("Feature_Parent")
+                .nullable()
+                .foreign_key("Feature", 1)
+                .id_string(38),
+            Column::build("Title")
+                .nullable()
+                .localizable()
+                .text_string(64),
+            Column::build("Description")
+                .nullable()
+                .localizable()
+                .text_string(255),
+            Column::build("Display").nullable().int16(),
+            Column::build("Level").int16(),
+            Column::build("Directory_")
+                .nullable()
+                .foreign_key("Directory", 1)
+                .category(msi::Category::UpperCase)
+                .string(72),
+            Column::build("Attributes").int16(),
+        ],
+        "FeatureComponents" => vec![
+            Column::build("Feature_")
+                .primary_key()
+                .foreign_key("Feature", 1)
+                .id_string(38),
+            Column::build("Component_")
+                .primary_key()
+                .foreign_key("Component", 1)
+                .id_string(72),
+        ],
+        "File" => vec![
+            Column::build("File").primary_key().id_string(72),
+            Column::build("Component_")
+                .foreign_key("Component", 1)
+                .id_string(72),
+            Column::build("FileName")
+                .localizable()
+                .category(msi::Category::Filename)
+                .string(255),
+            Column::build("FileSize").int32(),
+            Column::build("Version")
+                .nullable()
+                .category(msi::Category::Version)
+                .string(72),
+            Column::build("Language")
+                .nullable()
+                .category(msi::Category::Language)
+                .string(20),
+            Column::build("Attributes").nullable().int16(),
+            Column::build("Sequence").int32(),
+        ],
+        "Media" => vec![
+            Column::build("DiskId")
+                .primary_key()
+                .range(1, 32767)
+                .int16(),
+            Column::build("LastSequence").int32(),
+            Column::build("DiskPrompt")
+                .nullable()
+                .localizable()
+                .text_string(64),
+            Column::build("Cabinet")
+                .nullable()
+                .category(msi::Category::Cabinet)
+                .string(255),
+            Column::build("VolumeLabel").nullable().text_string(32),
+            Column::build("Source")
+                .nullable()
+                .category(msi::Category::Property)
+                .string(72),
+        ],
+        "Registry" => vec![
+            Column::build("Registry").primary_key().id_string(72),
+            Column::build("Root").range(-1, 3).int16(),
+            Column::build("Key")
+                .localizable()
+                .category(msi::Category::RegPath)
+                .string(255),
+            Column::build("Name")
+                .nullable()
+                .localizable()
+                .formatted_string(255),
+            Column::build("Value")
+                .nullable()
+                .localizable()
+                .formatted_string(0),
+            Column::build("Component_")
+                .foreign_key("Component", 1)
+                .id_string(72),
+        ],
+        "RemoveFile" => vec![
+            Column::build("FileKey").primary_key().id_string(72),
+            Column::build("Component_")
+                .foreign_key("Component", 1)
+                .id_string(72),
+            Column::build("FileName")
+                .nullable()
+                .localizable()
+                .category(msi::Category::WildCardFilename)
+                .string(255),
+            Column::build("DirProperty").id_string(72),
+            Column::build("InstallMode")
+                .enum_values(&["1", "2", "3"])
+                .int16(),
+        ],
+        "Shortcut" => vec![
+            Column::build("Shortcut").primary_key().id_string(72),
+            Column::build("Directory_")
+                .foreign_key("Directory", 1)
+                .id_string(72),
+            Column::build("Name")
+                .localizable()
+                .category(msi::Category::Filename)
+                .string(128),
+            Column::build("Component_")
+                .foreign_key("Component", 1)
+                .id_string(72),
+            Column::build("Target")
+                .category(msi::Category::Shortcut)
+                .string(72),
+            Column::build("Arguments").nullable().formatted_string(255),
+            Column::build("Description")
+                .nullable()
+                .localizable()
+                .text_string(255),
+            Column::build("Hotkey").nullable().int16(),
+            Column::build("Icon_")
+                .nullable()
+                .foreign_key("Icon", 1)
+                .id_string(72),
+            Column::build("IconIndex").nullable().int16(),
+            Column::build("ShowCmd").nullable().int16(),
+            Column::build("WkDir").nullable().id_string(72),
+        ],
+        "MsiShortcutProperty" => vec![
+            Column::build("MsiShortcutProperty")
+                .primary_key()
+                .id_string(72),
+            Column::build("Shortcut_")
+                .foreign_key("Shortcut", 1)
+                .id_string(72),
+            Column::build("PropertyKey").formatted_string(0),
+            Column::build("PropVariantValue").formatted_string(0),
+        ],
+        "Icon" => vec![
+            Column::build("Name").primary_key().id_string(72),
+            Column::build("Data").binary(),
+        ],
+        "Upgrade" => vec![
+            Column::build("UpgradeCode")
+                .primary_key()
+                .category(msi::Category::Guid)
+                .string(38),
+            Column::build("VersionMin")
+                .primary_key()
+                .nullable()
+                .text_string(20),
+            Column::build("VersionMax")
+                .primary_key()
+                .nullable()
+                .text_string(20),
+            Column::build("Language")
+                .primary_key()
+                .nullable()
+                .category(msi::Category::Language)
+                .string(255),
+            Column::build("Attributes").primary_key().int32(),
+            Column::build("Remove").nullable().formatted_string(255),
+            Column::build("ActionProperty")
+                .category(msi::Category::UpperCase)
+                .string(72),
+        ],
+        "LaunchCondition" => vec![
+            Column::build("Condition")
+                .primary_key()
+                .category(msi::Category::Condition)
+                .string(255),
+            Column::build("Description")
+                .localizable()
+                .formatted_string(255),
+        ],
+        "AppSearch" => vec![
+            Column::build("Property").primary_key().id_string(72),
+            Column::build("Signature_").primary_key().id_string(72),
+        ],
+        "RegLocator" => vec![
+            Column::build("Signature_").primary_key().id_string(72),
+            Column::build("Root").range(0, 3).int16(),
+            Column::build("Key")
+                .category(msi::Category::RegPath)
+                .string(255),
+            Column::build("Name").nullable().formatted_string(255),
+            Column::build("Type").nullable().int16(),
+        ],
+        "CustomAction" => vec![
+            Column::build("Action").primary_key().id_string(72),
+            Column::build("Type").int16(),
+            Column::build("Source")
+                .nullable()
+                .category(msi::Category::CustomSource)
+                .string(72),
+            Column::build("Target").nullable().formatted_string(255),
+        ],
+        // the sequence tables
+        _ => vec![
+            Column::build("Action").primary_key().id_string(72),
+            Column::build("Condition")
+                .nullable()
+                .category(msi::Category::Condition)
+                .string(255),
+            Column::build("Sequence").nullable().int16(),
+        ],
+    }
+}
+
+/// Returns whether `name` is a valid 8.3 file name.
+fn is_short_name(name: &str) -> bool {
+    let (base, ext) = name.split_once('.').unwrap_or((name, ""));
+    let is_valid_char = |c: char| c.is_ascii_alphanumeric() || "_-~!#$%&'()@^`{}".contains(c);
+    !base.is_empty()
+        && base.len() <= 8
+        && ext.len() <= 3
+        && base.chars().all(is_valid_char)
+        && ext.chars().all(is_valid_char)
+}
+
+/// Returns the `short|long` name used by the `File`, `Directory` and `Shortcut` tables.
+///
+/// Names that are not valid 8.3 names get a short name derived from a hash of the name and `key`.
+fn msi_file_name(key: &str, name: &str) -> String {
+    if is_short_name(name) {
+        return name.to_string();
+    }
+
+    let hash = Sha1::digest(format!("{key}|{name}"));
+    let extension = Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .filter(|e| !e.is_empty() && e.len() <= 3 && is_short_name(e))
+        .map(|e| format!(".{e}"))
+        .unwrap_or_default();
+    format!("{}{extension}|{name}", &hex::encode(hash)[..8])
+}
+
+/// Formats a GUID the way Windows Installer expects it, uppercase and wrapped in braces.
+fn msi_guid(guid: &str) -> String {
+    format!("{{{}}}", guid.to_uppercase())
+}
+
+/// The localized strings used by the installer.
+fn locale_strings(
+    config: &Config,
+    language_metadata: &LanguageMetadata,
+    locale_path: Option<PathBuf>,
+) -> crate::Result<HashMap<String, String>> {
+    let regex = Regex::new(r#"<String\s+Id="(\w+)"[^>]*>([^<]*)</String>"#)?;
+    let unescape = |s: &str| {
+        s.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&")
+    };
+
+    let defaults = include_str!("./default-locale-strings.xml")
+        .replace("__language__", &language_metadata.lang_id.to_string())
+        .replace("__codepage__", &language_metadata.ascii_code.to_string())
+        .replace("__productName__", &config.product_name);
+    let mut strings = regex
+        .captures_iter(&defaults)
+        .map(|c| (c[1].to_string(), unescape(&c[2])))
+        .collect::<HashMap<_, _>>();
+
+    if let Some(path) = locale_path {
+        let contents = fs::read_to_string(&path).map_err(|e| Error::IoWithPath(path, e))?;
+        for c in regex.captures_iter(&contents) {
+            strings.insert(c[1].to_string(), unescape(&c[2]));
+        }
+    }
+
+    Ok(strings)
+}
+
+/// Converts the icon to use on the installer to the ICO format if needed.
+fn product_icon(config: &Config, intermediates_path: &Path) -> crate::Result<Option<PathBuf>> {
+    let Some(icon) = config.find_ico()? else {
+        return Ok(None);
+    };
+
+    if icon.extension().and_then(|e| e.to_str()) == Some("ico") {
+        return Ok(Some(icon));
+    }
+
+    let ico_path = intermediates_path.join("icon.ico");
+    tracing::debug!("Converting {} to {}", icon.display(), ico_path.display());
+    image::open(&icon)?
+        .thumbnail(256, 256)
+        .save_with_format(&ico_path, image::ImageFormat::Ico)?;
+    Ok(Some(ico_path))
+}
+
+/// The rows of the installer database, keyed by table name.
+#[derive(Default)]
+struct Tables {
+    rows: BTreeMap<&'static str, Vec<Vec<Value>>>,
+    /// The files to store in the cabinet, in sequence order, with their `File` table key.
+    files: Vec<(String, PathBuf)>,
+    registry_count: usize,
+}
+
+impl Tables {
+    fn insert(&mut self, table: &'static str, row: Vec<Value>) {
+        self.rows.entry(table).or_default().push(row);
+    }
+
+    fn add_directory(&mut self, id: &str, parent: Option<&str>, default_dir: String) {
+        self.insert(
+            "Directory",
+            vec![
+                id.into(),
+                parent.map(Into::into).unwrap_or(Value::Null),
+                default_dir.into(),
+            ],
+        );
+    }
+
+    fn add_component(
+        &mut self,
+        id: &str,
+        guid: String,
+        directory: &str,
+        attributes: i32,
+        key_path: &str,
+    ) {
+        self.insert(
+            "Component",
+            vec![
+                id.into(),
+                guid.into(),
+                directory.into(),
+                attributes.into(),
+                Value::Null,
+                key_path.into(),
+            ],
+        );
+    }
+
+    fn add_file(&mut self, id: &str, component: &str, path: &Path) -> crate::Result<()> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| Error::FailedToExtractFilename(path.to_path_buf()))?
+            .to_string_lossy()
+            .into_owned();
+        let size = fs::metadata(path)
+            .map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?
+            .len();
+        let size = i32::try_from(size).map_err(|_| {
+            Error::IoWithPath(
+                path.to_path_buf(),
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "files larger than 2 GiB are not supported",
+                ),
+            )
+        })?;
+        self.files.push((id.to_string(), path.to_path_buf()));
+        let sequence = self.files.len() as i32;
+        self.insert(
+            "File",
+            vec![
+                id.into(),
+                component.into(),
+                msi_file_name(id, &name).into(),
+                size.into(),
+                Value::Null,
+                Value::Null,
+                FILE_VITAL.into(),
+                sequence.into(),
+            ],
+        );
+        Ok(())
+    }
+
+    /// Adds a registry value and returns its `Registry` table key.
+    fn add_registry(
+        &mut self,
+        component: &str,
+        root: i32,
+        key: &str,
+        name: Option<&str>,
+        value: Option<String>,
+    ) -> String {
+        self.registry_count += 1;
+        let id = format!("Reg{}", self.registry_count);
+        self.insert(
+            "Registry",
+            vec![
+                id.as_str().into(),
+                root.into(),
+                key.into(),
+                name.map(Into::into).unwrap_or(Value::Null),
+                value.map(Into::into).unwrap_or(Value::Null),
+                component.into(),
+            ],
+        );
+        id
+    }
+
+    fn add_feature_components(&mut self, feature: &str, components: &[&str]) {
+        for component in components {
+            self.insert(
+                "FeatureComponents",
+                vec![feature.into(), (*component).into()],
+            );
+        }
+    }
+
+    /// Adds the resource directories and files recursively.
+    fn add_resource_directory(
+        &mut self,
+        directory: ResourceDirectory,
+        parent: &str,
+        component_attributes: i32,
+        component_ids: &mut Vec<String>,
+    ) -> crate::Result<()> {
+        let directory_id = if directory.name.is_empty() {
+            parent.to_string()
+        } else {
+            let id = format!("I{}", Uuid::new_v4().as_simple());
+            self.add_directory(&id, Some(parent), msi_file_name(&id, &directory.name));
+            id
+        };
+
+        for file in directory.files {
+            let file_key = format!("PathFile_{}", file.id);
+            self.add_component(
+                &file.id,
+                msi_guid(&file.guid),
+                &directory_id,
+                component_attributes,
+                &file_key,
+            );
+            self.add_file(&file_key, &file.id, &file.path)?;
+            component_ids.push(file.id);
+        }
+
+        for child in directory.directories {
+            self.add_resource_directory(child, &directory_id, component_attributes, component_ids)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Generates the installer tables, shared by all languages.
+fn generate_tables(
+    config: &Config,
+    arch: &str,
+    main_binary_path: &Path,
+    icon_path: Option<&Path>,
+) -> crate::Result<Tables> {
+    let mut tables = Tables::default();
+
+    let product_name = &config.product_name;
+    let manufacturer = config.publisher();
+    let identifier = config.identifier();
+    let app_key = format!("Software\\{manufacturer}\\{product_name}");
+    let component_guid =
+        |id: &str| msi_guid(&generate_guid(format!("{identifier}.{id}").as_bytes()).to_string());
+
+    let (program_files, component_attributes) = match arch {
+        "x86" => ("ProgramFilesFolder", 0),
+        _ => ("ProgramFiles64Folder", COMPONENT_64BIT),
+    };
+
+    tables.add_directory("TARGETDIR", None, "SourceDir".into());
+    tables.add_directory("DesktopFolder", Some("TARGETDIR"), "Desktop".into());
+    tables.add_directory(program_files, Some("TARGETDIR"), "PFiles".into());
+    tables.add_directory(
+        "INSTALLDIR",
+        Some(program_files),
+        msi_file_name("INSTALLDIR", product_name),
+    );
+    tables.add_directory("ProgramMenuFolder", Some("TARGETDIR"), ".".into());
+    tables.add_directory(
+        "ApplicationProgramsFolder",
+        Some("ProgramMenuFolder"),
+        msi_file_name("ApplicationProgramsFolder", product_name),
+    );
+
+    let shortcut_name = msi_file_name("Shortcut", product_name);
+    let shortcut_description = format!("Runs {product_name}");
+    let icon = icon_path
+        .map(|_| Value::from("ProductIcon"))
+        .unwrap_or(Value::Null);
+
+    // desktop shortcut
+    let key_path = tables.add_registry(
+        "ApplicationShortcutDesktop",
+        HKCU,
+        &app_key,
+        Some("Desktop Shortcut"),
+        Some("#1".into()),
+    );
+    tables.add_component(
+        "ApplicationShortcutDesktop",
+        component_guid("ApplicationShortcutDesktop"),
+        "DesktopFolder",
+        COMPONENT_REGISTRY_KEY_PATH,
+        &key_path,
+    );
+    tables.insert(
+        "Shortcut",
+        vec![
+            "ApplicationDesktopShortcut".into(),
+            "DesktopFolder".into(),
+            shortcut_name.as_str().into(),
+            "ApplicationShortcutDesktop".into(),
+            "[#Path]".into(),
+            Value::Null,
+            shortcut_description.as_str().into(),
+            Value::Null,
+            icon.clone(),
+            Value::Null,
+            Value::Null,
+            "INSTALLDIR".into(),
+        ],
+    );
+
+    // install dir registry entries and deep links
+    let key_path = tables.add_registry(
+        "RegistryEntries",
+        HKCU,
+        &app_key,
+        Some("InstallDir"),
+        Some("[INSTALLDIR]".into()),
+    );
+    tables.add_component(
+        "RegistryEntries",
+        component_guid("RegistryEntries"),
+        "INSTALLDIR",
+        COMPONENT_REGISTRY_KEY_PATH | component_attributes,
+        &key_path,
+    );
+    for scheme in config
+        .deep_link_protocols
+        .iter()
+        .flatten()
+        .flat_map(|p| &p.schemes)
+    {
+        let key = format!("Software\\Classes\\{scheme}");
+        tables.add_registry("RegistryEntries", HKLM, &key, Some("URL Protocol"), None);
+        tables.add_registry(
+            "RegistryEntries",
+            HKLM,
+            &key,
+            None,
+            Some(format!("URL:{identifier} protocol")),
+        );
+        tables.add_registry(
+            "RegistryEntries",
+            HKLM,
+            &format!("{key}\\DefaultIcon"),
+            None,
+            Some("\"[#Path]\",0".into()),
+        );
+        tables.add_registry(
+            "RegistryEntries",
+            HKLM,
+            &format!("{key}\\shell\\open\\command"),
+            None,
+            Some("\"[#Path]\" \"%1\"".into()),
+        );
+    }
+
+    // main binary and file associations
+    tables.add_component(
+        "Path",
+        msi_guid(&generate_package_guid(config).to_string()),
+        "INSTALLDIR",
+        component_attributes,
+        "Path",
+    );
+    tables.add_file("Path", "Path", main_binary_path)?;
+    for association in config.file_associations.iter().flatten() {
+        for ext in &association.extensions {
+            let ext = ext.trim_start_matches('.');
+            let prog_id = format!("{product_name}.{ext}");
+            let prog_id_key = format!("Software\\Classes\\{prog_id}");
+            tables.add_registry(
+                "Path",
+                HKLM,
+                &format!("Software\\Classes\\.{ext}"),
+                None,
+                Some(prog_id.clone()),
+            );
+            if let Some(description) = &association.description {
+                tables.add_registry("Path", HKLM, &prog_id_key, None, Some(description.clone()));
+            }
+            tables.add_registry(
+                "Path",
+                HKLM,
+                &format!("{prog_id_key}\\DefaultIcon"),
+                None,
+                Some("\"[#Path]\",0".into()),
+            );
+            tables.add_registry(
+                "Path",
+                HKLM,
+                &format!("{prog_id_key}\\shell\\open\\command"),
+                None,
+                Some("\"[#Path]\" \"%1\"".into()),
+            );
+        }
+    }
+
+    // additional binaries
+    let binaries = generate_binaries_data(config)?;
+    for bin in &binaries {
+        let file_key = format!("Bin_{}", bin.id);
+        tables.add_component(
+            &bin.id,
+            msi_guid(&bin.guid),
+            "INSTALLDIR",
+            component_attributes,
+            &file_key,
+        );
+        tables.add_file(&file_key, &bin.id, Path::new(&bin.path))?;
+    }
+
+    // resources
+    let mut resource_component_ids = Vec::new();
+    for (_, directory) in generate_resource_data(config)? {
+        tables.add_resource_directory(
+            directory,
+            "INSTALLDIR",
+            component_attributes,
+            &mut resource_component_ids,
+        )?;
+    }
+
+    // uninstall shortcut
+    let key_path = tables.add_registry(
+        "CMP_UninstallShortcut",
+        HKCU,
+        &app_key,
+        Some("Uninstaller Shortcut"),
+        Some("#1".into()),
+    );
+    tables.add_component(
+        "CMP_UninstallShortcut",
+        component_guid("CMP_UninstallShortcut"),
+        "INSTALLDIR",
+        COMPONENT_REGISTRY_KEY_PATH,
+        &key_path,
+    );
+    tables.insert(
+        "Shortcut",
+        vec![
+            "UninstallShortcut".into(),
+            "INSTALLDIR".into(),
+            msi_file_name("UninstallShortcut", &format!("Uninstall {product_name}")).into(),
+            "CMP_UninstallShortcut".into(),
+            "[SystemFolder]msiexec.exe".into(),
+            "/x [ProductCode]".into(),
+            format!("Uninstalls {product_name}").into(),
+            Value::Null,
+            Value::Null,
+            Value::Null,
+            Value::Null,
+            Value::Null,
+        ],
+    );
+    tables.insert(
+        "RemoveFile",
+        vec![
+            "INSTALLDIR".into(),
+            "CMP_UninstallShortcut".into(),
+            Value::Null,
+            "INSTALLDIR".into(),
+            REMOVE_ON_UNINSTALL.into(),
+        ],
+    );
+
+    // start menu shortcut
+    let key_path = tables.add_registry(
+        "ApplicationShortcut",
+        HKCU,
+        &app_key,
+        Some("Start Menu Shortcut"),
+        Some("#1".into()),
+    );
+    tables.add_component(
+        "ApplicationShortcut",
+        component_guid("ApplicationShortcut"),
+        "ApplicationProgramsFolder",
+        COMPONENT_REGISTRY_KEY_PATH,
+        &key_path,
+    );
+    tables.insert(
+        "Shortcut",
+        vec![
+            "ApplicationStartMenuShortcut".into(),
+            "ApplicationProgramsFolder".into(),
+            shortcut_name.as_str().into(),
+            "ApplicationShortcut".into(),
+            "[#Path]".into(),
+            Value::Null,
+            shortcut_description.as_str().into(),
+            Value::Null,
+            icon,
+            Value::Null,
+            Value::Null,
+            "INSTALLDIR".into(),
+        ],
+    );
+    tables.insert(
+        "MsiShortcutProperty",
+        vec![
+            "ApplicationStartMenuShortcutAUMID".into(),
+            "ApplicationStartMenuShortcut".into(),
+            APP_USER_MODEL_ID_KEY.into(),
+            identifier.into(),
+        ],
+    );
+    tables.insert(
+        "RemoveFile",
+        vec![
+            "ApplicationProgramsFolder".into(),
+            "ApplicationShortcut".into(),
+            Value::Null,
+            "ApplicationProgramsFolder".into(),
+            REMOVE_ON_UNINSTALL.into(),
+        ],
+    );
+
+    // features
+    let mut main_components = vec!["RegistryEntries"];
+    main_components.extend(resource_component_ids.iter().map(String::as_str));
+    tables.add_feature_components("MainProgram", &main_components);
+    tables.add_feature_components(
+        "ShortcutsFeature",
+        &[
+            "Path",
+            "CMP_UninstallShortcut",
+            "ApplicationShortcut",
+            "ApplicationShortcutDesktop",
+        ],
+    );
+    let mut environment_components = vec!["Path"];
+    environment_components.extend(binaries.iter().map(|b| b.id.as_str()));
+    tables.add_feature_components("Environment", &environment_components);
+
+    // previous install dir
+    tables.insert(
+        "AppSearch",
+        vec!["INSTALLDIR".into(), "PrevInstallDirReg".into()],
+    );
+    tables.insert(
+        "RegLocator",
+        vec![
+            "PrevInstallDirReg".into(),
+            HKCU.into(),
+            app_key.as_str().into(),
+            "InstallDir".into(),
+            LOCATOR_RAW_VALUE.into(),
+        ],
+    );
+
+    tables.insert(
+        "CustomAction",
+        vec![
+            "SetARPINSTALLLOCATION".into(),
+            CUSTOM_ACTION_SET_PROPERTY.into(),
+            "ARPINSTALLLOCATION".into(),
+            "[INSTALLDIR]".into(),
+        ],
+    );
+
+    if icon_path.is_some() {
+        tables.insert("Icon", vec!["ProductIcon".into(), Value::Binary]);
+    }
+
+    tables.insert(
+        "Media",
+        vec![
+            1.into(),
+            (tables.files.len() as i32).into(),
+            Value::Null,
+            format!("#{CABINET_NAME}").into(),
+            Value::Null,
+            Value::Null,
+        ],
+    );
+
+    Ok(tables)
+}
+
+/// Writes the cabinet with all the installer files.
+fn write_cabinet(files: &[(String, PathBuf)], path: &Path) -> crate::Result<()> {
+    let mut builder = CabinetBuilder::new();
+    let folder = builder.add_folder(CompressionType::MsZip);
+    for (id, _) in files {
+        folder.add_file(id.as_str());
+    }
+
+    let file = File::create(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+    let mut writer = builder.build(file)?;
+    let mut sources = files.iter().map(|(_, source)| source);
+    while let Some(mut file_writer) = writer.next_file()? {
+        let source = sources.next().expect("cabinet file without a source");
+        let mut file =
+            File::open(source).map_err(|e| Error::IoWithPath(source.to_path_buf(), e))?;
+        io::copy(&mut file, &mut file_writer)?;
+    }
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Writes an installer database for the given language.
+#[allow(clippy::too_many_arguments)]
+fn write_package(
+    config: &Config,
+    tables: &Tables,
+    arch: &str,
+    version: &str,
+    language_metadata: &LanguageMetadata,
+    strings: &HashMap<String, String>,
+    cabinet_path: &Path,
+    icon_path: Option<&Path>,
+    msi_path: &Path,
+) -> crate::Result<()> {
+    let product_name = &config.product_name;
+    let manufacturer = config.publisher();
+    let upgrade_code = generate_upgrade_code(&config.main_binary_name()?);
+    let allow_downgrades = config.windows().map(|w| w.allow_downgrades).unwrap_or(true);
+    let language = Language::from_code(language_metadata.lang_id as u16);
+    let string = |id: &str| strings.get(id).cloned().unwrap_or_default();
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(msi_path)
+        .map_err(|e| Error::IoWithPath(msi_path.to_path_buf(), e))?;
+    let mut package = Package::create(PackageType::Installer, file)?;
+
+    if let Some(codepage) = CodePage::from_id(language_metadata.ascii_code as i32) {
+        package.set_database_codepage(codepage);
+    }
+
+    let summary_info = package.summary_info_mut();
+    summary_info.set_subject(product_name.as_str());
+    summary_info.set_author(manufacturer.as_str());
+    summary_info.set_keywords(&["Installer".to_string()]);
+    summary_info.set_comments(format!(
+        "This installer database contains the logic and data required to install {product_name}."
+    ));
+    summary_info.set_arch(match arch {
+        "x64" => "x64",
+        "arm64" => "Arm64",
+        _ => "Intel",
+    });
+    summary_info.set_languages(&[language]);
+    summary_info.set_uuid(Uuid::new_v4());
+    summary_info.set_creating_application("cargo-packager");
+    summary_info.set_creation_time_to_now();
+    // the minimum Windows Installer version, Arm64 packages require 5.0
+    summary_info.set_page_count(if arch == "arm64" { 500 } else { 450 });
+    // long file names, compressed
+    summary_info.set_word_count(2);
+
+    let mut rows = tables.rows.clone();
+
+    let mut properties = vec![
+        ("ProductCode", Value::from(Uuid::new_v4())),
+        ("ProductName", product_name.as_str().into()),
+        ("ProductVersion", version.into()),
+        ("ProductLanguage", language.code().to_string().into()),
+        ("Manufacturer", manufacturer.as_str().into()),
+        ("UpgradeCode", upgrade_code.into()),
+        ("ALLUSERS", "1".into()),
+        ("REINSTALLMODE", "amus".into()),
+        ("ARPNOREPAIR", "yes".into()),
+        ("ARPNOMODIFY", "1".into()),
+        (
+            "SecureCustomProperties",
+            "WIX_UPGRADE_DETECTED;WIX_DOWNGRADE_DETECTED".into(),
+        ),
+    ];
+    if icon_path.is_some() {
+        properties.push(("ARPPRODUCTICON", "ProductIcon".into()));
+    }
+    rows.insert(
+        "Property",
+        properties
+            .into_iter()
+            .map(|(name, value)| vec![name.into(), value])
+            .collect(),
+    );
+
+    rows.insert(
+        "Feature",
+        vec![
+            vec![
+                "MainProgram".into(),
+                Value::Null,
+                "Application".into(),
+                string("InstallAppFeature").into(),
+                1.into(),
+                1.into(),
+                "INSTALLDIR".into(),
+                (FEATURE_DISALLOW_ADVERTISE | FEATURE_UI_DISALLOW_ABSENT).into(),
+            ],
+            vec![
+                "ShortcutsFeature".into(),
+                "MainProgram".into(),
+                "Shortcuts".into(),
+                Value::Null,
+                2.into(),
+                1.into(),
+                Value::Null,
+                0.into(),
+            ],
+            vec![
+                "Environment".into(),
+                "MainProgram".into(),
+                "PATH Environment Variable".into(),
+                string("PathEnvVarFeature").into(),
+                4.into(),
+                1.into(),
+                Value::Null,
+                0.into(),
+            ],
+        ],
+    );
+
+    let upgrade_code = Value::from(upgrade_code);
+    let upgrade_rows = if allow_downgrades {
+        vec![vec![
+            upgrade_code,
+            "0.0.0.0".into(),
+            Value::Null,
+            Value::Null,
+            (UPGRADE_MIGRATE_FEATURES | UPGRADE_VERSION_MIN_INCLUSIVE).into(),
+            Value::Null,
+            "WIX_UPGRADE_DETECTED".into(),
+        ]]
+    } else {
+        rows.insert(
+            "LaunchCondition",
+            vec![vec![
+                "NOT WIX_DOWNGRADE_DETECTED".into(),
+                string("DowngradeErrorMessage").into(),
+            ]],
+        );
+        vec![
+            vec![
+                upgrade_code.clone(),
+                Value::Null,
+                version.into(),
+                Value::Null,
+                (UPGRADE_MIGRATE_FEATURES | UPGRADE_VERSION_MAX_INCLUSIVE).into(),
+                Value::Null,
+                "WIX_UPGRADE_DETECTED".into(),
+            ],
+            vec![
+                upgrade_code,
+                version.into(),
+                Value::Null,
+                Value::Null,
+                UPGRADE_ONLY_DETECT.into(),
+                Value::Null,
+                "WIX_DOWNGRADE_DETECTED".into(),
+            ],
+        ]
+    };
+    rows.insert("Upgrade", upgrade_rows);
+
+    for (table, sequence) in [
+        ("InstallUISequence", INSTALL_UI_SEQUENCE),
+        ("InstallExecuteSequence", INSTALL_EXECUTE_SEQUENCE),
+        ("AdminUISequence", ADMIN_UI_SEQUENCE),
+        ("AdminExecuteSequence", ADMIN_EXECUTE_SEQUENCE),
+        ("AdvtExecuteSequence", ADVT_EXECUTE_SEQUENCE),
+    ] {
+        rows.insert(
+            table,
+            sequence
+                .iter()
+                .filter(|(action, _, _)| *action != "LaunchConditions" || !allow_downgrades)
+                .map(|(action, condition, sequence)| {
+                    vec![
+                        (*action).into(),
+                        condition.map(Into::into).unwrap_or(Value::Null),
+                        (*sequence).into(),
+                    ]
+                })
+                .collect(),
+        );
+    }
+
+    for (table, table_rows) in rows {
+        package.create_table(table, table_columns(table))?;
+        package.insert_rows(Insert::into(table).rows(table_rows))?;
+    }
+
+    if let Some(icon_path) = icon_path {
+        let mut stream = package.write_stream("Icon.ProductIcon")?;
+        let mut icon =
+            File::open(icon_path).map_err(|e| Error::IoWithPath(icon_path.to_path_buf(), e))?;
+        io::copy(&mut icon, &mut stream)?;
+    }
+
+    {
+        let mut stream = package.write_stream(CABINET_NAME)?;
+        let mut cabinet = File::open(cabinet_path)
+            .map_err(|e| Error::IoWithPath(cabinet_path.to_path_buf(), e))?;
+        io::copy(&mut cabinet, &mut stream)?;
+    }
+
+    package.flush()?;
+
+    Ok(())
+}
+
+#[tracing::instrument(level = "trace", skip(ctx))]
+pub(crate) fn package(ctx: &Context) -> crate::Result<Vec<PathBuf>> {
+    let Context {
+        config,
+        intermediates_path,
+        ..
+    } = ctx;
+
+    let arch = match config.target_arch()? {
+        "x86_64" => "x64",
+        "x86" => "x86",
+        "aarch64" => "arm64",
+        target => return Err(Error::UnsupportedArch("wix".into(), target.into())),
+    };
+
+    if let Some(wix) = config.wix() {
+        if wix.template.is_some()
+            || wix.fragment_paths.is_some()
+            || wix.fragments.is_some()
+            || wix.merge_modules.is_some()
+            || wix.component_group_refs.is_some()
+            || wix.component_refs.is_some()
+            || wix.custom_action_refs.is_some()
+            || wix.feature_group_refs.is_some()
+            || wix.feature_refs.is_some()
+            || wix.merge_refs.is_some()
+            || wix.banner_path.is_some()
+            || wix.dialog_image_path.is_some()
+        {
+            tracing::warn!("Custom WiX templates, fragments, merge modules and dialog images require the WiX toolset which is only available on Windows hosts, for now, ignoring them...");
+        }
+    }
+
+    let version = convert_version(&config.version)?;
+
+    let main_binary = config.main_binary()?;
+    let main_binary_name = config.main_binary_name()?;
+    let main_binary_path = config.binary_executable_path(main_binary);
+
+    if config.can_sign() {
+        tracing::debug!("Codesigning {}", main_binary_path.display());
+        codesign::try_sign(&main_binary_path, config)?;
+    } else {
+        codesign::warn_unsupported_host("the main binary");
+    }
+
+    let intermediates_path = intermediates_path.join("wix").join(arch);
+    util::create_clean_dir(&intermediates_path)?;
+
+    let icon_path = product_icon(config, &intermediates_path)?;
+    let tables = generate_tables(config, arch, &main_binary_path, icon_path.as_deref())?;
+
+    let cabinet_path = intermediates_path.join(CABINET_NAME);
+    tracing::debug!("Writing {}", util::display_path(&cabinet_path));
+    write_cabinet(&tables.files, &cabinet_path)?;
+
+    let mut output_paths = Vec::new();
+
+    let language_map: HashMap<String, LanguageMetadata> =
+        serde_json::from_str(include_str!("./languages.json"))?;
+    let configured_languages = config
+        .wix()
+        .and_then(|w| w.languages.clone())
+        .unwrap_or_else(|| vec![WixLanguage::default()]);
+    for language in configured_languages {
+        let (language, locale_path) = match language {
+            WixLanguage::Identifier(identifier) => (identifier, None),
+            WixLanguage::Custom { identifier, path } => (identifier, path),
+        };
+
+        let language_metadata = language_map.get(&language).ok_or_else(|| {
+            Error::UnsupportedWixLanguage(
+                language.clone(),
+                language_map
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<String>>()
+                    .join(", "),
+            )
+        })?;
+        let strings = locale_strings(config, language_metadata, locale_path)?;
+
+        let msi_path = config.out_dir().join(format!(
+            "{}_{}_{}_{}.msi",
+            main_binary_name, config.version, arch, language
+        ));
+        let msi_path_parent = msi_path
+            .parent()
+            .ok_or_else(|| Error::ParentDirNotFound(msi_path.clone()))?;
+        fs::create_dir_all(msi_path_parent)
+            .map_err(|e| Error::IoWithPath(msi_path_parent.to_path_buf(), e))?;
+
+        tracing::info!(
+            "Packaging {} ({})",
+            msi_path.file_name().unwrap_or_default().to_string_lossy(),
+            util::display_path(&msi_path)
+        );
+
+        write_package(
+            config,
+            &tables,
+            arch,
+            &version,
+            language_metadata,
+            &strings,
+            &cabinet_path,
+            icon_path.as_deref(),
+            &msi_path,
+        )?;
+
+        if config.builtin_sign() {
+            tracing::warn!("MSI installers can't be signed with `config.windows.signing_certificate`, but you can specify a custom signing command in `config.windows.sign_command`, for now, skipping signing the installer...");
+        } else if config.can_sign() {
+            tracing::debug!("Codesigning {}", msi_path.display());
+            codesign::try_sign(&msi_path, config)?;
+        } else {
+            codesign::warn_unsupported_host("the installer");
+        }
+
+        output_paths.push(msi_path);
+    }
+
+    Ok(output_paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+
+    use msi::Select;
+
+    use super::*;
+    use crate::config::{Binary, Resource};
+
+    /// Packages an installer for `app`, the `my.tool` binary and an `assets/data.txt` resource.
+    fn package_fixture(dir: &Path) -> PathBuf {
+        for name in ["app.exe", "my.tool.exe"] {
+            fs::write(dir.join(name), name).unwrap();
+        }
+        let assets = dir.join("assets");
+        fs::create_dir(&assets).unwrap();
+        fs::write(assets.join("data.txt"), "data").unwrap();
+
+        let mut config = Config::default();
+        config.product_name = "Test App".into();
+        config.version = "1.2.3".into();
+        config.identifier = Some("com.example.testapp".into());
+        config.out_dir = dir.to_path_buf();
+        config.target_triple = Some("x86_64-pc-windows-msvc".into());
+        config.binaries = vec![Binary::new("app").main(true), Binary::new("my.tool")];
+        config.resources = Some(vec![Resource::Single(assets.display().to_string())]);
+
+        let intermediates_path = dir.join(".cargo-packager");
+        let ctx = Context {
+            tools_path: intermediates_path.clone(),
+            intermediates_path,
+            config,
+        };
+        let mut paths = package(&ctx).unwrap();
+        assert_eq!(paths.len(), 1);
+        paths.remove(0)
+    }
+
+    fn select(package: &mut Package<File>, table: &str) -> Vec<msi::Row> {
+        package.select_rows(Select::table(table)).unwrap().collect()
+    }
+
+    fn find<'a>(rows: &'a [msi::Row], column: &str, value: &str) -> &'a msi::Row {
+        rows.iter()
+            .find(|row| row[column].as_str() == Some(value))
+            .unwrap_or_else(|| panic!("no row with {column} = {value}"))
+    }
+
+    #[test]
+    fn generates_short_names() {
+        assert_eq!(msi_file_name("Path", "app.exe"), "app.exe");
+        assert_eq!(msi_file_name("Path", "README"), "README");
+
+        let name = msi_file_name("Path", "My App.exe");
+        let (short, long) = name.split_once('|').unwrap();
+        assert_eq!(long, "My App.exe");
+        assert_eq!(short.len(), 12);
+        assert!(short.ends_with(".exe"));
+        assert!(is_short_name(short));
+
+        let name = msi_file_name("Path", "settings.json");
+        let (short, _) = name.split_once('|').unwrap();
+        assert_eq!(short.len(), 8);
+        assert_ne!(name, msi_file_name("Other", "settings.json"));
+    }
+
+    #[test]
+    fn packages_readable_installer() {
+        let dir = tempfile::tempdir().unwrap();
+        let msi_path = package_fixture(dir.path());
+        assert_eq!(msi_path.file_name().unwrap(), "app_1.2.3_x64_en-US.msi");
+
+        let mut package = msi::open(&msi_path).unwrap();
+        assert_eq!(
+            package.summary_info().title(),
+            Some("Installation Database")
+        );
+
+        let directories = select(&mut package, "Directory");
+        let install_dir = find(&directories, "Directory", "INSTALLDIR");
+        assert_eq!(
+            install_dir["Directory_Parent"].as_str(),
+            Some("ProgramFiles64Folder")
+        );
+        assert!(install_dir["DefaultDir"]
+            .as_str()
+            .unwrap()
+            .ends_with("|Test App"));
+        let assets_dir = directories
+            .iter()
+            .find(|row| row["DefaultDir"].as_str() == Some("assets"))
+            .unwrap();
+        assert_eq!(assets_dir["Directory_Parent"].as_str(), Some("INSTALLDIR"));
+
+        let components = select(&mut package, "Component");
+        let main_component = find(&components, "Component", "Path");
+        assert_eq!(main_component["Directory_"].as_str(), Some("INSTALLDIR"));
+        assert_eq!(main_component["KeyPath"].as_str(), Some("Path"));
+        assert_eq!(main_component["Attributes"].as_int(), Some(COMPONENT_64BIT));
+
+        let files = select(&mut package, "File");
+        assert_eq!(files.len(), 3);
+        let main_file = find(&files, "File", "Path");
+        assert_eq!(main_file["FileName"].as_str(), Some("app.exe"));
+        assert_eq!(main_file["FileSize"].as_int(), Some(7));
+        assert_eq!(main_file["Sequence"].as_int(), Some(1));
+        let tool_file = files
+            .iter()
+            .find(|row| row["FileName"].as_str().unwrap().ends_with("|my.tool.exe"))
+            .unwrap();
+        assert_eq!(tool_file["Sequence"].as_int(), Some(2));
+        let tool_component = find(
+            &components,
+            "Component",
+            tool_file["Component_"].as_str().unwrap(),
+        );
+        assert_eq!(tool_component["Directory_"].as_str(), Some("INSTALLDIR"));
+        let data_file = find(&files, "FileName", "data.txt");
+        assert_eq!(data_file["Sequence"].as_int(), Some(3));
+        let data_component = find(
+            &components,
+            "Component",
+            data_file["Component_"].as_str().unwrap(),
+        );
+        assert_eq!(
+            data_component["Directory_"].as_str(),
+            assets_dir["Directory"].as_str()
+        );
+
+        let registry = select(&mut package, "Registry");
+        let install_dir_entry = find(&registry, "Name", "InstallDir");
+        assert_eq!(install_dir_entry["Root"].as_int(), Some(HKCU));
+        assert_eq!(
+            install_dir_entry["Key"].as_str(),
+            Some("Software\\example\\Test App")
+        );
+        assert_eq!(install_dir_entry["Value"].as_str(), Some("[INSTALLDIR]"));
+        assert_eq!(
+            install_dir_entry["Component_"].as_str(),
+            Some("RegistryEntries")
+        );
+
+        let shortcuts = select(&mut package, "Shortcut");
+        assert_eq!(shortcuts.len(), 3);
+        for id in ["ApplicationDesktopShortcut", "ApplicationStartMenuShortcut"] {
+            let shortcut = find(&shortcuts, "Shortcut", id);
+            assert_eq!(shortcut["Target"].as_str(), Some("[#Path]"));
+            assert!(shortcut["Name"].as_str().unwrap().ends_with("|Test App"));
+        }
+        let uninstall = find(&shortcuts, "Shortcut", "UninstallShortcut");
+        assert_eq!(uninstall["Arguments"].as_str(), Some("/x [ProductCode]"));
+
+        let upgrade_code = msi_guid(&generate_upgrade_code("app").to_string());
+        let upgrades = select(&mut package, "Upgrade");
+        assert_eq!(upgrades.len(), 1);
+        assert_eq!(
+            upgrades[0]["UpgradeCode"].as_str(),
+            Some(upgrade_code.as_str())
+        );
+        assert_eq!(
+            upgrades[0]["ActionProperty"].as_str(),
+            Some("WIX_UPGRADE_DETECTED")
+        );
+
+        let media = select(&mut package, "Media");
+        assert_eq!(media.len(), 1);
+        assert_eq!(media[0]["LastSequence"].as_int(), Some(3));
+        assert_eq!(media[0]["Cabinet"].as_str(), Some("#app.cab"));
+
+        let mut cabinet = Vec::new();
+        package
+            .read_stream(CABINET_NAME)
+            .unwrap()
+            .read_to_end(&mut cabinet)
+            .unwrap();
+        let mut cabinet = cab::Cabinet::new(Cursor::new(cabinet)).unwrap();
+        let mut sequence = files
+            .iter()
+            .map(|row| {
+                (
+                    row["Sequence"].as_int().unwrap(),
+                    row["File"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect::<Vec<_>>();
+        sequence.sort();
+        let cabinet_files = cabinet
+            .folder_entries()
+            .flat_map(|folder| folder.file_entries())
+            .map(|file| file.name().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            cabinet_files,
+            sequence.into_iter().map(|(_, id)| id).collect::<Vec<_>>()
+        );
+        let mut contents = String::new();
+        cabinet
+            .read_file("Path")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "app.exe");
+    }
+}
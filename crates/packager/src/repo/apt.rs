@@ -0,0 +1,526 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! APT repository generation.
+//!
+//! The repository follows the [Debian repository format](https://wiki.debian.org/DebianRepository/Format):
+//!
+//! ```text
+//! <repo>
+//! ├── pool/<component>/<prefix>/<package>/<package>_<version>_<arch>.deb
+//! └── dists/<suite>
+//!     ├── Release
+//!     ├── InRelease
+//!     ├── Release.gpg
+//!     └── <component>/binary-<arch>
+//!         ├── Packages
+//!         └── Packages.gz
+//! ```
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{read::GzDecoder, Compression, GzBuilder};
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+
+use crate::{openpgp::SecretKey, util, Error, PackageFormat, PackageOutput};
+
+/// The APT repository configuration.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[non_exhaustive]
+pub struct AptRepositoryConfig {
+    /// The distribution suite, which is also used as the codename. Defaults to `stable`.
+    pub suite: Option<String>,
+    /// The component the packages are added to. Defaults to `main`.
+    pub component: Option<String>,
+    /// The `Origin` field of the `Release` file.
+    pub origin: Option<String>,
+    /// The `Label` field of the `Release` file.
+    pub label: Option<String>,
+    /// The `Description` field of the `Release` file.
+    pub description: Option<String>,
+    /// The ASCII-armored OpenPGP secret key used to sign the `Release` file.
+    ///
+    /// When set, the `InRelease` and `Release.gpg` files are generated.
+    pub signing_key: Option<String>,
+    /// The password of the signing key.
+    pub signing_key_password: Option<String>,
+}
+
+impl AptRepositoryConfig {
+    /// Creates a new [`AptRepositoryConfig`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the distribution suite, which is also used as the codename. Defaults to `stable`.
+    pub fn suite<S: Into<String>>(mut self, suite: S) -> Self {
+        self.suite.replace(suite.into());
+        self
+    }
+
+    /// Set the component the packages are added to. Defaults to `main`.
+    pub fn component<S: Into<String>>(mut self, component: S) -> Self {
+        self.component.replace(component.into());
+        self
+    }
+
+    /// Set the `Origin` field of the `Release` file.
+    pub fn origin<S: Into<String>>(mut self, origin: S) -> Self {
+        self.origin.replace(origin.into());
+        self
+    }
+
+    /// Set the `Label` field of the `Release` file.
+    pub fn label<S: Into<String>>(mut self, label: S) -> Self {
+        self.label.replace(label.into());
+        self
+    }
+
+    /// Set the `Description` field of the `Release` file.
+    pub fn description<S: Into<String>>(mut self, description: S) -> Self {
+        self.description.replace(description.into());
+        self
+    }
+
+    /// Set the ASCII-armored OpenPGP secret key used to sign the `Release` file.
+    pub fn signing_key<S: Into<String>>(mut self, signing_key: S) -> Self {
+        self.signing_key.replace(signing_key.into());
+        self
+    }
+
+    /// Set the password of the signing key.
+    pub fn signing_key_password<S: Into<String>>(mut self, password: S) -> Self {
+        self.signing_key_password.replace(password.into());
+        self
+    }
+
+    fn suite_name(&self) -> &str {
+        self.suite.as_deref().unwrap_or("stable")
+    }
+
+    fn component_name(&self) -> &str {
+        self.component.as_deref().unwrap_or("main")
+    }
+}
+
+/// A deb822 paragraph, preserving the order of its fields.
+///
+/// Multi-line values are stored with their continuation lines, including the leading space.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Paragraph(Vec<(String, String)>);
+
+impl Paragraph {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn set(&mut self, key: &str, value: impl Into<String>) {
+        let value = value.into();
+        match self.0.iter_mut().find(|(k, _)| k.eq_ignore_ascii_case(key)) {
+            Some((_, v)) => *v = value,
+            None => self.0.push((key.into(), value)),
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> Option<String> {
+        let index = self
+            .0
+            .iter()
+            .position(|(k, _)| k.eq_ignore_ascii_case(key))?;
+        Some(self.0.remove(index).1)
+    }
+
+    fn write(&self, out: &mut String) {
+        for (key, value) in &self.0 {
+            out.push_str(key);
+            out.push(':');
+            if !value.starts_with('\n') {
+                out.push(' ');
+            }
+            out.push_str(value);
+            out.push('\n');
+        }
+    }
+}
+
+/// Parses deb822 paragraphs, like the ones in `control`, `Packages` and `Release` files.
+fn parse_paragraphs(contents: &str) -> Vec<Paragraph> {
+    let mut paragraphs = Vec::new();
+    let mut current = Paragraph::default();
+    for line in contents.lines() {
+        if line.trim().is_empty() {
+            if !current.0.is_empty() {
+                paragraphs.push(std::mem::take(&mut current));
+            }
+        } else if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = current.0.last_mut() {
+                value.push('\n');
+                value.push_str(line);
+            }
+        } else if let Some((key, value)) = line.split_once(':') {
+            current.0.push((key.trim().into(), value.trim().into()));
+        }
+    }
+    if !current.0.is_empty() {
+        paragraphs.push(current);
+    }
+    paragraphs
+}
+
+/// Returns the pool sub directory of a package, `lib` packages are grouped by their first four letters.
+fn pool_prefix(package: &str) -> &str {
+    let len = if package.starts_with("lib") && package.len() > 3 {
+        4
+    } else {
+        1
+    };
+    package.get(..len).unwrap_or(package)
+}
+
+/// The metadata of a Debian package.
+struct DebPackage {
+    control: Paragraph,
+    name: String,
+    version: String,
+    arch: String,
+}
+
+/// Reads the `control` file of the Debian package at `path`.
+fn read_deb_package(path: &Path, bytes: &[u8]) -> crate::Result<DebPackage> {
+    let invalid = |msg: &str| Error::InvalidDebianPackage(path.to_path_buf(), msg.into());
+
+    let mut archive = ar::Archive::new(bytes);
+    let mut control_tar = None;
+    while let Some(entry) = archive.next_entry() {
+        let mut entry = entry?;
+        let name = String::from_utf8_lossy(entry.header().identifier()).into_owned();
+        if name.starts_with("control.tar") {
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)?;
+            control_tar.replace((name, contents));
+            break;
+        }
+    }
+    let (name, contents) = control_tar.ok_or_else(|| invalid("missing control archive"))?;
+    let control_tar: Box<dyn Read> = match name.as_str() {
+        "control.tar" => Box::new(contents.as_slice()),
+        "control.tar.gz" => Box::new(GzDecoder::new(contents.as_slice())),
+        "control.tar.xz" => Box::new(liblzma::read::XzDecoder::new(contents.as_slice())),
+        "control.tar.zst" => Box::new(zstd::Decoder::new(contents.as_slice())?),
+        _ => return Err(invalid(&format!("unsupported control archive {name}"))),
+    };
+
+    let mut control = None;
+    for entry in tar::Archive::new(control_tar).entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?;
+        if path.strip_prefix(".").unwrap_or(&path) == Path::new("control") {
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents)?;
+            control = parse_paragraphs(&contents).into_iter().next();
+            break;
+        }
+    }
+    let control = control.ok_or_else(|| invalid("missing control file"))?;
+
+    let field = |key: &str| {
+        control
+            .get(key)
+            .map(ToString::to_string)
+            .ok_or_else(|| invalid(&format!("missing {key} field")))
+    };
+    Ok(DebPackage {
+        name: field("Package")?,
+        version: field("Version")?,
+        arch: field("Architecture")?,
+        control,
+    })
+}
+
+/// Writes `contents` to `path` and a gzip compressed copy to `path.gz`.
+fn write_with_gzip(path: &Path, contents: &[u8]) -> crate::Result<()> {
+    fs::write(path, contents).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+
+    let gz_path = path.with_extension("gz");
+    let gz_file = util::create_file(&gz_path)?;
+    let mut encoder = GzBuilder::new()
+        .mtime(0)
+        .write(gz_file, Compression::best());
+    encoder.write_all(contents)?;
+    encoder.finish()?.flush()?;
+    Ok(())
+}
+
+/// Generates the `Release` file of the suite at `dist_dir`,
+/// listing all indices of all components and architectures.
+fn generate_release(config: &AptRepositoryConfig, dist_dir: &Path) -> crate::Result<String> {
+    let previous = fs::read_to_string(dist_dir.join("Release"))
+        .ok()
+        .and_then(|r| parse_paragraphs(&r).into_iter().next())
+        .unwrap_or_default();
+
+    let mut components = BTreeSet::new();
+    let mut architectures = BTreeSet::new();
+    let mut indices = Vec::new();
+    for component in fs::read_dir(dist_dir).map_err(|e| Error::IoWithPath(dist_dir.into(), e))? {
+        let component = component?;
+        if !component.file_type()?.is_dir() {
+            continue;
+        }
+        let component_name = component.file_name().to_string_lossy().into_owned();
+        for arch_dir in fs::read_dir(component.path())? {
+            let arch_dir = arch_dir?;
+            let dir_name = arch_dir.file_name().to_string_lossy().into_owned();
+            let Some(arch) = dir_name.strip_prefix("binary-") else {
+                continue;
+            };
+            for index in ["Packages", "Packages.gz"] {
+                let path = arch_dir.path().join(index);
+                if path.exists() {
+                    let contents = fs::read(&path).map_err(|e| Error::IoWithPath(path, e))?;
+                    indices.push((format!("{component_name}/{dir_name}/{index}"), contents));
+                }
+            }
+            components.insert(component_name.clone());
+            architectures.insert(arch.to_string());
+        }
+    }
+    indices.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let format = time::format_description::parse(
+        "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] UTC",
+    )
+    .map_err(time::error::Error::from)?;
+    let date = time::OffsetDateTime::now_utc()
+        .format(&format)
+        .map_err(time::error::Error::from)?;
+
+    let mut release = Paragraph::default();
+    let field = |config: Option<&String>, key: &str| {
+        config
+            .map(ToString::to_string)
+            .or_else(|| previous.get(key).map(ToString::to_string))
+    };
+    if let Some(origin) = field(config.origin.as_ref(), "Origin") {
+        release.set("Origin", origin);
+    }
+    if let Some(label) = field(config.label.as_ref(), "Label") {
+        release.set("Label", label);
+    }
+    release.set("Suite", config.suite_name());
+    release.set("Codename", config.suite_name());
+    release.set("Date", date);
+    release.set(
+        "Architectures",
+        architectures.into_iter().collect::<Vec<_>>().join(" "),
+    );
+    release.set(
+        "Components",
+        components.into_iter().collect::<Vec<_>>().join(" "),
+    );
+    if let Some(description) = field(config.description.as_ref(), "Description") {
+        release.set("Description", description);
+    }
+
+    let checksums = |hash: fn(&[u8]) -> String| {
+        indices
+            .iter()
+            .map(|(path, contents)| format!("\n {} {} {path}", hash(contents), contents.len()))
+            .collect::<String>()
+    };
+    release.set("MD5Sum", checksums(|c| format!("{:x}", md5::compute(c))));
+    release.set("SHA1", checksums(|c| hex::encode(sha1::Sha1::digest(c))));
+    release.set(
+        "SHA256",
+        checksums(|c| hex::encode(sha2::Sha256::digest(c))),
+    );
+
+    let mut contents = String::new();
+    release.write(&mut contents);
+    Ok(contents)
+}
+
+/// Adds the Debian packages at `packages` to the APT repository at `repo_dir`,
+/// creating the repository if it doesn't exist.
+///
+/// Packages already in the repository with the same name, version and architecture are replaced,
+/// the others are kept. Returns the paths of the added packages inside the repository pool.
+#[tracing::instrument(level = "trace", skip(config))]
+pub fn add_packages<P: AsRef<Path> + std::fmt::Debug>(
+    config: &AptRepositoryConfig,
+    repo_dir: &Path,
+    packages: &[P],
+) -> crate::Result<Vec<PathBuf>> {
+    let signing_key = config
+        .signing_key
+        .as_deref()
+        .map(|key| SecretKey::from_armored(key, config.signing_key_password.as_deref()))
+        .transpose()?;
+
+    let component = config.component_name();
+    let dist_dir = repo_dir.join("dists").join(config.suite_name());
+
+    let mut pool_paths = Vec::new();
+    let mut entries = BTreeMap::<String, Vec<Paragraph>>::new();
+    for package in packages {
+        let path = package.as_ref();
+        let bytes = fs::read(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+        let deb = read_deb_package(path, &bytes)?;
+
+        // the epoch is not part of the file name
+        let version = deb.version.split_once(':').map_or(&*deb.version, |v| v.1);
+        let filename = format!(
+            "pool/{component}/{}/{}/{}_{version}_{}.deb",
+            pool_prefix(&deb.name),
+            deb.name,
+            deb.name,
+            deb.arch
+        );
+
+        tracing::info!("Adding {} to {}", path.display(), repo_dir.display());
+
+        let pool_path = repo_dir.join(&filename);
+        let parent = pool_path
+            .parent()
+            .ok_or_else(|| Error::ParentDirNotFound(pool_path.clone()))?;
+        fs::create_dir_all(parent).map_err(|e| Error::IoWithPath(parent.to_path_buf(), e))?;
+        fs::write(&pool_path, &bytes).map_err(|e| Error::IoWithPath(pool_path.clone(), e))?;
+
+        let mut entry = deb.control;
+        let description = entry.remove("Description");
+        entry.set("Filename", filename);
+        entry.set("Size", bytes.len().to_string());
+        entry.set("MD5sum", format!("{:x}", md5::compute(&bytes)));
+        entry.set("SHA1", hex::encode(sha1::Sha1::digest(&bytes)));
+        entry.set("SHA256", hex::encode(sha2::Sha256::digest(&bytes)));
+        if let Some(description) = description {
+            entry.set("Description", description);
+        }
+
+        entries.entry(deb.arch).or_default().push(entry);
+        pool_paths.push(pool_path);
+    }
+
+    for (arch, new_entries) in entries {
+        let arch_dir = dist_dir.join(component).join(format!("binary-{arch}"));
+        fs::create_dir_all(&arch_dir).map_err(|e| Error::IoWithPath(arch_dir.clone(), e))?;
+
+        tracing::debug!("Updating {}", arch_dir.join("Packages").display());
+
+        let key = |p: &Paragraph| {
+            (
+                p.get("Package").unwrap_or_default().to_string(),
+                p.get("Version").unwrap_or_default().to_string(),
+            )
+        };
+        let mut index = BTreeMap::new();
+        if let Ok(existing) = fs::read_to_string(arch_dir.join("Packages")) {
+            index.extend(
+                parse_paragraphs(&existing)
+                    .into_iter()
+                    .map(|p| (key(&p), p)),
+            );
+        }
+        index.extend(new_entries.into_iter().map(|p| (key(&p), p)));
+
+        let mut contents = String::new();
+        for (i, paragraph) in index.values().enumerate() {
+            if i > 0 {
+                contents.push('\n');
+            }
+            paragraph.write(&mut contents);
+        }
+        write_with_gzip(&arch_dir.join("Packages"), contents.as_bytes())?;
+    }
+
+    tracing::debug!("Generating {}", dist_dir.join("Release").display());
+    fs::create_dir_all(&dist_dir).map_err(|e| Error::IoWithPath(dist_dir.clone(), e))?;
+    let release = generate_release(config, &dist_dir)?;
+    let release_path = dist_dir.join("Release");
+    fs::write(&release_path, &release).map_err(|e| Error::IoWithPath(release_path, e))?;
+
+    let in_release_path = dist_dir.join("InRelease");
+    let release_gpg_path = dist_dir.join("Release.gpg");
+    match signing_key {
+        Some(key) => {
+            tracing::debug!("Signing {}", dist_dir.join("Release").display());
+            let in_release = key.clearsign(&release)?;
+            fs::write(&in_release_path, in_release)
+                .map_err(|e| Error::IoWithPath(in_release_path, e))?;
+            let signature = key.detach_sign(release.as_bytes())?;
+            fs::write(&release_gpg_path, signature)
+                .map_err(|e| Error::IoWithPath(release_gpg_path, e))?;
+        }
+        None => {
+            // stale signatures would no longer match the updated `Release` file
+            for path in [in_release_path, release_gpg_path] {
+                if path.exists() {
+                    fs::remove_file(&path).map_err(|e| Error::IoWithPath(path, e))?;
+                }
+            }
+        }
+    }
+
+    Ok(pool_paths)
+}
+
+/// Adds the [`PackageFormat::Deb`] packages of `outputs` to the APT repository at `repo_dir`.
+///
+/// See [`add_packages`].
+#[tracing::instrument(level = "trace", skip(config))]
+pub fn add_outputs(
+    config: &AptRepositoryConfig,
+    repo_dir: &Path,
+    outputs: &[PackageOutput],
+) -> crate::Result<Vec<PathBuf>> {
+    let packages = outputs
+        .iter()
+        .filter(|o| o.format == PackageFormat::Deb)
+        .flat_map(|o| o.paths.iter())
+        .collect::<Vec<_>>();
+    add_packages(config, repo_dir, &packages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_paragraphs() {
+        let paragraphs = parse_paragraphs(
+            "Package: foo\nVersion: 1.0\nDescription: short\n long line\n .\n\n\nPackage: bar\n",
+        );
+        assert_eq!(paragraphs.len(), 2);
+        assert_eq!(paragraphs[0].get("package"), Some("foo"));
+        assert_eq!(
+            paragraphs[0].get("Description"),
+            Some("short\n long line\n .")
+        );
+        assert_eq!(paragraphs[1].get("Package"), Some("bar"));
+
+        let mut out = String::new();
+        paragraphs[0].write(&mut out);
+        assert_eq!(
+            out,
+            "Package: foo\nVersion: 1.0\nDescription: short\n long line\n .\n"
+        );
+    }
+
+    #[test]
+    fn pool_prefixes() {
+        assert_eq!(pool_prefix("cargo-packager"), "c");
+        assert_eq!(pool_prefix("libfoo"), "libf");
+        assert_eq!(pool_prefix("lib"), "l");
+    }
+}
@@ -0,0 +1,11 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Package repositories generation.
+//!
+//! Generated packages can be published to a package repository so users
+//! can install and update them using their system package manager.
+
+pub mod apt;
+pub mod pacman;
@@ -0,0 +1,439 @@
+// Copyright 2024-2024 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Pacman repository database generation, similar to `repo-add`.
+//!
+//! The repository is a directory containing the packages next to the `<repo>.db.tar.zst`
+//! and `<repo>.files.tar.zst` databases, and their `<repo>.db` and `<repo>.files` links.
+//! Each package is described by a `<pkgname>-<pkgver>/desc` entry, and for the files database,
+//! a `<pkgname>-<pkgver>/files` entry listing its files.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use base64::Engine;
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+
+use crate::{util, Error, PackageFormat, PackageOutput};
+
+/// The pacman repository configuration.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[non_exhaustive]
+pub struct PacmanRepositoryConfig {
+    /// The repository name, as used in `pacman.conf`. Defaults to the repository directory name.
+    pub name: Option<String>,
+}
+
+impl PacmanRepositoryConfig {
+    /// Creates a new [`PacmanRepositoryConfig`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the repository name, as used in `pacman.conf`. Defaults to the repository directory name.
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name.replace(name.into());
+        self
+    }
+}
+
+/// The `desc` fields written after the name and base, and their matching `.PKGINFO` keys,
+/// in the order written by `repo-add`.
+const DESC_FIELDS: &[(&str, &str)] = &[
+    ("VERSION", "pkgver"),
+    ("DESC", "pkgdesc"),
+    ("GROUPS", "group"),
+];
+
+/// The `desc` fields written after the checksums, in the order written by `repo-add`.
+const DESC_TRAILING_FIELDS: &[(&str, &str)] = &[
+    ("URL", "url"),
+    ("LICENSE", "license"),
+    ("ARCH", "arch"),
+    ("BUILDDATE", "builddate"),
+    ("PACKAGER", "packager"),
+    ("REPLACES", "replaces"),
+    ("CONFLICTS", "conflict"),
+    ("PROVIDES", "provides"),
+    ("DEPENDS", "depend"),
+    ("OPTDEPENDS", "optdepend"),
+    ("MAKEDEPENDS", "makedepend"),
+    ("CHECKDEPENDS", "checkdepend"),
+];
+
+/// A package entry of the repository database.
+#[derive(Debug, Default)]
+struct DbEntry {
+    desc: String,
+    files: Option<String>,
+}
+
+/// Parses a `.PKGINFO` file into its `key = value` pairs, preserving their order.
+fn parse_pkginfo(contents: &str) -> Vec<(&str, &str)> {
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once(" = "))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect()
+}
+
+/// Returns the values of the `%FIELD%` section of a `desc` file.
+fn desc_field<'a>(desc: &'a str, field: &str) -> Vec<&'a str> {
+    let header = format!("%{field}%");
+    desc.split("\n\n")
+        .filter_map(|section| section.trim().strip_prefix(&header))
+        .flat_map(|values| values.lines().filter(|l| !l.is_empty()))
+        .collect()
+}
+
+/// Appends a `%FIELD%` section to a `desc` file, skipped if there are no values.
+fn write_desc_field<'a>(desc: &mut String, field: &str, values: impl IntoIterator<Item = &'a str>) {
+    let mut values = values.into_iter().peekable();
+    if values.peek().is_none() {
+        return;
+    }
+    desc.push_str(&format!("%{field}%\n"));
+    for value in values {
+        desc.push_str(value);
+        desc.push('\n');
+    }
+    desc.push('\n');
+}
+
+/// Returns a decompressing reader of a `.tar.zst`, `.tar.xz` or `.tar.gz` archive based on its file name.
+fn decompress<'a>(path: &Path, bytes: &'a [u8]) -> crate::Result<Box<dyn Read + 'a>> {
+    let name = path.to_string_lossy();
+    Ok(if name.ends_with(".zst") {
+        Box::new(zstd::Decoder::new(bytes)?)
+    } else if name.ends_with(".xz") {
+        Box::new(liblzma::read::XzDecoder::new(bytes))
+    } else if name.ends_with(".gz") {
+        Box::new(GzDecoder::new(bytes))
+    } else {
+        Box::new(bytes)
+    })
+}
+
+/// Reads the pacman package at `path` and generates its database entry.
+fn read_package(path: &Path, filename: &str, bytes: &[u8]) -> crate::Result<(String, DbEntry)> {
+    let mut pkginfo = None;
+    let mut files = Vec::new();
+    for entry in tar::Archive::new(decompress(path, bytes)?).entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.to_string_lossy().into_owned();
+        let entry_path = entry_path.trim_start_matches("./");
+        if entry_path == ".PKGINFO" {
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents)?;
+            pkginfo.replace(contents);
+        } else if !entry_path.is_empty() && !entry_path.starts_with('.') {
+            let mut entry_path = entry_path.to_string();
+            if entry.header().entry_type().is_dir() && !entry_path.ends_with('/') {
+                entry_path.push('/');
+            }
+            files.push(entry_path);
+        }
+    }
+    files.sort();
+    files.dedup();
+
+    let pkginfo = pkginfo.ok_or_else(|| {
+        Error::InvalidPacmanPackage(path.to_path_buf(), "missing .PKGINFO".into())
+    })?;
+    let pkginfo = parse_pkginfo(&pkginfo);
+    let values = |key: &str| {
+        pkginfo
+            .iter()
+            .filter(|(k, _)| *k == key)
+            .map(|(_, v)| *v)
+            .collect::<Vec<_>>()
+    };
+
+    let name = values("pkgname")
+        .first()
+        .copied()
+        .ok_or_else(|| Error::InvalidPacmanPackage(path.to_path_buf(), "missing pkgname".into()))?;
+    let version = values("pkgver")
+        .first()
+        .copied()
+        .ok_or_else(|| Error::InvalidPacmanPackage(path.to_path_buf(), "missing pkgver".into()))?;
+
+    let mut desc = String::new();
+    write_desc_field(&mut desc, "FILENAME", [filename]);
+    write_desc_field(&mut desc, "NAME", [name]);
+    write_desc_field(
+        &mut desc,
+        "BASE",
+        [values("pkgbase").first().copied().unwrap_or(name)],
+    );
+    for (field, key) in DESC_FIELDS {
+        write_desc_field(&mut desc, field, values(key));
+    }
+    let csize = bytes.len().to_string();
+    write_desc_field(&mut desc, "CSIZE", [csize.as_str()]);
+    write_desc_field(&mut desc, "ISIZE", values("size"));
+    let md5sum = format!("{:x}", md5::compute(bytes));
+    write_desc_field(&mut desc, "MD5SUM", [md5sum.as_str()]);
+    let sha256sum = hex::encode(sha2::Sha256::digest(bytes));
+    write_desc_field(&mut desc, "SHA256SUM", [sha256sum.as_str()]);
+
+    let signature_path = PathBuf::from(format!("{}.sig", path.display()));
+    if signature_path.exists() {
+        let signature =
+            fs::read(&signature_path).map_err(|e| Error::IoWithPath(signature_path.clone(), e))?;
+        let signature = base64::engine::general_purpose::STANDARD.encode(signature);
+        write_desc_field(&mut desc, "PGPSIG", [signature.as_str()]);
+    }
+
+    for (field, key) in DESC_TRAILING_FIELDS {
+        write_desc_field(&mut desc, field, values(key));
+    }
+
+    let mut files_contents = String::new();
+    write_desc_field(
+        &mut files_contents,
+        "FILES",
+        files.iter().map(String::as_str),
+    );
+
+    Ok((
+        format!("{name}-{version}"),
+        DbEntry {
+            desc,
+            files: Some(files_contents),
+        },
+    ))
+}
+
+/// Reads the entries of an existing repository database.
+fn read_database(path: &Path) -> crate::Result<BTreeMap<String, DbEntry>> {
+    let mut entries = BTreeMap::<String, DbEntry>::new();
+    let bytes = fs::read(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+    for entry in tar::Archive::new(decompress(path, &bytes)?).entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.to_string_lossy().into_owned();
+        let Some((dir, file)) = entry_path.split_once('/') else {
+            continue;
+        };
+        if file != "desc" && file != "files" {
+            continue;
+        }
+
+        let mut contents = String::new();
+        entry.read_to_string(&mut contents)?;
+        let db_entry = entries.entry(dir.to_string()).or_default();
+        match file {
+            "desc" => db_entry.desc = contents,
+            _ => db_entry.files = Some(contents),
+        }
+    }
+    Ok(entries)
+}
+
+/// Writes a repository database, including the `files` entries if `with_files` is `true`.
+fn write_database(
+    path: &Path,
+    entries: &BTreeMap<String, DbEntry>,
+    with_files: bool,
+) -> crate::Result<()> {
+    let file = util::create_file(path)?;
+    let encoder = zstd::Encoder::new(file, 19)?;
+    let mut builder = tar::Builder::new(encoder);
+
+    let mtime = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    let header = |entry_type: tar::EntryType, mode: u32, size: usize| {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_mode(mode);
+        header.set_size(size as u64);
+        header.set_mtime(mtime);
+        header.set_uid(0);
+        header.set_gid(0);
+        header
+    };
+
+    for (dir, entry) in entries {
+        let mut dir_header = header(tar::EntryType::Directory, 0o755, 0);
+        builder.append_data(&mut dir_header, format!("{dir}/"), std::io::empty())?;
+
+        let mut files = vec![("desc", Some(&entry.desc))];
+        if with_files {
+            files.push(("files", entry.files.as_ref()));
+        }
+        for (name, contents) in files {
+            let Some(contents) = contents else {
+                continue;
+            };
+            let mut file_header = header(tar::EntryType::Regular, 0o644, contents.len());
+            builder.append_data(
+                &mut file_header,
+                format!("{dir}/{name}"),
+                contents.as_bytes(),
+            )?;
+        }
+    }
+
+    builder.into_inner()?.finish()?.flush()?;
+    Ok(())
+}
+
+/// Creates the `<repo>.db` and `<repo>.files` links pacman downloads, pointing to the databases.
+fn link_database(repo_dir: &Path, link: &str, target: &str) -> crate::Result<()> {
+    let link_path = repo_dir.join(link);
+    if link_path.symlink_metadata().is_ok() {
+        fs::remove_file(&link_path).map_err(|e| Error::IoWithPath(link_path.clone(), e))?;
+    }
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, &link_path)
+        .map_err(|e| Error::Symlink(PathBuf::from(target), link_path.clone(), e))?;
+    #[cfg(not(unix))]
+    fs::copy(repo_dir.join(target), &link_path)
+        .map_err(|e| Error::CopyFile(repo_dir.join(target), link_path.clone(), e))?;
+
+    Ok(())
+}
+
+/// Adds the pacman packages at `packages` to the repository at `repo_dir`,
+/// creating the `<repo>.db.tar.zst` and `<repo>.files.tar.zst` databases if they don't exist.
+///
+/// Packages are copied to `repo_dir` along with their `.sig` signatures if any,
+/// and replace the entries of older versions of the same packages.
+/// Returns the paths of the databases.
+#[tracing::instrument(level = "trace", skip(config))]
+pub fn add_packages<P: AsRef<Path> + std::fmt::Debug>(
+    config: &PacmanRepositoryConfig,
+    repo_dir: &Path,
+    packages: &[P],
+) -> crate::Result<Vec<PathBuf>> {
+    fs::create_dir_all(repo_dir).map_err(|e| Error::IoWithPath(repo_dir.to_path_buf(), e))?;
+
+    let name = match &config.name {
+        Some(name) => name.clone(),
+        None => dunce::canonicalize(repo_dir)
+            .map_err(|e| Error::IoWithPath(repo_dir.to_path_buf(), e))?
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .ok_or_else(|| Error::FailedToExtractFilename(repo_dir.to_path_buf()))?,
+    };
+
+    let db_path = repo_dir.join(format!("{name}.db.tar.zst"));
+    let files_db_path = repo_dir.join(format!("{name}.files.tar.zst"));
+
+    // the files database is a superset of the packages database
+    let mut entries = if files_db_path.exists() {
+        read_database(&files_db_path)?
+    } else if db_path.exists() {
+        read_database(&db_path)?
+    } else {
+        BTreeMap::new()
+    };
+
+    for package in packages {
+        let path = package.as_ref();
+        let filename = path
+            .file_name()
+            .ok_or_else(|| Error::FailedToExtractFilename(path.to_path_buf()))?
+            .to_string_lossy()
+            .into_owned();
+
+        tracing::info!("Adding {} to {}", path.display(), db_path.display());
+
+        let bytes = fs::read(path).map_err(|e| Error::IoWithPath(path.to_path_buf(), e))?;
+        let (dir, entry) = read_package(path, &filename, &bytes)?;
+
+        // only one version of each package can be in the repository
+        let package_name = desc_field(&entry.desc, "NAME")
+            .first()
+            .map(|n| n.to_string());
+        entries.retain(|_, e| {
+            desc_field(&e.desc, "NAME").first().map(|n| n.to_string()) != package_name
+        });
+        entries.insert(dir, entry);
+
+        let dest = repo_dir.join(&filename);
+        let signature_path = PathBuf::from(format!("{}.sig", path.display()));
+        let mut copies = vec![(path.to_path_buf(), dest)];
+        if signature_path.exists() {
+            copies.push((signature_path, repo_dir.join(format!("{filename}.sig"))));
+        }
+        for (src, dest) in copies {
+            let same_file = dunce::canonicalize(&src).ok() == dunce::canonicalize(&dest).ok();
+            if !same_file {
+                fs::copy(&src, &dest).map_err(|e| Error::CopyFile(src, dest, e))?;
+            }
+        }
+    }
+
+    tracing::debug!("Writing {}", db_path.display());
+    write_database(&db_path, &entries, false)?;
+    link_database(
+        repo_dir,
+        &format!("{name}.db"),
+        &format!("{name}.db.tar.zst"),
+    )?;
+
+    tracing::debug!("Writing {}", files_db_path.display());
+    write_database(&files_db_path, &entries, true)?;
+    link_database(
+        repo_dir,
+        &format!("{name}.files"),
+        &format!("{name}.files.tar.zst"),
+    )?;
+
+    Ok(vec![db_path, files_db_path])
+}
+
+/// Adds the [`PackageFormat::Pacman`] packages of `outputs` to the repository at `repo_dir`.
+///
+/// See [`add_packages`].
+#[tracing::instrument(level = "trace", skip(config))]
+pub fn add_outputs(
+    config: &PacmanRepositoryConfig,
+    repo_dir: &Path,
+    outputs: &[PackageOutput],
+) -> crate::Result<Vec<PathBuf>> {
+    let packages = outputs
+        .iter()
+        .filter(|o| o.format == PackageFormat::Pacman)
+        .flat_map(|o| o.paths.iter())
+        .filter(|p| p.to_string_lossy().contains(".pkg.tar"))
+        .collect::<Vec<_>>();
+    add_packages(config, repo_dir, &packages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_and_reads_desc_fields() {
+        let mut desc = String::new();
+        write_desc_field(&mut desc, "NAME", ["foo"]);
+        write_desc_field(&mut desc, "GROUPS", std::iter::empty());
+        write_desc_field(&mut desc, "DEPENDS", ["glibc", "gtk3"]);
+        assert_eq!(desc, "%NAME%\nfoo\n\n%DEPENDS%\nglibc\ngtk3\n\n");
+        assert_eq!(desc_field(&desc, "DEPENDS"), vec!["glibc", "gtk3"]);
+        assert!(desc_field(&desc, "GROUPS").is_empty());
+    }
+
+    #[test]
+    fn parses_pkginfo() {
+        let pkginfo = parse_pkginfo("# comment\npkgname = foo\ndepend = glibc\ndepend = gtk3\n");
+        assert_eq!(
+            pkginfo,
+            vec![("pkgname", "foo"), ("depend", "glibc"), ("depend", "gtk3")]
+        );
+    }
+}
@@ -139,22 +139,22 @@ pub fn target_triple() -> crate::Result<String> {
     Ok(format!("{arch}-{os}"))
 }
 
-pub(crate) fn download(url: &str) -> crate::Result<Vec<u8>> {
-    tracing::debug!("Downloading {}", url);
-
+/// Creates the HTTP agent used for downloads and web services.
+pub(crate) fn http_agent() -> ureq::Agent {
+    let builder = ureq::AgentBuilder::new().try_proxy_from_env(true);
     // This is required because ureq does not bind native-tls as the default TLS implementation when rustls is not available.
     // See <https://github.com/crabnebula-dev/cargo-packager/issues/127>
     #[cfg(feature = "native-tls")]
-    let agent = ureq::AgentBuilder::new()
-        .tls_connector(std::sync::Arc::new(
-            native_tls::TlsConnector::new().unwrap(),
-        ))
-        .try_proxy_from_env(true)
-        .build();
-    #[cfg(not(feature = "native-tls"))]
-    let agent = ureq::AgentBuilder::new().try_proxy_from_env(true).build();
-
-    let response = agent.get(url).call().map_err(Box::new)?;
+    let builder = builder.tls_connector(std::sync::Arc::new(
+        native_tls::TlsConnector::new().unwrap(),
+    ));
+    builder.build()
+}
+
+pub(crate) fn download(url: &str) -> crate::Result<Vec<u8>> {
+    tracing::debug!("Downloading {}", url);
+
+    let response = http_agent().get(url).call().map_err(Box::new)?;
     let mut bytes = Vec::new();
     response.into_reader().read_to_end(&mut bytes)?;
     Ok(bytes)
@@ -162,7 +162,6 @@ pub(crate) fn download(url: &str) -> crate::Result<Vec<u8>> {
 
 #[derive(Clone, Copy)]
 pub(crate) enum HashAlgorithm {
-    #[cfg(target_os = "windows")]
     Sha256,
     Sha1,
 }
@@ -186,7 +185,6 @@ pub(crate) fn verify_hash(
     hash_algorithm: HashAlgorithm,
 ) -> crate::Result<()> {
     match hash_algorithm {
-        #[cfg(target_os = "windows")]
         HashAlgorithm::Sha256 => {
             let hasher = sha2::Sha256::new();
             verify_data_with_hasher(data, hash, hasher)
@@ -294,6 +292,21 @@ pub(crate) fn is_retina<P: AsRef<Path>>(path: P) -> bool {
         .unwrap_or(false)
 }
 
+/// Returns whether the file at `path` starts with the ELF magic, reading only its first bytes.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+pub(crate) fn is_elf(path: &Path) -> bool {
+    let mut magic = [0; 4];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .is_ok_and(|_| &magic == b"\x7fELF")
+}
+
 // Given a list of icon files, try to produce an ICNS file in the out_dir
 // and return the path to it.  Returns `Ok(None)` if no usable icons
 // were provided.
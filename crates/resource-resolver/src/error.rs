@@ -25,6 +25,9 @@ pub enum Error {
     /// `APPDIR` or `APPIMAGE` environment variable found but this application was not detected as an AppImage; this might be a security issue.
     #[error("`APPDIR` or `APPIMAGE` environment variable found but this application was not detected as an AppImage; this might be a security issue.")]
     InvalidAppImage,
+    /// Couldn't find `SNAP` environment variable.
+    #[error("Couldn't find `SNAP` environment variable")]
+    SnapDirNotFound,
     /// Couldn't find parent of path.
     #[error("Couldn't find parent of {0}")]
     ParentNotFound(PathBuf),
@@ -55,11 +55,19 @@ pub fn current_format() -> crate::Result<PackageFormat> {
     match std::option_env!("CARGO_PACKAGER_FORMAT") {
         Some("app") => Ok(PackageFormat::App),
         Some("dmg") => Ok(PackageFormat::Dmg),
+        Some("pkg") => Ok(PackageFormat::Pkg),
         Some("wix") => Ok(PackageFormat::Wix),
         Some("nsis") => Ok(PackageFormat::Nsis),
+        Some("msix") => Ok(PackageFormat::Msix),
         Some("deb") => Ok(PackageFormat::Deb),
         Some("appimage") => Ok(PackageFormat::AppImage),
         Some("pacman") => Ok(PackageFormat::Pacman),
+        Some("rpm") => Ok(PackageFormat::Rpm),
+        Some("flatpak") => Ok(PackageFormat::Flatpak),
+        Some("snap") => Ok(PackageFormat::Snap),
+        Some("apk") => Ok(PackageFormat::Apk),
+        Some("freebsd") => Ok(PackageFormat::FreeBsd),
+        Some("archive") => Ok(PackageFormat::Archive),
         _ => Err(Error::UnkownPackageFormat),
     }
 }
@@ -75,21 +83,21 @@ pub fn current_format() -> crate::Result<PackageFormat> {
 /// ```
 pub fn resources_dir(package_format: PackageFormat) -> Result<PathBuf> {
     match package_format {
-        PackageFormat::App | PackageFormat::Dmg => {
+        PackageFormat::App | PackageFormat::Dmg | PackageFormat::Pkg => {
             let exe = current_exe()?;
             let exe_dir = exe
                 .parent()
                 .ok_or_else(|| Error::ParentNotFound(exe.clone()))?;
             Ok(exe_dir.join("../Resources"))
         }
-        PackageFormat::Wix | PackageFormat::Nsis => {
+        PackageFormat::Wix | PackageFormat::Nsis | PackageFormat::Msix | PackageFormat::Archive => {
             let exe = current_exe()?;
             let exe_dir = exe
                 .parent()
                 .ok_or_else(|| Error::ParentNotFound(exe.clone()))?;
             Ok(exe_dir.to_path_buf())
         }
-        PackageFormat::Deb | PackageFormat::Pacman => {
+        PackageFormat::Deb | PackageFormat::Pacman | PackageFormat::Rpm | PackageFormat::Apk => {
             let exe = current_exe()?;
             let exe_name = exe.file_name().unwrap().to_string_lossy();
 
@@ -97,6 +105,30 @@ pub fn resources_dir(package_format: PackageFormat) -> Result<PathBuf> {
             Ok(PathBuf::from(path))
         }
 
+        PackageFormat::FreeBsd => {
+            let exe = current_exe()?;
+            let exe_name = exe.file_name().unwrap().to_string_lossy();
+
+            Ok(PathBuf::from(format!("/usr/local/lib/{exe_name}/")))
+        }
+
+        PackageFormat::Flatpak => {
+            let exe = current_exe()?;
+            let exe_name = exe.file_name().unwrap().to_string_lossy();
+
+            Ok(PathBuf::from(format!("/app/lib/{exe_name}/")))
+        }
+
+        PackageFormat::Snap => {
+            let snap = std::env::var_os("SNAP").ok_or(Error::SnapDirNotFound)?;
+            let snap: &std::path::Path = snap.as_ref();
+
+            let exe = current_exe()?;
+            let exe_name = exe.file_name().unwrap().to_string_lossy();
+
+            Ok(snap.join("usr/lib").join(&*exe_name))
+        }
+
         PackageFormat::AppImage => {
             let appdir = std::env::var_os("APPDIR").ok_or(Error::AppDirNotFound)?;
 
@@ -33,18 +33,36 @@ pub enum PackageFormat {
     Default,
     /// The macOS application bundle (.app).
     App,
-    /// The macOS DMG package (.dmg).
+    /// The macOS DMG package (.dmg) through `hdiutil` on macOS,
+    /// or generated without it on other hosts.
     Dmg,
-    /// The Microsoft Software Installer (.msi) through WiX Toolset.
+    /// The macOS installer package (.pkg).
+    Pkg,
+    /// The Microsoft Software Installer (.msi) through WiX Toolset on Windows,
+    /// or generated without it on other hosts.
     Wix,
     /// The NSIS installer (.exe).
     Nsis,
+    /// The MSIX package (.msix).
+    Msix,
     /// The Linux Debian package (.deb).
     Deb,
     /// The Linux AppImage package (.AppImage).
     AppImage,
     /// The Linux Pacman package (.tar.gz and PKGBUILD)
     Pacman,
+    /// The Linux RPM package (.rpm).
+    Rpm,
+    /// The Linux Flatpak bundle (.flatpak).
+    Flatpak,
+    /// The Linux snap package (.snap).
+    Snap,
+    /// The Alpine Linux package (.apk).
+    Apk,
+    /// The FreeBSD package (.pkg).
+    FreeBsd,
+    /// A portable archive of the app (.tar.gz, .tar.xz, .tar.zst or .zip).
+    Archive,
 }
 
 impl Display for PackageFormat {
@@ -55,16 +73,23 @@ impl Display for PackageFormat {
 
 impl PackageFormat {
     /// Maps a short name to a [PackageFormat].
-    /// Possible values are "deb", "pacman", "appimage", "dmg", "app", "wix", "nsis".
+    /// Possible values are "deb", "pacman", "rpm", "appimage", "dmg", "pkg", "app", "wix", "nsis", "msix", "flatpak", "snap", "apk", "freebsd", "archive".
     pub fn from_short_name(name: &str) -> Option<PackageFormat> {
-        // Other types we may eventually want to support: apk.
         match name {
             "app" => Some(PackageFormat::App),
             "dmg" => Some(PackageFormat::Dmg),
+            "pkg" => Some(PackageFormat::Pkg),
             "wix" => Some(PackageFormat::Wix),
             "nsis" => Some(PackageFormat::Nsis),
+            "msix" => Some(PackageFormat::Msix),
             "deb" => Some(PackageFormat::Deb),
             "appimage" => Some(PackageFormat::AppImage),
+            "rpm" => Some(PackageFormat::Rpm),
+            "flatpak" => Some(PackageFormat::Flatpak),
+            "snap" => Some(PackageFormat::Snap),
+            "apk" => Some(PackageFormat::Apk),
+            "freebsd" => Some(PackageFormat::FreeBsd),
+            "archive" => Some(PackageFormat::Archive),
             _ => None,
         }
     }
@@ -78,29 +103,41 @@ impl PackageFormat {
             PackageFormat::Default => "default",
             PackageFormat::App => "app",
             PackageFormat::Dmg => "dmg",
+            PackageFormat::Pkg => "pkg",
             PackageFormat::Wix => "wix",
             PackageFormat::Nsis => "nsis",
+            PackageFormat::Msix => "msix",
             PackageFormat::Deb => "deb",
             PackageFormat::AppImage => "appimage",
             PackageFormat::Pacman => "pacman",
+            PackageFormat::Rpm => "rpm",
+            PackageFormat::Flatpak => "flatpak",
+            PackageFormat::Snap => "snap",
+            PackageFormat::Apk => "apk",
+            PackageFormat::FreeBsd => "freebsd",
+            PackageFormat::Archive => "archive",
         }
     }
 
     /// Gets the list of the possible package types on the current OS.
     ///
-    /// - **macOS**: App, Dmg
-    /// - **Windows**: Nsis, Wix
-    /// - **Linux**: Deb, AppImage, Pacman
+    /// - **macOS**: App, Dmg, Pkg, Archive
+    /// - **Windows**: Nsis, Wix, Msix, Archive
+    /// - **Linux**: Deb, AppImage, Pacman, Rpm, Flatpak, Snap, Apk, FreeBsd, Archive
     pub fn platform_all() -> &'static [PackageFormat] {
         &[
             #[cfg(target_os = "macos")]
             PackageFormat::App,
             #[cfg(target_os = "macos")]
             PackageFormat::Dmg,
+            #[cfg(target_os = "macos")]
+            PackageFormat::Pkg,
             #[cfg(target_os = "windows")]
             PackageFormat::Wix,
             #[cfg(target_os = "windows")]
             PackageFormat::Nsis,
+            #[cfg(target_os = "windows")]
+            PackageFormat::Msix,
             #[cfg(any(
                 target_os = "linux",
                 target_os = "dragonfly",
@@ -125,6 +162,47 @@ impl PackageFormat {
                 target_os = "openbsd"
             ))]
             PackageFormat::Pacman,
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            PackageFormat::Rpm,
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            PackageFormat::Flatpak,
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            PackageFormat::Snap,
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            PackageFormat::Apk,
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            PackageFormat::FreeBsd,
+            PackageFormat::Archive,
         ]
     }
 
@@ -133,6 +211,7 @@ impl PackageFormat {
     /// - **macOS**: App, Dmg
     /// - **Windows**: Nsis
     /// - **Linux**: Deb, AppImage, Pacman
+    /// - **FreeBSD**: FreeBsd
     pub fn platform_default() -> &'static [PackageFormat] {
         &[
             #[cfg(target_os = "macos")]
@@ -144,7 +223,6 @@ impl PackageFormat {
             #[cfg(any(
                 target_os = "linux",
                 target_os = "dragonfly",
-                target_os = "freebsd",
                 target_os = "netbsd",
                 target_os = "openbsd"
             ))]
@@ -152,7 +230,6 @@ impl PackageFormat {
             #[cfg(any(
                 target_os = "linux",
                 target_os = "dragonfly",
-                target_os = "freebsd",
                 target_os = "netbsd",
                 target_os = "openbsd"
             ))]
@@ -160,17 +237,18 @@ impl PackageFormat {
             #[cfg(any(
                 target_os = "linux",
                 target_os = "dragonfly",
-                target_os = "freebsd",
                 target_os = "netbsd",
                 target_os = "openbsd"
             ))]
             PackageFormat::Pacman,
+            #[cfg(target_os = "freebsd")]
+            PackageFormat::FreeBsd,
         ]
     }
 
     /// Gets a number representing priority which used to sort package types
     /// in an order that guarantees that if a certain package type
-    /// depends on another (like Dmg depending on MacOsBundle), the dependency
+    /// depends on another (like Dmg and Pkg depending on MacOsBundle), the dependency
     /// will be built first
     ///
     /// The lower the number, the higher the priority
@@ -183,10 +261,18 @@ impl PackageFormat {
             PackageFormat::App => 0,
             PackageFormat::Wix => 0,
             PackageFormat::Nsis => 0,
+            PackageFormat::Msix => 0,
             PackageFormat::Deb => 0,
             PackageFormat::AppImage => 0,
             PackageFormat::Pacman => 0,
+            PackageFormat::Rpm => 0,
+            PackageFormat::Flatpak => 0,
+            PackageFormat::Snap => 0,
+            PackageFormat::Apk => 0,
+            PackageFormat::FreeBsd => 0,
+            PackageFormat::Archive => 0,
             PackageFormat::Dmg => 1,
+            PackageFormat::Pkg => 1,
         }
     }
 }